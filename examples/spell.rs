@@ -0,0 +1,102 @@
+//! Spell-checks the prose of a Markdown file with `hunspell`, which must be installed and on the
+//! `PATH`. Each misspelt word is reported with the line and column it's at:
+//!
+//! ```sh
+//! cargo run --example spell -- README.md [dictionary]
+//! ```
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{self, Command, Stdio};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let Some(file) = args.next() else {
+        eprintln!("usage: spell <file> [dictionary]");
+        process::exit(exitcode::USAGE);
+    };
+    let dictionary = args.next().unwrap_or_else(|| "en_GB".to_owned());
+    let Ok(contents) = fs::read_to_string(&file) else {
+        eprintln!("unable to read file `{file}`");
+        process::exit(exitcode::UNAVAILABLE);
+    };
+
+    let elements = sani::parse(&contents);
+    let mut misspelt_count = 0_usize;
+    sani::markdown::inspect_paragraphs(&elements, |text, span| {
+        let source = contents.get(span.clone()).unwrap_or_default();
+        // hunspell lists the words in the order they come in, so each is looked for after the
+        // last, so that a word misspelt twice is reported at both places
+        let mut searched = 0;
+        for word in misspelt_words(text, &dictionary) {
+            // point at the word in the source where possible, falling back to the paragraph
+            let offset = match find_word(source, &word, searched) {
+                Some(found) => {
+                    searched = found + word.len();
+                    span.start + found
+                }
+                None => span.start,
+            };
+            let before = contents.get(..offset).unwrap_or_default();
+            let line = before.matches('\n').count() + 1;
+            let column = before
+                .rsplit_once('\n')
+                .map_or(before, |(_, line)| line)
+                .chars()
+                .count()
+                + 1;
+            println!("{file}:{line}:{column}: {word}");
+            misspelt_count += 1;
+        }
+    });
+
+    if misspelt_count > 0 {
+        process::exit(exitcode::DATAERR);
+    }
+}
+
+/// The offset of the first whole-word occurrence of `word` in `text` at or after `from`
+fn find_word(text: &str, word: &str, from: usize) -> Option<usize> {
+    let is_word_character = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    text.get(from..)?
+        .match_indices(word)
+        .map(|(index, _)| from + index)
+        .find(|&start| {
+            let before = text
+                .get(..start)
+                .and_then(|before| before.chars().next_back());
+            let after = text
+                .get(start + word.len()..)
+                .and_then(|after| after.chars().next());
+            !is_word_character(before) && !is_word_character(after)
+        })
+}
+
+/// Runs `text` through `hunspell -l`, which lists the words it doesn't recognise
+fn misspelt_words(text: &str, dictionary: &str) -> Vec<String> {
+    let Ok(mut hunspell) = Command::new("hunspell")
+        .args(["-l", "-d", dictionary])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    else {
+        eprintln!("unable to run `hunspell`; is it installed?");
+        process::exit(exitcode::UNAVAILABLE);
+    };
+
+    if let Some(mut stdin) = hunspell.stdin.take() {
+        // a failed write shows up as hunspell not reporting anything, so there's no need to
+        // handle it separately
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    hunspell
+        .wait_with_output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
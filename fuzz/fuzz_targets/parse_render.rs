@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sani::options::RenderOptions;
+
+fuzz_target!(|input: (&str, u8)| {
+    let (text, width) = input;
+
+    for element in sani::parse(text) {
+        // spans must always be slicable out of the source
+        assert!(text.get(element.span()).is_some());
+
+        for options in [
+            RenderOptions::new(),
+            RenderOptions::new().width(usize::from(width)),
+        ] {
+            let render = element.render(&options);
+            // every formatting code switched on has to be switched off again
+            if let Err(error) = sani::testing::check_balanced_sgr_codes(&render) {
+                panic!("{error}");
+            }
+        }
+    }
+});
@@ -0,0 +1,41 @@
+/*
+ * The C interface to sani, a Markdown renderer, from the library built with the `ffi` feature:
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * All strings are NUL-terminated UTF-8. Everything returned must be freed with the matching
+ * `_free` function, and nothing else.
+ */
+
+#ifndef SANI_H
+#define SANI_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A parsed document */
+typedef struct SaniDocument SaniDocument;
+
+/* Parses `text`. Returns NULL if `text` is NULL or not valid UTF-8 */
+SaniDocument *sani_parse(const char *text);
+
+/*
+ * Renders `document` for the terminal, with formatting as ANSI escape codes, wrapped to `width`
+ * columns, or not wrapped at all if `width` is 0. Returns NULL if `document` is NULL
+ */
+char *sani_render_ansi(const SaniDocument *document, size_t width);
+
+/* Frees a document from `sani_parse`. Does nothing if `document` is NULL */
+void sani_document_free(SaniDocument *document);
+
+/* Frees a string from `sani_render_ansi`. Does nothing if `string` is NULL */
+void sani_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
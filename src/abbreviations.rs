@@ -0,0 +1,208 @@
+use crate::formatting::Format;
+
+#[derive(Debug)]
+struct Definition {
+    abbreviation: String,
+    expansion: String,
+    /// Whether the expansion has been shown yet, as it is only shown on first use
+    expanded: bool,
+}
+
+/// The abbreviations defined in a document (`*[HTML]: HyperText Markup Language`), applied to
+/// inline text as it is parsed
+#[derive(Debug, Default)]
+pub struct Abbreviations {
+    definitions: Vec<Definition>,
+}
+
+impl Abbreviations {
+    /// Collects every abbreviation definition in `text`. Definitions apply to the whole document,
+    /// wherever they appear in it; if an abbreviation is defined more than once, the first
+    /// definition is used
+    pub fn collect(text: &str) -> Self {
+        let mut definitions: Vec<Definition> = Vec::new();
+        for (abbreviation, expansion) in text.lines().filter_map(definition) {
+            if !definitions
+                .iter()
+                .any(|definition| definition.abbreviation == abbreviation)
+            {
+                definitions.push(Definition {
+                    abbreviation: abbreviation.to_owned(),
+                    expansion: expansion.to_owned(),
+                    expanded: false,
+                });
+            }
+        }
+        Self { definitions }
+    }
+
+    /// Marks up every whole-word occurrence of an abbreviation in `slices` with a dotted
+    /// underline. The first occurrence in the document is followed by the expansion, in brackets
+    pub fn apply(&mut self, slices: Vec<(String, Format)>) -> Vec<(String, Format)> {
+        if self.definitions.is_empty() {
+            return slices;
+        }
+
+        let mut applied = Vec::with_capacity(slices.len());
+        for (mut slice, format) in slices {
+            // code is left exactly as it was written
+            if format.is_code() {
+                applied.push((slice, format));
+                continue;
+            }
+            while let Some((start, index)) = self.first_occurrence(&slice) {
+                let Some(definition) = self.definitions.get_mut(index) else {
+                    break;
+                };
+                let end = start + definition.abbreviation.len();
+
+                let rest = slice.split_off(end);
+                let abbreviation = slice.split_off(start);
+                applied.push((slice, format.clone()));
+                applied.push((
+                    abbreviation,
+                    format.union(&Format::new().set_dotted_underline()),
+                ));
+                if !definition.expanded && !definition.expansion.is_empty() {
+                    applied.push((format!(" ({})", definition.expansion), format.clone()));
+                    definition.expanded = true;
+                }
+                slice = rest;
+            }
+            applied.push((slice, format));
+        }
+
+        applied.retain(|(slice, _)| !slice.is_empty());
+        applied
+    }
+
+    /// The byte index of the earliest whole-word abbreviation in `text`, along with the index of
+    /// its definition. Where two abbreviations start at the same place, the longer one is used
+    fn first_occurrence(&self, text: &str) -> Option<(usize, usize)> {
+        self.definitions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, definition)| {
+                text.match_indices(&definition.abbreviation)
+                    .find(|(start, abbreviation)| {
+                        is_word_boundary(text, *start)
+                            && is_word_boundary(text, start + abbreviation.len())
+                    })
+                    .map(|(start, _)| (start, index, definition.abbreviation.len()))
+            })
+            .min_by_key(|(start, _, length)| (*start, usize::MAX - length))
+            .map(|(start, index, _)| (start, index))
+    }
+}
+
+/// The abbreviation and expansion given by an abbreviation definition, if `line` is one
+pub fn definition(line: &str) -> Option<(&str, &str)> {
+    let (abbreviation, expansion) = line.trim().strip_prefix("*[")?.split_once("]:")?;
+    (!abbreviation.is_empty()).then_some((abbreviation, expansion.trim()))
+}
+
+/// Whether the byte index `index` of `text` lies between two characters that aren't both part
+/// of a word
+fn is_word_boundary(text: &str, index: usize) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let before = text
+        .get(..index)
+        .and_then(|before| before.chars().next_back());
+    let after = text.get(index..).and_then(|after| after.chars().next());
+    !(before.is_some_and(is_word_char) && after.is_some_and(is_word_char))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abbreviated() -> Format {
+        Format::new().set_dotted_underline()
+    }
+
+    mod definitions {
+        use super::*;
+
+        #[test]
+        fn definition_lines() {
+            assert_eq!(
+                Some(("HTML", "HyperText Markup Language")),
+                definition("*[HTML]: HyperText Markup Language")
+            );
+            assert_eq!(Some(("W3C", "")), definition("  *[W3C]:"));
+        }
+
+        #[test]
+        fn not_definitions() {
+            assert_eq!(None, definition("*[]: nothing"));
+            assert_eq!(None, definition("[HTML]: HyperText Markup Language"));
+            assert_eq!(None, definition("*[HTML] HyperText Markup Language"));
+        }
+
+        #[test]
+        fn first_definition_wins() {
+            let abbreviations = Abbreviations::collect("*[A]: first\nlorem\n*[A]: second");
+            assert_eq!(1, abbreviations.definitions.len());
+            assert!(abbreviations
+                .definitions
+                .iter()
+                .all(|definition| definition.expansion == "first"));
+        }
+    }
+
+    mod application {
+        use super::*;
+
+        #[test]
+        fn expansion_only_on_first_use() {
+            let mut abbreviations = Abbreviations::collect("*[HTML]: HyperText Markup Language");
+            assert_eq!(
+                vec![
+                    ("HTML".to_owned(), abbreviated()),
+                    (" (HyperText Markup Language)".to_owned(), Format::new()),
+                    (" and ".to_owned(), Format::new()),
+                    ("HTML".to_owned(), abbreviated()),
+                ],
+                abbreviations.apply(vec![("HTML and HTML".to_owned(), Format::new())])
+            );
+            assert_eq!(
+                vec![("HTML".to_owned(), abbreviated())],
+                abbreviations.apply(vec![("HTML".to_owned(), Format::new())])
+            );
+        }
+
+        #[test]
+        fn only_whole_words_are_abbreviations() {
+            let mut abbreviations = Abbreviations::collect("*[CSS]: Cascading Style Sheets");
+            let slices = vec![("CSSOM xCSS CSS_3".to_owned(), Format::new())];
+            assert_eq!(slices.clone(), abbreviations.apply(slices));
+        }
+
+        #[test]
+        fn existing_formatting_is_kept() {
+            let bold = Format::new().set_bold();
+            let mut abbreviations = Abbreviations::collect("*[W3C]: World Wide Web Consortium");
+            assert_eq!(
+                vec![
+                    ("the ".to_owned(), bold.clone()),
+                    ("W3C".to_owned(), bold.union(&abbreviated())),
+                    (" (World Wide Web Consortium)".to_owned(), bold.clone()),
+                ],
+                abbreviations.apply(vec![("the W3C".to_owned(), bold)])
+            );
+        }
+
+        #[test]
+        fn longest_abbreviation_is_preferred() {
+            let mut abbreviations =
+                Abbreviations::collect("*[HTML]: HyperText Markup Language\n*[HTML 5]: HTML, v5");
+            assert_eq!(
+                vec![
+                    ("HTML 5".to_owned(), abbreviated()),
+                    (" (HTML, v5)".to_owned(), Format::new()),
+                ],
+                abbreviations.apply(vec![("HTML 5".to_owned(), Format::new())])
+            );
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt::{self, Display};
+use std::ops::{Range, RangeInclusive};
+
+use serde::Deserialize;
+
+use crate::formatting::{Format, RESET};
+use crate::markdown::{render_lines, DocumentElement};
+use crate::options::RenderOptions;
+
+/// A review comment on a range of lines of a document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    /// The one-based lines the comment is on
+    pub lines: RangeInclusive<usize>,
+    pub comment: String,
+}
+
+/// The formats a sidecar file of annotations can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SidecarFormat {
+    Json,
+    Toml,
+}
+
+/// Something wrong with a sidecar file of annotations
+#[derive(Debug)]
+pub enum SidecarError {
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    /// An annotation's lines end before they start, or start at line 0
+    InvalidLines {
+        start: usize,
+        end: usize,
+    },
+}
+
+impl Display for SidecarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "invalid annotations: {error}"),
+            // `toml`'s errors otherwise show where they are, which `span` is for
+            Self::Toml(error) => write!(f, "invalid annotations: {}", error.message()),
+            Self::InvalidLines { start, end } => {
+                write!(f, "invalid annotation lines {start} to {end}")
+            }
+        }
+    }
+}
+
+impl SidecarError {
+    /// The byte range of `text`, the sidecar that couldn't be parsed, that the error is about, if
+    /// it is about one place in it
+    #[must_use]
+    pub fn span(&self, text: &str) -> Option<Range<usize>> {
+        match self {
+            Self::Json(error) => {
+                // `serde_json` counts lines from 1 and columns (in bytes) from 1, or gives line 0
+                // for errors that aren't about a place
+                let line = error.line().checked_sub(1)?;
+                let line_start = match line.checked_sub(1) {
+                    None => 0,
+                    Some(newlines) => text.match_indices('\n').nth(newlines)?.0 + 1,
+                };
+                let mut offset = (line_start + error.column().saturating_sub(1)).min(text.len());
+                while !text.is_char_boundary(offset) {
+                    offset -= 1;
+                }
+                Some(offset..offset)
+            }
+            Self::Toml(error) => error.span(),
+            Self::InvalidLines { .. } => None,
+        }
+    }
+}
+
+impl std::error::Error for SidecarError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(error) => Some(error),
+            Self::Toml(error) => Some(error),
+            Self::InvalidLines { .. } => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Sidecar {
+    annotations: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    /// Either a single line, or the first and last lines of a range
+    lines: Lines,
+    comment: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Lines {
+    Line(usize),
+    Range([usize; 2]),
+}
+
+/// Reads a sidecar file of annotations, a list of comments each on a single line or an inclusive
+/// range of lines. In JSON:
+///
+/// ```json
+/// {"annotations": [{"lines": [3, 5], "comment": "Needs a source"}]}
+/// ```
+///
+/// or in TOML:
+///
+/// ```toml
+/// [[annotations]]
+/// lines = 7
+/// comment = "Typo"
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `text` isn't a valid sidecar, or if any annotation's lines are out of order
+/// or include line 0
+pub fn parse_sidecar(text: &str, format: SidecarFormat) -> Result<Vec<Annotation>, SidecarError> {
+    let sidecar: Sidecar = match format {
+        SidecarFormat::Json => serde_json::from_str(text).map_err(SidecarError::Json)?,
+        SidecarFormat::Toml => toml::from_str(text).map_err(SidecarError::Toml)?,
+    };
+
+    sidecar
+        .annotations
+        .into_iter()
+        .map(|entry| {
+            let (start, end) = match entry.lines {
+                Lines::Line(line) => (line, line),
+                Lines::Range([start, end]) => (start, end),
+            };
+            if start == 0 || end < start {
+                return Err(SidecarError::InvalidLines { start, end });
+            }
+            Ok(Annotation {
+                lines: start..=end,
+                comment: entry.comment,
+            })
+        })
+        .collect()
+}
+
+/// Renders `elements` as [`render`](crate::render) does, with each annotation shown as a callout
+/// after the first element that reaches its first line. `text` is the source the elements were
+/// parsed from, for working out which lines they are on. Annotations past the last element are
+/// shown at the end
+#[must_use]
+pub fn render_annotated(
+    text: &str,
+    elements: Vec<Box<dyn DocumentElement>>,
+    annotations: &[Annotation],
+    options: &RenderOptions,
+) -> String {
+    let mut annotations: Vec<_> = annotations.iter().collect();
+    annotations.sort_by_key(|annotation| annotation.lines.start());
+    let mut annotations = annotations.into_iter().peekable();
+
+    let mut output = String::new();
+    for element in elements {
+        if !element.is_hidden(options) {
+            element.render_into(options, &mut output);
+            if options.resets && !output.ends_with(RESET) {
+                output.push_str(RESET);
+            }
+            output.push_str("\n\n");
+        }
+        let span = element.span();
+        let last_line = line_of(text, span.end.saturating_sub(1).max(span.start));
+        while let Some(annotation) =
+            annotations.next_if(|annotation| *annotation.lines.start() <= last_line)
+        {
+            output += &(render_callout(annotation, options) + "\n\n");
+        }
+    }
+    for annotation in annotations {
+        output += &(render_callout(annotation, options) + "\n\n");
+    }
+
+    output
+}
+
+/// The one-based line of `text` that the byte at `offset` is on
+fn line_of(text: &str, offset: usize) -> usize {
+    let before = text.get(..offset).unwrap_or(text);
+    before.matches('\n').count() + 1
+}
+
+/// Renders an annotation as a callout, its lines and comment set off by a bar down its side, all
+/// in the theme's formatting for annotations
+fn render_callout(annotation: &Annotation, options: &RenderOptions) -> String {
+    let format = options.theme.annotation_format();
+    let (start, end) = (annotation.lines.start(), annotation.lines.end());
+    let labels = options.labels;
+    let label = if start == end {
+        format!("{} {start}: ", labels.line)
+    } else {
+        format!("{} {start}–{end}: ", labels.lines)
+    };
+    let slices = [
+        (label, format.clone().set_bold()),
+        (annotation.comment.clone(), format.clone()),
+    ];
+
+    let mut bar = String::new();
+    // writing to a `String` can't fail
+    let _ = format.write_codes_for_format_change(&Format::new(), options.sgr_style, &mut bar);
+    bar += options.theme.glyphs.quote_bar;
+    let _ = if options.resets {
+        format.write_reset_codes(&mut bar)
+    } else {
+        Format::new().write_codes_for_format_change(format, options.sgr_style, &mut bar)
+    };
+    bar.push(' ');
+    render_lines(&slices, &options.clone().narrowed(2))
+        .lines()
+        .map(|line| bar.clone() + line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{Glyphs, Theme};
+
+    mod sidecars {
+        use super::*;
+
+        #[test]
+        fn json() {
+            assert_eq!(
+                vec![
+                    Annotation {
+                        lines: 3..=5,
+                        comment: "lorem".to_owned()
+                    },
+                    Annotation {
+                        lines: 7..=7,
+                        comment: "ipsum".to_owned()
+                    },
+                ],
+                parse_sidecar(
+                    r#"{"annotations": [
+                        {"lines": [3, 5], "comment": "lorem"},
+                        {"lines": 7, "comment": "ipsum"}
+                    ]}"#,
+                    SidecarFormat::Json
+                )
+                .unwrap()
+            );
+        }
+
+        #[test]
+        fn toml() {
+            assert_eq!(
+                vec![Annotation {
+                    lines: 2..=4,
+                    comment: "lorem".to_owned()
+                }],
+                parse_sidecar(
+                    "[[annotations]]\nlines = [2, 4]\ncomment = \"lorem\"\n",
+                    SidecarFormat::Toml
+                )
+                .unwrap()
+            );
+        }
+
+        #[test]
+        fn invalid_lines() {
+            assert!(matches!(
+                parse_sidecar(
+                    r#"{"annotations": [{"lines": [5, 3], "comment": "lorem"}]}"#,
+                    SidecarFormat::Json
+                ),
+                Err(SidecarError::InvalidLines { start: 5, end: 3 })
+            ));
+            assert!(matches!(
+                parse_sidecar(
+                    r#"{"annotations": [{"lines": 0, "comment": "lorem"}]}"#,
+                    SidecarFormat::Json
+                ),
+                Err(SidecarError::InvalidLines { start: 0, end: 0 })
+            ));
+        }
+
+        #[test]
+        fn syntax_errors_have_a_place() {
+            let json = "{\n  \"annotations\": [,]\n}";
+            let error = parse_sidecar(json, SidecarFormat::Json).unwrap_err();
+            assert_eq!(Some(20..20), error.span(json));
+
+            let toml = "[[annotations]]\nlines = \ncomment = \"lorem\"";
+            let error = parse_sidecar(toml, SidecarFormat::Toml).unwrap_err();
+            assert_eq!(Some(24), error.span(toml).map(|span| span.start));
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        fn render(text: &str, annotations: &[(RangeInclusive<usize>, &str)]) -> String {
+            let annotations: Vec<_> = annotations
+                .iter()
+                .map(|(lines, comment)| Annotation {
+                    lines: lines.clone(),
+                    comment: (*comment).to_owned(),
+                })
+                .collect();
+            let options = RenderOptions::new().theme(
+                Theme::dark()
+                    .annotation(Format::new())
+                    .glyphs(Glyphs::ascii()),
+            );
+            render_annotated(text, crate::parse(text), &annotations, &options)
+        }
+
+        #[test]
+        fn callouts_follow_the_element_they_start_in() {
+            let bold = Format::new().set_bold();
+            let label = |label: &str| {
+                format!(
+                    "| {}{label}{}",
+                    bold.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&bold)
+                )
+            };
+            assert_eq!(
+                format!(
+                    "lorem ipsum\n\n{}dolor\n\nsit\n\n{}amet\n\n{}consectetur\n\n",
+                    label("lines 2–3: "),
+                    label("line 4: "),
+                    label("line 9: "),
+                ),
+                render(
+                    "lorem\nipsum\n\nsit",
+                    &[(2..=3, "dolor"), (4..=4, "amet"), (9..=9, "consectetur")]
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use crate::code_block::fence_for;
+use crate::input::{constrained, lines, Prepared};
+
+/// The schemes of URLs that are made links of, along with `link:`, which can come before any URL
+/// or path
+const SCHEMES: [&str; 4] = ["https://", "http://", "mailto:", "link:"];
+
+/// Whether `line` opens or closes a block shown as it is written: a listing block (`----`) or a
+/// literal block (`....`)
+fn is_verbatim_delimiter(line: &str) -> bool {
+    let line = line.trim_end();
+    line.len() >= 4 && (line.chars().all(|c| c == '-') || line.chars().all(|c| c == '.'))
+}
+
+/// Parses a section title (`== Title`), returning its level, counting the document title as 1,
+/// and the title itself
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let title = line.trim_start_matches('=');
+    let level = line.len() - title.len();
+    let title = title.strip_prefix(' ')?.trim();
+    // closing marks are optional, as in `== Title ==`
+    let title = title.trim_end_matches('=').trim_end();
+    ((1..=6).contains(&level) && !title.is_empty()).then_some((level, title))
+}
+
+/// Translates the marker of a list item (`* item`, `** nested item`, `. numbered item` or
+/// `- item`) into Markdown, nesting items as deep as the marker is long
+fn list_item(line: &str) -> Option<(String, &str)> {
+    let unindented = line.trim_start();
+    let marker = unindented
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '*' | '.' | '-'))?;
+    let item = unindented.trim_start_matches(marker);
+    let depth = unindented.len() - item.len();
+    let item = item.strip_prefix(' ')?.trim_start();
+    if item.is_empty() || (marker == '-' && depth > 1) {
+        return None;
+    }
+    let marker = match marker {
+        '.' => format!("{}1. ", "   ".repeat(depth - 1)),
+        _ => format!("{}* ", "  ".repeat(depth - 1)),
+    };
+    Some((marker, item))
+}
+
+/// The language of a listing block from the attribute line before it, such as `[source,rust]`
+fn source_language(attributes: &str) -> Option<&str> {
+    let mut attributes = attributes.strip_prefix('[')?.strip_suffix(']')?.split(',');
+    let style = attributes.next()?.trim();
+    let language = attributes.next()?.trim();
+    ((style == "source" || style.is_empty()) && !language.is_empty()).then_some(language)
+}
+
+/// Whether `line` is an attribute entry (`:name: value`), which sets an attribute of the document
+/// rather than being part of its content
+fn is_attribute_entry(line: &str) -> bool {
+    line.strip_prefix(':')
+        .and_then(|entry| entry.split_once(':'))
+        .is_some_and(|(name, _)| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '!'))
+        })
+}
+
+/// Parses a link at the start of `text` (`https://example.com[label]`, or a bare URL), returning
+/// its length, target and label
+fn link(text: &str) -> Option<(usize, &str, &str)> {
+    let scheme = SCHEMES
+        .into_iter()
+        .find(|scheme| text.starts_with(scheme))?;
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '[')
+        .unwrap_or(text.len());
+    let target = text.get(..end)?;
+    let target = if scheme == "link:" {
+        target.strip_prefix(scheme)?
+    } else {
+        target.trim_end_matches(['.', ',', ';', ':', '!', '?', ')'])
+    };
+    if target.is_empty() || target.len() == scheme.len() {
+        return None;
+    }
+    let label = text
+        .get(end..)
+        .and_then(|rest| rest.strip_prefix('['))
+        .and_then(|rest| rest.get(..rest.find(']')?));
+    match label {
+        Some(label) => Some((end + label.len() + 2, target, label)),
+        None if scheme != "link:" => Some((target.len(), target, "")),
+        None => None,
+    }
+}
+
+/// Translates the inline markup of a line of AsciiDoc into Markdown: bold (`*bold*`) into strong
+/// emphasis, italics (`_italic_`) into emphasis, monospace as it is, and links with labels
+/// (`https://example.com[label]`) into wiki links (`[[https://example.com|label]]`)
+fn inline(line: &str) -> String {
+    let mut markdown = String::with_capacity(line.len());
+    let mut rest = line;
+    let mut previous = None;
+    while let Some(c) = rest.chars().next() {
+        let at_boundary = !previous.is_some_and(char::is_alphanumeric);
+        let mut translated = None;
+        if c == '`' {
+            let length = rest
+                .get(1..)
+                .and_then(|code| code.find('`'))
+                .map_or(1, |end| end + 2);
+            markdown.push_str(rest.get(..length).unwrap_or(rest));
+            rest = rest.get(length..).unwrap_or_default();
+            previous = Some('`');
+            continue;
+        }
+        for (mark, delimiter) in [("**", "**"), ("__", "*")] {
+            if let Some((content, _)) = rest
+                .strip_prefix(mark)
+                .and_then(|content| content.split_once(mark))
+                .filter(|(content, _)| !content.is_empty())
+            {
+                translated = translated.or(Some((
+                    content.len() + mark.len() * 2,
+                    format!("{delimiter}{}{delimiter}", inline(content)),
+                )));
+            }
+        }
+        if translated.is_none() && at_boundary && matches!(c, '*' | '_') {
+            let delimiter = if c == '*' { "**" } else { "*" };
+            translated = constrained(rest, c).map(|content| {
+                (
+                    content.len() + 2,
+                    format!("{delimiter}{}{delimiter}", inline(content)),
+                )
+            });
+        }
+        if translated.is_none() && at_boundary {
+            translated = link(rest).map(|(length, target, label)| {
+                let markup = if label.is_empty() {
+                    target.to_owned()
+                } else {
+                    format!("[[{target}|{label}]]")
+                };
+                (length, markup)
+            });
+        }
+
+        if let Some((length, translated)) = translated {
+            markdown.push_str(&translated);
+            previous = rest.get(..length).and_then(|markup| markup.chars().last());
+            rest = rest.get(length..).unwrap_or_default();
+        } else {
+            markdown.push(c);
+            previous = Some(c);
+            rest = rest.get(c.len_utf8()..).unwrap_or_default();
+        }
+    }
+    markdown
+}
+
+/// Translates basic AsciiDoc into Markdown, a line at a time so that element spans can be mapped
+/// back onto it. Section titles become headings, listing and literal blocks become fenced code
+/// blocks (in the language of a `[source,language]` line before them), list markers are
+/// translated, and block titles (`.Title`) are shown in bold. Comments, attribute entries and
+/// other attribute lines are left out
+pub(crate) fn to_markdown(text: &str) -> Prepared<'static> {
+    let lines = lines(text);
+    let mut markdown = Prepared::lines();
+    let mut language = None;
+    let mut index = 0;
+    while let Some(&(offset, line)) = lines.get(index) {
+        index += 1;
+
+        if is_verbatim_delimiter(line) || line.trim_end() == "////" {
+            let delimiter = line.trim_end();
+            let end = lines
+                .iter()
+                .skip(index)
+                .position(|(_, line)| line.trim_end() == delimiter)
+                .map_or(lines.len(), |length| index + length);
+            let content = lines.get(index..end).unwrap_or_default();
+            let close = lines.get(end).map_or(text.len(), |(offset, _)| *offset);
+            index = end + 1;
+            if delimiter == "////" {
+                continue;
+            }
+            let code: Vec<_> = content.iter().map(|(_, line)| *line).collect();
+            let fence = fence_for(&code.join("\n"));
+            let info = language.take().unwrap_or_default();
+            markdown.push_line(&format!("{fence}{info}"), offset);
+            for (offset, line) in content {
+                markdown.push_line(line, *offset);
+            }
+            markdown.push_line(&fence, close);
+            continue;
+        }
+        if line.starts_with("```") {
+            // fenced code blocks are written the same way in both
+            markdown.push_line(line, offset);
+            while let Some(&(offset, line)) = lines.get(index) {
+                index += 1;
+                markdown.push_line(line, offset);
+                if line.trim_end() == "```" {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            language = source_language(trimmed).map(str::to_owned);
+            continue;
+        }
+        language = None;
+        if (line.starts_with("//") && !line.starts_with("///"))
+            || is_attribute_entry(line)
+            || trimmed == "+"
+            || trimmed == "<<<"
+        {
+            continue;
+        }
+        if trimmed == "'''" {
+            markdown.push_line("---", offset);
+        } else if let Some((level, title)) = heading(line) {
+            markdown.push_line(&format!("{} {}", "#".repeat(level), inline(title)), offset);
+        } else if let Some((marker, item)) = list_item(line) {
+            markdown.push_line(&format!("{marker}{}", inline(item)), offset);
+        } else if let Some(title) = line
+            .strip_prefix('.')
+            .filter(|title| !title.starts_with(['.', ' ']) && !title.is_empty())
+        {
+            markdown.push_line(&format!("**{}**", inline(title)), offset);
+            markdown.push_line("", offset);
+        } else {
+            markdown.push_line(&inline(line), offset);
+        }
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translate(text: &str) -> String {
+        to_markdown(text).text.into_owned()
+    }
+
+    mod blocks {
+        use super::*;
+
+        #[test]
+        fn headings_and_lists() {
+            assert_eq!(
+                "# Lorem\n\n## Ipsum\n\n* dolor\n  * sit\n1. amet\n   1. consectetur\n",
+                translate("= Lorem\n\n== Ipsum\n\n* dolor\n** sit\n. amet\n.. consectetur\n")
+            );
+        }
+
+        #[test]
+        fn listing_blocks_become_fenced_code() {
+            assert_eq!(
+                "```rust\nfn main() {}\n\n*lorem*\n```\nipsum\n",
+                translate("[source,rust]\n----\nfn main() {}\n\n*lorem*\n----\nipsum\n")
+            );
+        }
+
+        #[test]
+        fn comments_and_attributes_are_left_out() {
+            assert_eq!(
+                "# Lorem\nipsum\n",
+                translate("= Lorem\n:toc: left\n// a comment\n////\nmore\n////\nipsum\n")
+            );
+        }
+    }
+
+    mod inline {
+        use super::*;
+
+        #[test]
+        fn inline_markup() {
+            assert_eq!(
+                "**lorem** *ipsum* `dolor` **s**it [[https://example.com|amet]] 2*3*4",
+                inline("*lorem* _ipsum_ `dolor` **s**it https://example.com[amet] 2*3*4")
+            );
+        }
+    }
+}
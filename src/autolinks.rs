@@ -0,0 +1,231 @@
+use std::ops::Range;
+
+/// A bare URL or email address in text, which is made into a link without any markup
+#[derive(Debug, PartialEq, Eq)]
+pub struct Autolink {
+    /// The byte range of the link in the text it was found in
+    pub span: Range<usize>,
+    /// Where the link points, with a scheme added if the text doesn't have one
+    pub target: String,
+}
+
+/// Finds the first autolink in `text`, as per GitHub Flavored Markdown's autolink extension: a
+/// domain starting with `www.`, an `http://` or `https://` URL, or an email address
+pub fn find(text: &str) -> Option<Autolink> {
+    text.char_indices().find_map(|(start, _)| {
+        let rest = text.get(start..)?;
+        let preceding = text.get(..start)?.chars().next_back();
+        url_at(rest, preceding)
+            .or_else(|| email_at(rest, preceding))
+            .map(|(length, target)| Autolink {
+                span: start..start + length,
+                target,
+            })
+    })
+}
+
+/// The length and target of the URL at the very start of `text`, if there is one there
+fn url_at(text: &str, preceding: Option<char>) -> Option<(usize, String)> {
+    if preceding.is_some_and(|c| !c.is_whitespace() && !"*_~(".contains(c)) {
+        return None;
+    }
+    let (domain_start, scheme) = if text.starts_with("www.") {
+        (0, "http://")
+    } else {
+        let scheme = ["http://", "https://"]
+            .into_iter()
+            .find(|scheme| text.starts_with(scheme))?;
+        (scheme.len(), "")
+    };
+
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(text.len());
+    let end = trim_trailing_punctuation(text, end);
+
+    let domain = text.get(domain_start..end)?;
+    let domain_end = domain.find(|c| !is_domain_char(c)).unwrap_or(domain.len());
+    let domain = domain.get(..domain_end)?.trim_end_matches('.');
+    if !is_valid_domain(domain) {
+        return None;
+    }
+
+    let url = text.get(..end)?;
+    Some((end, format!("{scheme}{url}")))
+}
+
+/// The length and target of the email address at the very start of `text`, if there is one
+/// there
+fn email_at(text: &str, preceding: Option<char>) -> Option<(usize, String)> {
+    // an address only starts at the beginning of a run of the characters it can contain
+    if preceding.is_some_and(is_local_part_char) {
+        return None;
+    }
+    let at = text.find(|c| !is_local_part_char(c))?;
+    if at == 0 || !text.get(at..)?.starts_with('@') {
+        return None;
+    }
+
+    let domain_start = at + 1;
+    let domain = text.get(domain_start..)?;
+    let domain_end = domain
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+        .unwrap_or(domain.len());
+    let domain = domain.get(..domain_end)?.trim_end_matches('.');
+    let is_valid = domain.contains('.')
+        && domain.split('.').all(|segment| !segment.is_empty())
+        && !domain.ends_with(['-', '_']);
+    if !is_valid {
+        return None;
+    }
+
+    let end = domain_start + domain.len();
+    let address = text.get(..end)?;
+    Some((end, format!("mailto:{address}")))
+}
+
+/// Moves `end` back past any punctuation that is more likely to belong to the surrounding text
+/// than to the link: full stops and the like, closing brackets without an opening one in the link,
+/// and entity references
+fn trim_trailing_punctuation(text: &str, mut end: usize) -> usize {
+    while let Some(link) = text.get(..end) {
+        let Some(last) = link.chars().next_back() else {
+            break;
+        };
+        if "?!.,:*_~".contains(last)
+            || (last == ')' && link.matches(')').count() > link.matches('(').count())
+        {
+            end -= 1;
+        } else if last == ';' {
+            let entity = link
+                .get(..end - 1)
+                .and_then(|before| Some((before.rfind('&')?, before)));
+            match entity {
+                Some((ampersand, before))
+                    if before.get(ampersand + 1..).is_some_and(|name| {
+                        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric())
+                    }) =>
+                {
+                    end = ampersand;
+                }
+                _ => break,
+            }
+        } else {
+            break;
+        }
+    }
+    end
+}
+
+/// Whether `domain` is made up of at least two segments separated by full stops, with no
+/// underscores in the last two
+fn is_valid_domain(domain: &str) -> bool {
+    let segments: Vec<_> = domain.split('.').collect();
+    segments.len() >= 2
+        && segments.iter().all(|segment| !segment.is_empty())
+        && segments
+            .iter()
+            .rev()
+            .take(2)
+            .all(|segment| !segment.contains('_'))
+}
+
+fn is_domain_char(c: char) -> bool {
+    c.is_alphanumeric() || "-_.".contains(c)
+}
+
+fn is_local_part_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ".-_+".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(text: &str) -> Option<(&str, String)> {
+        find(text).map(|autolink| (text.get(autolink.span).unwrap_or_default(), autolink.target))
+    }
+
+    mod urls {
+        use super::*;
+
+        #[test]
+        fn www_domains_get_a_scheme() {
+            assert_eq!(
+                Some((
+                    "www.commonmark.org/help",
+                    "http://www.commonmark.org/help".to_owned()
+                )),
+                link("visit www.commonmark.org/help for more")
+            );
+        }
+
+        #[test]
+        fn urls_with_a_scheme() {
+            assert_eq!(
+                Some((
+                    "https://example.com/a?b=c",
+                    "https://example.com/a?b=c".to_owned()
+                )),
+                link("see https://example.com/a?b=c")
+            );
+        }
+
+        #[test]
+        fn trailing_punctuation_is_left_out() {
+            assert_eq!(
+                Some(("www.example.com", "http://www.example.com".to_owned())),
+                link("(see www.example.com).")
+            );
+            assert_eq!(
+                Some((
+                    "www.example.com/a_(b)",
+                    "http://www.example.com/a_(b)".to_owned()
+                )),
+                link("www.example.com/a_(b)?")
+            );
+            assert_eq!(
+                Some(("www.example.com/a", "http://www.example.com/a".to_owned())),
+                link("www.example.com/a&hellip;")
+            );
+        }
+
+        #[test]
+        fn invalid_domains() {
+            assert_eq!(None, link("www. lorem"));
+            assert_eq!(None, link("www.example_site.com"));
+            assert_eq!(None, link("https://localhost"));
+        }
+
+        #[test]
+        fn must_start_a_word() {
+            assert_eq!(None, link("awww.example.com"));
+            assert_eq!(
+                Some(("www.example.com", "http://www.example.com".to_owned())),
+                link("_www.example.com_")
+            );
+        }
+    }
+
+    mod emails {
+        use super::*;
+
+        #[test]
+        fn addresses_get_a_mailto_scheme() {
+            assert_eq!(
+                Some((
+                    "foo.bar+baz@example.com",
+                    "mailto:foo.bar+baz@example.com".to_owned()
+                )),
+                link("write to foo.bar+baz@example.com.")
+            );
+        }
+
+        #[test]
+        fn invalid_addresses() {
+            assert_eq!(None, link("lorem@ipsum"));
+            assert_eq!(None, link("lorem@ipsum.dolor_"));
+            assert_eq!(None, link("@example.com"));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+mod epub;
+mod events;
+mod html;
+mod latex;
+mod man;
+mod pandoc;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod speech;
+#[cfg(feature = "svg")]
+mod svg;
+mod text;
+
+use std::path::Path;
+
+use crate::formatting::Format;
+use crate::list::List;
+use crate::table::Table;
+use crate::title::TitleBlock;
+
+pub use epub::Epub;
+pub use events::Events;
+#[cfg(feature = "cli")]
+pub(crate) use html::escape;
+pub use html::Html;
+pub use latex::Latex;
+pub use man::Man;
+pub use pandoc::PandocJson;
+#[cfg(feature = "pdf")]
+pub use pdf::Pdf;
+pub use speech::Speech;
+#[cfg(feature = "svg")]
+pub(crate) use svg::from_terminal as svg_from_terminal;
+pub use text::Text;
+
+/// Something that parsed elements can be rendered into, other than a terminal. Each element calls
+/// the method for its kind, in document order, after which [`Backend::finish`] gives the output
+pub trait Backend: Send {
+    /// The title, author, date and language from the document's front matter, given before the
+    /// title and byline are, for formats that record them apart from the text
+    fn metadata(&mut self, _title_block: &TitleBlock) {}
+
+    /// A paragraph of formatted text, in which `\n` marks a hard line break
+    fn paragraph(&mut self, text: &[(String, Format)]);
+
+    /// A heading of the given level (1 to 6), including its section number, if it has one
+    fn heading(&mut self, level: u8, text: &[(String, Format)]);
+
+    fn thematic_break(&mut self);
+
+    fn table(&mut self, table: &Table);
+
+    /// The start of a list. Each of its items follows, from [`Backend::item_start`] to
+    /// [`Backend::item_end`], then [`Backend::list_end`]
+    fn list_start(&mut self, list: &List);
+
+    fn list_end(&mut self);
+
+    /// The start of the item at `index` in the list, the blocks of which follow. `task` is
+    /// whether the item is checked, if it is a task list item
+    fn item_start(&mut self, index: usize, task: Option<bool>);
+
+    fn item_end(&mut self);
+
+    /// The start of a blockquote, the blocks of which follow, then its attribution, if it has
+    /// one, then [`Backend::quote_end`]
+    fn quote_start(&mut self);
+
+    /// Who or what the current blockquote is attributed to, without the dash before it
+    fn attribution(&mut self, text: &[(String, Format)]);
+
+    fn quote_end(&mut self);
+
+    /// A fenced code block, with the info string after its opening fence, which is empty if there
+    /// isn't one
+    fn code_block(&mut self, info: &str, code: &str);
+
+    /// An HTML comment, given the text between `<!--` and `-->`, which most formats leave out
+    fn comment(&mut self, _text: &str) {}
+
+    /// Makes room up front for about `additional` more bytes of output, for backends that write
+    /// it into a buffer as they go
+    fn reserve(&mut self, _additional: usize) {}
+
+    /// Finishes the document off and returns the output
+    fn finish(&mut self) -> String;
+
+    /// Finishes the document off and returns the output as bytes, which for binary formats is
+    /// the output proper, rather than whatever text [`Backend::finish`] gives
+    fn finish_bytes(&mut self) -> Vec<u8> {
+        self.finish().into_bytes()
+    }
+}
+
+/// The formats that rendered output can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Text with terminal formatting codes, as normally written to standard output
+    Terminal,
+    /// Text without any formatting
+    Text,
+    Html,
+    /// A roff man page, for the given manual section
+    Man(u8),
+    Latex,
+    /// An EPUB book, which is binary, so is only given in full by [`render_bytes`](crate::render_bytes)
+    Epub,
+    /// A stream of events, one JSON object per line, describing the document's structure
+    Events,
+    /// Pandoc's JSON representation of the document, for pandoc to write out in other formats
+    PandocJson,
+    /// A PDF document, which is binary, so is only given in full by
+    /// [`render_bytes`](crate::render_bytes)
+    #[cfg(feature = "pdf")]
+    Pdf,
+    /// Plain text for screen readers and speech synthesis, with visual cues said in words
+    Speech,
+    /// An SVG image of the terminal output, for sharing it as it looks in a terminal
+    #[cfg(feature = "svg")]
+    Svg,
+}
+
+/// An output format that sani was built without, named by the feature that it needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MissingFeature(pub &'static str);
+
+impl std::fmt::Display for MissingFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sani was built without the {} feature", self.0)
+    }
+}
+
+impl std::error::Error for MissingFeature {}
+
+impl OutputFormat {
+    /// Works out the output format from a file's extension: `.html` (or `.htm`), `.txt`, `.tex`,
+    /// `.epub`, `.jsonl` for events, `.json` for pandoc's JSON, `.pdf`, `.svg`, or a manual section
+    /// number (`.1` to `.9`) for man pages. Anything else gets terminal output
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MissingFeature`] for `.pdf` and `.svg` when sani is built without the `pdf` or
+    /// `svg` feature, rather than writing terminal output to a file named for another format
+    pub fn from_path(path: &Path) -> Result<Self, MissingFeature> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        Ok(match extension {
+            "html" | "htm" => Self::Html,
+            "txt" => Self::Text,
+            "tex" => Self::Latex,
+            "epub" => Self::Epub,
+            "jsonl" => Self::Events,
+            "json" => Self::PandocJson,
+            #[cfg(feature = "pdf")]
+            "pdf" => Self::Pdf,
+            #[cfg(not(feature = "pdf"))]
+            "pdf" => return Err(MissingFeature("pdf")),
+            #[cfg(feature = "svg")]
+            "svg" => Self::Svg,
+            #[cfg(not(feature = "svg"))]
+            "svg" => return Err(MissingFeature("svg")),
+            _ => match extension.parse() {
+                Ok(section @ 1..=9) => Self::Man(section),
+                _ => Self::Terminal,
+            },
+        })
+    }
+
+    /// Whether output in this format is binary rather than text
+    #[must_use]
+    pub const fn is_binary(self) -> bool {
+        match self {
+            Self::Epub => true,
+            #[cfg(feature = "pdf")]
+            Self::Pdf => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod output_formats {
+        use super::*;
+
+        #[test]
+        fn format_from_extension() {
+            for (path, format) in [
+                ("out.html", OutputFormat::Html),
+                ("out.htm", OutputFormat::Html),
+                ("notes/out.txt", OutputFormat::Text),
+                ("sani.1", OutputFormat::Man(1)),
+                ("sani.conf.5", OutputFormat::Man(5)),
+                ("out.tex", OutputFormat::Latex),
+                ("book.epub", OutputFormat::Epub),
+                ("events.jsonl", OutputFormat::Events),
+                ("ast.json", OutputFormat::PandocJson),
+                #[cfg(feature = "pdf")]
+                ("out.pdf", OutputFormat::Pdf),
+                #[cfg(feature = "svg")]
+                ("out.svg", OutputFormat::Svg),
+                ("out.ans", OutputFormat::Terminal),
+                ("sani.0", OutputFormat::Terminal),
+                ("out", OutputFormat::Terminal),
+            ] {
+                assert_eq!(
+                    Ok(format),
+                    OutputFormat::from_path(Path::new(path)),
+                    "{path}"
+                );
+            }
+        }
+
+        #[test]
+        fn formats_built_without_are_errors() {
+            #[cfg(not(feature = "pdf"))]
+            assert_eq!(
+                Err(MissingFeature("pdf")),
+                OutputFormat::from_path(Path::new("out.pdf"))
+            );
+            #[cfg(not(feature = "svg"))]
+            assert_eq!(
+                Err(MissingFeature("svg")),
+                OutputFormat::from_path(Path::new("out.svg"))
+            );
+            assert_eq!(
+                "sani was built without the pdf feature",
+                MissingFeature("pdf").to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,477 @@
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::formatting::Format;
+use crate::list::List;
+use crate::locale::Labels;
+use crate::table::Table;
+use crate::title::TitleBlock;
+
+use super::html::{escape, Html};
+use super::Backend;
+
+/// Renders an EPUB 3 book, with the whole document as its one chapter. The first level 1 heading
+/// gives the book's title, every heading has an entry in its table of contents, and the author,
+/// date and language in the document's front matter are recorded in its metadata. As an EPUB is a ZIP archive rather than text, the book itself comes
+/// from [`Backend::finish_bytes`]; [`Backend::finish`] only gives the chapter's XHTML
+#[derive(Debug)]
+pub struct Epub {
+    html: Html,
+    title: Option<String>,
+    /// The level and text of each heading so far, for the table of contents
+    headings: Vec<(u8, String)>,
+    author: Option<String>,
+    date: Option<String>,
+    language: Option<String>,
+    labels: Labels,
+}
+
+impl Epub {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            html: Html::xhtml(),
+            title: None,
+            headings: Vec::new(),
+            author: None,
+            date: None,
+            language: None,
+            labels: Labels::english(),
+        }
+    }
+
+    /// Sets the text generated for the book, such as its title if the document has none
+    #[must_use]
+    pub const fn labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the language the book is written in, as a language tag such as `en-GB`, if the
+    /// document's front matter doesn't give one. Without either, the language is recorded as
+    /// undetermined (`und`)
+    #[must_use]
+    pub fn language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_owned());
+        self
+    }
+}
+
+impl Default for Epub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for Epub {
+    fn metadata(&mut self, title_block: &TitleBlock) {
+        self.author = title_block.author().map(str::to_owned);
+        self.date = title_block.date().map(str::to_owned);
+        if let Some(language) = title_block.language() {
+            self.language = Some(language.to_owned());
+        }
+    }
+
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        self.html.paragraph(text);
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        let plain: String = text.iter().map(|(slice, _)| slice.as_str()).collect();
+        if level == 1 && self.title.is_none() {
+            self.title = Some(plain.clone());
+        }
+        self.headings.push((level, plain));
+        self.html
+            .heading_with_id(level, text, &heading_id(self.headings.len()));
+    }
+
+    fn thematic_break(&mut self) {
+        self.html.thematic_break();
+    }
+
+    fn table(&mut self, table: &Table) {
+        self.html.table(table);
+    }
+
+    fn list_start(&mut self, list: &List) {
+        self.html.list_start(list);
+    }
+
+    fn list_end(&mut self) {
+        self.html.list_end();
+    }
+
+    fn item_start(&mut self, index: usize, task: Option<bool>) {
+        self.html.item_start(index, task);
+    }
+
+    fn item_end(&mut self) {
+        self.html.item_end();
+    }
+
+    fn quote_start(&mut self) {
+        self.html.quote_start();
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        self.html.attribution(text);
+    }
+
+    fn quote_end(&mut self) {
+        self.html.quote_end();
+    }
+
+    fn code_block(&mut self, info: &str, code: &str) {
+        self.html.code_block(info, code);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.html.reserve(additional);
+    }
+
+    fn finish(&mut self) -> String {
+        format!(
+            include_str!("epub/chapter.xhtml"),
+            title = escape(self.title.as_deref().unwrap_or(self.labels.untitled)),
+            body = self.html.finish()
+        )
+    }
+
+    /// Finishes the document off and returns the packaged book
+    fn finish_bytes(&mut self) -> Vec<u8> {
+        let chapter = self.finish();
+        let title = escape(self.title.as_deref().unwrap_or(self.labels.untitled));
+        let modified = utc_timestamp(now());
+
+        let mut zip = Zip::default();
+        // the MIME type must come first, uncompressed, so that the file can be recognised
+        zip.add("mimetype", "application/epub+zip");
+        zip.add("META-INF/container.xml", CONTAINER);
+        zip.add(
+            "OEBPS/content.opf",
+            &format!(
+                include_str!("epub/content.opf"),
+                identifier = format_args!("urn:sani:{:08x}", crc32(chapter.as_bytes())),
+                title = title,
+                creator = element("creator", self.author.as_deref()),
+                date = element("date", self.date.as_deref()),
+                language = escape(self.language.as_deref().unwrap_or("und")),
+                modified = modified,
+            ),
+        );
+        let contents = if self.headings.is_empty() {
+            format!("<ol><li><a href=\"chapter.xhtml\">{title}</a></li></ol>")
+        } else {
+            table_of_contents(&self.headings, self.labels.untitled)
+        };
+        zip.add(
+            "OEBPS/nav.xhtml",
+            &format!(
+                include_str!("epub/nav.xhtml"),
+                title = title,
+                contents = contents
+            ),
+        );
+        zip.add("OEBPS/chapter.xhtml", &chapter);
+        zip.finish()
+    }
+}
+
+const CONTAINER: &str = include_str!("epub/container.xml");
+
+/// The `id` of the heading that is `number`th in the chapter, counting from 1
+fn heading_id(number: usize) -> String {
+    format!("heading-{number}")
+}
+
+/// The list of links to `headings` in the navigation document, with the headings below each
+/// nested in its item. A heading without text is listed as `untitled`
+fn table_of_contents(headings: &[(u8, String)], untitled: &str) -> String {
+    let mut contents = String::from("<ol>");
+    // the levels of the headings whose items are still open, innermost last
+    let mut open: Vec<u8> = Vec::new();
+    for (index, (level, text)) in headings.iter().enumerate() {
+        match open.last() {
+            Some(last) if level > last => contents += "<ol>",
+            Some(_) => {
+                contents += "</li>";
+                open.pop();
+                while open.last().is_some_and(|last| last >= level) {
+                    contents += "</ol></li>";
+                    open.pop();
+                }
+            }
+            None => {}
+        }
+        let text = if text.is_empty() { untitled } else { text };
+        // writing to a `String` can't fail
+        let _ = write!(
+            contents,
+            "<li><a href=\"chapter.xhtml#{}\">{}</a>",
+            heading_id(index + 1),
+            escape(text)
+        );
+        open.push(*level);
+    }
+    contents += "</li>";
+    contents += &"</ol></li>".repeat(open.len().saturating_sub(1));
+    contents + "</ol>"
+}
+
+/// A line of the package document's metadata for the Dublin Core element `name`, or nothing if
+/// there is no `value` for it
+fn element(name: &str, value: Option<&str>) -> String {
+    value.map_or_else(String::new, |value| {
+        format!("\n    <dc:{name}>{}</dc:{name}>", escape(value))
+    })
+}
+
+/// Writes a ZIP archive, with every file stored as it is rather than compressed
+#[derive(Default)]
+struct Zip {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+impl Zip {
+    /// The date of every file, as the archive format encodes it: 1980-01-01, the earliest it can
+    /// represent, as the dates don't matter
+    const DATE: u16 = 1 << 5 | 1;
+
+    fn add(&mut self, name: &str, contents: &str) {
+        let offset = length(&self.data);
+        let crc = crc32(contents.as_bytes());
+        let size = length(contents.as_bytes());
+        #[allow(clippy::cast_possible_truncation)] // the few names used are all short
+        let name_length = name.len() as u16;
+
+        // local file header
+        self.data.extend(0x0403_4b50_u32.to_le_bytes());
+        for field in [10, 0, 0, 0, Self::DATE] {
+            self.data.extend(u16::to_le_bytes(field));
+        }
+        for field in [crc, size, size] {
+            self.data.extend(field.to_le_bytes());
+        }
+        self.data.extend(name_length.to_le_bytes());
+        self.data.extend(0_u16.to_le_bytes());
+        self.data.extend(name.as_bytes());
+        self.data.extend(contents.as_bytes());
+
+        // the file's entry in the central directory, which repeats most of the header
+        self.central_directory.extend(0x0201_4b50_u32.to_le_bytes());
+        for field in [20, 10, 0, 0, 0, Self::DATE] {
+            self.central_directory.extend(u16::to_le_bytes(field));
+        }
+        for field in [crc, size, size] {
+            self.central_directory.extend(field.to_le_bytes());
+        }
+        for field in [name_length, 0, 0, 0, 0] {
+            self.central_directory.extend(field.to_le_bytes());
+        }
+        for field in [0, offset] {
+            self.central_directory.extend(u32::to_le_bytes(field));
+        }
+        self.central_directory.extend(name.as_bytes());
+
+        self.entries += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = length(&self.data);
+        let size = length(&self.central_directory);
+        self.data.append(&mut self.central_directory);
+
+        // end of central directory record
+        self.data.extend(0x0605_4b50_u32.to_le_bytes());
+        for field in [0, 0, self.entries, self.entries] {
+            self.data.extend(u16::to_le_bytes(field));
+        }
+        for field in [size, offset] {
+            self.data.extend(field.to_le_bytes());
+        }
+        self.data.extend(0_u16.to_le_bytes());
+        self.data
+    }
+}
+
+/// The length of `bytes` as a ZIP archive stores it
+fn length(bytes: &[u8]) -> u32 {
+    u32::try_from(bytes.len()).unwrap_or(u32::MAX)
+}
+
+/// The CRC-32 checksum of `bytes`, as used by ZIP archives
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The current time as a Unix timestamp, or the epoch if there is no clock to ask, as in
+/// WebAssembly without a host
+fn now() -> u64 {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        return 0;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Formats a Unix timestamp as an ISO 8601 date and time in UTC, such as `2000-02-29T12:00:00Z`
+fn utc_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // converts days since 1970-01-01 to a date, treating years as starting in March so that
+    // leap days fall at the end
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::DocumentElement;
+
+    /// The book `epub` makes of `elements`, as text, which its files can be found in as they are
+    /// stored uncompressed
+    fn render_book(elements: Vec<Box<dyn DocumentElement>>, mut epub: Epub) -> String {
+        for element in elements {
+            element.visit(&mut epub);
+        }
+        String::from_utf8_lossy(&epub.finish_bytes()).into_owned()
+    }
+
+    mod archive {
+        use super::*;
+
+        #[test]
+        fn checksum() {
+            assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+            assert_eq!(0, crc32(b""));
+        }
+
+        #[test]
+        fn timestamps() {
+            assert_eq!("1970-01-01T00:00:00Z", utc_timestamp(0));
+            assert_eq!("2000-02-29T12:34:56Z", utc_timestamp(951_827_696));
+            assert_eq!("2024-12-31T23:59:59Z", utc_timestamp(1_735_689_599));
+        }
+
+        #[test]
+        fn mimetype_comes_first_and_uncompressed() {
+            let book = Epub::new().finish_bytes();
+            assert_eq!(Some(&b"PK\x03\x04"[..]), book.get(..4));
+            assert_eq!(Some(&b"mimetype"[..]), book.get(30..38));
+            assert_eq!(Some(&b"application/epub+zip"[..]), book.get(38..58));
+        }
+    }
+
+    mod metadata {
+        use super::*;
+
+        #[test]
+        fn chapter_title_comes_from_the_first_heading() {
+            let mut epub = Epub::new();
+            epub.heading(2, &[("lorem".to_owned(), Format::new())]);
+            epub.heading(1, &[("ipsum & dolor".to_owned(), Format::new())]);
+            let chapter = epub.finish();
+            assert!(chapter.contains("<title>ipsum &amp; dolor</title>"));
+            assert!(chapter.contains(
+                "<h2 id=\"heading-1\">lorem</h2>\n<h1 id=\"heading-2\">ipsum &amp; dolor</h1>\n"
+            ));
+        }
+
+        #[test]
+        fn metadata_comes_from_the_front_matter() {
+            let book = render_book(
+                crate::parse("---\ntitle: Lorem\nauthor: Cicero & Atticus\ndate: 45 BC\nlang: la\n---\nipsum"),
+                Epub::new().language("en"),
+            );
+            assert!(book.contains("<dc:title>Lorem</dc:title>"));
+            assert!(book.contains("<dc:creator>Cicero &amp; Atticus</dc:creator>"));
+            assert!(book.contains("<dc:date>45 BC</dc:date>"));
+            assert!(book.contains("<dc:language>la</dc:language>"));
+        }
+
+        #[test]
+        fn language_comes_from_the_options_without_front_matter() {
+            let book = render_book(crate::parse("# Lorem"), Epub::new().language("de"));
+            assert!(book.contains("<dc:language>de</dc:language>"));
+            assert!(!book.contains("<dc:creator>"));
+            assert!(!book.contains("<dc:date>"));
+
+            let book = render_book(crate::parse("# Lorem"), Epub::new());
+            assert!(book.contains("<dc:language>und</dc:language>"));
+        }
+    }
+    mod table_of_contents {
+        use super::*;
+
+        #[test]
+        fn headings_are_listed_with_their_numbers() {
+            let mut elements = crate::parse("# lorem\n## ipsum & dolor\n### sit\n# amet\n");
+            crate::markdown::number_headings(&mut elements);
+            let book = render_book(elements, Epub::new());
+            assert!(book.contains("<h2 id=\"heading-2\">1.1 ipsum &amp; dolor</h2>"));
+            assert!(book.contains(concat!(
+                "<nav epub:type=\"toc\"><ol>",
+                "<li><a href=\"chapter.xhtml#heading-1\">1 lorem</a><ol>",
+                "<li><a href=\"chapter.xhtml#heading-2\">1.1 ipsum &amp; dolor</a><ol>",
+                "<li><a href=\"chapter.xhtml#heading-3\">1.1.1 sit</a></li>",
+                "</ol></li></ol></li>",
+                "<li><a href=\"chapter.xhtml#heading-4\">2 amet</a></li>",
+                "</ol></nav>"
+            )));
+        }
+
+        #[test]
+        fn headings_above_the_first_close_its_item() {
+            assert_eq!(
+                concat!(
+                    "<ol><li><a href=\"chapter.xhtml#heading-1\">lorem</a></li>",
+                    "<li><a href=\"chapter.xhtml#heading-2\">Untitled</a></li></ol>"
+                ),
+                table_of_contents(&[(2, "lorem".to_owned()), (1, String::new())], "Untitled")
+            );
+        }
+
+        #[test]
+        fn a_book_without_headings_lists_its_chapter() {
+            let book = render_book(crate::parse("lorem"), Epub::new());
+            assert!(book.contains(
+                "<nav epub:type=\"toc\"><ol><li><a href=\"chapter.xhtml\">Untitled</a></li></ol></nav>"
+            ));
+        }
+    }
+}
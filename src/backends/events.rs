@@ -0,0 +1,364 @@
+use std::fmt::Write;
+
+use crate::formatting::{Format, Roles};
+use crate::list::{List, ListKind};
+use crate::table::{Alignment, Table};
+
+use super::Backend;
+
+/// Renders a stream of events, one JSON object per line, for other programs to consume. Each
+/// block and each run of inline formatting is a `start` event and a matching `end` event with the
+/// same `tag`, with `text`, `code`, `hard_break` and `rule` events in between. Tables are a
+/// `table`, holding a `table_head` and a `table_row` for each body row, which hold a `table_cell`
+/// for each cell. Lists are a `list`, saying whether it is `ordered` (and if so, its `start`) and
+/// `tight`, holding an `item` for each item, which holds the item's blocks. Blockquotes are a
+/// `blockquote` holding their blocks, then an `attribution`, if they have one. Code blocks are a
+/// `code_block`, with its `info` string if it has one, holding a `text` event of the code. For
+/// example, `# *lorem*` becomes:
+///
+/// ```text
+/// {"event":"start","tag":"heading","level":1}
+/// {"event":"start","tag":"emphasis"}
+/// {"event":"text","text":"lorem"}
+/// {"event":"end","tag":"emphasis"}
+/// {"event":"end","tag":"heading"}
+/// ```
+#[derive(Debug, Default)]
+pub struct Events {
+    output: String,
+    /// The inline tags that are currently open, innermost last
+    open: Vec<Tag>,
+}
+
+/// A run of inline formatting
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Tag {
+    Link(String),
+    Strong,
+    Emphasis,
+    Strikethrough,
+    Underline,
+}
+
+impl Tag {
+    /// The tags for `format`, outermost first
+    fn of(format: &Format) -> Vec<Self> {
+        let link = format.link().map(|target| Self::Link(target.to_owned()));
+        let tags = [
+            (format.roles().contains(Roles::STRONG), Self::Strong),
+            (format.roles().contains(Roles::EMPHASIS), Self::Emphasis),
+            (format.roles().contains(Roles::DELETED), Self::Strikethrough),
+            (format.is_underline(), Self::Underline),
+        ];
+        link.into_iter()
+            .chain(
+                tags.into_iter()
+                    .filter_map(|(applies, tag)| applies.then_some(tag)),
+            )
+            .collect()
+    }
+
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Link(_) => "link",
+            Self::Strong => "strong",
+            Self::Emphasis => "emphasis",
+            Self::Strikethrough => "strikethrough",
+            Self::Underline => "underline",
+        }
+    }
+}
+
+impl Events {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn start(&mut self, tag: &str, attributes: &str) {
+        // writing to a `String` can't fail
+        let _ = writeln!(
+            self.output,
+            "{{\"event\":\"start\",\"tag\":\"{tag}\"{attributes}}}"
+        );
+    }
+
+    fn end(&mut self, tag: &str) {
+        let _ = writeln!(self.output, "{{\"event\":\"end\",\"tag\":\"{tag}\"}}");
+    }
+
+    /// Emits the events for formatted text, opening and closing inline tags as the formatting
+    /// changes, and leaving none open at the end
+    fn inline(&mut self, text: &[(String, Format)]) {
+        for (slice, format) in text {
+            let tags = Tag::of(format);
+            // tags stay open for as long as the slices go on having them, and any opened inside
+            // of one that doesn't go on are closed along with it
+            let kept = self
+                .open
+                .iter()
+                .take_while(|tag| tags.contains(tag))
+                .count();
+            self.close_tags(kept);
+            for tag in tags {
+                if !self.open.contains(&tag) {
+                    let attributes = match &tag {
+                        Tag::Link(target) => format!(",\"target\":{}", string(target)),
+                        _ => String::new(),
+                    };
+                    self.start(tag.name(), &attributes);
+                    self.open.push(tag);
+                }
+            }
+
+            for (index, line) in slice.split('\n').enumerate() {
+                if index > 0 {
+                    self.output += "{\"event\":\"hard_break\"}\n";
+                }
+                if !line.is_empty() {
+                    let event = if format.is_code() { "code" } else { "text" };
+                    let _ = writeln!(
+                        self.output,
+                        "{{\"event\":\"{event}\",\"text\":{}}}",
+                        string(line)
+                    );
+                }
+            }
+        }
+        self.close_tags(0);
+    }
+
+    /// Emits the cells of a table row, each with its column's alignment, if it has one
+    fn cells(&mut self, cells: &[Vec<(String, Format)>], alignments: &[Alignment]) {
+        for (cell, alignment) in cells.iter().zip(alignments) {
+            let attributes = match alignment {
+                Alignment::None => "",
+                Alignment::Left => ",\"align\":\"left\"",
+                Alignment::Center => ",\"align\":\"center\"",
+                Alignment::Right => ",\"align\":\"right\"",
+            };
+            self.start("table_cell", attributes);
+            self.inline(cell);
+            self.end("table_cell");
+        }
+    }
+
+    /// Closes the open inline tags, innermost first, until only `count` are left open
+    fn close_tags(&mut self, count: usize) {
+        while self.open.len() > count {
+            if let Some(tag) = self.open.pop() {
+                self.end(tag.name());
+            }
+        }
+    }
+}
+
+impl Backend for Events {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        self.start("paragraph", "");
+        self.inline(text);
+        self.end("paragraph");
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        self.start("heading", &format!(",\"level\":{level}"));
+        self.inline(text);
+        self.end("heading");
+    }
+
+    fn thematic_break(&mut self) {
+        self.output += "{\"event\":\"rule\"}\n";
+    }
+
+    fn table(&mut self, table: &Table) {
+        self.start("table", "");
+        self.start("table_head", "");
+        self.cells(table.header(), table.alignments());
+        self.end("table_head");
+        for row in table.rows() {
+            self.start("table_row", "");
+            self.cells(row, table.alignments());
+            self.end("table_row");
+        }
+        self.end("table");
+    }
+
+    fn list_start(&mut self, list: &List) {
+        let ordered = match list.kind() {
+            ListKind::Bullet(_) => "false".to_owned(),
+            ListKind::Ordered { start, .. } => format!("true,\"start\":{start}"),
+        };
+        self.start(
+            "list",
+            &format!(",\"ordered\":{ordered},\"tight\":{}", list.is_tight()),
+        );
+    }
+
+    fn list_end(&mut self) {
+        self.end("list");
+    }
+
+    fn item_start(&mut self, _index: usize, task: Option<bool>) {
+        let checked = task.map_or_else(String::new, |checked| format!(",\"checked\":{checked}"));
+        self.start("item", &checked);
+    }
+
+    fn item_end(&mut self) {
+        self.end("item");
+    }
+
+    fn quote_start(&mut self) {
+        self.start("blockquote", "");
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        self.start("attribution", "");
+        self.inline(text);
+        self.end("attribution");
+    }
+
+    fn quote_end(&mut self) {
+        self.end("blockquote");
+    }
+
+    fn code_block(&mut self, info: &str, code: &str) {
+        let attributes = if info.is_empty() {
+            String::new()
+        } else {
+            format!(",\"info\":{}", string(info))
+        };
+        self.start("code_block", &attributes);
+        // writing to a `String` can't fail
+        let _ = writeln!(
+            self.output,
+            "{{\"event\":\"text\",\"text\":{}}}",
+            string(code)
+        );
+        self.end("code_block");
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.output.reserve(additional);
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
+
+/// Quotes and escapes `text` as a JSON string
+fn string(text: &str) -> String {
+    let mut json = String::from('"');
+    for character in text.chars() {
+        match character {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\t' => json += "\\t",
+            '\r' => json += "\\r",
+            _ if character.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(character));
+            }
+            _ => json.push(character),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod block_events {
+        use super::*;
+
+        #[test]
+        fn blocks() {
+            let mut events = Events::new();
+            events.heading(2, &[("lorem".to_owned(), Format::new())]);
+            events.paragraph(&[("ipsum\ndolor".to_owned(), Format::new())]);
+            events.thematic_break();
+            assert_eq!(
+                concat!(
+                    "{\"event\":\"start\",\"tag\":\"heading\",\"level\":2}\n",
+                    "{\"event\":\"text\",\"text\":\"lorem\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"heading\"}\n",
+                    "{\"event\":\"start\",\"tag\":\"paragraph\"}\n",
+                    "{\"event\":\"text\",\"text\":\"ipsum\"}\n",
+                    "{\"event\":\"hard_break\"}\n",
+                    "{\"event\":\"text\",\"text\":\"dolor\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"paragraph\"}\n",
+                    "{\"event\":\"rule\"}\n",
+                ),
+                events.finish()
+            );
+        }
+
+        #[test]
+        fn lists_and_code_blocks() {
+            let mut events = Events::new();
+            for element in crate::parse("2) lorem\n\n~~~ sh\nls\n~~~") {
+                element.visit(&mut events);
+            }
+            assert_eq!(
+                concat!(
+                    "{\"event\":\"start\",\"tag\":\"list\",\"ordered\":true,\"start\":2,\"tight\":true}\n",
+                    "{\"event\":\"start\",\"tag\":\"item\"}\n",
+                    "{\"event\":\"start\",\"tag\":\"paragraph\"}\n",
+                    "{\"event\":\"text\",\"text\":\"lorem\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"paragraph\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"item\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"list\"}\n",
+                    "{\"event\":\"start\",\"tag\":\"code_block\",\"info\":\"sh\"}\n",
+                    "{\"event\":\"text\",\"text\":\"ls\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"code_block\"}\n",
+                ),
+                events.finish()
+            );
+        }
+
+        #[test]
+        fn tags_nest() {
+            let mut events = Events::new();
+            events.paragraph(&[
+                ("a".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
+                (
+                    "b".to_owned(),
+                    Format::new()
+                        .set_roles(Roles::EMPHASIS)
+                        .set_roles(Roles::STRONG),
+                ),
+                (
+                    "c".to_owned(),
+                    Format::new().set_link("https://example.com"),
+                ),
+            ]);
+            assert_eq!(
+                concat!(
+                    "{\"event\":\"start\",\"tag\":\"paragraph\"}\n",
+                    "{\"event\":\"start\",\"tag\":\"emphasis\"}\n",
+                    "{\"event\":\"text\",\"text\":\"a\"}\n",
+                    "{\"event\":\"start\",\"tag\":\"strong\"}\n",
+                    "{\"event\":\"text\",\"text\":\"b\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"strong\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"emphasis\"}\n",
+                    "{\"event\":\"start\",\"tag\":\"link\",\"target\":\"https://example.com\"}\n",
+                    "{\"event\":\"text\",\"text\":\"c\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"link\"}\n",
+                    "{\"event\":\"end\",\"tag\":\"paragraph\"}\n",
+                ),
+                events.finish()
+            );
+        }
+    }
+
+    mod json {
+        use super::*;
+
+        #[test]
+        fn strings_are_escaped() {
+            assert_eq!(r#""a\"b\\c\u001b""#, string("a\"b\\c\u{1b}"));
+        }
+    }
+}
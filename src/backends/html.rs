@@ -0,0 +1,410 @@
+use std::fmt::Write;
+
+use crate::formatting::{Format, Roles};
+use crate::list::{List, ListKind};
+use crate::table::{Alignment, Table};
+
+use super::Backend;
+
+/// Renders an HTML fragment, with one element per block
+#[derive(Debug, Default)]
+pub struct Html {
+    output: String,
+    xhtml: bool,
+    /// The lists and blockquotes that are open, innermost last, with whether each is tight (which
+    /// a blockquote never is)
+    containers: Vec<(&'static str, bool)>,
+}
+
+impl Html {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders XHTML instead, in which empty elements such as `<br />` are closed
+    #[must_use]
+    pub fn xhtml() -> Self {
+        Self {
+            xhtml: true,
+            ..Self::default()
+        }
+    }
+
+    /// A heading with an `id` attribute, for linking to it from a table of contents
+    pub(crate) fn heading_with_id(&mut self, level: u8, text: &[(String, Format)], id: &str) {
+        self.start_block();
+        // writing to a `String` can't fail
+        let _ = writeln!(
+            self.output,
+            "<h{level} id=\"{id}\">{}</h{level}>",
+            inline(text, self.line_break())
+        );
+    }
+
+    /// Renders a table row, with each cell in a `tag` element
+    fn row(&mut self, tag: &str, cells: &[Vec<(String, Format)>], alignments: &[Alignment]) {
+        self.output += "<tr>";
+        for (cell, alignment) in cells.iter().zip(alignments) {
+            let style = match alignment {
+                Alignment::None => "",
+                Alignment::Left => " style=\"text-align: left\"",
+                Alignment::Center => " style=\"text-align: center\"",
+                Alignment::Right => " style=\"text-align: right\"",
+            };
+            // writing to a `String` can't fail
+            let _ = write!(
+                self.output,
+                "<{tag}{style}>{}</{tag}>",
+                inline(cell, self.line_break())
+            );
+        }
+        self.output += "</tr>\n";
+    }
+
+    /// Starts a block on a line of its own, as a block in a tight list item can follow the item's
+    /// text on the same line
+    fn start_block(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+    }
+
+    /// Closes the innermost open list or blockquote
+    fn close_container(&mut self) {
+        if let Some((tag, _)) = self.containers.pop() {
+            self.start_block();
+            // writing to a `String` can't fail
+            let _ = writeln!(self.output, "</{tag}>");
+        }
+    }
+
+    const fn line_break(&self) -> &'static str {
+        if self.xhtml {
+            "<br />"
+        } else {
+            "<br>"
+        }
+    }
+}
+
+impl Backend for Html {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        // the paragraphs of tight list items aren't set apart from the rest of the item
+        if matches!(self.containers.last(), Some((_, true))) {
+            self.output += &inline(text, self.line_break());
+            return;
+        }
+        self.start_block();
+        // writing to a `String` can't fail
+        let _ = writeln!(self.output, "<p>{}</p>", inline(text, self.line_break()));
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        self.start_block();
+        let _ = writeln!(
+            self.output,
+            "<h{level}>{}</h{level}>",
+            inline(text, self.line_break())
+        );
+    }
+
+    fn thematic_break(&mut self) {
+        self.start_block();
+        self.output += if self.xhtml { "<hr />\n" } else { "<hr>\n" };
+    }
+
+    fn table(&mut self, table: &Table) {
+        self.start_block();
+        self.output += "<table>\n<thead>\n";
+        self.row("th", table.header(), table.alignments());
+        self.output += "</thead>\n";
+        if !table.rows().is_empty() {
+            self.output += "<tbody>\n";
+            for row in table.rows() {
+                self.row("td", row, table.alignments());
+            }
+            self.output += "</tbody>\n";
+        }
+        self.output += "</table>\n";
+    }
+
+    fn list_start(&mut self, list: &List) {
+        self.start_block();
+        let tag = match list.kind() {
+            ListKind::Bullet(_) => {
+                self.output += "<ul>\n";
+                "ul"
+            }
+            ListKind::Ordered { start: 1, .. } => {
+                self.output += "<ol>\n";
+                "ol"
+            }
+            ListKind::Ordered { start, .. } => {
+                // writing to a `String` can't fail
+                let _ = writeln!(self.output, "<ol start=\"{start}\">");
+                "ol"
+            }
+        };
+        self.containers.push((tag, list.is_tight()));
+    }
+
+    fn list_end(&mut self) {
+        self.close_container();
+    }
+
+    fn item_start(&mut self, _index: usize, task: Option<bool>) {
+        self.output += match task {
+            Some(true) => "<li><input type=\"checkbox\" checked disabled> ",
+            Some(false) => "<li><input type=\"checkbox\" disabled> ",
+            None => "<li>",
+        };
+    }
+
+    fn item_end(&mut self) {
+        self.output += "</li>\n";
+    }
+
+    fn quote_start(&mut self) {
+        self.start_block();
+        self.output += "<blockquote>\n";
+        self.containers.push(("blockquote", false));
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        self.start_block();
+        // writing to a `String` can't fail
+        let _ = writeln!(
+            self.output,
+            "<footer>— <cite>{}</cite></footer>",
+            inline(text, self.line_break())
+        );
+    }
+
+    fn quote_end(&mut self) {
+        self.close_container();
+    }
+
+    fn code_block(&mut self, info: &str, code: &str) {
+        self.start_block();
+        self.output += "<pre><code";
+        if let Some(language) = info.split_whitespace().next() {
+            // writing to a `String` can't fail
+            let _ = write!(self.output, " class=\"language-{}\"", escape(language));
+        }
+        self.output.push('>');
+        self.output += &escape(code);
+        if !code.is_empty() {
+            self.output.push('\n');
+        }
+        self.output += "</code></pre>\n";
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.start_block();
+        // writing to a `String` can't fail
+        let _ = writeln!(self.output, "<!--{text}-->");
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.output.reserve(additional);
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
+
+/// Renders formatted text, wrapping each slice in the tags for its formatting, with hard line
+/// breaks as `line_break`
+fn inline(text: &[(String, Format)], line_break: &str) -> String {
+    text.iter()
+        .map(|(slice, format)| {
+            let tags = [
+                (format.roles().contains(Roles::STRONG), "strong"),
+                (format.roles().contains(Roles::EMPHASIS), "em"),
+                (format.roles().contains(Roles::DELETED), "del"),
+                (format.is_underline(), "u"),
+                (format.is_code(), "code"),
+            ];
+            let mut html = escape(slice).replace('\n', &format!("{line_break}\n"));
+            for (_, tag) in tags.iter().rev().filter(|(applies, _)| *applies) {
+                html = format!("<{tag}>{html}</{tag}>");
+            }
+            if let Some(link) = format.link() {
+                html = format!("<a href=\"{}\">{html}</a>", escape(link));
+            }
+            html
+        })
+        .collect()
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod block_elements {
+        use super::*;
+
+        #[test]
+        fn blocks() {
+            let mut html = Html::new();
+            html.heading(2, &[("lorem".to_owned(), Format::new())]);
+            html.paragraph(&[("ipsum".to_owned(), Format::new())]);
+            html.thematic_break();
+            assert_eq!("<h2>lorem</h2>\n<p>ipsum</p>\n<hr>\n", html.finish());
+        }
+
+        #[test]
+        fn tables() {
+            let elements = crate::parse("a | b\n:-- | --:\n1 | 2");
+            let mut html = Html::new();
+            for element in &elements {
+                element.visit(&mut html);
+            }
+            assert_eq!(
+                concat!(
+                    "<table>\n<thead>\n",
+                    "<tr><th style=\"text-align: left\">a</th><th style=\"text-align: right\">b</th></tr>\n",
+                    "</thead>\n<tbody>\n",
+                    "<tr><td style=\"text-align: left\">1</td><td style=\"text-align: right\">2</td></tr>\n",
+                    "</tbody>\n</table>\n",
+                ),
+                html.finish()
+            );
+        }
+
+        #[test]
+        fn lists() {
+            let elements =
+                crate::parse("3. lorem\n   - ipsum\n4. dolor\n\n   sit\n\n```rust\na < b\n```");
+            let mut html = Html::new();
+            for element in &elements {
+                element.visit(&mut html);
+            }
+            assert_eq!(
+                concat!(
+                    "<ol start=\"3\">\n",
+                    "<li>\n<p>lorem</p>\n<ul>\n<li>ipsum</li>\n</ul>\n</li>\n",
+                    "<li>\n<p>dolor</p>\n<p>sit</p>\n</li>\n",
+                    "</ol>\n",
+                    "<pre><code class=\"language-rust\">a &lt; b\n</code></pre>\n",
+                ),
+                html.finish()
+            );
+        }
+
+        #[test]
+        fn task_lists_have_checkboxes() {
+            let elements = crate::parse("- [x] lorem\n- [ ] ipsum\n- dolor");
+            let mut html = Html::new();
+            for element in &elements {
+                element.visit(&mut html);
+            }
+            assert_eq!(
+                concat!(
+                    "<ul>\n",
+                    "<li><input type=\"checkbox\" checked disabled> lorem</li>\n",
+                    "<li><input type=\"checkbox\" disabled> ipsum</li>\n",
+                    "<li>dolor</li>\n",
+                    "</ul>\n",
+                ),
+                html.finish()
+            );
+        }
+
+        #[test]
+        fn blockquotes() {
+            let elements = crate::parse("- > lorem\n  >\n  > — ipsum");
+            let mut html = Html::new();
+            for element in &elements {
+                element.visit(&mut html);
+            }
+            assert_eq!(
+                concat!(
+                    "<ul>\n<li>\n<blockquote>\n<p>lorem</p>\n",
+                    "<footer>— <cite>ipsum</cite></footer>\n</blockquote>\n</li>\n</ul>\n",
+                ),
+                html.finish()
+            );
+        }
+
+        #[test]
+        fn xhtml_closes_empty_elements() {
+            let mut html = Html::xhtml();
+            html.paragraph(&[("lorem\nipsum".to_owned(), Format::new())]);
+            html.thematic_break();
+            assert_eq!("<p>lorem<br />\nipsum</p>\n<hr />\n", html.finish());
+        }
+    }
+
+    mod inline_elements {
+        use super::*;
+
+        #[test]
+        fn inline_formatting() {
+            assert_eq!(
+                "a <strong><em>b</em></strong> <del>c</del>",
+                inline(
+                    &[
+                        ("a ".to_owned(), Format::new()),
+                        (
+                            "b".to_owned(),
+                            Format::new()
+                                .set_roles(Roles::STRONG)
+                                .set_roles(Roles::EMPHASIS)
+                        ),
+                        (" ".to_owned(), Format::new()),
+                        ("c".to_owned(), Format::new().set_roles(Roles::DELETED)),
+                    ],
+                    "<br>"
+                )
+            );
+        }
+
+        #[test]
+        fn code() {
+            assert_eq!(
+                "<em><code>&lt;a&gt;</code></em>",
+                inline(
+                    &[(
+                        "<a>".to_owned(),
+                        Format::new().set_roles(Roles::EMPHASIS).set_code()
+                    )],
+                    "<br>"
+                )
+            );
+        }
+
+        #[test]
+        fn links() {
+            assert_eq!(
+                "<a href=\"https://example.com/?a=b&amp;c\"><em>lorem</em></a>",
+                inline(
+                    &[(
+                        "lorem".to_owned(),
+                        Format::new()
+                            .set_roles(Roles::EMPHASIS)
+                            .set_link("https://example.com/?a=b&c")
+                    )],
+                    "<br>"
+                )
+            );
+        }
+
+        #[test]
+        fn special_characters_are_escaped() {
+            assert_eq!(
+                "&lt;b&gt; &amp; &quot;c&quot;<br>\nd",
+                inline(&[("<b> & \"c\"\nd".to_owned(), Format::new())], "<br>")
+            );
+        }
+    }
+}
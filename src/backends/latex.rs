@@ -0,0 +1,261 @@
+use std::fmt::Write;
+
+use crate::formatting::{Format, Roles};
+use crate::list::{List, ListKind};
+use crate::table::{Alignment, Table};
+use crate::theme::{Glyphs, NumberingStyle};
+
+use super::Backend;
+
+/// Renders a LaTeX document body. Strikethrough needs the `ulem` package
+#[derive(Debug, Default)]
+pub struct Latex {
+    output: String,
+    /// The markers of the items of the lists that are open, innermost last, for ordered lists,
+    /// the numbering of which doesn't always start from 1
+    lists: Vec<Option<Vec<String>>>,
+}
+
+impl Latex {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for Latex {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        // writing to a `String` can't fail
+        let _ = write!(self.output, "{}\n\n", inline(text));
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        let command = match level {
+            1 => "section",
+            2 => "subsection",
+            3 => "subsubsection",
+            4 => "paragraph",
+            _ => "subparagraph",
+        };
+        // headings are numbered by sani, if at all, so LaTeX's own numbering is turned off
+        let _ = write!(self.output, "\\{command}*{{{}}}\n\n", inline(text));
+    }
+
+    fn thematic_break(&mut self) {
+        self.output += "\\noindent\\rule{\\linewidth}{0.4pt}\n\n";
+    }
+
+    fn table(&mut self, table: &Table) {
+        let columns: String = table
+            .alignments()
+            .iter()
+            .map(|alignment| match alignment {
+                Alignment::None | Alignment::Left => 'l',
+                Alignment::Center => 'c',
+                Alignment::Right => 'r',
+            })
+            .collect();
+        let row = |cells: &[Vec<(String, Format)>]| {
+            cells
+                .iter()
+                .map(|cell| inline(cell))
+                .collect::<Vec<_>>()
+                .join(" & ")
+        };
+        let header: Vec<Vec<_>> = table
+            .header()
+            .iter()
+            .map(|cell| {
+                cell.iter()
+                    .map(|(slice, format)| (slice.clone(), format.clone().set_roles(Roles::STRONG)))
+                    .collect()
+            })
+            .collect();
+
+        // writing to a `String` can't fail
+        let _ = write!(
+            self.output,
+            "\\begin{{tabular}}{{{columns}}}\n{} \\\\\n\\hline\n",
+            row(&header)
+        );
+        for cells in table.rows() {
+            let _ = writeln!(self.output, "{} \\\\", row(cells));
+        }
+        self.output += "\\end{tabular}\n\n";
+    }
+
+    fn list_start(&mut self, list: &List) {
+        let (environment, markers) = match list.kind() {
+            ListKind::Bullet(_) => ("itemize", None),
+            ListKind::Ordered { .. } => {
+                let glyphs = Glyphs::ascii();
+                let checkboxes = (glyphs.checked, glyphs.unchecked);
+                let markers = list.markers("", NumberingStyle::Arabic, checkboxes);
+                ("enumerate", Some(markers))
+            }
+        };
+        // writing to a `String` can't fail
+        let _ = writeln!(self.output, "\\begin{{{environment}}}");
+        if list.is_tight() {
+            self.output += "\\setlength{\\itemsep}{0pt}\n";
+        }
+        self.lists.push(markers);
+    }
+
+    fn list_end(&mut self) {
+        let environment = match self.lists.pop() {
+            Some(Some(_)) => "enumerate",
+            _ => "itemize",
+        };
+        // writing to a `String` can't fail
+        let _ = write!(self.output, "\\end{{{environment}}}\n\n");
+    }
+
+    fn item_start(&mut self, index: usize, task: Option<bool>) {
+        let glyphs = Glyphs::ascii();
+        // the markers of ordered lists have the boxes of task list items in them already
+        let marker = self
+            .lists
+            .last()
+            .and_then(|markers| markers.as_ref()?.get(index))
+            .map(|marker| marker.trim())
+            .or_else(|| {
+                task.map(|checked| {
+                    if checked {
+                        glyphs.checked
+                    } else {
+                        glyphs.unchecked
+                    }
+                })
+            });
+        match marker {
+            Some(marker) => {
+                // writing to a `String` can't fail
+                let _ = write!(self.output, "\\item[{{{}}}] ", escape(marker));
+            }
+            None => self.output += "\\item ",
+        }
+    }
+
+    fn item_end(&mut self) {}
+
+    fn quote_start(&mut self) {
+        self.output += "\\begin{quote}\n";
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        let italic: Vec<_> = text
+            .iter()
+            .map(|(slice, format)| (slice.clone(), format.clone().set_roles(Roles::EMPHASIS)))
+            .collect();
+        // writing to a `String` can't fail
+        let _ = write!(
+            self.output,
+            "\\begin{{flushright}}\n\\emph{{---}} {}\n\\end{{flushright}}\n",
+            inline(&italic)
+        );
+    }
+
+    fn quote_end(&mut self) {
+        self.output += "\\end{quote}\n\n";
+    }
+
+    fn code_block(&mut self, _info: &str, code: &str) {
+        // writing to a `String` can't fail
+        let _ = write!(
+            self.output,
+            "\\begin{{verbatim}}\n{code}\n\\end{{verbatim}}\n\n"
+        );
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.output.reserve(additional);
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
+
+/// Renders formatted text, wrapping each slice in the commands for its formatting
+fn inline(text: &[(String, Format)]) -> String {
+    text.iter()
+        .map(|(slice, format)| {
+            let commands = [
+                (format.roles().contains(Roles::STRONG), "textbf"),
+                (format.roles().contains(Roles::EMPHASIS), "emph"),
+                (format.roles().contains(Roles::DELETED), "sout"),
+                (format.is_underline(), "underline"),
+                (format.is_code(), "texttt"),
+            ];
+            let mut latex = escape(slice).replace('\n', "\\\\\n");
+            for (_, command) in commands.iter().rev().filter(|(applies, _)| *applies) {
+                latex = format!("\\{command}{{{latex}}}");
+            }
+            latex
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '\\' => escaped += "\\textbackslash{}",
+            '~' => escaped += "\\textasciitilde{}",
+            '^' => escaped += "\\textasciicircum{}",
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(character);
+            }
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod block_elements {
+        use super::*;
+
+        #[test]
+        fn blocks() {
+            let mut latex = Latex::new();
+            latex.heading(2, &[("lorem".to_owned(), Format::new())]);
+            latex.paragraph(&[("ipsum".to_owned(), Format::new().set_roles(Roles::EMPHASIS))]);
+            assert_eq!("\\subsection*{lorem}\n\n\\emph{ipsum}\n\n", latex.finish());
+        }
+
+        #[test]
+        fn lists() {
+            let elements = crate::parse("2. lorem\n   - ipsum");
+            let mut latex = Latex::new();
+            for element in &elements {
+                element.visit(&mut latex);
+            }
+            assert_eq!(
+                concat!(
+                    "\\begin{enumerate}\n\\setlength{\\itemsep}{0pt}\n\\item[{2.}] lorem\n\n",
+                    "\\begin{itemize}\n\\setlength{\\itemsep}{0pt}\n\\item ipsum\n\n",
+                    "\\end{itemize}\n\n\\end{enumerate}\n\n",
+                ),
+                latex.finish()
+            );
+        }
+    }
+
+    mod inline_elements {
+        use super::*;
+
+        #[test]
+        fn special_characters_are_escaped() {
+            assert_eq!(
+                "50\\% \\& \\$5 \\textbackslash{}n \\{x\\}",
+                escape("50% & $5 \\n {x}")
+            );
+        }
+    }
+}
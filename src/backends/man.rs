@@ -0,0 +1,316 @@
+use std::fmt::Write;
+
+use crate::formatting::{Format, Roles};
+use crate::list::{List, ListKind};
+use crate::options::RenderOptions;
+use crate::table::Table;
+use crate::theme::{Glyphs, NumberingStyle, Theme};
+
+use super::Backend;
+
+/// Renders a roff man page. The first level 1 heading gives the page's title; level 1 and 2
+/// headings become sections and subsections, and deeper headings bold paragraphs
+#[derive(Debug)]
+pub struct Man {
+    section: u8,
+    title: Option<String>,
+    body: String,
+    /// The lists that are open, innermost last
+    lists: Vec<ManList>,
+    /// The marker of the current list item, until the item's first block is tagged with it
+    item_marker: Option<String>,
+}
+
+/// A list being rendered as indented paragraphs
+#[derive(Debug)]
+struct ManList {
+    markers: Vec<String>,
+    /// How far the items are indented, in ens
+    indent: usize,
+    tight: bool,
+}
+
+impl Man {
+    /// A man page for the given manual section
+    #[must_use]
+    pub const fn new(section: u8) -> Self {
+        Self {
+            section,
+            title: None,
+            body: String::new(),
+            lists: Vec::new(),
+            item_marker: None,
+        }
+    }
+
+    /// Starts a paragraph, which in a list item is indented to the item's content, with the
+    /// item's marker hung off of it if it is the item's first
+    fn start_block(&mut self) {
+        match self.lists.last() {
+            Some(list) => {
+                let marker = self.item_marker.take().unwrap_or_default();
+                // writing to a `String` can't fail
+                let _ = writeln!(self.body, ".IP \"{marker}\" {}", list.indent);
+            }
+            None => self.body += ".PP\n",
+        }
+    }
+}
+
+impl Backend for Man {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        // writing to a `String` can't fail
+        self.start_block();
+        let _ = writeln!(self.body, "{}", inline(text));
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        let plain: String = text.iter().map(|(slice, _)| slice.as_str()).collect();
+        if level == 1 && self.title.is_none() {
+            self.title = Some(plain.to_uppercase());
+        }
+        // writing to a `String` can't fail
+        let _ = match level {
+            1 => writeln!(self.body, ".SH \"{}\"", escape(&plain.to_uppercase())),
+            2 => writeln!(self.body, ".SS \"{}\"", escape(&plain)),
+            _ => {
+                self.start_block();
+                writeln!(self.body, "\\fB{}\\fR", escape(&plain))
+            }
+        };
+    }
+
+    fn thematic_break(&mut self) {
+        // roff has no rules as such, so a blank line stands in for one
+        self.body += ".sp\n";
+    }
+
+    fn table(&mut self, table: &Table) {
+        // laid out as for plain text, rather than with tbl, which man doesn't always run
+        let options = RenderOptions::new().theme(Theme::dark().glyphs(Glyphs::ascii()));
+        self.start_block();
+        self.body += ".nf\n";
+        for line in table.layout(&options) {
+            let plain: String = line.iter().map(|(slice, _)| slice.as_str()).collect();
+            // writing to a `String` can't fail
+            let _ = writeln!(self.body, "\\&{}", escape(&plain));
+        }
+        self.body += ".fi\n";
+    }
+
+    fn list_start(&mut self, list: &List) {
+        // a nested list is indented to the content of the item it is in
+        if !self.lists.is_empty() {
+            if self.item_marker.is_some() {
+                self.start_block();
+            }
+            self.body += ".RS\n";
+        }
+        if list.is_tight() {
+            self.body += ".PD 0\n";
+        }
+        let glyphs = Glyphs::ascii();
+        let checkboxes = (glyphs.checked, glyphs.unchecked);
+        let markers: Vec<_> = match list.kind() {
+            ListKind::Bullet(_) => list.markers("\\(bu", NumberingStyle::Arabic, checkboxes),
+            ListKind::Ordered { .. } => list
+                .markers("", NumberingStyle::Arabic, checkboxes)
+                .iter()
+                .map(|marker| marker.trim().to_owned())
+                .collect(),
+        };
+        let indent = match list.kind() {
+            ListKind::Bullet(_) if list.items().iter().all(|item| item.task().is_none()) => 2,
+            // the boxes of task list items and numbers are wider than bullets
+            ListKind::Bullet(_) | ListKind::Ordered { .. } => {
+                markers.iter().map(String::len).max().unwrap_or(0) + 1
+            }
+        };
+        self.lists.push(ManList {
+            markers,
+            indent,
+            tight: list.is_tight(),
+        });
+    }
+
+    fn list_end(&mut self) {
+        if let Some(list) = self.lists.pop() {
+            if list.tight {
+                self.body += ".PD\n";
+            }
+        }
+        if !self.lists.is_empty() {
+            self.body += ".RE\n";
+        }
+    }
+
+    // the boxes of task list items are part of their markers
+    fn item_start(&mut self, index: usize, _task: Option<bool>) {
+        self.item_marker = self
+            .lists
+            .last()
+            .and_then(|list| list.markers.get(index))
+            .cloned();
+    }
+
+    fn item_end(&mut self) {
+        // an empty item still shows its marker
+        if self.item_marker.is_some() {
+            self.start_block();
+        }
+    }
+
+    fn quote_start(&mut self) {
+        if self.item_marker.is_some() {
+            self.start_block();
+        }
+        self.body += ".RS 4\n";
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        let italic: Vec<_> = text
+            .iter()
+            .map(|(slice, format)| (slice.clone(), format.clone().set_roles(Roles::EMPHASIS)))
+            .collect();
+        self.start_block();
+        // the line is broken before going back to the usual adjustment, as adjustment only
+        // applies to lines as they are output. Writing to a `String` can't fail
+        let _ = writeln!(self.body, ".ad r\n\\(em {}\n.br\n.ad", inline(&italic));
+    }
+
+    fn quote_end(&mut self) {
+        self.body += ".RE\n";
+    }
+
+    fn code_block(&mut self, _info: &str, code: &str) {
+        self.start_block();
+        self.body += ".nf\n";
+        for line in code.split('\n') {
+            // writing to a `String` can't fail
+            let _ = writeln!(self.body, "\\&\\f(CW{}\\fR", escape(line));
+        }
+        self.body += ".fi\n";
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.body.reserve(additional);
+    }
+
+    fn finish(&mut self) -> String {
+        let title = self.title.take().unwrap_or_else(|| "UNTITLED".to_owned());
+        format!(
+            ".TH \"{}\" \"{}\"\n{}",
+            escape(&title),
+            self.section,
+            std::mem::take(&mut self.body)
+        )
+    }
+}
+
+/// Renders formatted text with roff font changes. Only strong emphasis (in bold), emphasis (in
+/// italics) and code (in a constant-width font, where it isn't also emphasised) can be shown
+fn inline(text: &[(String, Format)]) -> String {
+    let mut roff = String::new();
+    for (slice, format) in text {
+        let font = match (
+            format.roles().contains(Roles::STRONG),
+            format.roles().contains(Roles::EMPHASIS),
+        ) {
+            (true, true) => "\\f(BI",
+            (true, false) => "\\fB",
+            (false, true) => "\\fI",
+            (false, false) if format.is_code() => "\\f(CW",
+            (false, false) => "\\fR",
+        };
+        roff += font;
+        let lines: Vec<_> = escape(slice)
+            .split('\n')
+            .enumerate()
+            .map(|(index, line)| {
+                // a line starting with a control character would be taken as a request. Only
+                // lines after a break can, as the others start with a font change
+                if index > 0 && line.starts_with(['.', '\'']) {
+                    format!("\\&{line}")
+                } else {
+                    line.to_owned()
+                }
+            })
+            .collect();
+        roff += &lines.join("\n.br\n");
+    }
+    roff += "\\fR";
+    roff
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod block_elements {
+        use super::*;
+
+        #[test]
+        fn page_structure() {
+            let mut man = Man::new(1);
+            man.heading(1, &[("sani".to_owned(), Format::new())]);
+            man.heading(2, &[("Usage".to_owned(), Format::new())]);
+            man.paragraph(&[("lorem".to_owned(), Format::new())]);
+            assert_eq!(
+                ".TH \"SANI\" \"1\"\n.SH \"SANI\"\n.SS \"Usage\"\n.PP\n\\fRlorem\\fR\n",
+                man.finish()
+            );
+        }
+
+        #[test]
+        fn lists() {
+            let elements = crate::parse("- lorem\n\n  ipsum\n  1. dolor\n\n```\n.sit\n```");
+            let mut man = Man::new(1);
+            for element in &elements {
+                element.visit(&mut man);
+            }
+            assert_eq!(
+                concat!(
+                    ".TH \"UNTITLED\" \"1\"\n",
+                    ".IP \"\\(bu\" 2\n\\fRlorem\\fR\n",
+                    ".IP \"\" 2\n\\fRipsum\\fR\n",
+                    ".RS\n.PD 0\n.IP \"1.\" 3\n\\fRdolor\\fR\n.PD\n.RE\n",
+                    ".PP\n.nf\n\\&\\f(CW.sit\\fR\n.fi\n",
+                ),
+                man.finish()
+            );
+        }
+    }
+
+    mod inline_elements {
+        use super::*;
+
+        #[test]
+        fn inline_formatting() {
+            assert_eq!(
+                "\\fRa \\fBb\\f(BIc\\fR",
+                inline(&[
+                    ("a ".to_owned(), Format::new()),
+                    ("b".to_owned(), Format::new().set_roles(Roles::STRONG)),
+                    (
+                        "c".to_owned(),
+                        Format::new()
+                            .set_roles(Roles::STRONG)
+                            .set_roles(Roles::EMPHASIS)
+                    ),
+                ])
+            );
+        }
+
+        #[test]
+        fn control_characters_are_escaped() {
+            assert_eq!(
+                "\\fR\\e \\-v\n.br\n\\&.lorem\\fR",
+                inline(&[("\\ -v\n.lorem".to_owned(), Format::new())])
+            );
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use serde_json::{json, Value};
+
+use crate::formatting::{Format, Roles};
+use crate::list::{List, ListKind};
+use crate::table::{Alignment, Table};
+
+use super::Backend;
+
+/// The version of pandoc's document model that the output is written for
+const API_VERSION: [u32; 3] = [1, 23, 1];
+
+/// A list, item or blockquote that has been started but not yet ended
+enum Container {
+    List {
+        /// The number of the first item and the style of the numbers, if the list is ordered
+        numbering: Option<Value>,
+        tight: bool,
+        items: Vec<Value>,
+    },
+    /// An item's blocks, and whether it is checked, if it is a task list item
+    Item(Vec<Value>, Option<bool>),
+    Quote(Vec<Value>),
+}
+
+/// Renders a document as pandoc's JSON representation of its document model, as written by
+/// `pandoc -t json`, so that pandoc can write it out in any of its formats. Attributions of
+/// blockquotes, which pandoc has no place for, are a paragraph at the end of their blockquote
+#[derive(Default)]
+pub struct PandocJson {
+    blocks: Vec<Value>,
+    /// The containers that are open, innermost last
+    open: Vec<Container>,
+}
+
+/// An empty identifier, list of classes and list of attributes, which most elements start with
+fn no_attributes() -> Value {
+    json!(["", [], []])
+}
+
+/// The words of `text`, with spaces and line breaks between them
+fn words(text: &str) -> Vec<Value> {
+    let mut inlines = Vec::new();
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            inlines.push(json!({"t": "LineBreak"}));
+        }
+        let mut words = line.split(' ').peekable();
+        while let Some(word) = words.next() {
+            if !word.is_empty() {
+                inlines.push(json!({"t": "Str", "c": word}));
+            }
+            let is_space = inlines.last().and_then(|last| last.get("t")) == Some(&json!("Space"));
+            if words.peek().is_some() && !is_space {
+                inlines.push(json!({"t": "Space"}));
+            }
+        }
+    }
+    inlines
+}
+
+/// The inlines inside a formatting or link inline, if `inline` is one
+fn contents_mut(inline: &mut Value) -> Option<&mut Vec<Value>> {
+    match inline.get("t")?.as_str()? {
+        "Strong" | "Emph" | "Strikeout" | "Underline" => inline.get_mut("c")?.as_array_mut(),
+        "Link" => inline.get_mut("c")?.get_mut(1)?.as_array_mut(),
+        _ => None,
+    }
+}
+
+/// Adds `inline` to the end of `inlines`, merging it into the last of them if both are the same
+/// formatting, or links to the same place, so that formatting spanning several slices stays whole
+fn push_merged(inlines: &mut Vec<Value>, mut inline: Value) {
+    if let Some(last) = inlines.last_mut() {
+        let is_same = last.get("t") == inline.get("t")
+            && (inline.get("t") != Some(&json!("Link"))
+                || last.get("c").and_then(|c| c.get(2)) == inline.get("c").and_then(|c| c.get(2)));
+        if is_same {
+            if let (Some(last_contents), Some(contents)) =
+                (contents_mut(last), contents_mut(&mut inline))
+            {
+                for inline in contents.drain(..) {
+                    push_merged(last_contents, inline);
+                }
+                return;
+            }
+        }
+    }
+    inlines.push(inline);
+}
+
+/// The inlines for formatted text
+fn inlines(text: &[(String, Format)]) -> Vec<Value> {
+    let mut inlines = Vec::new();
+    for (slice, format) in text {
+        let mut nodes = if format.is_code() {
+            vec![json!({"t": "Code", "c": [no_attributes(), slice]})]
+        } else {
+            words(slice)
+        };
+        let wrappers = [
+            (format.is_underline(), "Underline"),
+            (format.roles().contains(Roles::DELETED), "Strikeout"),
+            (format.roles().contains(Roles::EMPHASIS), "Emph"),
+            (format.roles().contains(Roles::STRONG), "Strong"),
+        ];
+        for (_, wrapper) in wrappers.into_iter().filter(|(applies, _)| *applies) {
+            nodes = vec![json!({"t": wrapper, "c": nodes})];
+        }
+        if let Some(target) = format.link() {
+            nodes = vec![json!({"t": "Link", "c": [no_attributes(), nodes, [target, ""]]})];
+        }
+        for node in nodes {
+            push_merged(&mut inlines, node);
+        }
+    }
+    inlines
+}
+
+/// The cells of a table row
+fn row(cells: &[Vec<(String, Format)>]) -> Value {
+    let cells: Vec<_> = cells
+        .iter()
+        .map(|cell| {
+            json!([
+                no_attributes(),
+                {"t": "AlignDefault"},
+                1,
+                1,
+                [{"t": "Plain", "c": inlines(cell)}]
+            ])
+        })
+        .collect();
+    json!([no_attributes(), cells])
+}
+
+impl PandocJson {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `block` to the innermost open item or blockquote, or to the document if there is none
+    fn push(&mut self, block: Value) {
+        match self.open.last_mut() {
+            Some(Container::Item(blocks, _) | Container::Quote(blocks)) => blocks.push(block),
+            Some(Container::List { .. }) | None => self.blocks.push(block),
+        }
+    }
+}
+
+impl Backend for PandocJson {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        self.push(json!({"t": "Para", "c": inlines(text)}));
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        self.push(json!({"t": "Header", "c": [level, no_attributes(), inlines(text)]}));
+    }
+
+    fn thematic_break(&mut self) {
+        self.push(json!({"t": "HorizontalRule"}));
+    }
+
+    fn table(&mut self, table: &Table) {
+        let columns: Vec<_> = table
+            .alignments()
+            .iter()
+            .map(|alignment| {
+                let alignment = match alignment {
+                    Alignment::None => "AlignDefault",
+                    Alignment::Left => "AlignLeft",
+                    Alignment::Center => "AlignCenter",
+                    Alignment::Right => "AlignRight",
+                };
+                json!([{"t": alignment}, {"t": "ColWidthDefault"}])
+            })
+            .collect();
+        let rows: Vec<_> = table.rows().iter().map(|cells| row(cells)).collect();
+        self.push(json!({
+            "t": "Table",
+            "c": [
+                no_attributes(),
+                [null, []],
+                columns,
+                [no_attributes(), [row(table.header())]],
+                [[no_attributes(), 0, [], rows]],
+                [no_attributes(), []]
+            ]
+        }));
+    }
+
+    fn list_start(&mut self, list: &List) {
+        let numbering = match list.kind() {
+            ListKind::Bullet(_) => None,
+            ListKind::Ordered { start, delimiter } => {
+                let delimiter = if delimiter == ')' {
+                    "OneParen"
+                } else {
+                    "Period"
+                };
+                Some(json!([start, {"t": "Decimal"}, {"t": delimiter}]))
+            }
+        };
+        self.open.push(Container::List {
+            numbering,
+            tight: list.is_tight(),
+            items: Vec::new(),
+        });
+    }
+
+    fn list_end(&mut self) {
+        if let Some(Container::List {
+            numbering, items, ..
+        }) = self.open.pop()
+        {
+            self.push(match numbering {
+                Some(numbering) => json!({"t": "OrderedList", "c": [numbering, items]}),
+                None => json!({"t": "BulletList", "c": items}),
+            });
+        }
+    }
+
+    fn item_start(&mut self, _index: usize, task: Option<bool>) {
+        self.open.push(Container::Item(Vec::new(), task));
+    }
+
+    fn item_end(&mut self) {
+        let Some(Container::Item(mut blocks, task)) = self.open.pop() else {
+            return;
+        };
+        // pandoc reads the boxes of task list items as the characters for them, at the start of
+        // the item's text
+        if let Some(checked) = task {
+            let checkbox = json!({"t": "Str", "c": if checked { "☒" } else { "☐" }});
+            let is_text = |block: &Value| {
+                matches!(
+                    block.get("t").and_then(Value::as_str),
+                    Some("Para" | "Plain")
+                )
+            };
+            match blocks.first_mut() {
+                Some(block) if is_text(block) => {
+                    if let Some(Value::Array(inlines)) = block.get_mut("c") {
+                        inlines.splice(0..0, [checkbox, json!({"t": "Space"})]);
+                    }
+                }
+                _ => blocks.insert(0, json!({"t": "Plain", "c": [checkbox]})),
+            }
+        }
+        if let Some(Container::List { tight, items, .. }) = self.open.last_mut() {
+            // pandoc writes the paragraphs of tight lists as plain text
+            if *tight {
+                for block in &mut blocks {
+                    if block.get("t") == Some(&json!("Para")) {
+                        block["t"] = json!("Plain");
+                    }
+                }
+            }
+            items.push(Value::Array(blocks));
+        }
+    }
+
+    fn quote_start(&mut self) {
+        self.open.push(Container::Quote(Vec::new()));
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        let mut attribution = vec![json!({"t": "Str", "c": "—"}), json!({"t": "Space"})];
+        attribution.extend(inlines(text));
+        self.push(json!({"t": "Para", "c": attribution}));
+    }
+
+    fn quote_end(&mut self) {
+        if let Some(Container::Quote(blocks)) = self.open.pop() {
+            self.push(json!({"t": "BlockQuote", "c": blocks}));
+        }
+    }
+
+    fn code_block(&mut self, info: &str, code: &str) {
+        let classes: Vec<_> = info.split_whitespace().take(1).collect();
+        self.push(json!({"t": "CodeBlock", "c": [["", classes, []], code]}));
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.push(json!({"t": "RawBlock", "c": ["html", format!("<!--{text}-->")]}));
+    }
+
+    fn finish(&mut self) -> String {
+        while !self.open.is_empty() {
+            match self.open.last() {
+                Some(Container::List { .. }) => self.list_end(),
+                Some(Container::Item(..)) => self.item_end(),
+                Some(Container::Quote(_)) => self.quote_end(),
+                None => {}
+            }
+        }
+        json!({
+            "pandoc-api-version": API_VERSION,
+            "meta": {},
+            "blocks": std::mem::take(&mut self.blocks)
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The blocks of the document `text` is rendered as
+    fn render(text: &str) -> Value {
+        let mut pandoc = PandocJson::new();
+        for element in crate::parse(text) {
+            element.visit(&mut pandoc);
+        }
+        let document: Value = serde_json::from_str(&pandoc.finish()).unwrap();
+        document.get("blocks").unwrap().clone()
+    }
+
+    mod inline_elements {
+        use super::*;
+
+        #[test]
+        fn formatting_spanning_slices_stays_whole() {
+            assert_eq!(
+                json!([{"t": "Para", "c": [
+                    {"t": "Str", "c": "lorem"},
+                    {"t": "Space"},
+                    {"t": "Strong", "c": [
+                        {"t": "Str", "c": "ipsum"},
+                        {"t": "Space"},
+                        {"t": "Emph", "c": [{"t": "Str", "c": "dolor"}]}
+                    ]},
+                    {"t": "Space"},
+                    {"t": "Code", "c": [["", [], []], "sit"]}
+                ]}]),
+                render("lorem **ipsum *dolor*** `sit`")
+            );
+        }
+    }
+
+    mod block_elements {
+        use super::*;
+
+        #[test]
+        fn lists_and_quotes_hold_their_blocks() {
+            assert_eq!(
+                json!([
+                    {"t": "OrderedList", "c": [
+                        [3, {"t": "Decimal"}, {"t": "Period"}],
+                        [[{"t": "Plain", "c": [{"t": "Str", "c": "lorem"}]}]]
+                    ]},
+                    {"t": "BlockQuote", "c": [{"t": "Para", "c": [{"t": "Str", "c": "ipsum"}]}]}
+                ]),
+                render("3. lorem\n\n> ipsum")
+            );
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use std::mem;
+
+use printpdf::{
+    BuiltinFont, CustomPdfConformance, IndirectFontRef, Mm, PdfConformance, PdfDocument,
+    PdfLayerReference, Point, Pt,
+};
+
+use crate::code_block::{code_lines, CODE_INDENT};
+use crate::formatting::{Format, Roles};
+use crate::list::{bullet, checkboxes, indent, numbering, List};
+use crate::markdown::render_rule;
+use crate::options::RenderOptions;
+use crate::quote;
+use crate::table::Table;
+use crate::theme::Glyphs;
+use crate::wrapping::wrap;
+
+use super::Backend;
+
+/// The size of an A4 page, in points
+const PAGE_WIDTH: f32 = 595.28;
+const PAGE_HEIGHT: f32 = 841.89;
+/// The space left around the text on every side, in points
+const MARGIN: f32 = 72.0;
+/// The font size of body text, in points
+const BODY_SIZE: f32 = 10.0;
+/// The font size of headings of each level, in points
+const HEADING_SIZES: [f32; 6] = [20.0, 16.0, 14.0, 12.0, 11.0, 10.0];
+/// The width of every character in Courier, as a fraction of the font size
+const CHARACTER_WIDTH: f32 = 0.6;
+/// The height of a line, as a multiple of its font size
+const LINE_SPACING: f32 = 1.2;
+
+/// Renders a PDF document of A4 pages. Text is set in Courier, every character of which is the
+/// same width, so that lines can be broken by the same wrapping as terminal output. The first
+/// level 1 heading gives the document's title. As a PDF is binary, the document itself comes
+/// from [`Backend::finish_bytes`]; [`Backend::finish`] gives the text as it is laid out, with
+/// pages separated by form feeds
+#[derive(Debug, Default)]
+pub struct Pdf {
+    options: RenderOptions,
+    lines: Vec<Line>,
+    title: Option<String>,
+    /// The lists and blockquotes that are open, innermost last
+    containers: Vec<Container>,
+    /// How many columns the blocks being laid out are indented by, inside of lists and
+    /// blockquotes
+    indent: usize,
+}
+
+/// A list or blockquote being laid out, along with what was laid out before it
+#[derive(Debug)]
+struct Container {
+    kind: ContainerKind,
+    /// How many columns the container indents its blocks by
+    indent: usize,
+    outer_lines: Vec<Line>,
+}
+
+#[derive(Debug)]
+enum ContainerKind {
+    List {
+        markers: Vec<String>,
+        tight: bool,
+        /// The lines of the items laid out so far
+        items: Vec<Vec<Line>>,
+        /// The index of the item being laid out
+        index: usize,
+    },
+    Quote,
+}
+
+/// A line of the document as it is laid out on the page
+#[derive(Debug)]
+enum Line {
+    /// A line of text in the given font size
+    Text(f32, Vec<(String, Format)>),
+    /// A horizontal rule across the page
+    Rule,
+    /// The space of a blank line of body text, which is left out at the top of a page
+    Gap,
+}
+
+impl Line {
+    /// The height of the line, in points
+    fn height(&self) -> f32 {
+        match self {
+            Self::Text(size, _) => size * LINE_SPACING,
+            Self::Rule | Self::Gap => BODY_SIZE * LINE_SPACING,
+        }
+    }
+}
+
+impl Pdf {
+    /// Creates a backend that renders as `options` ask, except that the wrapping width always
+    /// comes from the width of the page
+    #[must_use]
+    pub fn new(options: &RenderOptions) -> Self {
+        Self {
+            options: options.clone(),
+            lines: Vec::new(),
+            title: None,
+            containers: Vec::new(),
+            indent: 0,
+        }
+    }
+
+    /// Leaves a gap before the next block, if there is anything before it that isn't in the same
+    /// item of a tight list
+    fn gap(&mut self) {
+        let in_tight_list = matches!(
+            self.containers.last(),
+            Some(Container {
+                kind: ContainerKind::List { tight: true, .. },
+                ..
+            })
+        );
+        if !self.lines.is_empty() && !in_tight_list {
+            self.lines.push(Line::Gap);
+        }
+    }
+
+    /// The options for laying out text of the given font size, across the page less the
+    /// indentation of any lists and blockquotes
+    fn options(&self, size: f32) -> RenderOptions {
+        self.options
+            .clone()
+            .width(columns(size).saturating_sub(self.indent))
+    }
+
+    /// Opens a container that indents its blocks by `indent` columns
+    fn open(&mut self, kind: ContainerKind, indent: usize) {
+        self.gap();
+        self.indent += indent;
+        self.containers.push(Container {
+            kind,
+            indent,
+            outer_lines: mem::take(&mut self.lines),
+        });
+    }
+
+    /// Closes the innermost container, returning it along with its lines
+    fn close(&mut self) -> Option<(ContainerKind, Vec<Line>)> {
+        let container = self.containers.pop()?;
+        self.indent -= container.indent;
+        let lines = mem::replace(&mut self.lines, container.outer_lines);
+        Some((container.kind, lines))
+    }
+
+    /// Lays out `text` in the given font size, after a gap if there is anything before it
+    fn push_lines(&mut self, size: f32, text: &[(String, Format)]) {
+        self.gap();
+        let options = self.options(size);
+        self.lines.extend(
+            wrap(text, &options)
+                .into_iter()
+                .map(|line| Line::Text(size, line)),
+        );
+    }
+
+    /// Splits the lines up into pages
+    fn pages(&self) -> Vec<Vec<&Line>> {
+        let mut pages = vec![];
+        let mut page = vec![];
+        let mut height = 0.0;
+        for line in &self.lines {
+            if height + line.height() > PAGE_HEIGHT - 2.0 * MARGIN && !page.is_empty() {
+                pages.push(mem::take(&mut page));
+                height = 0.0;
+            }
+            if page.is_empty() && matches!(line, Line::Gap) {
+                continue;
+            }
+            height += line.height();
+            page.push(line);
+        }
+        if !page.is_empty() || pages.is_empty() {
+            pages.push(page);
+        }
+        pages
+    }
+}
+
+impl Backend for Pdf {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        self.push_lines(BODY_SIZE, text);
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        if level == 1 && self.title.is_none() {
+            self.title = Some(text.iter().map(|(slice, _)| slice.as_str()).collect());
+        }
+        let size = HEADING_SIZES
+            .get(usize::from(level.saturating_sub(1)))
+            .copied()
+            .unwrap_or(BODY_SIZE);
+        let bold: Vec<_> = text
+            .iter()
+            .map(|(slice, format)| (slice.clone(), format.clone().set_roles(Roles::STRONG)))
+            .collect();
+        self.push_lines(size, &bold);
+    }
+
+    fn thematic_break(&mut self) {
+        self.gap();
+        self.lines.push(Line::Rule);
+    }
+
+    fn table(&mut self, table: &Table) {
+        self.gap();
+        // the builtin fonts have no box-drawing characters
+        let theme = self.options.theme.clone().glyphs(Glyphs::ascii());
+        let options = self.options(BODY_SIZE).theme(theme);
+        self.lines.extend(
+            table
+                .layout(&options)
+                .into_iter()
+                .map(|line| Line::Text(BODY_SIZE, line)),
+        );
+    }
+
+    fn list_start(&mut self, list: &List) {
+        // the builtin fonts have no bullets other than the ASCII ones
+        let options = self
+            .options
+            .clone()
+            .theme(self.options.theme.clone().glyphs(Glyphs::ascii()));
+        let markers = list.markers(
+            bullet(&options),
+            numbering(&self.options),
+            checkboxes(&options),
+        );
+        let indent = indent(&markers);
+        self.options.list_depth += 1;
+        let kind = ContainerKind::List {
+            markers,
+            tight: list.is_tight(),
+            items: Vec::new(),
+            index: 0,
+        };
+        self.open(kind, indent);
+    }
+
+    fn list_end(&mut self) {
+        if let Some((ContainerKind::List { tight, items, .. }, _)) = self.close() {
+            self.options.list_depth -= 1;
+            for (index, item) in items.into_iter().enumerate() {
+                if index > 0 && !tight {
+                    self.lines.push(Line::Gap);
+                }
+                self.lines.extend(item);
+            }
+        }
+    }
+
+    // the boxes of task list items are part of their markers
+    fn item_start(&mut self, index: usize, _task: Option<bool>) {
+        if let Some(Container {
+            kind: ContainerKind::List { index: current, .. },
+            ..
+        }) = self.containers.last_mut()
+        {
+            *current = index;
+        }
+    }
+
+    fn item_end(&mut self) {
+        let mut lines = mem::take(&mut self.lines);
+        if let Some(Container {
+            kind:
+                ContainerKind::List {
+                    markers,
+                    items,
+                    index,
+                    ..
+                },
+            indent,
+            ..
+        }) = self.containers.last_mut()
+        {
+            let marker = markers.get(*index).cloned().unwrap_or_default();
+            let hanging = format!("{marker:<width$}", width = *indent);
+            let padding = " ".repeat(*indent);
+            let mut first = true;
+            for line in &mut lines {
+                if let Line::Text(_, fragments) = line {
+                    let prefix = if first { &hanging } else { &padding };
+                    fragments.insert(0, (prefix.clone(), Format::new()));
+                    first = false;
+                }
+            }
+            if first {
+                lines.push(Line::Text(BODY_SIZE, vec![(marker, Format::new())]));
+            }
+            items.push(lines);
+        }
+    }
+
+    fn quote_start(&mut self) {
+        self.open(ContainerKind::Quote, 2);
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        let theme = self.options.theme.clone().glyphs(Glyphs::ascii());
+        let options = self.options(BODY_SIZE).theme(theme);
+        self.lines.extend(
+            quote::attribution_lines(text, &options)
+                .into_iter()
+                .map(|line| Line::Text(BODY_SIZE, line)),
+        );
+    }
+
+    fn quote_end(&mut self) {
+        if let Some((ContainerKind::Quote, lines)) = self.close() {
+            let bar = Glyphs::ascii().quote_bar;
+            self.lines.extend(lines.into_iter().map(|line| match line {
+                Line::Text(size, mut fragments) => {
+                    fragments.insert(0, (format!("{bar} "), Format::new()));
+                    Line::Text(size, fragments)
+                }
+                // the bar goes on down the side of the gaps between blocks
+                Line::Gap => Line::Text(BODY_SIZE, vec![(bar.to_owned(), Format::new())]),
+                Line::Rule => Line::Rule,
+            }));
+        }
+    }
+
+    fn code_block(&mut self, _info: &str, code: &str) {
+        self.gap();
+        let indent = " ".repeat(CODE_INDENT);
+        self.lines.extend(
+            code_lines(code, &self.options(BODY_SIZE))
+                .into_iter()
+                .map(|line| {
+                    Line::Text(
+                        BODY_SIZE,
+                        vec![(indent.clone() + &line, Format::new().set_code())],
+                    )
+                }),
+        );
+    }
+
+    fn finish(&mut self) -> String {
+        let rule = render_rule(&self.options.clone().width(columns(BODY_SIZE)));
+        let pages: Vec<String> = self
+            .pages()
+            .iter()
+            .map(|page| {
+                let lines: Vec<String> = page
+                    .iter()
+                    .map(|line| match line {
+                        Line::Text(_, fragments) => {
+                            fragments.iter().map(|(slice, _)| slice.as_str()).collect()
+                        }
+                        Line::Rule => rule.clone(),
+                        Line::Gap => String::new(),
+                    })
+                    .collect();
+                lines.join("\n")
+            })
+            .collect();
+        self.lines.clear();
+        pages.join("\n\u{c}") + "\n"
+    }
+
+    /// Finishes the document off and returns the PDF
+    fn finish_bytes(&mut self) -> Vec<u8> {
+        // a plain document, rather than the print-ready PDF/X that is the default, which has a
+        // colour profile embedded in it
+        let document = PdfDocument::empty(
+            self.title
+                .as_deref()
+                .unwrap_or(self.options.labels.untitled),
+        )
+        .with_conformance(PdfConformance::Custom(CustomPdfConformance {
+            allows_default_fonts: true,
+            ..CustomPdfConformance::default()
+        }));
+        // adding one of the built-in fonts can't fail, as there is nothing to load
+        let fonts = [
+            BuiltinFont::Courier,
+            BuiltinFont::CourierBold,
+            BuiltinFont::CourierOblique,
+            BuiltinFont::CourierBoldOblique,
+        ]
+        .map(|font| document.add_builtin_font(font).ok());
+
+        for page in self.pages() {
+            let (page_index, layer_index) =
+                document.add_page(Mm::from(Pt(PAGE_WIDTH)), Mm::from(Pt(PAGE_HEIGHT)), "text");
+            let layer = document.get_page(page_index).get_layer(layer_index);
+            let mut top = PAGE_HEIGHT - MARGIN;
+            for line in page {
+                match line {
+                    Line::Text(size, fragments) => {
+                        draw_text(&layer, &fonts, *size, top - size, fragments);
+                    }
+                    Line::Rule => draw_rule(&layer, top - line.height() / 2.0),
+                    Line::Gap => {}
+                }
+                top -= line.height();
+            }
+        }
+
+        self.lines.clear();
+        // saving can only fail if writing does, which it can't in memory
+        document.save_to_bytes().unwrap_or_default()
+    }
+}
+
+/// The number of characters of the given font size that fit across the page
+fn columns(size: f32) -> usize {
+    // the result is always small and positive
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let columns = ((PAGE_WIDTH - 2.0 * MARGIN) / (size * CHARACTER_WIDTH)) as usize;
+    columns
+}
+
+/// Draws a line of text with its baseline at `baseline`, in the font for each fragment's format:
+/// regular, bold, oblique, or bold oblique, in that order in `fonts`
+fn draw_text(
+    layer: &PdfLayerReference,
+    fonts: &[Option<IndirectFontRef>; 4],
+    size: f32,
+    baseline: f32,
+    fragments: &[(String, Format)],
+) {
+    layer.begin_text_section();
+    layer.set_text_cursor(Mm::from(Pt(MARGIN)), Mm::from(Pt(baseline)));
+    for (slice, format) in fragments {
+        let index = usize::from(format.roles().contains(Roles::STRONG))
+            + 2 * usize::from(format.roles().contains(Roles::EMPHASIS));
+        if let Some(Some(font)) = fonts.get(index) {
+            layer.set_font(font, size);
+            // characters outside of the fonts' encoding (Windows-1252) are left out
+            layer.write_text(slice.as_str(), font);
+        }
+    }
+    layer.end_text_section();
+}
+
+/// Draws a horizontal rule across the page at `height`
+fn draw_rule(layer: &PdfLayerReference, height: f32) {
+    layer.set_outline_thickness(0.5);
+    layer.add_line(printpdf::Line {
+        points: vec![
+            (
+                Point::new(Mm::from(Pt(MARGIN)), Mm::from(Pt(height))),
+                false,
+            ),
+            (
+                Point::new(Mm::from(Pt(PAGE_WIDTH - MARGIN)), Mm::from(Pt(height))),
+                false,
+            ),
+        ],
+        is_closed: false,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lay_out(elements: impl FnOnce(&mut Pdf)) -> String {
+        let mut pdf = Pdf::new(&RenderOptions::new());
+        elements(&mut pdf);
+        pdf.finish()
+    }
+
+    mod layout {
+        use super::*;
+
+        #[test]
+        fn text_wraps_to_the_page() {
+            let text = "lorem ipsum ".repeat(10);
+            assert_eq!(
+                format!(
+                    "{}\n{}\n",
+                    "lorem ipsum ".repeat(6).trim_end(),
+                    "lorem ipsum ".repeat(4).trim_end()
+                ),
+                lay_out(|pdf| pdf.paragraph(&[(text.trim_end().to_owned(), Format::new())]))
+            );
+        }
+
+        #[test]
+        fn headings_wrap_sooner() {
+            let text = "lorem ipsum ".repeat(4);
+            assert_eq!(
+                "lorem ipsum lorem ipsum lorem ipsum\nlorem ipsum\n",
+                lay_out(|pdf| pdf.heading(1, &[(text.trim_end().to_owned(), Format::new())]))
+            );
+        }
+
+        #[test]
+        fn list_items_hang_off_their_markers() {
+            let text = "lorem ipsum ".repeat(7);
+            let output = lay_out(|pdf| {
+                for element in crate::parse(&format!("1. {}\n2. dolor", text.trim_end())) {
+                    element.visit(pdf);
+                }
+            });
+            assert_eq!(
+                format!(
+                    "1. {}\n   {}\n2. dolor\n",
+                    "lorem ipsum ".repeat(6).trim_end(),
+                    "lorem ipsum"
+                ),
+                output
+            );
+        }
+
+        #[test]
+        fn pages_break_without_leading_gaps() {
+            let output = lay_out(|pdf| {
+                for _ in 0..60 {
+                    pdf.paragraph(&[("lorem".to_owned(), Format::new())]);
+                }
+            });
+            let pages: Vec<_> = output.split('\u{c}').collect();
+            assert_eq!(3, pages.len());
+            assert!(pages.iter().skip(1).all(|page| page.starts_with("lorem")));
+        }
+    }
+
+    mod output {
+        use super::*;
+
+        #[test]
+        fn output_is_a_pdf() {
+            let mut pdf = Pdf::new(&RenderOptions::new());
+            pdf.heading(1, &[("lorem".to_owned(), Format::new())]);
+            pdf.thematic_break();
+            assert_eq!(Some(&b"%PDF-"[..]), pdf.finish_bytes().get(..5));
+        }
+    }
+}
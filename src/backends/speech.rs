@@ -0,0 +1,304 @@
+use crate::formatting::{Format, Roles};
+use crate::list::{List, ListKind};
+use crate::table::Table;
+
+use super::Backend;
+
+/// Renders plain text for screen readers and speech synthesis, with every purely visual cue said
+/// in words instead: headings give their level, emphasis and links are announced where they start
+/// and end, and lists, blockquotes, tables and code blocks say where they begin and end. Each
+/// block is a paragraph of its own, without any wrapping
+#[derive(Debug, Default)]
+pub struct Speech {
+    blocks: Vec<String>,
+    /// The kinds of the lists that are open, innermost last
+    lists: Vec<ListKind>,
+    /// What is said before the next block, which is the first of a list item
+    item: Option<String>,
+}
+
+/// A run of inline formatting that is announced
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Cue {
+    Link(String),
+    Strong,
+    Emphasis,
+    Strikethrough,
+    Code,
+}
+
+impl Cue {
+    /// The cues for `format`, outermost first
+    fn of(format: &Format) -> Vec<Self> {
+        let link = format.link().map(|target| Self::Link(target.to_owned()));
+        let cues = [
+            (format.roles().contains(Roles::STRONG), Self::Strong),
+            (format.roles().contains(Roles::EMPHASIS), Self::Emphasis),
+            (format.roles().contains(Roles::DELETED), Self::Strikethrough),
+            (format.is_code(), Self::Code),
+        ];
+        link.into_iter()
+            .chain(
+                cues.into_iter()
+                    .filter_map(|(applies, cue)| applies.then_some(cue)),
+            )
+            .collect()
+    }
+
+    fn start(&self) -> &'static str {
+        match self {
+            Self::Link(_) => "link:",
+            Self::Strong => "begin strong emphasis",
+            Self::Emphasis => "begin emphasis",
+            Self::Strikethrough => "begin deleted text",
+            Self::Code => "begin code",
+        }
+    }
+
+    /// What is said where the run ends, given what was said inside of it
+    fn end(&self, text: &str) -> String {
+        match self {
+            // bare URLs are only said once
+            Self::Link(target) if text.trim() == target => String::new(),
+            Self::Link(target) => format!(", target: {target}"),
+            Self::Strong => "end strong emphasis".to_owned(),
+            Self::Emphasis => "end emphasis".to_owned(),
+            Self::Strikethrough => "end deleted text".to_owned(),
+            Self::Code => "end code".to_owned(),
+        }
+    }
+}
+
+/// Adds a cue to the end of `output`, apart from what was said before it
+fn say_cue(output: &mut String, cue: &str) {
+    if !(output.is_empty() || output.ends_with(' ') || cue.starts_with(',')) {
+        output.push(' ');
+    }
+    output.push_str(cue);
+}
+
+/// Says formatted text, announcing each run of formatting where it starts and ends. Hard line
+/// breaks are read as spaces
+fn inline(text: &[(String, Format)]) -> String {
+    let mut output = String::new();
+    let mut open: Vec<Cue> = Vec::new();
+    // whether a cue was just said, which the text after it needs a space to be kept apart from
+    let mut after_cue = false;
+    // where what is said inside each open cue starts in `output`
+    let mut starts: Vec<usize> = Vec::new();
+
+    for (slice, format) in text {
+        let cues = Cue::of(format);
+        // cues stay open for as long as the slices go on having them, and any opened inside of
+        // one that doesn't go on are closed along with it
+        let kept = open.iter().take_while(|cue| cues.contains(cue)).count();
+        while open.len() > kept {
+            close(&mut output, &mut open, &mut starts);
+            after_cue = true;
+        }
+        for cue in cues {
+            if !open.contains(&cue) {
+                say_cue(&mut output, cue.start());
+                after_cue = true;
+                open.push(cue);
+                starts.push(output.len());
+            }
+        }
+
+        let slice = slice.replace('\n', " ");
+        if after_cue && !slice.starts_with([' ', '.', ',', ';', ':', '!', '?', ')']) {
+            output.push(' ');
+        }
+        after_cue = after_cue && slice.is_empty();
+        output += &slice;
+    }
+    while !open.is_empty() {
+        close(&mut output, &mut open, &mut starts);
+    }
+    output
+}
+
+/// Says where the innermost open cue ends
+fn close(output: &mut String, open: &mut Vec<Cue>, starts: &mut Vec<usize>) {
+    if let (Some(cue), Some(start)) = (open.pop(), starts.pop()) {
+        let end = cue.end(output.get(start..).unwrap_or_default());
+        if !end.is_empty() {
+            say_cue(output, &end);
+        }
+    }
+}
+
+impl Speech {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, block: String) {
+        let block = match self.item.take() {
+            Some(item) => format!("{item} {block}"),
+            None => block,
+        };
+        self.blocks.push(block);
+    }
+}
+
+impl Backend for Speech {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        self.push(inline(text));
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        self.push(format!("heading level {level}: {}", inline(text)));
+    }
+
+    fn thematic_break(&mut self) {
+        self.push("separator".to_owned());
+    }
+
+    fn table(&mut self, table: &Table) {
+        let rows = table.rows();
+        let columns = table.header().len();
+        let mut lines = vec![format!(
+            "table, {columns} {}, {} {}",
+            if columns == 1 { "column" } else { "columns" },
+            rows.len(),
+            if rows.len() == 1 { "row" } else { "rows" },
+        )];
+        for (index, row) in rows.iter().enumerate() {
+            let cells: Vec<_> = table
+                .header()
+                .iter()
+                .zip(row)
+                .map(|(header, cell)| {
+                    let cell = inline(cell);
+                    let cell = if cell.trim().is_empty() {
+                        "blank".to_owned()
+                    } else {
+                        cell
+                    };
+                    format!("{}: {cell}", inline(header))
+                })
+                .collect();
+            lines.push(format!("row {}: {}", index + 1, cells.join("; ")));
+        }
+        lines.push("end table".to_owned());
+        self.push(lines.join("\n"));
+    }
+
+    fn list_start(&mut self, list: &List) {
+        let count = list.items().len();
+        let items = if count == 1 { "item" } else { "items" };
+        let description = match list.kind() {
+            ListKind::Bullet(_) => format!("list, {count} {items}"),
+            ListKind::Ordered { .. } => format!("numbered list, {count} {items}"),
+        };
+        self.push(description);
+        self.lists.push(list.kind());
+    }
+
+    fn list_end(&mut self) {
+        self.lists.pop();
+        self.push("end list".to_owned());
+    }
+
+    fn item_start(&mut self, index: usize, task: Option<bool>) {
+        let number = match self.lists.last() {
+            Some(ListKind::Ordered { start, .. }) => {
+                u64::from(*start) + u64::try_from(index).unwrap_or_default()
+            }
+            _ => u64::try_from(index).unwrap_or_default() + 1,
+        };
+        self.item = Some(match task {
+            Some(true) => format!("item {number}, checked:"),
+            Some(false) => format!("item {number}, not checked:"),
+            None => format!("item {number}:"),
+        });
+    }
+
+    fn item_end(&mut self) {
+        // an item without any blocks is still said
+        if let Some(item) = self.item.take() {
+            self.blocks.push(item);
+        }
+    }
+
+    fn quote_start(&mut self) {
+        self.push("begin quote".to_owned());
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        self.push(format!("attributed to: {}", inline(text)));
+    }
+
+    fn quote_end(&mut self) {
+        self.push("end quote".to_owned());
+    }
+
+    fn code_block(&mut self, info: &str, code: &str) {
+        let start = match info.split_whitespace().next() {
+            Some(language) => format!("begin code block, {language}"),
+            None => "begin code block".to_owned(),
+        };
+        let mut lines = vec![start];
+        if !code.is_empty() {
+            lines.push(code.to_owned());
+        }
+        lines.push("end code block".to_owned());
+        self.push(lines.join("\n"));
+    }
+
+    fn finish(&mut self) -> String {
+        let mut output = self.blocks.join("\n\n");
+        output.push('\n');
+        self.blocks.clear();
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::{Dialect, ParserOptions, RenderOptions};
+    use crate::{parse_with_options, render_as, OutputFormat};
+
+    fn say(text: &str) -> String {
+        let options = ParserOptions::new().dialect(Dialect::Gfm);
+        render_as(
+            parse_with_options(text, &options),
+            OutputFormat::Speech,
+            &RenderOptions::new(),
+        )
+    }
+
+    mod inline_elements {
+        use super::*;
+
+        #[test]
+        fn emphasis_is_announced() {
+            assert_eq!(
+                "lorem begin emphasis ipsum end emphasis, begin strong emphasis dolor end strong emphasis\n",
+                say("lorem *ipsum*, **dolor**")
+            );
+        }
+
+        #[test]
+        fn links_give_their_targets() {
+            assert_eq!(
+                "see link: https://example.com for more, or link: lorem@example.com, target: mailto:lorem@example.com\n",
+                say("see https://example.com for more, or lorem@example.com")
+            );
+        }
+    }
+
+    mod block_elements {
+        use super::*;
+
+        #[test]
+        fn blocks_say_where_they_begin_and_end() {
+            assert_eq!(
+                "heading level 2: lorem\n\nnumbered list, 2 items\n\nitem 3: ipsum\n\nitem 4: dolor\n\nend list\n\nbegin quote\n\nsit\n\nattributed to: Cicero\n\nend quote\n",
+                say("## lorem\n\n3. ipsum\n4. dolor\n\n> sit\n> — Cicero")
+            );
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt::Write;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::formatting::Colour;
+
+use super::escape;
+
+/// The font size of the text, in pixels
+const FONT_SIZE: usize = 15;
+/// The width of a column, which is that of every character of a monospace font at [`FONT_SIZE`]
+const COLUMN_WIDTH: usize = 9;
+const LINE_HEIGHT: usize = 20;
+/// The space left around the text on every side, in pixels
+const PADDING: usize = 16;
+/// The colours of text and of the background where the output doesn't set them
+const FOREGROUND: Rgb = (229, 229, 229);
+const BACKGROUND: Rgb = (30, 30, 30);
+
+/// A colour's red, green and blue components
+type Rgb = (u8, u8, u8);
+
+/// The style that terminal output has at some point, as set by its escape codes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // one for each attribute an SGR code switches on and off
+struct Style {
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    inverse: bool,
+    foreground: Option<Colour>,
+    background: Option<Colour>,
+    link: Option<String>,
+}
+
+/// A run of text in one style, starting at a column of its line
+#[derive(Debug)]
+struct Run {
+    column: usize,
+    text: String,
+    style: Style,
+}
+
+impl Style {
+    /// Applies the parameters of an SGR sequence, such as `1;38;5;208`
+    fn apply(&mut self, parameters: &str) {
+        let mut parameters = parameters.split(';');
+        while let Some(parameter) = parameters.next() {
+            // the style of an underline (`4:3`) makes no difference here
+            let code = parameter.split(':').next().unwrap_or_default();
+            match code.parse::<u8>().unwrap_or_default() {
+                0 => {
+                    *self = Self {
+                        link: self.link.take(),
+                        ..Self::default()
+                    };
+                }
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                9 => self.strikethrough = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                29 => self.strikethrough = false,
+                code @ 30..=37 => self.foreground = Some(Colour::Indexed(code - 30)),
+                38 => self.foreground = extended_colour(&mut parameters),
+                39 => self.foreground = None,
+                code @ 40..=47 => self.background = Some(Colour::Indexed(code - 40)),
+                48 => self.background = extended_colour(&mut parameters),
+                49 => self.background = None,
+                code @ 90..=97 => self.foreground = Some(Colour::Indexed(code - 90 + 8)),
+                code @ 100..=107 => self.background = Some(Colour::Indexed(code - 100 + 8)),
+                _ => {}
+            }
+        }
+    }
+
+    /// The colours the text and its background are shown in, taking inverse video into account
+    fn colours(&self) -> (Rgb, Option<Rgb>) {
+        let foreground = self.foreground.map_or(FOREGROUND, Colour::rgb);
+        let background = self.background.map(Colour::rgb);
+        if self.inverse {
+            (background.unwrap_or(BACKGROUND), Some(foreground))
+        } else {
+            (foreground, background)
+        }
+    }
+}
+
+/// Reads the colour given after SGR 38 or 48, as `5;index` or `2;red;green;blue`
+fn extended_colour<'a>(parameters: &mut impl Iterator<Item = &'a str>) -> Option<Colour> {
+    let mut next = || {
+        parameters
+            .next()
+            .and_then(|parameter| parameter.parse().ok())
+    };
+    match next()? {
+        5 => Some(Colour::Indexed(next()?)),
+        2 => Some(Colour::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// Splits a line of terminal output up into runs of text in one style, going by its SGR and OSC 8
+/// escape sequences. Other escape sequences are dropped. `style` is the style the line starts
+/// in, which is left as the style it ends in
+fn runs(line: &str, style: &mut Style) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    let mut column = 0;
+    let mut characters = line.chars().peekable();
+    while let Some(character) = characters.next() {
+        if character == '\x1b' {
+            match characters.next() {
+                Some('[') => {
+                    let sequence: String = characters
+                        .by_ref()
+                        .take_while(|character| !('@'..='~').contains(character))
+                        .collect();
+                    style.apply(&sequence);
+                }
+                Some(']') => {
+                    let mut sequence = String::new();
+                    while let Some(character) = characters.next() {
+                        match character {
+                            '\x07' => break,
+                            '\x1b' => {
+                                characters.next_if_eq(&'\\');
+                                break;
+                            }
+                            _ => sequence.push(character),
+                        }
+                    }
+                    if let Some(target) = sequence
+                        .strip_prefix("8;")
+                        .and_then(|link| link.split_once(';'))
+                        .map(|(_, target)| target)
+                    {
+                        style.link = Some(target.to_owned()).filter(|target| !target.is_empty());
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match runs.last_mut() {
+            Some(run) if run.style == *style => run.text.push(character),
+            _ => runs.push(Run {
+                column,
+                text: character.to_string(),
+                style: style.clone(),
+            }),
+        }
+        column += character.to_string().width();
+    }
+    runs
+}
+
+fn hex((red, green, blue): Rgb) -> String {
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
+
+/// Lays terminal output out as an SVG image, as a terminal with a dark background would show it:
+/// in a monospace font, with its formatting, colours and hyperlinks. Each column of the output
+/// is the same width, so that tables and code blocks stay lined up
+#[must_use]
+pub fn from_terminal(output: &str) -> String {
+    let lines: Vec<_> = output.trim_end_matches('\n').lines().collect();
+    let mut style = Style::default();
+    let lines: Vec<Vec<Run>> = lines.iter().map(|line| runs(line, &mut style)).collect();
+    let columns = lines
+        .iter()
+        .filter_map(|runs| runs.last())
+        .map(|run| run.column + run.text.width())
+        .max()
+        .unwrap_or_default();
+
+    let width = columns * COLUMN_WIDTH + 2 * PADDING;
+    let height = lines.len() * LINE_HEIGHT + 2 * PADDING;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+        hex(BACKGROUND)
+    );
+
+    // writing to a `String` can't fail
+    for (index, runs) in lines.iter().enumerate() {
+        let top = PADDING + index * LINE_HEIGHT;
+        for run in runs {
+            if let (_, Some(background)) = run.style.colours() {
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{top}\" width=\"{}\" height=\"{LINE_HEIGHT}\" fill=\"{}\"/>",
+                    PADDING + run.column * COLUMN_WIDTH,
+                    run.text.width() * COLUMN_WIDTH,
+                    hex(background)
+                );
+            }
+        }
+
+        // the baseline, which leaves room for descenders below it
+        let baseline = top + LINE_HEIGHT - (LINE_HEIGHT - FONT_SIZE) / 2 - 3;
+        let _ = write!(
+            svg,
+            "<text y=\"{baseline}\" font-family=\"monospace\" font-size=\"{FONT_SIZE}\" xml:space=\"preserve\">"
+        );
+        for run in runs {
+            let (foreground, _) = run.style.colours();
+            let mut attributes = format!(
+                "x=\"{}\" fill=\"{}\"",
+                PADDING + run.column * COLUMN_WIDTH,
+                hex(foreground)
+            );
+            if run.style.bold {
+                attributes += " font-weight=\"bold\"";
+            }
+            if run.style.italic {
+                attributes += " font-style=\"italic\"";
+            }
+            if run.style.dim {
+                attributes += " fill-opacity=\"0.6\"";
+            }
+            let decorations: Vec<_> = [
+                (run.style.underline, "underline"),
+                (run.style.strikethrough, "line-through"),
+            ]
+            .into_iter()
+            .filter_map(|(applies, decoration)| applies.then_some(decoration))
+            .collect();
+            if !decorations.is_empty() {
+                let _ = write!(attributes, " text-decoration=\"{}\"", decorations.join(" "));
+            }
+
+            let tspan = format!("<tspan {attributes}>{}</tspan>", escape(&run.text));
+            match &run.style.link {
+                Some(target) => {
+                    let _ = write!(svg, "<a href=\"{}\">{tspan}</a>", escape(target));
+                }
+                None => svg += &tspan,
+            }
+        }
+        svg += "</text>\n";
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod formatting {
+        use super::*;
+
+        #[test]
+        fn formatting_is_carried_over() {
+            let svg =
+                from_terminal("lorem \x1b[1;38;5;208mipsum\x1b[22;39m \x1b[7mdolor\x1b[27m\n");
+            assert!(svg.starts_with(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"185\" height=\"52\""
+            ));
+            assert!(svg.contains("<tspan x=\"16\" fill=\"#e5e5e5\">lorem </tspan>"));
+            assert!(
+                svg.contains("<tspan x=\"70\" fill=\"#ff8700\" font-weight=\"bold\">ipsum</tspan>")
+            );
+            // inverse video swaps the colours
+            assert!(svg.contains(
+                "<rect x=\"124\" y=\"16\" width=\"45\" height=\"20\" fill=\"#e5e5e5\"/>"
+            ));
+            assert!(svg.contains("<tspan x=\"124\" fill=\"#1e1e1e\">dolor</tspan>"));
+        }
+
+        #[test]
+        fn styles_carry_on_across_lines() {
+            let svg = from_terminal("\x1b[3mlorem\nipsum\x1b[23m");
+            assert!(svg.contains("font-style=\"italic\">lorem</tspan>"));
+            assert!(svg.contains("font-style=\"italic\">ipsum</tspan>"));
+        }
+
+        #[test]
+        fn links_are_kept() {
+            let svg =
+                from_terminal("\x1b]8;;https://example.com/?a&b\x1b\\link\x1b]8;;\x1b\\ <text>");
+            assert!(svg.contains(
+                "<a href=\"https://example.com/?a&amp;b\"><tspan x=\"16\" fill=\"#e5e5e5\">link</tspan></a>"
+            ));
+            assert!(svg.contains("<tspan x=\"52\" fill=\"#e5e5e5\"> &lt;text&gt;</tspan>"));
+        }
+    }
+
+    mod layout {
+        use super::*;
+
+        #[test]
+        fn wide_characters_take_two_columns() {
+            let svg = from_terminal("日本\x1b[1m語\x1b[22m");
+            assert!(
+                svg.contains("<tspan x=\"52\" fill=\"#e5e5e5\" font-weight=\"bold\">語</tspan>")
+            );
+        }
+    }
+}
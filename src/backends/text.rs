@@ -0,0 +1,223 @@
+use crate::code_block;
+use crate::formatting::Format;
+use crate::list::{bullet, checkboxes, hang, indent, nested, numbering, List};
+use crate::markdown::render_rule;
+use crate::options::RenderOptions;
+use crate::quote;
+use crate::table::Table;
+use crate::wrapping::wrap;
+
+use super::Backend;
+
+/// Renders plain text, with all formatting removed but wrapping and decorations kept as they are
+/// in terminal output
+#[derive(Debug, Default)]
+pub struct Text {
+    options: RenderOptions,
+    blocks: Vec<String>,
+    /// The lists and blockquotes that are open, innermost last
+    containers: Vec<Container>,
+}
+
+/// A list or blockquote being rendered, along with what was being rendered around it
+#[derive(Debug)]
+struct Container {
+    kind: ContainerKind,
+    /// The blocks before the container, and the options they were rendered with
+    outer_blocks: Vec<String>,
+    outer_options: RenderOptions,
+}
+
+#[derive(Debug)]
+enum ContainerKind {
+    List {
+        markers: Vec<String>,
+        indent: usize,
+        separator: &'static str,
+        /// The items rendered so far
+        items: Vec<String>,
+        /// The index of the item being rendered
+        index: usize,
+    },
+    Quote {
+        attribution: Option<String>,
+    },
+}
+
+impl Text {
+    #[must_use]
+    pub fn new(options: &RenderOptions) -> Self {
+        Self {
+            options: options.clone(),
+            blocks: Vec::new(),
+            containers: Vec::new(),
+        }
+    }
+
+    fn push_lines(&mut self, text: &[(String, Format)]) {
+        let plain: Vec<_> = text
+            .iter()
+            .map(|(slice, _)| (slice.clone(), Format::new()))
+            .collect();
+        let lines: Vec<String> = wrap(&plain, &self.options)
+            .iter()
+            .map(|line| line.iter().map(|(slice, _)| slice.as_str()).collect())
+            .collect();
+        self.blocks.push(lines.join("\n"));
+    }
+
+    /// Opens a container, the blocks of which are rendered with `options`
+    fn open(&mut self, kind: ContainerKind, options: RenderOptions) {
+        self.containers.push(Container {
+            kind,
+            outer_blocks: std::mem::take(&mut self.blocks),
+            outer_options: std::mem::replace(&mut self.options, options),
+        });
+    }
+
+    /// Closes the innermost container, returning it along with its blocks
+    fn close(&mut self) -> Option<(ContainerKind, Vec<String>)> {
+        let container = self.containers.pop()?;
+        let blocks = std::mem::replace(&mut self.blocks, container.outer_blocks);
+        self.options = container.outer_options;
+        Some((container.kind, blocks))
+    }
+}
+
+impl Backend for Text {
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        self.push_lines(text);
+    }
+
+    fn heading(&mut self, _level: u8, text: &[(String, Format)]) {
+        self.push_lines(text);
+    }
+
+    fn thematic_break(&mut self) {
+        self.blocks.push(render_rule(&self.options));
+    }
+
+    fn table(&mut self, table: &Table) {
+        let lines: Vec<String> = table
+            .layout(&self.options)
+            .iter()
+            .map(|line| line.iter().map(|(slice, _)| slice.as_str()).collect())
+            .collect();
+        self.blocks.push(lines.join("\n"));
+    }
+
+    fn list_start(&mut self, list: &List) {
+        let markers = list.markers(
+            bullet(&self.options),
+            numbering(&self.options),
+            checkboxes(&self.options),
+        );
+        let indent = indent(&markers);
+        let options = nested(&self.options, indent);
+        let kind = ContainerKind::List {
+            markers,
+            indent,
+            separator: list.separator(),
+            items: Vec::new(),
+            index: 0,
+        };
+        self.open(kind, options);
+    }
+
+    fn list_end(&mut self) {
+        if let Some((
+            ContainerKind::List {
+                items, separator, ..
+            },
+            _,
+        )) = self.close()
+        {
+            self.blocks.push(items.join(separator));
+        }
+    }
+
+    // the boxes of task list items are part of their markers
+    fn item_start(&mut self, index: usize, _task: Option<bool>) {
+        if let Some(Container {
+            kind: ContainerKind::List { index: current, .. },
+            ..
+        }) = self.containers.last_mut()
+        {
+            *current = index;
+        }
+    }
+
+    fn item_end(&mut self) {
+        let blocks = std::mem::take(&mut self.blocks);
+        if let Some(Container {
+            kind:
+                ContainerKind::List {
+                    markers,
+                    indent,
+                    separator,
+                    items,
+                    index,
+                },
+            ..
+        }) = self.containers.last_mut()
+        {
+            let marker = markers.get(*index).map_or("", String::as_str);
+            items.push(hang(marker, *indent, &blocks.join(separator)));
+        }
+    }
+
+    fn quote_start(&mut self) {
+        let options = quote::inner(&self.options);
+        self.open(ContainerKind::Quote { attribution: None }, options);
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        let lines: Vec<String> = quote::attribution_lines(text, &self.options)
+            .iter()
+            .map(|line| line.iter().map(|(slice, _)| slice.as_str()).collect())
+            .collect();
+        if let Some(Container {
+            kind: ContainerKind::Quote { attribution },
+            ..
+        }) = self.containers.last_mut()
+        {
+            *attribution = Some(lines.join("\n"));
+        }
+    }
+
+    fn quote_end(&mut self) {
+        if let Some((ContainerKind::Quote { attribution }, blocks)) = self.close() {
+            let mut content = blocks.join("\n\n");
+            if let Some(attribution) = attribution {
+                if !content.is_empty() {
+                    content.push('\n');
+                }
+                content += &attribution;
+            }
+            self.blocks
+                .push(quote::bar(self.options.theme.glyphs.quote_bar, &content));
+        }
+    }
+
+    #[cfg_attr(not(feature = "diagrams"), allow(unused_variables))]
+    fn code_block(&mut self, info: &str, code: &str) {
+        #[cfg(feature = "diagrams")]
+        if let Some(art) = crate::diagram::draw(info, code, &self.options) {
+            self.blocks.push(art);
+            return;
+        }
+        // highlighted lines can't be told apart without formatting
+        let lines: Vec<String> = code_block::layout(code, &[], &self.options)
+            .iter()
+            .map(|line| line.iter().map(|(slice, _)| slice.as_str()).collect())
+            .collect();
+        self.blocks.push(lines.join("\n"));
+    }
+
+    fn finish(&mut self) -> String {
+        let mut output = self.blocks.join("\n\n");
+        output.push('\n');
+        self.blocks.clear();
+        output
+    }
+}
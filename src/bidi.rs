@@ -0,0 +1,241 @@
+use unicode_bidi::{get_base_direction, Direction, Level, ParagraphBidiInfo};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::formatting::Format;
+use crate::options::RenderOptions;
+
+/// Whether the paragraph made of `slices` is right-to-left, which is decided by the first
+/// character in it that has a direction of its own
+pub(crate) fn is_right_to_left(slices: &[(String, Format)]) -> bool {
+    let text: String = slices.iter().map(|(slice, _)| slice.as_str()).collect();
+    get_base_direction(text.as_str()) == Direction::Rtl
+}
+
+/// The bracket that `character` looks like when written from right to left, if it is one
+const fn mirrored(character: char) -> char {
+    match character {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        _ => character,
+    }
+}
+
+/// Adds `character` to the end of `line`, in the last slice if it has the same format
+fn push(line: &mut Vec<(String, Format)>, character: char, format: &Format) {
+    match line.last_mut() {
+        Some((slice, last)) if last == format => slice.push(character),
+        _ => line.push((character.to_string(), format.clone())),
+    }
+}
+
+/// Puts a line of formatted slices, from a paragraph that is right-to-left or not, into the order
+/// it is shown in from left to right. Each slice keeps its format, brackets in right-to-left runs
+/// are mirrored, and combining marks stay after the characters they combine with. Hard line
+/// breaks (`\n`) are kept where they are, with the text between them reordered on its own
+pub(crate) fn reorder(line: &[(String, Format)], right_to_left: bool) -> Vec<(String, Format)> {
+    let text: String = line.iter().map(|(slice, _)| slice.as_str()).collect();
+    let level = if right_to_left {
+        Level::rtl()
+    } else {
+        Level::ltr()
+    };
+    // the format of each character of `text`, by its byte offset
+    let formats: Vec<(usize, &Format)> = line
+        .iter()
+        .flat_map(|(slice, format)| slice.char_indices().map(move |_| format))
+        .zip(text.char_indices())
+        .map(|(format, (offset, _))| (offset, format))
+        .collect();
+    let format_at = |offset: usize| {
+        let index = formats.partition_point(|(start, _)| *start < offset);
+        formats.get(index).map(|(_, format)| *format)
+    };
+
+    let mut reordered = Vec::with_capacity(line.len());
+    let mut start = 0;
+    for segment in text.split('\n') {
+        if start > 0 {
+            push(
+                &mut reordered,
+                '\n',
+                format_at(start - 1).unwrap_or(&Format::new()),
+            );
+        }
+        if segment.is_empty() {
+            start += 1;
+            continue;
+        }
+        let info = ParagraphBidiInfo::new(segment, Some(level));
+        let (levels, runs) = info.visual_runs(0..segment.len());
+        for run in runs {
+            let Some(characters) = segment.get(run.clone()) else {
+                continue;
+            };
+            // combining marks are kept with the character before them
+            let mut clusters: Vec<(usize, usize)> = Vec::new();
+            for (offset, character) in characters.char_indices() {
+                match clusters.last_mut() {
+                    Some((_, end)) if character.width() == Some(0) => {
+                        *end = offset + character.len_utf8();
+                    }
+                    _ => clusters.push((offset, offset + character.len_utf8())),
+                }
+            }
+            let is_rtl = levels.get(run.start).is_some_and(Level::is_rtl);
+            if is_rtl {
+                clusters.reverse();
+            }
+            for (cluster_start, cluster_end) in clusters {
+                let cluster = characters
+                    .get(cluster_start..cluster_end)
+                    .unwrap_or_default();
+                for (offset, character) in cluster.char_indices() {
+                    let offset = start + run.start + cluster_start + offset;
+                    let character = if is_rtl {
+                        mirrored(character)
+                    } else {
+                        character
+                    };
+                    push(
+                        &mut reordered,
+                        character,
+                        format_at(offset).unwrap_or(&Format::new()),
+                    );
+                }
+            }
+        }
+        start += segment.len() + 1;
+    }
+    reordered
+}
+
+/// Right-aligns the lines wrapped from `slices` to the wrapping width, if there is one, when they
+/// are a right-to-left paragraph that has been put into the order it is shown in
+pub(crate) fn aligned(
+    lines: Vec<Vec<(String, Format)>>,
+    slices: &[(String, Format)],
+    options: &RenderOptions,
+) -> Vec<Vec<(String, Format)>> {
+    let Some(width) = options
+        .width
+        .filter(|_| options.bidi && is_right_to_left(slices))
+    else {
+        return lines;
+    };
+    lines
+        .into_iter()
+        .map(|mut line| {
+            let line_width: usize = line.iter().map(|(slice, _)| slice.width()).sum();
+            if line_width < width {
+                line.insert(0, (" ".repeat(width - line_width), Format::new()));
+            }
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod reordering {
+        use super::*;
+
+        fn plain(text: &str) -> Vec<(String, Format)> {
+            vec![(text.to_owned(), Format::new())]
+        }
+
+        #[test]
+        fn direction_comes_from_the_first_strong_character() {
+            assert!(is_right_to_left(&plain("123 שלום world")));
+            assert!(!is_right_to_left(&plain("hello שלום")));
+            assert!(!is_right_to_left(&plain("123")));
+        }
+
+        #[test]
+        fn right_to_left_text_is_reversed() {
+            assert_eq!(plain("םולש"), reorder(&plain("שלום"), true));
+            assert_eq!(
+                plain("lorem םולש ipsum"),
+                reorder(&plain("lorem שלום ipsum"), false)
+            );
+            assert_eq!(
+                plain("lorem ipsum םולש"),
+                reorder(&plain("שלום lorem ipsum"), true)
+            );
+        }
+
+        #[test]
+        fn formats_follow_their_characters() {
+            let bold = Format::new().set_bold();
+            let line = vec![
+                ("של".to_owned(), bold.clone()),
+                ("ום (א)".to_owned(), Format::new()),
+            ];
+            assert_eq!(
+                vec![
+                    ("(א) םו".to_owned(), Format::new()),
+                    ("לש".to_owned(), bold),
+                ],
+                reorder(&line, true)
+            );
+        }
+
+        #[test]
+        fn combining_marks_stay_with_their_characters() {
+            assert_eq!(plain("בָּא"), reorder(&plain("אבָּ"), true));
+        }
+
+        #[test]
+        fn hard_breaks_are_kept() {
+            assert_eq!(plain("בא\nדג"), reorder(&plain("אב\nגד"), true));
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        #[test]
+        fn right_to_left_paragraphs_are_right_aligned() {
+            let options = RenderOptions::new().width(7).bidi(true);
+            assert_eq!(
+                "   םולש\n  ipsum",
+                crate::render(crate::parse("שלום ipsum"), &options).trim_end()
+            );
+            assert_eq!(
+                "lorem\nםולש",
+                crate::render(crate::parse("lorem שלום"), &options).trim_end()
+            );
+        }
+
+        #[test]
+        fn tables_stay_aligned() {
+            let options = RenderOptions::new().width(30).bidi(true);
+            let table = "| שם | lorem |\n|---|---|\n| אבג דהו | ipsum (א) |\n";
+            let render = crate::render(crate::parse(table), &options);
+            let render = regex::Regex::new("\x1b\\[[0-9;]*m")
+                .unwrap()
+                .replace_all(&render, "");
+            // the column separators all line up
+            let widths: Vec<_> = render
+                .trim_end()
+                .lines()
+                .map(|line| line.split(['│', '┼']).next().unwrap().width())
+                .collect();
+            assert!(
+                widths.windows(2).all(|pair| pair.first() == pair.last()),
+                "{render}"
+            );
+            assert!(render.contains("ipsum (א)"), "{render}");
+            assert!(render.contains("והד גבא"), "{render}");
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::ops::{Range, RangeInclusive};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::backends::Backend;
+use crate::formatting::Format;
+use crate::markdown::{with_role_formats, write_line, DocumentElement, ElementKind};
+use crate::options::RenderOptions;
+use crate::table::Table;
+
+/// The number of columns between tab stops in code
+const TAB_WIDTH: usize = 4;
+/// The indentation of code blocks in terminal and plain text output
+pub(crate) const CODE_INDENT: usize = 2;
+
+/// A fenced code block (```` ``` ```` or `~~~`), the content of which is shown verbatim
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    info: String,
+    code: String,
+    span: Range<usize>,
+}
+
+impl CodeBlock {
+    /// Parses a fenced code block at the start of `text`, returning it along with the number of
+    /// bytes of `text` it takes up, not counting the newline after its closing fence. Returns
+    /// `None` if `text` doesn't start with an opening fence. A block that is never closed goes on
+    /// to the end of `text`
+    #[must_use]
+    pub fn parse(text: &str) -> Option<(Self, usize)> {
+        let opening = text.split('\n').next().unwrap_or_default();
+        let (fence, indent, info) = opening_fence(opening)?;
+
+        let mut code = vec![];
+        let mut end = opening.len();
+        let mut line_start = opening.len() + 1;
+        for line in text
+            .get(line_start..)
+            .unwrap_or_default()
+            .split_inclusive('\n')
+        {
+            let content = line.trim_end_matches('\n');
+            end = line_start + content.len();
+            if is_closing_fence(content, fence) {
+                break;
+            }
+            // the code is indented by as much as the opening fence was, at most
+            let unindented = content.trim_start_matches(' ');
+            let removed = (content.len() - unindented.len()).min(indent);
+            code.push(content.get(removed..).unwrap_or_default());
+            line_start += line.len();
+        }
+
+        Some((
+            Self {
+                info: info.to_owned(),
+                code: code.join("\n"),
+                span: 0..end,
+            },
+            end,
+        ))
+    }
+
+    /// Moves the code block's span along by `offset` bytes, for when `text` wasn't the whole
+    /// document
+    #[must_use]
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        self.span = self.span.start + offset..self.span.end + offset;
+        self
+    }
+
+    /// The info string after the opening fence, which is empty if there isn't one
+    #[must_use]
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
+    /// The language of the code, from the first word of the info string, unless that is the
+    /// lines to highlight
+    #[must_use]
+    pub fn language(&self) -> Option<&str> {
+        self.info
+            .split_whitespace()
+            .next()
+            .filter(|word| !word.starts_with('{'))
+    }
+
+    /// The data in a `csv` or `tsv` block as a table, with its first record as the header.
+    /// Returns `None` for blocks of anything else, and for blocks with no records
+    #[must_use]
+    pub fn to_table(&self) -> Option<Table> {
+        let delimiter = match self.language()? {
+            "csv" => ',',
+            "tsv" => '\t',
+            _ => return None,
+        };
+        Table::from_delimited(&self.code, delimiter).map(|table| table.with_span(self.span.clone()))
+    }
+
+    /// The lines to highlight, counting from 1, as given in the info string by a comma-separated
+    /// list of line numbers and ranges of them in braces (`{1,3-5}`)
+    #[must_use]
+    pub fn highlighted_lines(&self) -> Vec<RangeInclusive<usize>> {
+        highlighted_lines(&self.info)
+    }
+
+    #[must_use]
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
+
+impl DocumentElement for CodeBlock {
+    fn render(&self, options: &RenderOptions) -> String {
+        let mut output = String::new();
+        self.render_into(options, &mut output);
+        output
+    }
+
+    fn render_into(&self, options: &RenderOptions, output: &mut String) {
+        #[cfg(feature = "diagrams")]
+        if let Some(art) = crate::diagram::draw(&self.info, &self.code, options) {
+            output.push_str(&art);
+            return;
+        }
+        let lines = layout(&self.code, &self.highlighted_lines(), options);
+        for (index, line) in lines.into_iter().enumerate() {
+            if index > 0 {
+                output.push('\n');
+            }
+            write_line(with_role_formats(&line, options), options, output);
+        }
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::CodeBlock
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.code_block(&self.info, &self.code);
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+}
+
+/// Parses an opening code fence, of at least three backticks or tildes, returning the fence
+/// itself, its indentation and the info string after it
+pub(crate) fn opening_fence(line: &str) -> Option<(&str, usize, &str)> {
+    let unindented = line.trim_start_matches(' ');
+    let indent = line.len() - unindented.len();
+    if indent > 3 {
+        return None;
+    }
+    let character = unindented
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '`' | '~'))?;
+    let info = unindented.trim_start_matches(character);
+    let fence = unindented.get(..unindented.len() - info.len())?;
+    // backticks in the info string would make the fence an inline code span instead
+    if fence.len() < 3 || (character == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((fence, indent, info.trim()))
+}
+
+/// Whether `line` closes a code block opened with `fence`: a run of the same character, at least
+/// as long, with nothing after it but spaces
+pub(crate) fn is_closing_fence(line: &str, fence: &str) -> bool {
+    let unindented = line.trim_start_matches(' ');
+    let Some(character) = fence.chars().next() else {
+        return false;
+    };
+    let rest = unindented.trim_start_matches(character);
+    line.len() - unindented.len() <= 3
+        && unindented.len() - rest.len() >= fence.len()
+        && rest.trim_end_matches(' ').is_empty()
+}
+
+/// Parses the lines to highlight out of an info string, ignoring anything in the braces that
+/// isn't a line number or a range of them
+fn highlighted_lines(info: &str) -> Vec<RangeInclusive<usize>> {
+    info.split_whitespace()
+        .filter_map(|word| word.strip_prefix('{')?.strip_suffix('}'))
+        .flat_map(|ranges| ranges.split(','))
+        .filter_map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            Some(start.trim().parse().ok()?..=end.trim().parse().ok()?)
+        })
+        .collect()
+}
+
+/// Whether `line` opens a fenced code block
+pub(crate) fn is_opening_fence(line: &str) -> bool {
+    opening_fence(line).is_some()
+}
+
+/// A fence for a code block of `code`, longer than any run of backticks in it so that nothing in
+/// the code can close the block early
+pub(crate) fn fence_for(code: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Writes `code` as a fenced code block with the info string `info`
+#[cfg(feature = "notebook")]
+pub(crate) fn fenced(info: &str, code: &str) -> String {
+    let fence = fence_for(code);
+    let code = code.strip_suffix('\n').unwrap_or(code);
+    format!("{fence}{info}\n{code}\n{fence}")
+}
+
+/// Breaks code up into lines no wider than the width set in `options` less the indentation of
+/// code blocks, breaking them wherever they overflow, as code has no good places to break it.
+/// Tabs are expanded to spaces, so that the width of each line is known
+pub(crate) fn code_lines(code: &str, options: &RenderOptions) -> Vec<String> {
+    let width = options
+        .width
+        .map(|width| width.saturating_sub(CODE_INDENT).max(1));
+
+    let mut lines = vec![];
+    for line in code.split('\n') {
+        let mut current = String::new();
+        let mut current_width = 0;
+        let mut column = 0;
+        for character in line.chars() {
+            let (text, character_width) = if character == '\t' {
+                let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                (" ".repeat(spaces), spaces)
+            } else {
+                (character.to_string(), character.width().unwrap_or_default())
+            };
+            if width.is_some_and(|width| current_width + character_width > width)
+                && !current.is_empty()
+            {
+                lines.push(std::mem::take(&mut current));
+                current_width = 0;
+            }
+            current += &text;
+            current_width += character_width;
+            column += character_width;
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+/// Lays out code as it is shown in the terminal: indented, with the lines in `highlights`
+/// highlighted across the whole width of the block, and numbered in a gutter if the options ask
+/// for line numbers. Lines with nothing to show are left empty. Plain code blocks are laid out
+/// just as they are written
+pub(crate) fn layout(
+    code: &str,
+    highlights: &[RangeInclusive<usize>],
+    options: &RenderOptions,
+) -> Vec<Vec<(String, Format)>> {
+    if options.code_blocks.plain {
+        return code
+            .split('\n')
+            .map(|line| {
+                if line.is_empty() {
+                    vec![]
+                } else {
+                    vec![(line.to_owned(), Format::new().set_code())]
+                }
+            })
+            .collect();
+    }
+
+    let source_lines: Vec<_> = code.split('\n').collect();
+    let digits = source_lines.len().to_string().len();
+    let gutter_width = if options.code_blocks.line_numbers {
+        digits + 1
+    } else {
+        0
+    };
+    let code_options = options.clone().narrowed(gutter_width);
+    let wrapped: Vec<_> = source_lines
+        .iter()
+        .map(|line| code_lines(line, &code_options))
+        .collect();
+    let block_width = code_options.width.map_or_else(
+        || wrapped.iter().flatten().map(|line| line.width()).max(),
+        |width| Some(width.saturating_sub(CODE_INDENT)),
+    );
+
+    let mut lines = vec![];
+    for (number, pieces) in (1..).zip(wrapped) {
+        let highlighted = highlights.iter().any(|range| range.contains(&number));
+        for (index, mut piece) in pieces.into_iter().enumerate() {
+            if piece.is_empty() && !highlighted && !options.code_blocks.line_numbers {
+                lines.push(vec![]);
+                continue;
+            }
+            let mut line = vec![(" ".repeat(CODE_INDENT), Format::new())];
+            if options.code_blocks.line_numbers {
+                // lines broken to fit are only numbered on their first part
+                let label = if index == 0 {
+                    number.to_string()
+                } else {
+                    String::new()
+                };
+                line.push((
+                    format!("{label:>digits$} "),
+                    options.theme.gutter_format().clone(),
+                ));
+            }
+            let mut format = Format::new().set_code();
+            if highlighted {
+                format = format.union(options.theme.highlighted_line_format());
+                let width = piece.width();
+                piece += &" ".repeat(block_width.unwrap_or_default().saturating_sub(width));
+            }
+            if !piece.is_empty() {
+                line.push((piece, format));
+            }
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod parsing {
+        use super::*;
+
+        #[test]
+        fn fences() {
+            let (block, length) =
+                CodeBlock::parse("```rust title\nfn main() {}\n```\nlorem").unwrap();
+            assert_eq!("rust title", block.info());
+            assert_eq!(Some("rust"), block.language());
+            assert_eq!("fn main() {}", block.code());
+            assert_eq!(30, length);
+
+            let (block, _) = CodeBlock::parse("~~~~\n```\n~~~\n~~~~").unwrap();
+            assert_eq!("```\n~~~", block.code());
+        }
+
+        #[test]
+        fn fence_indentation_is_removed() {
+            let (block, _) = CodeBlock::parse("  ```\n    a\n b\n  ```").unwrap();
+            assert_eq!("  a\nb", block.code());
+        }
+
+        #[test]
+        fn unclosed_blocks_go_on_to_the_end() {
+            let (block, length) = CodeBlock::parse("```\na\n\nb").unwrap();
+            assert_eq!("a\n\nb", block.code());
+            assert_eq!(8, length);
+        }
+
+        #[test]
+        fn highlighted_lines() {
+            let (block, _) = CodeBlock::parse("```rust {1,3-5} title\n```").unwrap();
+            assert_eq!(Some("rust"), block.language());
+            assert_eq!(vec![1..=1, 3..=5], block.highlighted_lines());
+
+            let (block, _) = CodeBlock::parse("``` {2,x,4-}\n```").unwrap();
+            assert_eq!(None, block.language());
+            assert_eq!(vec![2..=2], block.highlighted_lines());
+        }
+
+        #[test]
+        fn not_fences() {
+            assert!(CodeBlock::parse("``\na").is_none());
+            assert!(CodeBlock::parse("``` a`b\nc").is_none());
+            assert!(CodeBlock::parse("    ```\na").is_none());
+        }
+
+        #[test]
+        #[cfg(feature = "notebook")]
+        fn fenced_code_parses_back() {
+            let text = fenced("python", "print('````')\n");
+            let (block, length) = CodeBlock::parse(&text).unwrap();
+            assert_eq!("python", block.info());
+            assert_eq!("print('````')", block.code());
+            assert_eq!(text.len(), length);
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        #[test]
+        fn long_lines_are_broken_and_tabs_expanded() {
+            assert_eq!(
+                vec!["abcde", "f", "", "    a"],
+                code_lines("abcdef\n\n\ta", &RenderOptions::new().width(7))
+            );
+        }
+
+        #[test]
+        fn line_numbers_are_only_on_the_first_part_of_broken_lines() {
+            let options = RenderOptions::new().width(8).line_numbers(true);
+            let lines: Vec<String> = layout("abcdefgh\n\nb", &[], &options)
+                .iter()
+                .map(|line| line.iter().map(|(slice, _)| slice.as_str()).collect())
+                .collect();
+            assert_eq!(vec!["  1 abcd", "    efgh", "  2 ", "  3 b"], lines);
+        }
+
+        #[test]
+        fn plain_code_blocks_are_left_as_they_are() {
+            let options = RenderOptions::new()
+                .width(4)
+                .line_numbers(true)
+                .plain_code_blocks(true);
+            assert_eq!(
+                vec![
+                    vec![("\tabcdef".to_owned(), Format::new().set_code())],
+                    vec![]
+                ],
+                layout("\tabcdef\n", &[1..=1], &options)
+            );
+        }
+
+        #[test]
+        fn highlighted_lines_fill_the_block() {
+            let options = RenderOptions::new().width(6);
+            let highlighted = Format::new()
+                .set_code()
+                .union(options.theme.highlighted_line_format());
+            assert_eq!(
+                vec![
+                    vec![],
+                    vec![
+                        ("  ".to_owned(), Format::new()),
+                        ("a   ".to_owned(), highlighted)
+                    ],
+                    vec![],
+                ],
+                layout("\na\n", &[2..=2], &options)
+            );
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::ops::Range;
+
+use crate::backends::Backend;
+use crate::markdown::{render_lines, DocumentElement, ElementKind};
+use crate::options::RenderOptions;
+
+/// An HTML comment (`<!-- lorem -->`) on lines of its own, which is only shown if the render
+/// options ask for comments
+#[derive(Debug, PartialEq, Eq)]
+pub struct Comment {
+    /// The lines the comment is on, exactly as they were written
+    source: String,
+    span: Range<usize>,
+}
+
+impl Comment {
+    /// The text of the comment, between `<!--` and `-->`
+    #[must_use]
+    pub fn text(&self) -> &str {
+        let text = self.source.split_once("<!--").map_or("", |(_, text)| text);
+        text.split_once("-->").map_or(text, |(text, _)| text)
+    }
+}
+
+impl DocumentElement for Comment {
+    fn render(&self, options: &RenderOptions) -> String {
+        if self.is_hidden(options) {
+            return String::new();
+        }
+        let format = options.theme.comment_format();
+        self.source
+            .split('\n')
+            .map(|line| render_lines(&[(line.to_owned(), format.clone())], options))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn is_hidden(&self, options: &RenderOptions) -> bool {
+        !options.show_comments
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::Comment
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.comment(self.text());
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+}
+
+/// Whether `line` starts an HTML comment
+pub(crate) fn starts_comment(line: &str) -> bool {
+    let unindented = line.trim_start_matches(' ');
+    line.len() - unindented.len() <= 3 && unindented.starts_with("<!--")
+}
+
+/// Parses an HTML comment starting at the first line of `text`, if one starts there, returning it
+/// along with the number of bytes of `text` it takes up, not counting the newline after its last
+/// line. The comment goes on to the end of the line it is closed on, or to the end of `text` if
+/// it isn't closed
+pub(crate) fn parse_comment(text: &str) -> Option<(Comment, usize)> {
+    if !starts_comment(text.split('\n').next().unwrap_or_default()) {
+        return None;
+    }
+
+    let opening = text.find("<!--")? + "<!--".len();
+    let end = match text.get(opening..).and_then(|rest| rest.find("-->")) {
+        Some(closing) => {
+            let after = opening + closing + "-->".len();
+            text.get(after..)
+                .and_then(|rest| rest.find('\n'))
+                .map_or(text.len(), |newline| after + newline)
+        }
+        None => text.trim_end_matches('\n').len(),
+    };
+    Some((
+        Comment {
+            source: text.get(..end).unwrap_or_default().to_owned(),
+            span: 0..end,
+        },
+        end,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatting::Format;
+
+    mod parsing {
+        use super::*;
+
+        #[test]
+        fn comments_end_on_the_line_they_are_closed() {
+            let (comment, length) = parse_comment("<!-- lorem\n\nipsum --> dolor\nsit").unwrap();
+            assert_eq!(27, length);
+            assert_eq!(" lorem\n\nipsum ", comment.text());
+            assert_eq!(None, parse_comment("lorem <!-- ipsum -->"));
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        #[test]
+        fn comments_are_only_shown_when_asked_for() {
+            let (comment, _) = parse_comment("<!-- lorem -->").unwrap();
+            assert_eq!("", comment.render(&RenderOptions::new()));
+            let dim = Format::new().set_dim();
+            assert_eq!(
+                format!(
+                    "{}<!-- lorem -->{}",
+                    dim.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&dim)
+                ),
+                comment.render(&RenderOptions::new().show_comments(true))
+            );
+        }
+    }
+}
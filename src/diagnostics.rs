@@ -0,0 +1,197 @@
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use crate::markdown::DocumentElement;
+use crate::report::Report;
+use crate::tokenizer::{Token, Tokenizer};
+
+/// A likely mistake in a document: something that parses, but probably not as intended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The byte range of the source text the problem was found in
+    pub span: Range<usize>,
+    pub message: String,
+    /// What could be done about the problem
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    /// The one-based line and column (in characters) of the start of the diagnostic's span in
+    /// `text`, the source it was found in
+    #[must_use]
+    pub fn location(&self, text: &str) -> (usize, usize) {
+        let before = text.get(..self.span.start).unwrap_or(text);
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let column = before.get(line_start..).unwrap_or_default().chars().count();
+        (before.matches('\n').count() + 1, column + 1)
+    }
+
+    /// A warning about the problem, showing where it is in `text`, the contents of the file
+    /// called `file`
+    #[must_use]
+    pub fn report<'a>(&self, file: &'a str, text: &'a str) -> Report<'a> {
+        let report = Report::warning(&self.message).source(file, text, self.span.clone());
+        match &self.hint {
+            Some(hint) => report.hint(hint),
+            None => report,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Checks parsed `elements` for likely mistakes, given the `text` they were parsed from. This
+/// catches emphasis, strong emphasis and strikethrough left unclosed at the end of a paragraph or
+/// heading, headings that skip a level, and headings without any text
+#[must_use]
+pub fn check(text: &str, elements: &[Box<dyn DocumentElement>]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut previous_level = None;
+
+    for element in elements {
+        let span = element.span();
+        let is_inline = element.as_paragraph().is_some() || element.as_heading().is_some();
+        if is_inline {
+            let source = text.get(span.clone()).unwrap_or_default();
+            for (name, delimiter, opened) in unclosed_delimiters(source) {
+                diagnostics.push(Diagnostic {
+                    span: span.start + opened.start..span.start + opened.end,
+                    message: format!("unclosed {name}"),
+                    hint: Some(format!(
+                        "close it with another `{delimiter}`, or write `\\{delimiter}` for the character itself"
+                    )),
+                });
+            }
+        }
+
+        if let Some(heading) = element.as_heading() {
+            let level = heading.level();
+            if let Some(previous_level) = previous_level.filter(|previous| level > previous + 1) {
+                diagnostics.push(Diagnostic {
+                    span: span.clone(),
+                    message: format!("heading level jumps from {previous_level} to {level}"),
+                    hint: Some(format!(
+                        "make this a level {} heading, or add one in between",
+                        previous_level + 1
+                    )),
+                });
+            }
+            previous_level = Some(level);
+
+            if heading.plain_text().trim().is_empty() {
+                diagnostics.push(Diagnostic {
+                    span,
+                    message: "empty heading".to_owned(),
+                    hint: Some("give it some text, or remove it".to_owned()),
+                });
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// The inline delimiters opened but never closed in `source`: their names, the delimiters
+/// themselves and where in `source` they were opened
+fn unclosed_delimiters(source: &str) -> Vec<(&'static str, &'static str, Range<usize>)> {
+    let (mut emphasis, mut strong, mut strikethrough) = (None, None, None);
+    let mut tokenizer = Tokenizer::new(source);
+    loop {
+        let start = tokenizer.offset();
+        let opened = match tokenizer.next() {
+            Some(Token::Emphasis) => &mut emphasis,
+            Some(Token::Strong) => &mut strong,
+            Some(Token::Strikethrough) => &mut strikethrough,
+            Some(_) => continue,
+            None => break,
+        };
+        *opened = match opened {
+            Some(_) => None,
+            None => Some(start..tokenizer.offset()),
+        };
+    }
+
+    [
+        (emphasis, "emphasis (`*`)", "*"),
+        (strong, "strong emphasis (`**`)", "**"),
+        (strikethrough, "strikethrough (`~~`)", "~~"),
+    ]
+    .into_iter()
+    .filter_map(|(opened, name, delimiter)| Some((name, delimiter, opened?)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(text: &str) -> Vec<String> {
+        check(text, &crate::parse(text))
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    mod reports {
+        use super::*;
+
+        #[test]
+        fn well_formed_document() {
+            assert!(messages("# lorem\n\n*ipsum* **dolor** ~~sit~~\n\n## amet").is_empty());
+        }
+
+        #[test]
+        fn location() {
+            let text = "lorem\n\nipsum *dolor";
+            let diagnostics = check(text, &crate::parse(text));
+            assert_eq!(
+                vec![(3, 7)],
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| diagnostic.location(text))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    mod delimiters {
+        use super::*;
+
+        #[test]
+        fn unclosed_delimiters() {
+            assert_eq!(
+                vec!["unclosed emphasis (`*`)", "unclosed strikethrough (`~~`)"],
+                messages("*lorem ~~ipsum")
+            );
+            assert_eq!(
+                vec!["unclosed strong emphasis (`**`)"],
+                messages("# **lorem")
+            );
+            assert!(messages(r"\*lorem").is_empty());
+        }
+    }
+
+    mod headings {
+        use super::*;
+
+        #[test]
+        fn skipped_heading_level() {
+            assert_eq!(
+                vec![
+                    "heading level jumps from 1 to 3",
+                    "heading level jumps from 2 to 4"
+                ],
+                messages("# lorem\n### ipsum\n## dolor\n#### sit")
+            );
+        }
+
+        #[test]
+        fn empty_heading() {
+            assert_eq!(vec!["empty heading"], messages("lorem\n##\nipsum"));
+        }
+    }
+}
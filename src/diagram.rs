@@ -0,0 +1,106 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use tracing::debug;
+
+use crate::code_block::CODE_INDENT;
+use crate::options::RenderOptions;
+use crate::theme::Glyphs;
+
+/// The languages of code blocks that can be drawn as diagrams
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Diagram {
+    Mermaid,
+    Graphviz,
+}
+
+impl Diagram {
+    fn from_language(language: &str) -> Option<Self> {
+        match language {
+            "mermaid" => Some(Self::Mermaid),
+            "graphviz" | "dot" => Some(Self::Graphviz),
+            _ => None,
+        }
+    }
+
+    /// The program that draws the diagram as text, and the arguments it takes to read the source
+    /// from standard input, in ASCII or in box-drawing characters
+    fn command(self, ascii: bool) -> (&'static str, &'static [&'static str]) {
+        match (self, ascii) {
+            (Self::Mermaid, true) => ("mermaid-ascii", &["--ascii"]),
+            (Self::Mermaid, false) => ("mermaid-ascii", &[]),
+            (Self::Graphviz, true) => ("graph-easy", &["--from=graphviz", "--as=ascii"]),
+            (Self::Graphviz, false) => ("graph-easy", &["--from=graphviz", "--as=boxart"]),
+        }
+    }
+}
+
+/// Draws a code block in a diagram language, going by the first word of its info string, as text
+/// art indented as code blocks are, if the options ask for diagrams to be drawn. Returns `None` if
+/// the block isn't a diagram, or the program to draw it isn't installed or fails, in which case
+/// the code is shown as it is
+pub(crate) fn draw(info: &str, code: &str, options: &RenderOptions) -> Option<String> {
+    if !options.code_blocks.diagrams || options.code_blocks.plain {
+        return None;
+    }
+    let diagram = Diagram::from_language(info.split_whitespace().next()?)?;
+    let (program, arguments) = diagram.command(options.theme.glyphs == Glyphs::ascii());
+
+    let mut child = Command::new(program)
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .inspect_err(|error| debug!(program, %error, "unable to run program to draw diagram"))
+        .ok()?;
+    // dropping standard input closes it, so that the program knows it has the whole source
+    child.stdin.take()?.write_all(code.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        debug!(program, status = %output.status, "program to draw diagram failed");
+        return None;
+    }
+    let art = String::from_utf8(output.stdout).ok()?;
+
+    let indent = " ".repeat(CODE_INDENT);
+    let lines: Vec<_> = art
+        .trim_end()
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                String::new()
+            } else {
+                indent.clone() + line.trim_end()
+            }
+        })
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod drawing {
+        use super::*;
+
+        #[test]
+        fn only_diagram_languages_are_drawn() {
+            assert_eq!(Some(Diagram::Graphviz), Diagram::from_language("dot"));
+            assert_eq!(None, Diagram::from_language("rust"));
+
+            let options = RenderOptions::new().draw_diagrams(true);
+            assert_eq!(None, draw("rust", "fn main() {}", &options));
+            assert_eq!(None, draw("", "graph TD; a-->b", &options));
+        }
+
+        #[test]
+        fn diagrams_are_only_drawn_when_asked_for() {
+            assert_eq!(
+                None,
+                draw("mermaid", "graph TD; a-->b", &RenderOptions::new())
+            );
+        }
+    }
+}
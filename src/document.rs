@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::backends::OutputFormat;
+use crate::markdown::{atx_heading, DocumentElement};
+use crate::options::{ParserOptions, RenderOptions};
+
+/// A parsed document: the elements it is made up of, in order. Like the options it is rendered
+/// with, it is `Send` and `Sync`, so it can be parsed on a blocking thread pool and rendered from
+/// anywhere
+#[derive(Debug, Default, PartialEq)]
+pub struct Document {
+    elements: Vec<Box<dyn DocumentElement>>,
+}
+
+// fails to compile if anything needed to parse or render on other threads stops being thread-safe
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Document>();
+    assert_send_sync::<Box<dyn DocumentElement>>();
+    assert_send_sync::<OutlineEntry>();
+    assert_send_sync::<Section<'_>>();
+    assert_send_sync::<ParserOptions>();
+    assert_send_sync::<RenderOptions>();
+    assert_send_sync::<OutputFormat>();
+};
+
+/// A heading in a document's [outline](Document::outline)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlineEntry {
+    /// The heading's text with all markup removed, not including any section number
+    pub text: String,
+    /// The heading's section number (`1.2`), if the headings have been
+    /// [numbered](Document::number_headings)
+    pub number: Option<String>,
+    /// The heading level, from 1 to 6
+    pub level: u8,
+    /// The byte range of the heading in the source text
+    pub span: Range<usize>,
+    /// An identifier for the heading made from its text, as GitHub makes them (`Lorem Ipsum!`
+    /// becomes `lorem-ipsum`), which is unique within the document
+    pub slug: String,
+}
+
+/// A top-level section of a document, from [`Document::sections`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section<'a> {
+    /// The [slug](OutlineEntry::slug) of the heading the section starts with, which is empty if
+    /// the document has no headings
+    pub slug: String,
+    /// The section's source text, exactly as it is in the document
+    pub text: &'a str,
+}
+
+impl Document {
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        Self::from(crate::parse(text))
+    }
+
+    /// Parses `text` as [`Document::parse`] does, with control over how it is parsed
+    #[must_use]
+    pub fn parse_with_options(text: &str, options: &ParserOptions) -> Self {
+        Self::from(crate::parse_with_options(text, options))
+    }
+
+    #[must_use]
+    pub fn elements(&self) -> &[Box<dyn DocumentElement>] {
+        &self.elements
+    }
+
+    #[must_use]
+    pub fn into_elements(self) -> Vec<Box<dyn DocumentElement>> {
+        self.elements
+    }
+
+    /// Renders the document in the given output format, as [`render_as`](crate::render_as) does
+    #[must_use]
+    pub fn render(&self, format: OutputFormat, options: &RenderOptions) -> String {
+        crate::render_elements(&self.elements, format, options)
+    }
+
+    /// Numbers the document's headings by section (1, 1.1, 1.1.2), as
+    /// [`number_headings`](crate::markdown::number_headings) does, so that the numbers are shown
+    /// before them when it is rendered and given in its [outline](Self::outline)
+    pub fn number_headings(&mut self) {
+        crate::markdown::number_headings(&mut self.elements);
+    }
+
+    /// Returns every heading in the document, in order, for building navigation such as a table
+    /// of contents
+    #[must_use]
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        let mut slugs = HashSet::new();
+        self.elements
+            .iter()
+            .filter_map(|element| element.as_heading())
+            .map(|heading| {
+                let text = heading.plain_text();
+                OutlineEntry {
+                    slug: unique_slug(&text, &mut slugs),
+                    text,
+                    number: heading.number().map(str::to_owned),
+                    level: heading.level(),
+                    span: heading.span(),
+                }
+            })
+            .collect()
+    }
+
+    /// Splits `text`, the source the document was parsed from, into its top-level sections: one
+    /// for each heading at the highest level in the document, running up to the next. Anything
+    /// before the first of those headings goes in the first section, so that the sections' text
+    /// put together is all of `text`. A document without headings is one section
+    #[must_use]
+    pub fn sections<'a>(&self, text: &'a str) -> Vec<Section<'a>> {
+        let outline = self.outline();
+        let Some(top_level) = outline.iter().map(|entry| entry.level).min() else {
+            return vec![Section {
+                slug: String::new(),
+                text,
+            }];
+        };
+        let headings: Vec<_> = outline
+            .into_iter()
+            .filter(|entry| entry.level == top_level)
+            .collect();
+
+        let mut sections = Vec::with_capacity(headings.len());
+        let mut start = 0;
+        for (index, heading) in headings.iter().enumerate() {
+            let end = headings
+                .get(index + 1)
+                .map_or(text.len(), |next| next.span.start);
+            sections.push(Section {
+                slug: heading.slug.clone(),
+                text: text.get(start..end).unwrap_or_default(),
+            });
+            start = end;
+        }
+        sections
+    }
+}
+
+impl From<Vec<Box<dyn DocumentElement>>> for Document {
+    fn from(elements: Vec<Box<dyn DocumentElement>>) -> Self {
+        Self { elements }
+    }
+}
+
+/// Joins `texts`, the sources of several documents, into one, with a blank line between each.
+/// Headings are moved `demote` levels down (to level 6 at most) and written in a normal form, and
+/// links to headings (`[lorem](#ipsum)`) are changed to point to the same heading in the joined
+/// document, where the slugs of headings with the same text are numbered to keep them unique
+#[must_use]
+pub fn concatenate(texts: &[&str], options: &ParserOptions, demote: u8) -> String {
+    let mut used = HashSet::new();
+    let mut documents = Vec::with_capacity(texts.len());
+    for text in texts {
+        // the byte order mark of any but the first text would end up in the middle
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let document = Document::parse_with_options(text, options);
+        let slugs: HashMap<_, _> = document
+            .outline()
+            .into_iter()
+            .map(|entry| (entry.slug, unique_slug(&entry.text, &mut used)))
+            .collect();
+
+        let mut output = String::with_capacity(text.len());
+        let mut position = 0;
+        for heading in document
+            .elements()
+            .iter()
+            .filter_map(|element| element.as_heading())
+        {
+            let span = heading.span();
+            let before = text.get(position..span.start).unwrap_or_default();
+            output += &retarget_links(before, &slugs);
+            let source = text.get(span.clone()).unwrap_or_default();
+            if let Some((level, content)) = atx_heading(source) {
+                let level = level.saturating_add(demote).min(6);
+                output += &"#".repeat(usize::from(level));
+                if !content.is_empty() {
+                    output.push(' ');
+                    output += &retarget_links(content, &slugs);
+                }
+            }
+            position = span.end;
+        }
+        output += &retarget_links(text.get(position..).unwrap_or_default(), &slugs);
+        documents.push(output.trim_end().to_owned());
+    }
+
+    documents.retain(|document| !document.trim().is_empty());
+    let mut output = documents.join("\n\n");
+    output.push('\n');
+    output
+}
+
+/// Changes the targets of links to headings (`[lorem](#ipsum)`) in `text` to the slugs they are
+/// mapped to in `slugs`, leaving links to anything else as they are
+fn retarget_links(text: &str, slugs: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find("](#") {
+        let (before, after) = rest.split_at(index + "](#".len());
+        output += before;
+        rest = after;
+        let Some(end) = rest.find(')') else {
+            break;
+        };
+        if let Some(slug) = rest.get(..end).and_then(|target| slugs.get(target)) {
+            output += slug;
+            rest = rest.get(end..).unwrap_or_default();
+        }
+    }
+    output += rest;
+    output
+}
+
+/// Makes a slug from `text`, which is lower-cased, stripped of punctuation other than hyphens and
+/// underscores, and has spaces replaced with hyphens. Slugs already in `used` have a number added
+/// to the end to keep them unique
+fn unique_slug(text: &str, used: &mut HashSet<String>) -> String {
+    let base: String = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            _ if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect();
+
+    let mut slug = base.clone();
+    let mut count = 0_usize;
+    while used.contains(&slug) {
+        count += 1;
+        slug = format!("{base}-{count}");
+    }
+    used.insert(slug.clone());
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod outline {
+        use super::*;
+
+        fn slugs(text: &str) -> Vec<String> {
+            Document::parse(text)
+                .outline()
+                .into_iter()
+                .map(|entry| entry.slug)
+                .collect()
+        }
+
+        #[test]
+        fn headings_make_up_the_outline() {
+            assert_eq!(
+                vec![
+                    OutlineEntry {
+                        text: "lorem".to_owned(),
+                        number: None,
+                        level: 1,
+                        span: 0..7,
+                        slug: "lorem".to_owned(),
+                    },
+                    OutlineEntry {
+                        text: "ipsum dolor".to_owned(),
+                        number: None,
+                        level: 2,
+                        span: 19..35,
+                        slug: "ipsum-dolor".to_owned(),
+                    },
+                ],
+                Document::parse("# lorem\n\nsit amet\n\n## ipsum *dolor*\n\n---").outline()
+            );
+        }
+
+        #[test]
+        fn numbers_are_given_apart_from_the_text() {
+            let mut document = Document::parse("## lorem\n### ipsum\n## dolor");
+            document.number_headings();
+            let outline = document.outline();
+            assert_eq!(
+                vec![Some("1"), Some("1.1"), Some("2")],
+                outline
+                    .iter()
+                    .map(|entry| entry.number.as_deref())
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(Some("ipsum"), outline.get(1).map(|entry| &*entry.text));
+        }
+
+        #[test]
+        fn slugs_drop_punctuation() {
+            assert_eq!(
+                vec!["whats-new-in-v20", "lorem_ipsum---dolor", "日本語"],
+                slugs("# What's New in v2.0?\n# Lorem_Ipsum - Dolor\n# 日本語")
+            );
+        }
+
+        #[test]
+        fn duplicate_slugs_are_numbered() {
+            assert_eq!(
+                vec!["lorem", "lorem-1", "lorem-2"],
+                slugs("# lorem\n## Lorem\n### lorem")
+            );
+        }
+    }
+
+    mod sections {
+        use super::*;
+
+        #[test]
+        fn sections_cover_the_whole_text() {
+            let text = "intro\n\n## lorem\n\n### ipsum\n\n## dolor\nsit\n";
+            let sections = Document::parse(text).sections(text);
+            assert_eq!(
+                vec![
+                    Section {
+                        slug: "lorem".to_owned(),
+                        text: "intro\n\n## lorem\n\n### ipsum\n\n",
+                    },
+                    Section {
+                        slug: "dolor".to_owned(),
+                        text: "## dolor\nsit\n",
+                    },
+                ],
+                sections
+            );
+            assert_eq!(
+                vec![Section {
+                    slug: String::new(),
+                    text: "lorem"
+                }],
+                Document::parse("lorem").sections("lorem")
+            );
+        }
+    }
+
+    mod concatenation {
+        use super::*;
+
+        #[test]
+        fn concatenated_headings_are_demoted() {
+            assert_eq!(
+                "## lorem\n\nipsum\n\n### dolor\n\n## sit\n",
+                concatenate(
+                    &["# lorem ##\n\nipsum\n\n## dolor\n\n", "\u{feff}# sit"],
+                    &ParserOptions::new(),
+                    1
+                )
+            );
+            assert_eq!(
+                "###### lorem\n",
+                concatenate(&["##### lorem"], &ParserOptions::new(), 3)
+            );
+        }
+
+        #[test]
+        fn concatenated_links_follow_renumbered_slugs() {
+            assert_eq!(
+                "# lorem\n\n[a](#lorem)\n\n# lorem\n\n[b](#lorem-1) [c](#elsewhere) [d](https://example.com)\n",
+                concatenate(
+                    &[
+                        "# lorem\n\n[a](#lorem)",
+                        "# lorem\n\n[b](#lorem) [c](#elsewhere) [d](https://example.com)"
+                    ],
+                    &ParserOptions::new(),
+                    0
+                )
+            );
+        }
+    }
+}
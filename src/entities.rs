@@ -0,0 +1,165 @@
+/// The named character references that are decoded, sorted by name so that they can be binary
+/// searched. This is the commonly-used subset of the HTML list; anything else is left as-is
+const NAMED_REFERENCES: [(&str, char); 64] = [
+    ("AElig", 'Æ'),
+    ("Aacute", 'Á'),
+    ("Eacute", 'É'),
+    ("Ntilde", 'Ñ'),
+    ("Ouml", 'Ö'),
+    ("Uuml", 'Ü'),
+    ("aacute", 'á'),
+    ("acute", '´'),
+    ("aelig", 'æ'),
+    ("agrave", 'à'),
+    ("amp", '&'),
+    ("apos", '\''),
+    ("auml", 'ä'),
+    ("bull", '•'),
+    ("ccedil", 'ç'),
+    ("cent", '¢'),
+    ("check", '✓'),
+    ("copy", '©'),
+    ("dagger", '†'),
+    ("darr", '↓'),
+    ("deg", '°'),
+    ("divide", '÷'),
+    ("eacute", 'é'),
+    ("egrave", 'è'),
+    ("euro", '€'),
+    ("frac12", '½'),
+    ("frac14", '¼'),
+    ("frac34", '¾'),
+    ("ge", '≥'),
+    ("gt", '>'),
+    ("harr", '↔'),
+    ("hellip", '…'),
+    ("iexcl", '¡'),
+    ("infin", '∞'),
+    ("iquest", '¿'),
+    ("laquo", '«'),
+    ("larr", '←'),
+    ("ldquo", '“'),
+    ("le", '≤'),
+    ("lsquo", '‘'),
+    ("lt", '<'),
+    ("mdash", '—'),
+    ("micro", 'µ'),
+    ("middot", '·'),
+    ("nbsp", '\u{a0}'),
+    ("ndash", '–'),
+    ("ne", '≠'),
+    ("not", '¬'),
+    ("ntilde", 'ñ'),
+    ("ouml", 'ö'),
+    ("para", '¶'),
+    ("plusmn", '±'),
+    ("pound", '£'),
+    ("quot", '"'),
+    ("raquo", '»'),
+    ("rarr", '→'),
+    ("rdquo", '”'),
+    ("reg", '®'),
+    ("rsquo", '’'),
+    ("sect", '§'),
+    ("shy", '\u{ad}'),
+    ("times", '×'),
+    ("trade", '™'),
+    ("uarr", '↑'),
+];
+
+/// Decodes the entity or numeric character reference (`&amp;`, `&#169;`, `&#x1F600;`) at the
+/// start of `text`, returning the character it stands for and the length of the reference in
+/// bytes. Returns `None` if `text` doesn't start with a reference that can be decoded
+pub fn decode(text: &str) -> Option<(char, usize)> {
+    let (reference, _) = text.strip_prefix('&')?.split_once(';')?;
+    // '&' and ';' on top of the reference itself
+    let length = reference.len() + 2;
+
+    if let Some(number) = reference.strip_prefix('#') {
+        let (digits, radix, max_digits) = match number.strip_prefix(['x', 'X']) {
+            Some(hex_digits) => (hex_digits, 16, 6),
+            None => (number, 10, 7),
+        };
+        if !(1..=max_digits).contains(&digits.len())
+            || !digits.chars().all(|digit| digit.is_digit(radix))
+        {
+            return None;
+        }
+        // invalid code points (and NUL) are replaced, as they are in HTML
+        let character = u32::from_str_radix(digits, radix)
+            .ok()
+            .filter(|code_point| *code_point != 0)
+            .and_then(char::from_u32)
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        return Some((character, length));
+    }
+
+    NAMED_REFERENCES
+        .binary_search_by_key(&reference, |(name, _)| name)
+        .ok()
+        .and_then(|index| NAMED_REFERENCES.get(index))
+        .map(|(_, character)| (*character, length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod named {
+        use super::*;
+
+        #[test]
+        fn named_references_are_sorted() {
+            assert!(NAMED_REFERENCES
+                .windows(2)
+                .all(|pair| matches!(pair, [(first, _), (second, _)] if first < second)));
+        }
+
+        #[test]
+        fn named_reference() {
+            assert_eq!(Some(('&', 5)), decode("&amp;"));
+            assert_eq!(Some(('©', 6)), decode("&copy; 2023"));
+        }
+    }
+
+    mod numeric {
+        use super::*;
+
+        #[test]
+        fn decimal_reference() {
+            assert_eq!(Some(('©', 6)), decode("&#169;"));
+        }
+
+        #[test]
+        fn hexadecimal_reference() {
+            assert_eq!(Some(('😀', 9)), decode("&#x1F600;"));
+            assert_eq!(Some(('😀', 9)), decode("&#X1f600;"));
+        }
+
+        #[test]
+        fn invalid_code_points_are_replaced() {
+            assert_eq!(Some((char::REPLACEMENT_CHARACTER, 4)), decode("&#0;"));
+            assert_eq!(
+                Some((char::REPLACEMENT_CHARACTER, 10)),
+                decode("&#xD800FF;")
+            );
+            assert_eq!(Some((char::REPLACEMENT_CHARACTER, 8)), decode("&#xD800;"));
+        }
+    }
+
+    mod malformed {
+        use super::*;
+
+        #[test]
+        fn not_references() {
+            assert_eq!(None, decode("&amp"));
+            assert_eq!(None, decode("& amp;"));
+            assert_eq!(None, decode("&nosuchentity;"));
+            assert_eq!(None, decode("&#;"));
+            assert_eq!(None, decode("&#x;"));
+            assert_eq!(None, decode("&#12345678;"));
+            assert_eq!(None, decode("&#12a;"));
+            assert_eq!(None, decode("amp;"));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::{self, Display};
+use std::io::{self, Read};
+use std::time::Duration;
+
+/// The most that will be downloaded from a URL, in bytes
+pub const SIZE_LIMIT: u64 = 10 * 1024 * 1024;
+
+/// How long to wait for a server to accept a connection
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a whole download may take, from connecting to reading the last of the response
+#[cfg(not(test))]
+pub const DEADLINE: Duration = Duration::from_mins(2);
+#[cfg(test)]
+pub const DEADLINE: Duration = Duration::from_millis(500);
+
+/// Something that went wrong while fetching a document over HTTP(S)
+#[derive(Debug)]
+pub enum FetchError {
+    /// The request failed, or the server responded with an error
+    Request {
+        url: String,
+        error: Box<ureq::Error>,
+    },
+    /// The response couldn't be read, or took longer than [`DEADLINE`]
+    Io { url: String, error: io::Error },
+    /// The response was larger than [`SIZE_LIMIT`]
+    TooLarge { url: String },
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // `ureq`'s errors already start with the URL
+            Self::Request { error, .. } => write!(f, "unable to fetch {error}"),
+            Self::Io { url, error } => write!(f, "unable to read `{url}`: {error}"),
+            Self::TooLarge { url } => write!(
+                f,
+                "`{url}` is larger than the limit of {} MiB",
+                SIZE_LIMIT / 1024 / 1024
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request { error, .. } => Some(error),
+            Self::Io { error, .. } => Some(error),
+            Self::TooLarge { .. } => None,
+        }
+    }
+}
+
+/// Whether `input` names an HTTP(S) URL rather than a file
+#[must_use]
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Downloads the document at `url` as it is sent, to be decoded like a file. Gives up after
+/// [`TIMEOUT`] without a connection, if the download takes longer than [`DEADLINE`] in all, or if
+/// there is more than [`SIZE_LIMIT`] of it
+///
+/// # Errors
+///
+/// Returns an error if the request fails, the response can't be read in time, or it is too large
+pub fn fetch(url: &str) -> Result<Vec<u8>, FetchError> {
+    let response = ureq::AgentBuilder::new()
+        .timeout_connect(TIMEOUT)
+        .timeout(DEADLINE)
+        .build()
+        .get(url)
+        .call()
+        .map_err(|error| FetchError::Request {
+            url: url.to_owned(),
+            error: Box::new(error),
+        })?;
+
+    let mut bytes = Vec::new();
+    // one byte over the limit is read, so that a response of exactly the limit is allowed
+    response
+        .into_reader()
+        .take(SIZE_LIMIT + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|error| FetchError::Io {
+            url: url.to_owned(),
+            error,
+        })?;
+    if bytes.len() as u64 > SIZE_LIMIT {
+        return Err(FetchError::TooLarge {
+            url: url.to_owned(),
+        });
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    mod urls {
+        use super::*;
+
+        #[test]
+        fn urls() {
+            assert!(is_url("https://example.com/README.md"));
+            assert!(is_url("http://example.com"));
+            assert!(!is_url("README.md"));
+            assert!(!is_url("ftp://example.com/README.md"));
+        }
+    }
+
+    mod fetching {
+        use super::*;
+
+        /// Serves one request on a local port with `respond`, given the connection once the request
+        /// has been read, returning the URL to fetch
+        fn serve(respond: impl FnOnce(&mut dyn Write) + Send + 'static) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/", listener.local_addr().unwrap());
+            thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while request.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                respond(&mut stream);
+            });
+            url
+        }
+
+        #[test]
+        fn responses_are_given_as_they_are_sent() {
+            let url = serve(|stream| {
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ncaf\xe9\n");
+            });
+            assert_eq!(b"caf\xe9\n".to_vec(), fetch(&url).unwrap());
+        }
+
+        #[test]
+        fn downloads_that_take_too_long_are_given_up_on() {
+            // each byte comes well within the time allowed for it, but all of them don't
+            let url = serve(|stream| {
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n");
+                for _ in 0..100 {
+                    if stream
+                        .write_all(b"a")
+                        .and_then(|()| stream.flush())
+                        .is_err()
+                    {
+                        return;
+                    }
+                    thread::sleep(DEADLINE / 10);
+                }
+            });
+            assert!(matches!(fetch(&url), Err(FetchError::Io { .. })));
+        }
+    }
+}
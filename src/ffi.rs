@@ -0,0 +1,123 @@
+//! A C interface to the parser and renderer, declared in `include/sani.h`. To build it as a
+//! library that can be linked from C, run
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib` (or `staticlib`)
+
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use crate::backends::OutputFormat;
+use crate::document::Document;
+use crate::options::RenderOptions;
+
+/// A parsed document, which C only ever sees behind a pointer
+#[derive(Debug)]
+pub struct SaniDocument(Document);
+
+/// Parses `text`, a NUL-terminated UTF-8 string. Returns null if `text` is null or not valid
+/// UTF-8. The document must be freed with [`sani_document_free`]
+///
+/// # Safety
+///
+/// `text` must be null or point to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn sani_parse(text: *const c_char) -> *mut SaniDocument {
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    // SAFETY: the caller guarantees that a non-null `text` is NUL-terminated
+    let Ok(text) = unsafe { CStr::from_ptr(text) }.to_str() else {
+        return ptr::null_mut();
+    };
+    Box::into_raw(Box::new(SaniDocument(Document::parse(text))))
+}
+
+/// Renders `document` for the terminal, with formatting as ANSI escape codes, wrapped to `width`
+/// columns, or not wrapped at all if `width` is 0. Returns null if `document` is null. The
+/// string must be freed with [`sani_string_free`]
+///
+/// # Safety
+///
+/// `document` must be null or a document from [`sani_parse`] that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn sani_render_ansi(
+    document: *const SaniDocument,
+    width: usize,
+) -> *mut c_char {
+    // SAFETY: the caller guarantees that a non-null `document` is live
+    let Some(SaniDocument(document)) = (unsafe { document.as_ref() }) else {
+        return ptr::null_mut();
+    };
+    let options = if width == 0 {
+        RenderOptions::new()
+    } else {
+        RenderOptions::new().width(width)
+    };
+
+    let render = document.render(OutputFormat::Terminal, &options);
+    // the output only contains a NUL if the text did, which it can't have
+    CString::new(render).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Frees a document from [`sani_parse`]. Does nothing if `document` is null
+///
+/// # Safety
+///
+/// `document` must be null or a document from [`sani_parse`] that hasn't already been freed
+#[no_mangle]
+pub unsafe extern "C" fn sani_document_free(document: *mut SaniDocument) {
+    if !document.is_null() {
+        // SAFETY: the caller guarantees that `document` came from `Box::into_raw` in `sani_parse`
+        drop(unsafe { Box::from_raw(document) });
+    }
+}
+
+/// Frees a string from [`sani_render_ansi`]. Does nothing if `string` is null
+///
+/// # Safety
+///
+/// `string` must be null or a string from [`sani_render_ansi`] that hasn't already been freed
+#[no_mangle]
+pub unsafe extern "C" fn sani_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: the caller guarantees that `string` came from `CString::into_raw`
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod rendering {
+        use super::*;
+
+        #[test]
+        fn round_trip() {
+            let text = CString::new("# lorem\n\nipsum").unwrap();
+            unsafe {
+                let document = sani_parse(text.as_ptr());
+                assert!(!document.is_null());
+                let render = sani_render_ansi(document, 0);
+                assert_eq!(
+                    Ok(crate::render(
+                        crate::parse("# lorem\n\nipsum"),
+                        &RenderOptions::new()
+                    )),
+                    CStr::from_ptr(render).to_str().map(str::to_owned)
+                );
+                sani_string_free(render);
+                sani_document_free(document);
+            }
+        }
+
+        #[test]
+        fn null_and_invalid_text() {
+            let invalid = CString::new(vec![0xff_u8]).unwrap();
+            unsafe {
+                assert!(sani_parse(ptr::null()).is_null());
+                assert!(sani_parse(invalid.as_ptr()).is_null());
+                assert!(sani_render_ansi(ptr::null(), 80).is_null());
+            }
+        }
+    }
+}
@@ -1,90 +1,689 @@
+use std::fmt::{self, Debug, Display, Write};
+use std::sync::Arc;
+
 use bitflags::bitflags;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Format(FormatFlags);
+/// The style of a run of text: any number of on/off attributes (bold, italic, ...), foreground
+/// and background colours, and a hyperlink target, along with the inline [`Roles`] of the text,
+/// which the theme gives formatting of its own when it is rendered
+#[derive(Clone, Default, PartialEq)]
+pub struct Format {
+    flags: FormatFlags,
+    roles: Roles,
+    foreground: Option<Colour>,
+    background: Option<Colour>,
+    link: Option<Arc<str>>,
+}
+
+/// An on/off attribute, along with the SGR parameters that switch it on and off
+struct Attribute {
+    flag: FormatFlags,
+    on: &'static str,
+    off: &'static str,
+}
+
+/// The code that switches off all formatting but hyperlinks
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// Every on/off attribute, in the order their codes are emitted. Some attributes share an off
+/// code (SGR 24 ends every style of underline), which the diffing in
+/// [`Format::get_codes_for_format_change`] accounts for
+const ATTRIBUTES: [Attribute; 7] = [
+    Attribute {
+        flag: FormatFlags::BOLD,
+        on: "1",
+        off: "22",
+    },
+    Attribute {
+        flag: FormatFlags::DIM,
+        on: "2",
+        off: "22",
+    },
+    Attribute {
+        flag: FormatFlags::ITALIC,
+        on: "3",
+        off: "23",
+    },
+    Attribute {
+        flag: FormatFlags::STRIKETHROUGH,
+        on: "9",
+        off: "29",
+    },
+    Attribute {
+        flag: FormatFlags::UNDERLINE,
+        on: "4",
+        off: "24",
+    },
+    Attribute {
+        flag: FormatFlags::INVERSE,
+        on: "7",
+        off: "27",
+    },
+    Attribute {
+        flag: FormatFlags::DOTTED_UNDERLINE,
+        on: "4:4",
+        off: "24",
+    },
+];
 
 impl Format {
+    #[must_use]
     pub const fn new() -> Self {
-        Self(FormatFlags::empty())
+        Self {
+            flags: FormatFlags::empty(),
+            roles: Roles::empty(),
+            foreground: None,
+            background: None,
+            link: None,
+        }
+    }
+
+    /// Returns the codes required to change the terminal's formatting from `previous_format` to
+    /// this format: the end codes of any discontinued formatting, followed by the start codes of
+    /// any new formatting, all in one `String`. Formatting common to both is left alone. The SGR
+    /// parameters are combined into a single escape sequence
+    ///
+    /// # Ordering
+    ///
+    /// The codes always come in the same order, so that output is reproducible:
+    ///
+    /// 1. the end codes of discontinued attributes, in the order bold and dim (which share SGR
+    ///    22), italic, strikethrough, underline (SGR 24, for every style of underline) and
+    ///    inverse, each emitted once
+    /// 2. the end codes of discontinued foreground and then background colours
+    /// 3. the start codes of new attributes, along with those of attributes switched off by an end
+    ///    code shared with a discontinued one, in the order bold, dim, italic, strikethrough,
+    ///    underline, inverse and dotted underline
+    /// 4. the codes of new foreground and then background colours
+    /// 5. the end of a discontinued hyperlink, or the start of a new one, which aren't SGR codes
+    ///    and so always come after them
+    #[must_use]
+    pub fn get_codes_for_format_change(&self, previous_format: &Self) -> String {
+        let mut codes = String::new();
+        // writing to a `String` can't fail
+        let _ = self.write_codes_for_format_change(previous_format, SgrStyle::Combined, &mut codes);
+        codes
+    }
+
+    /// Writes the codes required to change the terminal's formatting from `previous_format` to
+    /// this format to `output`, as [`get_codes_for_format_change`] returns them (in the same
+    /// order) but with the SGR parameters written in the given style, without allocating a
+    /// `String` for them
+    ///
+    /// [`get_codes_for_format_change`]: Self::get_codes_for_format_change
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `output` fails
+    pub fn write_codes_for_format_change<W: Write>(
+        &self,
+        previous_format: &Self,
+        style: SgrStyle,
+        output: &mut W,
+    ) -> fmt::Result {
+        let mut sgr = Sgr::new(output, style);
+
+        // switch off whatever isn't wanted any more, emitting each off code once
+        let discontinued = previous_format.flags.difference(self.flags);
+        for (index, attribute) in ATTRIBUTES.iter().enumerate() {
+            let emitted = ATTRIBUTES
+                .iter()
+                .take(index)
+                .any(|earlier| earlier.off == attribute.off && discontinued.contains(earlier.flag));
+            if discontinued.contains(attribute.flag) && !emitted {
+                sgr.push(attribute.off)?;
+            }
+        }
+        if previous_format.foreground.is_some() && self.foreground.is_none() {
+            sgr.push(39)?;
+        }
+        if previous_format.background.is_some() && self.background.is_none() {
+            sgr.push(49)?;
+        }
+
+        // switch on whatever is new, along with anything wanted still that was switched off by
+        // an off code shared with a discontinued attribute
+        for attribute in &ATTRIBUTES {
+            let switched_off = ATTRIBUTES
+                .iter()
+                .any(|other| other.off == attribute.off && discontinued.contains(other.flag));
+            if self.flags.contains(attribute.flag)
+                && (!previous_format.flags.contains(attribute.flag) || switched_off)
+            {
+                sgr.push(attribute.on)?;
+            }
+        }
+        // a change of colour only needs the new colour's code
+        if let Some(colour) = self
+            .foreground
+            .filter(|_| self.foreground != previous_format.foreground)
+        {
+            sgr.push(ColourCode {
+                colour,
+                background: false,
+            })?;
+        }
+        if let Some(colour) = self
+            .background
+            .filter(|_| self.background != previous_format.background)
+        {
+            sgr.push(ColourCode {
+                colour,
+                background: true,
+            })?;
+        }
+        let output = sgr.finish()?;
+
+        // links aren't SGR codes, so they go after the sequence rather than in it
+        if previous_format.link.is_some() && self.link.is_none() {
+            write_osc_8(output, "")?;
+        }
+        if let Some(link) = self
+            .link
+            .as_deref()
+            .filter(|_| self.link != previous_format.link)
+        {
+            write_osc_8(output, link)?;
+        }
+
+        Ok(())
     }
 
-    /// Returns the start and end codes required to bring about the required terminal formatting
-    /// change. Returns the end codes to terminate any discontinued formatting, followed by the
-    /// start codes to bring about the new formatting, all in one `String`
-    pub fn get_codes_for_format_change(self, previous_format: Self) -> String {
-        let new_format_flags = self.0.difference(previous_format.0);
-        let discontinued_format_flags = previous_format.0.difference(self.0);
+    /// Writes the codes that end this format all at once to `output`: a single reset (SGR 0),
+    /// which ends every attribute and colour, followed by the end of any hyperlink, which a reset
+    /// leaves alone. Nothing is written for a format with no formatting
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `output` fails
+    pub fn write_reset_codes<W: Write>(&self, output: &mut W) -> fmt::Result {
+        if !self.flags.is_empty() || self.foreground.is_some() || self.background.is_some() {
+            output.write_str(RESET)?;
+        }
+        if self.link.is_some() {
+            write_osc_8(output, "")?;
+        }
+        Ok(())
+    }
+
+    /// Combines two formats, applying the formatting of both. Where both set a colour or link,
+    /// `self`'s takes precedence
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            flags: self.flags.union(other.flags),
+            roles: self.roles.union(other.roles),
+            foreground: self.foreground.or(other.foreground),
+            background: self.background.or(other.background),
+            link: self.link.clone().or_else(|| other.link.clone()),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_bold(&self) -> bool {
+        self.flags.contains(FormatFlags::BOLD)
+    }
 
-        Self(discontinued_format_flags).get_end_codes() + &Self(new_format_flags).get_start_codes()
+    #[must_use]
+    pub const fn is_italic(&self) -> bool {
+        self.flags.contains(FormatFlags::ITALIC)
+    }
+
+    #[must_use]
+    pub const fn is_strikethrough(&self) -> bool {
+        self.flags.contains(FormatFlags::STRIKETHROUGH)
+    }
+
+    /// Whether the format has an underline of any style
+    #[must_use]
+    pub const fn is_underline(&self) -> bool {
+        self.flags
+            .intersects(FormatFlags::UNDERLINE.union(FormatFlags::DOTTED_UNDERLINE))
+    }
+
+    /// Whether the text is code, from a code span
+    #[must_use]
+    pub const fn is_code(&self) -> bool {
+        self.roles.contains(Roles::CODE)
+    }
+
+    /// The inline roles of the text, such as emphasis or code
+    #[must_use]
+    pub const fn roles(&self) -> Roles {
+        self.roles
+    }
+
+    /// The target of the hyperlink the text is part of, if any
+    #[must_use]
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
     }
 
     pub fn toggle_bold(&mut self) {
-        self.0.toggle(FormatFlags::BOLD);
+        self.flags.toggle(FormatFlags::BOLD);
     }
 
     pub fn toggle_italic(&mut self) {
-        self.0.toggle(FormatFlags::ITALIC);
+        self.flags.toggle(FormatFlags::ITALIC);
     }
 
     pub fn toggle_strikethrough(&mut self) {
-        self.0.toggle(FormatFlags::STRIKETHROUGH);
+        self.flags.toggle(FormatFlags::STRIKETHROUGH);
     }
 
-    fn get_start_codes(self) -> String {
-        let mut codes = String::new();
-        if self.0.contains(FormatFlags::BOLD) {
-            codes += "\x1b[1m";
+    // the codes for switching the format on or off in one go, which tests compare against
+    #[cfg(test)]
+    fn get_start_codes(&self) -> String {
+        self.get_codes_for_format_change(&Self::new())
+    }
+
+    #[cfg(test)]
+    fn get_end_codes(&self) -> String {
+        Self::new().get_codes_for_format_change(self)
+    }
+
+    #[must_use]
+    pub const fn set_bold(self) -> Self {
+        self.with_flags(FormatFlags::BOLD)
+    }
+
+    #[must_use]
+    pub const fn set_italic(self) -> Self {
+        self.with_flags(FormatFlags::ITALIC)
+    }
+
+    #[must_use]
+    pub const fn set_strikethrough(self) -> Self {
+        self.with_flags(FormatFlags::STRIKETHROUGH)
+    }
+
+    #[must_use]
+    pub const fn set_underline(self) -> Self {
+        self.with_flags(FormatFlags::UNDERLINE)
+    }
+
+    /// Faint text, in terminals that support it
+    #[must_use]
+    pub const fn set_dim(self) -> Self {
+        self.with_flags(FormatFlags::DIM)
+    }
+
+    /// Swaps the foreground and background colours
+    #[must_use]
+    pub const fn set_inverse(self) -> Self {
+        self.with_flags(FormatFlags::INVERSE)
+    }
+
+    /// A dotted underline, in terminals that support styled underlines. Others generally fall
+    /// back to a plain underline
+    #[must_use]
+    pub const fn set_dotted_underline(self) -> Self {
+        self.with_flags(FormatFlags::DOTTED_UNDERLINE)
+    }
+
+    /// Marks the text as code. This has no formatting codes of its own: code is shown in the
+    /// theme's formatting for it
+    #[must_use]
+    pub const fn set_code(self) -> Self {
+        self.set_roles(Roles::CODE)
+    }
+
+    /// Gives the text inline roles, on top of those it has. Roles have no formatting codes of
+    /// their own: the text is shown in the theme's formatting for each of them
+    #[must_use]
+    pub const fn set_roles(mut self, roles: Roles) -> Self {
+        self.roles = self.roles.union(roles);
+        self
+    }
+
+    #[must_use]
+    pub const fn set_foreground(mut self, colour: Colour) -> Self {
+        self.foreground = Some(colour);
+        self
+    }
+
+    #[must_use]
+    pub const fn set_background(mut self, colour: Colour) -> Self {
+        self.background = Some(colour);
+        self
+    }
+
+    /// Swaps the format's colours for the nearest ones a terminal that can show `depth` colours
+    /// has
+    #[must_use]
+    pub fn downgraded(mut self, depth: ColourDepth) -> Self {
+        self.foreground = self.foreground.map(|colour| colour.downgraded(depth));
+        self.background = self.background.map(|colour| colour.downgraded(depth));
+        self
+    }
+
+    /// Makes the text a link to `target`, which gives it the [link role](Roles::LINK) and makes
+    /// it a hyperlink in terminals that support OSC 8 hyperlinks
+    #[must_use]
+    pub fn set_link(mut self, target: &str) -> Self {
+        self.link = Some(Arc::from(target));
+        self.set_roles(Roles::LINK)
+    }
+
+    const fn with_flags(mut self, flags: FormatFlags) -> Self {
+        self.flags = self.flags.union(flags);
+        self
+    }
+}
+
+/// How the SGR parameters of a change of formatting are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SgrStyle {
+    /// All in one escape sequence (`\x1b[22;3;9m`)
+    #[default]
+    Combined,
+    /// Each in an escape sequence of its own (`\x1b[22m\x1b[3m\x1b[9m`), for terminals that
+    /// mishandle sequences with several parameters
+    Separate,
+}
+
+/// Writes SGR parameters to an output in the given style
+struct Sgr<'a, W> {
+    output: &'a mut W,
+    style: SgrStyle,
+    /// Whether a combined escape sequence has been started, and needs ending
+    open: bool,
+}
+
+impl<'a, W: Write> Sgr<'a, W> {
+    const fn new(output: &'a mut W, style: SgrStyle) -> Self {
+        Self {
+            output,
+            style,
+            open: false,
         }
-        if self.0.contains(FormatFlags::ITALIC) {
-            codes += "\x1b[3m";
+    }
+
+    fn push(&mut self, parameter: impl Display) -> fmt::Result {
+        match self.style {
+            SgrStyle::Separate => write!(self.output, "\x1b[{parameter}m"),
+            SgrStyle::Combined if self.open => write!(self.output, ";{parameter}"),
+            SgrStyle::Combined => {
+                self.open = true;
+                write!(self.output, "\x1b[{parameter}")
+            }
         }
-        if self.0.contains(FormatFlags::STRIKETHROUGH) {
-            codes += "\x1b[9m";
+    }
+
+    /// Ends the escape sequence, if one was started, giving back the output
+    fn finish(self) -> Result<&'a mut W, fmt::Error> {
+        if self.open {
+            self.output.write_char('m')?;
         }
-        codes
+        Ok(self.output)
     }
+}
 
-    fn get_end_codes(self) -> String {
-        let mut codes = String::new();
-        if self.0.contains(FormatFlags::BOLD) {
-            codes += "\x1b[22m";
+/// Starts a hyperlink to `target`, or ends the current one if `target` is empty
+fn write_osc_8(output: &mut impl Write, target: &str) -> fmt::Result {
+    write!(output, "\x1b]8;;{target}\x1b\\")
+}
+
+impl Debug for Format {
+    /// Formats compactly and on a single line (e.g. `Format(BOLD | ITALIC | fg: Cyan)`), even when
+    /// pretty-printed, to keep dumps of parsed documents readable
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut attributes: Vec<String> = self
+            .flags
+            .iter_names()
+            .map(|(name, _)| name)
+            .chain(self.roles.iter_names().map(|(name, _)| name))
+            .map(str::to_owned)
+            .collect();
+        if let Some(colour) = self.foreground {
+            attributes.push(format!("fg: {colour:?}"));
         }
-        if self.0.contains(FormatFlags::ITALIC) {
-            codes += "\x1b[23m";
+        if let Some(colour) = self.background {
+            attributes.push(format!("bg: {colour:?}"));
         }
-        if self.0.contains(FormatFlags::STRIKETHROUGH) {
-            codes += "\x1b[29m";
+        if let Some(link) = &self.link {
+            attributes.push(format!("link: {link}"));
         }
-        codes
+        write!(f, "Format({})", attributes.join(" | "))
     }
+}
 
-    // these three methods are just used for test formulation, hence the 'allow dead code' annotations
+/// A terminal colour: one of the 16 standard colours, which the user's terminal theme maps onto
+/// actual colours, one of the 256 of the extended palette, or any 24-bit colour
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colour {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// A colour of the extended palette, the first 16 of which are the standard colours, followed
+    /// by a 6×6×6 colour cube and a greyscale ramp
+    Indexed(u8),
+    /// A colour given by its red, green and blue components
+    Rgb(u8, u8, u8),
+}
 
-    #[allow(dead_code)]
-    pub(crate) fn set_bold(mut self) -> Self {
-        self.0.set(FormatFlags::BOLD, true);
-        self
+/// How many colours a terminal can show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColourDepth {
+    /// The 16 standard colours
+    Standard,
+    /// The 256 colours of the extended palette
+    Indexed,
+    /// Any 24-bit colour
+    #[default]
+    TrueColour,
+}
+
+/// The standard colours, in the order of their indices in the extended palette
+const STANDARD_COLOURS: [Colour; 16] = [
+    Colour::Black,
+    Colour::Red,
+    Colour::Green,
+    Colour::Yellow,
+    Colour::Blue,
+    Colour::Magenta,
+    Colour::Cyan,
+    Colour::White,
+    Colour::BrightBlack,
+    Colour::BrightRed,
+    Colour::BrightGreen,
+    Colour::BrightYellow,
+    Colour::BrightBlue,
+    Colour::BrightMagenta,
+    Colour::BrightCyan,
+    Colour::BrightWhite,
+];
+
+/// The components of each standard colour in xterm's default palette, which is what other
+/// colours are matched against when they are downgraded to one of them, as there is no knowing
+/// what the user's terminal theme makes of them
+const STANDARD_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+impl Colour {
+    /// The nearest colour to this one of those a terminal that can show `depth` colours has
+    #[must_use]
+    pub fn downgraded(self, depth: ColourDepth) -> Self {
+        match (self, depth) {
+            (Self::Rgb(red, green, blue), ColourDepth::Indexed) => {
+                Self::Indexed(nearest_indexed((red, green, blue)))
+            }
+            (Self::Indexed(index), ColourDepth::Standard) => STANDARD_COLOURS
+                .get(usize::from(index))
+                .copied()
+                .unwrap_or_else(|| nearest_standard(self.rgb())),
+            (Self::Rgb(..), ColourDepth::Standard) => nearest_standard(self.rgb()),
+            _ => self,
+        }
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn set_italic(mut self) -> Self {
-        self.0.set(FormatFlags::ITALIC, true);
-        self
+    /// The colour's index in the extended palette, if it is a standard colour
+    fn standard_index(self) -> Option<usize> {
+        STANDARD_COLOURS.iter().position(|colour| *colour == self)
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn set_strikethrough(mut self) -> Self {
-        self.0.set(FormatFlags::STRIKETHROUGH, true);
-        self
+    /// The colour's red, green and blue components, as xterm shows it by default
+    pub(crate) fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Self::Rgb(red, green, blue) => (red, green, blue),
+            Self::Indexed(index @ 16..=231) => {
+                let cube = index - 16;
+                (
+                    cube_level(cube / 36),
+                    cube_level(cube / 6 % 6),
+                    cube_level(cube % 6),
+                )
+            }
+            Self::Indexed(index @ 232..) => {
+                let grey = 8 + (index - 232) * 10;
+                (grey, grey, grey)
+            }
+            Self::Indexed(index) => STANDARD_RGB
+                .get(usize::from(index))
+                .copied()
+                .unwrap_or_default(),
+            standard => standard
+                .standard_index()
+                .and_then(|index| STANDARD_RGB.get(index))
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The value of each component at the given step (0 to 5) along the extended palette's colour
+/// cube
+const fn cube_level(step: u8) -> u8 {
+    if step == 0 {
+        0
+    } else {
+        55 + step * 40
+    }
+}
+
+/// The index of the colour of the extended palette's colour cube or greyscale ramp nearest to
+/// the given one. The standard colours are left out, as what they look like is up to the terminal
+fn nearest_indexed((red, green, blue): (u8, u8, u8)) -> u8 {
+    // the nearest step along the cube for each component, going by the midpoints between levels
+    let step = |component: u8| match component {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (component - 35) / 40,
+    };
+    let cube = 16 + 36 * step(red) + 6 * step(green) + step(blue);
+
+    let average = (u16::from(red) + u16::from(green) + u16::from(blue)) / 3;
+    let grey = u8::try_from(average.saturating_sub(3) / 10)
+        .unwrap_or(u8::MAX)
+        .min(23);
+    let grey = 232 + grey;
+
+    let target = (red, green, blue);
+    if distance(Colour::Indexed(grey).rgb(), target) < distance(Colour::Indexed(cube).rgb(), target)
+    {
+        grey
+    } else {
+        cube
+    }
+}
+
+/// The standard colour nearest to the given one
+fn nearest_standard(target: (u8, u8, u8)) -> Colour {
+    STANDARD_COLOURS
+        .into_iter()
+        .zip(STANDARD_RGB)
+        .min_by_key(|(_, rgb)| distance(*rgb, target))
+        .map_or(Colour::Black, |(colour, _)| colour)
+}
+
+/// The squared distance between two colours, taken as points in RGB space
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    [(a.0, b.0), (a.1, b.1), (a.2, b.2)]
+        .into_iter()
+        .map(|(a, b)| u32::from(a.abs_diff(b)).pow(2))
+        .sum()
+}
+
+/// The SGR parameters that set a colour, as the foreground or background colour
+struct ColourCode {
+    colour: Colour,
+    background: bool,
+}
+
+impl Display for ColourCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let layer = if self.background { 4 } else { 3 };
+        match self.colour {
+            Colour::Indexed(index) => write!(f, "{layer}8;5;{index}"),
+            Colour::Rgb(red, green, blue) => write!(f, "{layer}8;2;{red};{green};{blue}"),
+            standard => match standard.standard_index().unwrap_or_default() {
+                index @ 0..=7 => write!(f, "{layer}{index}"),
+                // the bright colours have codes of their own, 60 on from the others
+                index => write!(f, "{}{}", layer + 6, index - 8),
+            },
+        }
     }
 }
 
 bitflags! {
-    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
     struct FormatFlags: u8 {
         const BOLD = 1 << 0;
         const ITALIC = 1 << 1;
         const STRIKETHROUGH = 1 << 2;
+        const UNDERLINE = 1 << 3;
+        const INVERSE = 1 << 4;
+        const DOTTED_UNDERLINE = 1 << 5;
+        const DIM = 1 << 6;
+    }
+}
+
+bitflags! {
+    /// What runs of inline text are, rather than how they look, such as emphasised or code. These
+    /// are kept apart from the attributes and colours text is shown in, which the
+    /// [theme](crate::theme::Theme) gives each role when the text is rendered, so that backends
+    /// for other formats can write each role as that format has it
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Roles: u8 {
+        /// Emphasised text (`*text*`)
+        const EMPHASIS = 1 << 0;
+        /// Strongly emphasised text (`**text**`)
+        const STRONG = 1 << 1;
+        /// Text marked as deleted (`~~text~~`)
+        const DELETED = 1 << 2;
+        /// Code, from a code span
+        const CODE = 1 << 3;
+        /// The text of a link, the target of which is the format's [link](Format::link)
+        const LINK = 1 << 4;
     }
 }
 
@@ -147,24 +746,70 @@ mod tests {
             assert_eq!("\x1b[29m".to_owned(), format.get_end_codes());
         }
 
+        #[test]
+        fn check_underline_codes() {
+            let format = Format::new().set_underline();
+            assert_eq!("\x1b[4m".to_owned(), format.get_start_codes());
+            assert_eq!("\x1b[24m".to_owned(), format.get_end_codes());
+        }
+
+        #[test]
+        fn check_inverse_codes() {
+            let format = Format::new().set_inverse();
+            assert_eq!("\x1b[7m".to_owned(), format.get_start_codes());
+            assert_eq!("\x1b[27m".to_owned(), format.get_end_codes());
+        }
+
+        #[test]
+        fn check_dim_codes() {
+            let format = Format::new().set_dim();
+            assert_eq!("\x1b[2m".to_owned(), format.get_start_codes());
+            assert_eq!("\x1b[22m".to_owned(), format.get_end_codes());
+        }
+
+        #[test]
+        fn check_dotted_underline_codes() {
+            let format = Format::new().set_dotted_underline();
+            assert_eq!("\x1b[4:4m".to_owned(), format.get_start_codes());
+            assert_eq!("\x1b[24m".to_owned(), format.get_end_codes());
+        }
+
+        #[test]
+        fn check_foreground_codes() {
+            let format = Format::new().set_foreground(Colour::Cyan);
+            assert_eq!("\x1b[36m".to_owned(), format.get_start_codes());
+            assert_eq!("\x1b[39m".to_owned(), format.get_end_codes());
+        }
+
+        #[test]
+        fn check_background_codes() {
+            let format = Format::new().set_background(Colour::Blue);
+            assert_eq!("\x1b[44m".to_owned(), format.get_start_codes());
+            assert_eq!("\x1b[49m".to_owned(), format.get_end_codes());
+        }
+
+        #[test]
+        fn check_link_codes() {
+            let format = Format::new().set_link("https://example.com");
+            assert_eq!(
+                "\x1b]8;;https://example.com\x1b\\".to_owned(),
+                format.get_start_codes()
+            );
+            assert_eq!("\x1b]8;;\x1b\\".to_owned(), format.get_end_codes());
+        }
+
+        #[test]
+        fn check_bright_foreground_start_code() {
+            let format = Format::new().set_foreground(Colour::BrightRed);
+            assert_eq!("\x1b[91m".to_owned(), format.get_start_codes());
+        }
+
         #[test]
         fn combination_of_formats_yeilds_correct_start_codes() {
             let mut format = Format::new();
             format.toggle_strikethrough();
             format.toggle_italic();
-            let start_codes = format.get_start_codes();
-            assert!(
-                start_codes.contains("\x1b[9m"),
-                "Start codes returned did not include a strikethrough start code"
-            );
-            assert!(
-                start_codes.contains("\x1b[3m"),
-                "Start codes returned did not include an italic start code"
-            );
-            assert!(
-                !start_codes.contains("\x1b[1m"),
-                "Start codes returned contained a bold start code when they should not have"
-            );
+            assert_eq!("\x1b[3;9m".to_owned(), format.get_start_codes());
         }
     }
 
@@ -174,21 +819,21 @@ mod tests {
         #[test]
         fn empty_previous_format() {
             let current_format = Format::new().set_bold().set_strikethrough();
-            let codes = current_format.get_codes_for_format_change(Format::new());
+            let codes = current_format.get_codes_for_format_change(&Format::new());
             assert_eq!(current_format.get_start_codes(), codes,);
         }
 
         #[test]
         fn empty_current_format() {
             let previous_format = Format::new().set_bold().set_italic();
-            let codes = Format::new().get_codes_for_format_change(previous_format);
+            let codes = Format::new().get_codes_for_format_change(&previous_format);
             assert_eq!(previous_format.get_end_codes(), codes);
         }
 
         #[test]
         fn no_change_in_format() {
             let format = Format::new().set_italic();
-            let codes = format.get_codes_for_format_change(format);
+            let codes = format.get_codes_for_format_change(&format);
             assert_eq!(String::new(), codes);
         }
 
@@ -196,7 +841,7 @@ mod tests {
         fn both_formats_empty() {
             assert_eq!(
                 String::new(),
-                Format::new().get_codes_for_format_change(Format::new())
+                Format::new().get_codes_for_format_change(&Format::new())
             );
         }
 
@@ -204,18 +849,15 @@ mod tests {
         fn no_format_overlap() {
             let previous_format = Format::new().set_italic();
             let current_format = Format::new().set_bold();
-            let codes = current_format.get_codes_for_format_change(previous_format);
-            assert_eq!(
-                previous_format.get_end_codes() + &current_format.get_start_codes(),
-                codes
-            );
+            let codes = current_format.get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b[23;1m".to_owned(), codes);
         }
 
         #[test]
         fn some_format_overlap_only_removal() {
             let previous_format = Format::new().set_bold().set_strikethrough();
             let current_format = Format::new().set_strikethrough();
-            let codes = current_format.get_codes_for_format_change(previous_format);
+            let codes = current_format.get_codes_for_format_change(&previous_format);
             assert_eq!(Format::new().set_bold().get_end_codes(), codes);
         }
 
@@ -223,20 +865,282 @@ mod tests {
         fn some_format_overlap_only_addition() {
             let previous_format = Format::new().set_strikethrough();
             let current_format = Format::new().set_bold().set_strikethrough();
-            let codes = current_format.get_codes_for_format_change(previous_format);
+            let codes = current_format.get_codes_for_format_change(&previous_format);
             assert_eq!(Format::new().set_bold().get_start_codes(), codes);
         }
 
+        #[test]
+        fn foreground_colour_change_only_emits_new_colour() {
+            let previous_format = Format::new().set_foreground(Colour::Red);
+            let current_format = Format::new().set_foreground(Colour::Blue);
+            let codes = current_format.get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b[34m".to_owned(), codes);
+        }
+
+        #[test]
+        fn foreground_colour_removal_resets_colour() {
+            let previous_format = Format::new().set_bold().set_foreground(Colour::Red);
+            let current_format = Format::new().set_bold();
+            let codes = current_format.get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b[39m".to_owned(), codes);
+        }
+
+        #[test]
+        fn unchanged_foreground_colour_emits_nothing() {
+            let format = Format::new().set_foreground(Colour::Green);
+            let codes = format
+                .clone()
+                .set_italic()
+                .get_codes_for_format_change(&format);
+            assert_eq!("\x1b[3m".to_owned(), codes);
+        }
+
+        #[test]
+        fn shared_end_code_restores_remaining_attribute() {
+            // SGR 24 ends both styles of underline, so the plain one has to be switched back on
+            let previous_format = Format::new().set_underline().set_dotted_underline();
+            let current_format = Format::new().set_underline();
+            let codes = current_format.get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b[24;4m".to_owned(), codes);
+        }
+
+        #[test]
+        fn ending_dim_keeps_bold() {
+            // SGR 22 ends both bold and dim text
+            let previous_format = Format::new().set_bold().set_dim();
+            let codes = Format::new()
+                .set_bold()
+                .get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b[22;1m".to_owned(), codes);
+        }
+
+        #[test]
+        fn shared_end_code_is_only_emitted_once() {
+            let previous_format = Format::new().set_underline().set_dotted_underline();
+            let codes = Format::new().get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b[24m".to_owned(), codes);
+        }
+
+        #[test]
+        fn link_change_only_emits_new_link() {
+            let previous_format = Format::new().set_bold().set_link("a");
+            let current_format = Format::new().set_bold().set_link("b");
+            let codes = current_format.get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b]8;;b\x1b\\".to_owned(), codes);
+        }
+
         #[test]
         fn some_format_overlap_both_addition_and_removal() {
             let previous_format = Format::new().set_bold().set_italic();
             let current_format = Format::new().set_bold().set_strikethrough();
-            let codes = current_format.get_codes_for_format_change(previous_format);
+            let codes = current_format.get_codes_for_format_change(&previous_format);
+            assert_eq!("\x1b[23;9m".to_owned(), codes);
+        }
+
+        #[test]
+        fn codes_are_in_a_fixed_order() {
+            let previous_format = Format::new()
+                .set_inverse()
+                .set_italic()
+                .set_dim()
+                .set_background(Colour::Red)
+                .set_link("a");
+            let current_format = Format::new()
+                .set_dotted_underline()
+                .set_bold()
+                .set_foreground(Colour::Blue);
+            assert_eq!(
+                "\x1b[22;23;27;49;1;4:4;34m\x1b]8;;\x1b\\",
+                current_format.get_codes_for_format_change(&previous_format)
+            );
+        }
+
+        #[test]
+        fn reset_codes() {
+            let mut codes = String::new();
+            Format::new()
+                .set_bold()
+                .set_link("a")
+                .write_reset_codes(&mut codes)
+                .unwrap();
+            assert_eq!("\x1b[0m\x1b]8;;\x1b\\", codes);
+
+            codes.clear();
+            Format::new()
+                .set_code()
+                .write_reset_codes(&mut codes)
+                .unwrap();
+            assert_eq!("", codes);
+        }
+
+        #[test]
+        fn separate_codes() {
+            let previous_format = Format::new().set_bold().set_italic().set_link("a");
+            let current_format = Format::new().set_strikethrough();
+            let mut codes = String::new();
+            current_format
+                .write_codes_for_format_change(&previous_format, SgrStyle::Separate, &mut codes)
+                .unwrap();
+            assert_eq!("\x1b[22m\x1b[23m\x1b[9m\x1b]8;;\x1b\\", codes);
             assert_eq!(
-                Format::new().set_italic().get_end_codes()
-                    + &Format::new().set_strikethrough().get_start_codes(),
-                codes
+                "\x1b[22;23;9m\x1b]8;;\x1b\\",
+                current_format.get_codes_for_format_change(&previous_format)
             );
         }
+
+        #[test]
+        fn codes_are_written_after_what_is_already_there() {
+            let mut output = "lorem".to_owned();
+            Format::new()
+                .set_italic()
+                .set_foreground(Colour::Red)
+                .write_codes_for_format_change(
+                    &Format::new().set_bold(),
+                    SgrStyle::Separate,
+                    &mut output,
+                )
+                .unwrap();
+            assert_eq!("lorem\x1b[22m\x1b[3m\x1b[31m", output);
+        }
+    }
+
+    mod colour_depth {
+        use super::*;
+
+        #[test]
+        fn extended_colour_codes() {
+            assert_eq!(
+                "\x1b[38;5;208;48;2;0;95;135m",
+                Format::new()
+                    .set_foreground(Colour::Indexed(208))
+                    .set_background(Colour::Rgb(0, 95, 135))
+                    .get_start_codes()
+            );
+            assert_eq!(
+                "\x1b[100m",
+                Format::new()
+                    .set_background(Colour::BrightBlack)
+                    .get_start_codes()
+            );
+        }
+
+        #[test]
+        fn rgb_colours_are_downgraded_to_the_nearest_indexed_colour() {
+            assert_eq!(
+                Colour::Indexed(24),
+                Colour::Rgb(0, 95, 135).downgraded(ColourDepth::Indexed)
+            );
+            assert_eq!(
+                Colour::Indexed(208),
+                Colour::Rgb(250, 130, 10).downgraded(ColourDepth::Indexed)
+            );
+            // greys go on the greyscale ramp, which is finer than the cube's greys
+            assert_eq!(
+                Colour::Indexed(240),
+                Colour::Rgb(90, 88, 89).downgraded(ColourDepth::Indexed)
+            );
+        }
+
+        #[test]
+        fn colours_are_downgraded_to_the_nearest_standard_colour() {
+            assert_eq!(
+                Colour::BrightRed,
+                Colour::Rgb(250, 20, 30).downgraded(ColourDepth::Standard)
+            );
+            assert_eq!(
+                Colour::Blue,
+                Colour::Indexed(4).downgraded(ColourDepth::Standard)
+            );
+            assert_eq!(
+                Colour::BrightBlack,
+                Colour::Indexed(244).downgraded(ColourDepth::Standard)
+            );
+        }
+
+        #[test]
+        fn colours_the_terminal_can_show_are_kept() {
+            for depth in [ColourDepth::Standard, ColourDepth::Indexed] {
+                assert_eq!(Colour::Cyan, Colour::Cyan.downgraded(depth));
+            }
+            assert_eq!(
+                Colour::Indexed(100),
+                Colour::Indexed(100).downgraded(ColourDepth::Indexed)
+            );
+            assert_eq!(
+                Colour::Rgb(1, 2, 3),
+                Colour::Rgb(1, 2, 3).downgraded(ColourDepth::TrueColour)
+            );
+        }
+    }
+
+    mod debug_formatting {
+        use super::*;
+
+        #[test]
+        fn colours_and_link() {
+            assert_eq!(
+                "Format(UNDERLINE | LINK | fg: Red | bg: White | link: https://example.com)",
+                format!(
+                    "{:?}",
+                    Format::new()
+                        .set_underline()
+                        .set_foreground(Colour::Red)
+                        .set_background(Colour::White)
+                        .set_link("https://example.com")
+                )
+            );
+        }
+
+        #[test]
+        fn empty_format() {
+            assert_eq!("Format()", format!("{:#?}", Format::new()));
+        }
+
+        #[test]
+        fn flags_and_colour() {
+            assert_eq!(
+                "Format(BOLD | ITALIC | fg: Cyan)",
+                format!(
+                    "{:#?}",
+                    Format::new()
+                        .set_italic()
+                        .set_bold()
+                        .set_foreground(Colour::Cyan)
+                )
+            );
+        }
+    }
+
+    mod format_union {
+        use super::*;
+
+        #[test]
+        fn own_link_takes_precedence() {
+            let format = Format::new().set_link("a");
+            let other = Format::new().set_bold().set_link("b");
+            assert_eq!(Some("a"), format.union(&other).link());
+            assert_eq!(Some("b"), Format::new().union(&other).link());
+        }
+
+        #[test]
+        fn flags_are_combined() {
+            assert_eq!(
+                Format::new().set_bold().set_italic(),
+                Format::new().set_bold().union(&Format::new().set_italic())
+            );
+        }
+
+        #[test]
+        fn own_foreground_takes_precedence() {
+            let format = Format::new().set_foreground(Colour::Red);
+            let other = Format::new().set_foreground(Colour::Blue);
+            assert_eq!(format, format.union(&other));
+        }
+
+        #[test]
+        fn other_foreground_used_if_none_set() {
+            let other = Format::new().set_foreground(Colour::Blue);
+            assert_eq!(other, Format::new().union(&other));
+        }
     }
 }
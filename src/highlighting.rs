@@ -0,0 +1,120 @@
+use regex::Regex;
+
+use crate::formatting::Format;
+
+/// A pattern whose matches are highlighted in rendered output
+#[derive(Clone, Debug)]
+pub struct Highlight(Regex);
+
+impl Highlight {
+    pub const fn new(pattern: Regex) -> Self {
+        Self(pattern)
+    }
+
+    /// Shows the matches of the pattern in a line of formatted slices in reverse video, on top of
+    /// their own formatting. Slices are split where a match starts or ends partway through one
+    pub fn apply(&self, line: Vec<(String, Format)>) -> Vec<(String, Format)> {
+        let text: String = line.iter().map(|(slice, _)| slice.as_str()).collect();
+        let matches: Vec<_> = self
+            .0
+            .find_iter(&text)
+            .map(|found| found.range())
+            .filter(|range| !range.is_empty())
+            .collect();
+        if matches.is_empty() {
+            return line;
+        }
+
+        let mut highlighted = Vec::with_capacity(line.len());
+        let mut offset = 0;
+        for (slice, format) in line {
+            let end = offset + slice.len();
+            let cuts = matches
+                .iter()
+                .flat_map(|range| [range.start, range.end])
+                .filter(|cut| (offset + 1..end).contains(cut))
+                .chain([end]);
+
+            let mut start = offset;
+            for cut in cuts {
+                let Some(piece) = slice.get(start - offset..cut - offset) else {
+                    continue;
+                };
+                let is_match = matches.iter().any(|range| range.contains(&start));
+                let format = if is_match {
+                    format.clone().set_inverse()
+                } else {
+                    format.clone()
+                };
+                highlighted.push((piece.to_owned(), format));
+                start = cut;
+            }
+            offset = end;
+        }
+        highlighted
+    }
+}
+
+/// Patterns are the same if they were written the same
+impl PartialEq for Highlight {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlight(pattern: &str, line: &[(&str, Format)]) -> Vec<(String, Format)> {
+        let line = line
+            .iter()
+            .map(|(slice, format)| ((*slice).to_owned(), format.clone()))
+            .collect();
+        Highlight::new(Regex::new(pattern).unwrap()).apply(line)
+    }
+
+    mod matches {
+        use super::*;
+
+        #[test]
+        fn matches_are_split_out() {
+            assert_eq!(
+                vec![
+                    ("lo".to_owned(), Format::new()),
+                    ("rem".to_owned(), Format::new().set_inverse()),
+                    (" ipsum ".to_owned(), Format::new()),
+                    ("rem".to_owned(), Format::new().set_inverse()),
+                ],
+                highlight("rem", &[("lorem ipsum rem", Format::new())])
+            );
+        }
+
+        #[test]
+        fn matches_span_slices_and_keep_their_formatting() {
+            assert_eq!(
+                vec![
+                    ("lor".to_owned(), Format::new()),
+                    ("em".to_owned(), Format::new().set_inverse()),
+                    ("ip".to_owned(), Format::new().set_bold().set_inverse()),
+                    ("sum".to_owned(), Format::new().set_bold()),
+                ],
+                highlight(
+                    "em ?ip",
+                    &[
+                        ("lorem", Format::new()),
+                        ("ipsum", Format::new().set_bold())
+                    ]
+                )
+            );
+        }
+
+        #[test]
+        fn empty_matches_are_ignored() {
+            assert_eq!(
+                vec![("lorem".to_owned(), Format::new())],
+                highlight("x*", &[("lorem", Format::new())])
+            );
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::code_block::{is_closing_fence, opening_fence};
+
+/// Something that went wrong while expanding `@include` directives
+#[derive(Debug)]
+pub enum IncludeError {
+    /// An included file couldn't be read
+    Io { path: PathBuf, error: io::Error },
+    /// A file ended up including itself, either directly or through other files
+    Cycle { path: PathBuf },
+}
+
+impl Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => {
+                write!(f, "unable to include file `{}`: {error}", path.display())
+            }
+            Self::Cycle { path } => write!(f, "file `{}` includes itself", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Cycle { .. } => None,
+        }
+    }
+}
+
+/// Replaces every `@include(path)` line in `text` with the contents of the file at `path`, so that
+/// a document split across several files can be parsed as one. Paths are relative to the
+/// directory containing `source`, the file `text` was read from, and included files can include
+/// others in turn.
+///
+/// A directive must be on a line of its own (save for indentation), outside fenced code blocks,
+/// to be expanded. Note that the spans of parsed elements refer to the expanded text, not to any
+/// one file
+///
+/// # Errors
+///
+/// Returns an error if an included file can't be read, or if a file includes itself
+pub fn expand_includes(text: &str, source: &Path) -> Result<String, IncludeError> {
+    let mut including = vec![canonical(source)];
+    expand(text, source, false, &mut including)
+}
+
+/// Expands `@include(path)` directives as [`expand_includes`] does, along with Obsidian embeds of
+/// other notes, `![[Page]]`, which include the file `Page.md`. Embeds of anything other than
+/// Markdown files, such as images, are left as they are, as is the part of a note an embed names
+/// after a `#`, which embeds the whole note
+///
+/// # Errors
+///
+/// Returns an error if an included or embedded file can't be read, or if a file includes itself
+pub fn expand_embeds(text: &str, source: &Path) -> Result<String, IncludeError> {
+    let mut including = vec![canonical(source)];
+    expand(text, source, true, &mut including)
+}
+
+/// Expands the directives in `text`, read from `source`, along with any embeds if `embeds` is
+/// set. `including` holds the files currently being expanded, innermost last, so that cycles can
+/// be caught
+fn expand(
+    text: &str,
+    source: &Path,
+    embeds: bool,
+    including: &mut Vec<PathBuf>,
+) -> Result<String, IncludeError> {
+    let directory = source.parent().unwrap_or_else(|| Path::new(""));
+    let mut expanded = String::with_capacity(text.len());
+    // the fence of the code block that is open, if there is one, inside which directives are
+    // only shown
+    let mut fence: Option<String> = None;
+
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches('\n');
+        match &fence {
+            Some(open) => {
+                if is_closing_fence(content, open) {
+                    fence = None;
+                }
+                expanded.push_str(line);
+                continue;
+            }
+            None => {
+                if let Some((open, ..)) = opening_fence(content) {
+                    fence = Some(open.to_owned());
+                    expanded.push_str(line);
+                    continue;
+                }
+            }
+        }
+
+        let target = directive_target(line)
+            .map(PathBuf::from)
+            .or_else(|| embed_target(line).filter(|_| embeds));
+        let Some(target) = target else {
+            expanded.push_str(line);
+            continue;
+        };
+
+        let path = directory.join(target);
+        let canonical_path = canonical(&path);
+        if including.contains(&canonical_path) {
+            return Err(IncludeError::Cycle { path });
+        }
+        let bytes = fs::read(&path).map_err(|error| IncludeError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        // an included file with stray bytes shouldn't stop the rest of the document rendering
+        #[cfg(not(feature = "encoding"))]
+        let (contents, _) = crate::input::decode_lossy(&bytes);
+        #[cfg(feature = "encoding")]
+        let contents = crate::input::transcode(&bytes, None);
+
+        including.push(canonical_path);
+        let included = expand(&contents, &path, embeds, including)?;
+        including.pop();
+
+        // the included text takes the place of the directive's line, newline and all
+        expanded.push_str(included.trim_end_matches('\n'));
+        if line.ends_with('\n') {
+            expanded.push('\n');
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// The path given by an `@include(path)` directive, if `line` is one
+fn directive_target(line: &str) -> Option<&str> {
+    let target = line
+        .trim()
+        .strip_prefix("@include(")?
+        .strip_suffix(')')?
+        .trim();
+    (!target.is_empty()).then_some(target)
+}
+
+/// The file embedded by an Obsidian embed, `![[Page]]`, if `line` is one of a Markdown file
+fn embed_target(line: &str) -> Option<PathBuf> {
+    let target = line.trim().strip_prefix("![[")?.strip_suffix("]]")?;
+    let target = target.split(['|', '#']).next()?.trim();
+    if target.is_empty() {
+        return None;
+    }
+    // notes are named without their extension, but attachments (which can't be shown) with it
+    let extension = Path::new(target)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "md" => Some(PathBuf::from(target)),
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "bmp" | "pdf" | "mp3" | "wav" | "ogg"
+        | "mp4" | "webm" | "mov" | "canvas" | "base" => None,
+        _ => Some(PathBuf::from(format!("{target}.md"))),
+    }
+}
+
+/// The canonical form of `path`, for comparing files, or `path` itself if it doesn't exist (in
+/// which case reading it will fail anyway)
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// A scratch directory, removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("sani-{name}-{}", std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, file: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(file);
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    mod directives {
+        use super::*;
+
+        #[test]
+        fn directive_on_its_own_line() {
+            assert_eq!(
+                Some("chapter.md"),
+                directive_target("@include(chapter.md)\n")
+            );
+            assert_eq!(Some("a b.md"), directive_target("  @include( a b.md )"));
+        }
+
+        #[test]
+        fn embeds_of_notes() {
+            assert_eq!(
+                Some(PathBuf::from("Lorem.md")),
+                embed_target("![[Lorem]]\n")
+            );
+            assert_eq!(
+                Some(PathBuf::from("v1.2 notes.md")),
+                embed_target("![[v1.2 notes#Ipsum|dolor]]")
+            );
+            assert_eq!(Some(PathBuf::from("a.md")), embed_target("![[a.md]]"));
+            assert_eq!(None, embed_target("![[diagram.png]]"));
+            assert_eq!(None, embed_target("see ![[Lorem]]"));
+        }
+
+        #[test]
+        fn not_directives() {
+            assert_eq!(None, directive_target("see @include(chapter.md)"));
+            assert_eq!(None, directive_target("@include(chapter.md"));
+            assert_eq!(None, directive_target("@include()"));
+        }
+    }
+
+    mod expansion {
+        use super::*;
+
+        #[test]
+        fn text_without_directives_is_unchanged() {
+            let text = "# lorem\n\nipsum\n";
+            assert_eq!(
+                text,
+                expand_includes(text, Path::new("document.md")).unwrap()
+            );
+        }
+
+        #[test]
+        fn directives_in_code_blocks_are_shown() {
+            let text = "````markdown\n@include(chapter.md)\n```\n![[Lorem]]\n````\n";
+            let source = Path::new("document.md");
+            assert_eq!(text, expand_includes(text, source).unwrap());
+            assert_eq!(text, expand_embeds(text, source).unwrap());
+        }
+
+        #[test]
+        fn embeds_are_only_expanded_when_asked_for() {
+            let dir = TempDir::new("embeds");
+            dir.write("Lorem Ipsum.md", "ipsum\n");
+            let source = dir.write("note.md", "# note\n\n![[Lorem Ipsum]]\n");
+            let text = fs::read_to_string(&source).unwrap();
+
+            assert_eq!("# note\n\nipsum\n", expand_embeds(&text, &source).unwrap());
+            assert_eq!(text, expand_includes(&text, &source).unwrap());
+        }
+
+        #[test]
+        fn includes_are_expanded_relative_to_the_source() {
+            let dir = TempDir::new("relative");
+            fs::create_dir_all(dir.0.join("chapters")).unwrap();
+            dir.write("chapters/one.md", "## one\n\n@include(two.md)\n");
+            dir.write("chapters/two.md", "## two\n");
+            let source = dir.write("book.md", "# book\n\n@include(chapters/one.md)\n\nend\n");
+
+            assert_eq!(
+                "# book\n\n## one\n\n## two\n\nend\n",
+                expand_includes(&fs::read_to_string(&source).unwrap(), &source).unwrap()
+            );
+        }
+
+        #[test]
+        fn missing_files_are_reported() {
+            let dir = TempDir::new("missing");
+            let source = dir.write("book.md", "@include(nowhere.md)\n");
+
+            assert!(matches!(
+                expand_includes(&fs::read_to_string(&source).unwrap(), &source),
+                Err(IncludeError::Io { path, .. }) if path.ends_with("nowhere.md")
+            ));
+        }
+
+        #[test]
+        fn cycles_are_reported() {
+            let dir = TempDir::new("cycle");
+            dir.write("one.md", "@include(two.md)\n");
+            dir.write("two.md", "@include(one.md)\n");
+            let source = dir.write("book.md", "@include(one.md)\n");
+
+            assert!(matches!(
+                expand_includes(&fs::read_to_string(&source).unwrap(), &source),
+                Err(IncludeError::Cycle { path }) if path.ends_with("one.md")
+            ));
+        }
+
+        #[test]
+        fn files_can_be_included_more_than_once() {
+            let dir = TempDir::new("repeated");
+            dir.write("note.md", "note");
+            let source = dir.write("book.md", "@include(note.md)\n\n@include(note.md)\n");
+
+            assert_eq!(
+                "note\n\nnote\n",
+                expand_includes(&fs::read_to_string(&source).unwrap(), &source).unwrap()
+            );
+        }
+    }
+}
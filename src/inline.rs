@@ -0,0 +1,595 @@
+use std::mem;
+use std::ops::Range;
+
+use memchr::{memchr, memchr3};
+use tracing::trace;
+
+use crate::autolinks::{self, Autolink};
+use crate::formatting::{Format, Roles};
+use crate::options::{Dialect, ParserOptions};
+use crate::tokenizer::{Token, Tokenizer};
+
+/// A piece of inline content, as found in paragraphs, headings and any other block that contains
+/// text. Every kind of block parses its text with [`parse_inline`], so that inline markup behaves
+/// the same wherever it appears
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InlineNode {
+    /// Literal text
+    Text(String),
+    /// A line break kept in the output (from a backslash-escaped newline)
+    HardBreak,
+    /// `*emphasis*`
+    Emphasis(Vec<InlineNode>),
+    /// `**strong emphasis**`
+    Strong(Vec<InlineNode>),
+    /// `~~strikethrough~~`
+    Strikethrough(Vec<InlineNode>),
+    /// A `` `code span` ``, whose content is never parsed as markup
+    Code(String),
+    /// A link to `target`
+    Link {
+        target: String,
+        children: Vec<InlineNode>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Delimiter {
+    Emphasis,
+    Strong,
+    Strikethrough,
+}
+
+impl Delimiter {
+    fn node(self, children: Vec<InlineNode>) -> InlineNode {
+        match self {
+            Self::Emphasis => InlineNode::Emphasis(children),
+            Self::Strong => InlineNode::Strong(children),
+            Self::Strikethrough => InlineNode::Strikethrough(children),
+        }
+    }
+}
+
+/// Builds up the tree of inline nodes, keeping a stack of the delimiters still open
+#[derive(Default)]
+struct Builder {
+    root: Vec<InlineNode>,
+    open: Vec<(Delimiter, Vec<InlineNode>)>,
+    text: String,
+}
+
+impl Builder {
+    fn current(&mut self) -> &mut Vec<InlineNode> {
+        match self.open.last_mut() {
+            Some((_, children)) => children,
+            None => &mut self.root,
+        }
+    }
+
+    /// Ends the current run of text, if there is one
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let text = mem::take(&mut self.text);
+            self.current().push(InlineNode::Text(text));
+        }
+    }
+
+    /// Closes the innermost open delimiter, dropping it if it has no content
+    fn close_innermost(&mut self) -> Option<Delimiter> {
+        let (delimiter, children) = self.open.pop()?;
+        if children.is_empty() {
+            trace!(?delimiter, "dropped delimiter without any content");
+        } else {
+            self.current().push(delimiter.node(children));
+        }
+        Some(delimiter)
+    }
+
+    /// Opens `delimiter`, or closes it if it is already open. Closing a delimiter that isn't the
+    /// innermost one (as in `**lorem *ipsum** dolor*`) closes the ones inside it too, and opens
+    /// them again straight after, so that their formatting carries on
+    fn delimiter(&mut self, delimiter: Delimiter) {
+        self.flush();
+        let Some(position) = self.open.iter().position(|(open, _)| *open == delimiter) else {
+            trace!(?delimiter, "opened delimiter");
+            self.open.push((delimiter, Vec::new()));
+            return;
+        };
+
+        let mut reopen = Vec::new();
+        while self.open.len() > position + 1 {
+            reopen.extend(self.close_innermost());
+        }
+        self.close_innermost();
+        trace!(
+            ?delimiter,
+            ?reopen,
+            "closed delimiter, reopening any inside it"
+        );
+        for delimiter in reopen.into_iter().rev() {
+            self.open.push((delimiter, Vec::new()));
+        }
+    }
+
+    /// Closes everything still open, which carries on to the end of the text
+    fn finish(mut self) -> Vec<InlineNode> {
+        self.flush();
+        while let Some(delimiter) = self.close_innermost() {
+            trace!(
+                ?delimiter,
+                "delimiter left open applies to the end of the text"
+            );
+        }
+        self.root
+    }
+}
+
+/// Parses inline text into a tree of [`InlineNode`]s. Newlines become spaces, and delimiters
+/// left open at the end of the text apply up to the end of it. In the [`Dialect::Gfm`] dialect,
+/// bare URLs and email addresses become links, and with
+/// [`wiki_links`](ParserOptions::wiki_links), so do `[[Page]]` and `[[Page|label]]`
+#[must_use]
+pub fn parse_inline(text: &str, options: &ParserOptions) -> Vec<InlineNode> {
+    let mut nodes = plain(text, *options).unwrap_or_else(|| tokenized(text, *options));
+    if options.wiki_links {
+        nodes = link(nodes, &wiki_link);
+    }
+    if options.dialect == Dialect::Gfm {
+        nodes = link(nodes, &|text: &str| {
+            autolinks::find(text).map(|Autolink { span, target }| {
+                let label = text.get(span.clone()).unwrap_or_default().to_owned();
+                (span, target, label)
+            })
+        });
+    }
+    nodes
+}
+
+/// The nodes for `text` if there is no markup in it at all, as is the case for most prose, which
+/// is then split into its lines without going through the tokenizer. Each line but the last ends
+/// with a space, as newlines are read as spaces
+fn plain(text: &str, options: ParserOptions) -> Option<Vec<InlineNode>> {
+    let bytes = text.as_bytes();
+    let has_markup = memchr3(b'\\', b'*', b'`', bytes).is_some()
+        || memchr(b'~', bytes).is_some()
+        || (options.entities && memchr(b'&', bytes).is_some());
+    if has_markup {
+        return None;
+    }
+
+    let mut nodes = Vec::new();
+    let mut lines = text.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        if lines.peek().is_some() {
+            nodes.push(InlineNode::Text(format!("{line} ")));
+        } else if !line.is_empty() {
+            nodes.push(InlineNode::Text(line.to_owned()));
+        }
+    }
+    Some(nodes)
+}
+
+/// Parses inline text into its nodes token by token
+fn tokenized(text: &str, options: ParserOptions) -> Vec<InlineNode> {
+    let mut builder = Builder::default();
+
+    let tokens = Tokenizer::new(text)
+        .decode_entities(options.entities)
+        .single_tilde_strikethrough(options.single_tilde_strikethrough);
+    for token in tokens {
+        match token {
+            Token::Text(literal) => builder.text.push_str(literal),
+            Token::Entity(character) => builder.text.push(character),
+            Token::Escaped(literal) => {
+                // an escaped character starts a new run of text, so that it can't be mistaken
+                // for markup later on
+                builder.flush();
+                builder.text.push_str(literal);
+            }
+            Token::SoftBreak => {
+                builder.text.push(' ');
+                builder.flush();
+            }
+            Token::HardBreak => {
+                builder.flush();
+                builder.current().push(InlineNode::HardBreak);
+            }
+            Token::Emphasis => builder.delimiter(Delimiter::Emphasis),
+            Token::Strong => builder.delimiter(Delimiter::Strong),
+            Token::Strikethrough => builder.delimiter(Delimiter::Strikethrough),
+            Token::Code(code) => {
+                builder.flush();
+                builder
+                    .current()
+                    .push(InlineNode::Code(code_span_content(code)));
+            }
+        }
+    }
+
+    builder.finish()
+}
+
+/// Normalises the content of a code span: newlines become spaces, and a single space is stripped
+/// from each end if there is one at both, so that code starting or ending with a backtick can be
+/// set apart from the delimiters
+fn code_span_content(code: &str) -> String {
+    let code = code.replace('\n', " ");
+    match code
+        .strip_prefix(' ')
+        .and_then(|code| code.strip_suffix(' '))
+    {
+        Some(stripped) if !code.trim_start_matches(' ').is_empty() => stripped.to_owned(),
+        _ => code,
+    }
+}
+
+/// Finds the first wiki link (`[[Page]]` or `[[Page|label]]`) in `text`, returning its byte range
+/// along with the page, which is where it links to, and its label, which is the page unless it is
+/// given one
+fn wiki_link(text: &str) -> Option<(Range<usize>, String, String)> {
+    let mut from = 0;
+    while let Some(open) = text.get(from..)?.find("[[") {
+        let start = from + open;
+        let inner_start = start + "[[".len();
+        let inner = text.get(inner_start..)?;
+        let close = inner.find("]]")?;
+        let (page, label) = inner
+            .get(..close)?
+            .split_once('|')
+            .unwrap_or_else(|| (inner.get(..close).unwrap_or_default(), ""));
+        let (page, label) = (page.trim(), label.trim());
+        if page.is_empty() || page.contains(['[', ']']) {
+            from = start + 1;
+            continue;
+        }
+        let label = if label.is_empty() { page } else { label };
+        return Some((
+            start..inner_start + close + "]]".len(),
+            page.to_owned(),
+            label.to_owned(),
+        ));
+    }
+    None
+}
+
+/// Makes links of whatever `find` finds in text nodes, given the byte range of each link in the
+/// text, its target and its label
+fn link(
+    nodes: Vec<InlineNode>,
+    find: &impl Fn(&str) -> Option<(Range<usize>, String, String)>,
+) -> Vec<InlineNode> {
+    let mut linked = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match node {
+            InlineNode::Text(mut text) => {
+                while let Some((span, target, label)) = find(&text) {
+                    let rest = text.split_off(span.end);
+                    text.truncate(span.start);
+                    if !text.is_empty() {
+                        linked.push(InlineNode::Text(text));
+                    }
+                    linked.push(InlineNode::Link {
+                        target,
+                        children: vec![InlineNode::Text(label)],
+                    });
+                    text = rest;
+                }
+                if !text.is_empty() {
+                    linked.push(InlineNode::Text(text));
+                }
+            }
+            InlineNode::Emphasis(children) => {
+                linked.push(InlineNode::Emphasis(link(children, find)));
+            }
+            InlineNode::Strong(children) => linked.push(InlineNode::Strong(link(children, find))),
+            InlineNode::Strikethrough(children) => {
+                linked.push(InlineNode::Strikethrough(link(children, find)));
+            }
+            InlineNode::HardBreak | InlineNode::Code(_) | InlineNode::Link { .. } => {
+                linked.push(node);
+            }
+        }
+    }
+    linked
+}
+
+/// Flattens inline nodes into runs of text, each with the formatting of the nodes it is inside
+#[must_use]
+pub fn to_slices(nodes: &[InlineNode]) -> Vec<(String, Format)> {
+    let mut slices = Vec::new();
+    push_slices(nodes, &Format::new(), &mut slices);
+    slices
+}
+
+fn push_slices(nodes: &[InlineNode], format: &Format, slices: &mut Vec<(String, Format)>) {
+    for node in nodes {
+        match node {
+            InlineNode::Text(text) => slices.push((text.clone(), format.clone())),
+            InlineNode::HardBreak => slices.push(("\n".to_owned(), format.clone())),
+            InlineNode::Code(code) => slices.push((code.clone(), format.clone().set_code())),
+            InlineNode::Emphasis(children) => {
+                push_slices(children, &format.clone().set_roles(Roles::EMPHASIS), slices);
+            }
+            InlineNode::Strong(children) => {
+                push_slices(children, &format.clone().set_roles(Roles::STRONG), slices);
+            }
+            InlineNode::Strikethrough(children) => {
+                push_slices(children, &format.clone().set_roles(Roles::DELETED), slices);
+            }
+            InlineNode::Link { target, children } => {
+                push_slices(children, &format.clone().set_link(target), slices);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<InlineNode> {
+        parse_inline(text, &ParserOptions::new())
+    }
+
+    fn text(text: &str) -> InlineNode {
+        InlineNode::Text(text.to_owned())
+    }
+
+    mod tree {
+        use super::*;
+
+        #[test]
+        fn plain_text() {
+            assert_eq!(vec![text("lorem ipsum")], parse("lorem ipsum"));
+        }
+
+        #[test]
+        fn nested_delimiters() {
+            assert_eq!(
+                vec![InlineNode::Strong(vec![
+                    text("lorem "),
+                    InlineNode::Emphasis(vec![text("ipsum")]),
+                ])],
+                parse("**lorem *ipsum***")
+            );
+        }
+
+        #[test]
+        fn code_spans_are_verbatim() {
+            assert_eq!(
+                vec![
+                    InlineNode::Emphasis(vec![
+                        text("lorem "),
+                        InlineNode::Code("**ipsum** ~~dolor~~".to_owned()),
+                    ]),
+                    text(" sit"),
+                ],
+                parse("*lorem `**ipsum** ~~dolor~~`* sit")
+            );
+        }
+
+        #[test]
+        fn code_span_spacing() {
+            for (markdown, code) in [
+                ("`` `lorem` ``", "`lorem`"),
+                ("`  lorem `", " lorem"),
+                ("`lorem\nipsum`", "lorem ipsum"),
+                ("`  `", "  "),
+            ] {
+                assert_eq!(vec![InlineNode::Code(code.to_owned())], parse(markdown));
+            }
+        }
+
+        #[test]
+        fn overlapping_delimiters_are_reopened() {
+            assert_eq!(
+                vec![
+                    InlineNode::Strong(vec![
+                        text("lorem "),
+                        InlineNode::Emphasis(vec![text("ipsum")]),
+                    ]),
+                    InlineNode::Emphasis(vec![text(" dolor")]),
+                ],
+                parse("**lorem *ipsum** dolor*")
+            );
+        }
+
+        #[test]
+        fn unclosed_delimiter_runs_to_the_end() {
+            assert_eq!(
+                vec![text("lorem "), InlineNode::Emphasis(vec![text("ipsum")])],
+                parse("lorem *ipsum")
+            );
+        }
+
+        #[test]
+        fn empty_delimiters_are_dropped() {
+            assert_eq!(vec![text("lorem")], parse("lorem****~~~~"));
+        }
+
+        #[test]
+        fn single_tilde_strikethrough() {
+            let options = ParserOptions::new().single_tilde_strikethrough(true);
+            assert_eq!(
+                vec![
+                    text("lorem "),
+                    InlineNode::Strikethrough(vec![text("ipsum")])
+                ],
+                parse_inline("lorem ~ipsum~", &options)
+            );
+            assert_eq!(vec![text("~lorem~")], parse("~lorem~"));
+        }
+
+        #[test]
+        fn breaks() {
+            assert_eq!(
+                vec![
+                    text("lorem "),
+                    text("ipsum"),
+                    InlineNode::HardBreak,
+                    text("dolor")
+                ],
+                parse("lorem\nipsum\\\ndolor")
+            );
+        }
+    }
+
+    mod plain_text {
+        use super::*;
+
+        #[test]
+        fn matches_tokenizing() {
+            let options = ParserOptions::new();
+            for text in ["", "lorem", "lorem\nipsum\n", "\nlorem  ipsum", "a & b < c"] {
+                assert_eq!(Some(tokenized(text, options)), plain(text, options));
+            }
+        }
+
+        #[test]
+        fn markup_is_tokenized() {
+            let options = ParserOptions::new();
+            for text in ["*lorem*", "lorem\\", "`ipsum`", "~~dolor~~"] {
+                assert_eq!(None, plain(text, options));
+            }
+            assert_eq!(None, plain("&amp;", ParserOptions::new().entities(true)));
+        }
+    }
+
+    mod autolinks {
+        use super::*;
+
+        fn parse_gfm(text: &str) -> Vec<InlineNode> {
+            parse_inline(text, &ParserOptions::new().dialect(Dialect::Gfm))
+        }
+
+        #[test]
+        fn only_in_gfm() {
+            assert_eq!(
+                vec![text("see www.example.com")],
+                parse("see www.example.com")
+            );
+            assert_eq!(
+                vec![
+                    text("see "),
+                    InlineNode::Link {
+                        target: "http://www.example.com".to_owned(),
+                        children: vec![text("www.example.com")],
+                    },
+                    text("."),
+                ],
+                parse_gfm("see www.example.com.")
+            );
+        }
+
+        #[test]
+        fn inside_other_formatting() {
+            assert_eq!(
+                vec![InlineNode::Strong(vec![
+                    InlineNode::Link {
+                        target: "mailto:lorem@example.com".to_owned(),
+                        children: vec![text("lorem@example.com")],
+                    },
+                    text(" and "),
+                    InlineNode::Link {
+                        target: "https://example.com".to_owned(),
+                        children: vec![text("https://example.com")],
+                    },
+                ])],
+                parse_gfm("**lorem@example.com and https://example.com**")
+            );
+        }
+
+        #[test]
+        fn links_are_flattened_to_hyperlinks() {
+            assert_eq!(
+                vec![(
+                    "www.example.com".to_owned(),
+                    Format::new().set_link("http://www.example.com")
+                )],
+                to_slices(&parse_gfm("www.example.com"))
+            );
+        }
+    }
+
+    mod wiki_links {
+        use super::*;
+
+        fn parse_wiki(text: &str) -> Vec<InlineNode> {
+            parse_inline(text, &ParserOptions::new().wiki_links(true))
+        }
+
+        fn wiki_link(page: &str, label: &str) -> InlineNode {
+            InlineNode::Link {
+                target: page.to_owned(),
+                children: vec![text(label)],
+            }
+        }
+
+        #[test]
+        fn only_when_enabled() {
+            assert_eq!(
+                vec![text("see [[Lorem Ipsum]]")],
+                parse("see [[Lorem Ipsum]]")
+            );
+            assert_eq!(
+                vec![
+                    text("see "),
+                    wiki_link("Lorem Ipsum", "Lorem Ipsum"),
+                    text(" and "),
+                    wiki_link("Dolor", "sit amet"),
+                ],
+                parse_wiki("see [[Lorem Ipsum]] and [[ Dolor | sit amet ]]")
+            );
+        }
+
+        #[test]
+        fn empty_and_unclosed_links_are_left_alone() {
+            assert_eq!(vec![text("[[]] [[|lorem]]")], parse_wiki("[[]] [[|lorem]]"));
+            assert_eq!(vec![text("[[lorem")], parse_wiki("[[lorem"));
+            assert_eq!(
+                vec![text("["), wiki_link("lorem", "lorem")],
+                parse_wiki("[[[lorem]]")
+            );
+        }
+
+        #[test]
+        fn inside_other_formatting() {
+            assert_eq!(
+                vec![InlineNode::Emphasis(vec![wiki_link("Lorem", "ipsum")])],
+                parse_wiki("*[[Lorem|ipsum]]*")
+            );
+        }
+    }
+
+    mod slices {
+        use super::*;
+
+        #[test]
+        fn formatting_is_combined_when_nested() {
+            assert_eq!(
+                vec![
+                    ("lorem ".to_owned(), Format::new().set_roles(Roles::STRONG)),
+                    (
+                        "ipsum".to_owned(),
+                        Format::new()
+                            .set_roles(Roles::STRONG)
+                            .set_roles(Roles::EMPHASIS)
+                    ),
+                ],
+                to_slices(&parse("**lorem *ipsum***"))
+            );
+        }
+
+        #[test]
+        fn hard_break_is_a_newline() {
+            assert_eq!(
+                vec![
+                    ("lorem".to_owned(), Format::new()),
+                    ("\n".to_owned(), Format::new()),
+                    ("ipsum".to_owned(), Format::new()),
+                ],
+                to_slices(&parse("lorem\\\nipsum"))
+            );
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::borrow::Cow;
+use std::fmt::{self, Display, Write};
+
+use crate::options::Invisibles;
+
+#[cfg(feature = "encoding")]
+pub use encoding_rs::Encoding;
+
+/// Input that isn't valid UTF-8
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    /// The byte offset of the first invalid sequence
+    pub offset: usize,
+}
+
+impl Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid UTF-8 at byte {}", self.offset)
+    }
+}
+
+impl std::error::Error for InvalidUtf8 {}
+
+/// Decodes `bytes` as UTF-8, failing at the first invalid sequence
+///
+/// # Errors
+///
+/// Returns [`InvalidUtf8`], with the offset of the first invalid sequence, if `bytes` isn't valid
+/// UTF-8
+pub fn decode(bytes: &[u8]) -> Result<&str, InvalidUtf8> {
+    std::str::from_utf8(bytes).map_err(|error| InvalidUtf8 {
+        offset: error.valid_up_to(),
+    })
+}
+
+/// Decodes `bytes` as UTF-8, replacing invalid sequences with U+FFFD (`�`), along with the offset
+/// of the first one, if there were any
+#[must_use]
+pub fn decode_lossy(bytes: &[u8]) -> (Cow<'_, str>, Option<InvalidUtf8>) {
+    (String::from_utf8_lossy(bytes), decode(bytes).err())
+}
+
+/// Guesses the encoding of `bytes`: the one its byte order mark is for, if it has one, or otherwise
+/// UTF-16 if every other byte is mostly zero, UTF-8 if it is valid, or Latin-1 if not (as
+/// Windows-1252, which it is a subset of)
+#[cfg(feature = "encoding")]
+#[must_use]
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    // text that is mostly ASCII has a zero byte in nearly every UTF-16 code unit, on the side
+    // given by its endianness
+    let sample = bytes.get(..1024).unwrap_or(bytes);
+    let zeros = |parity| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let (even_zeros, odd_zeros) = (zeros(0), zeros(1));
+    let units = sample.len() / 2;
+    if units > 0 && odd_zeros * 2 > units && even_zeros * 8 < units {
+        encoding_rs::UTF_16LE
+    } else if units > 0 && even_zeros * 2 > units && odd_zeros * 8 < units {
+        encoding_rs::UTF_16BE
+    } else if decode(bytes).is_ok() {
+        encoding_rs::UTF_8
+    } else {
+        encoding_rs::WINDOWS_1252
+    }
+}
+
+/// Decodes `bytes` from `encoding`, or from the one [`detect`]ed if it is `None`, leaving out any
+/// byte order mark and replacing anything invalid with U+FFFD (`�`)
+#[cfg(feature = "encoding")]
+#[must_use]
+pub fn transcode<'a>(bytes: &'a [u8], encoding: Option<&'static Encoding>) -> Cow<'a, str> {
+    let encoding = encoding.unwrap_or_else(|| detect(bytes));
+    encoding.decode_with_bom_removal(bytes).0
+}
+
+/// Text prepared for parsing, along with where it differs from the text it came from
+pub(crate) struct Prepared<'a> {
+    pub(crate) text: Cow<'a, str>,
+    /// The offsets into the prepared and original text at which each unchanged stretch starts
+    pub(crate) anchors: Vec<(usize, usize)>,
+}
+
+impl Prepared<'_> {
+    /// The offset into the original text of an `offset` into the prepared text
+    pub(crate) fn original_offset(&self, offset: usize) -> usize {
+        let index = self
+            .anchors
+            .partition_point(|(prepared, _)| *prepared <= offset)
+            .saturating_sub(1);
+        self.anchors
+            .get(index)
+            .map_or(offset, |(prepared, original)| original + offset - prepared)
+    }
+
+    /// The offset into `original`, the text this was prepared from, of an `offset` into the
+    /// prepared text, kept within `original` and moved back onto the start of a character. Within
+    /// translated lines, offsets don't line up with the original text
+    pub(crate) fn original_boundary(&self, offset: usize, original: &str) -> usize {
+        let mut offset = self.original_offset(offset).min(original.len());
+        while !original.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    /// Whether any offsets differ between the prepared and original text
+    pub(crate) fn is_shifted(&self) -> bool {
+        self.anchors
+            .iter()
+            .any(|(prepared, original)| prepared != original)
+    }
+}
+
+impl Prepared<'static> {
+    /// Prepared text to be built up a line at a time, as when translating another markup language
+    /// into Markdown
+    pub(crate) fn lines() -> Self {
+        Self {
+            text: Cow::Owned(String::new()),
+            anchors: Vec::new(),
+        }
+    }
+
+    /// Adds `line` to the end of the prepared text, as standing for the original text from
+    /// `original` onwards
+    pub(crate) fn push_line(&mut self, line: &str, original: usize) {
+        let text = self.text.to_mut();
+        self.anchors.push((text.len(), original));
+        text.push_str(line);
+        text.push('\n');
+    }
+}
+
+/// The lines of `text`, without their line endings, each with the offset it starts at
+pub(crate) fn lines(text: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line.trim_end_matches(['\n', '\r']))
+        })
+        .collect()
+}
+
+/// Finds the end of constrained formatting, as in AsciiDoc's and Org's `*bold*`, opened with
+/// `mark` at the start of `text`. The mark has to be followed by something other than a space,
+/// and closed by a `mark` that isn't followed by a letter or number. Returns what is between them
+pub(crate) fn constrained(text: &str, mark: char) -> Option<&str> {
+    let content = text.strip_prefix(mark)?;
+    if content.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut from = 0;
+    while let Some(close) = content.get(from..)?.find(mark) {
+        let end = from + close;
+        let inner = content.get(..end)?;
+        let after = content.get(end + mark.len_utf8()..)?.chars().next();
+        if !inner.is_empty()
+            && !inner.ends_with(char::is_whitespace)
+            && !after.is_some_and(char::is_alphanumeric)
+        {
+            return Some(inner);
+        }
+        from = end + mark.len_utf8();
+    }
+    None
+}
+
+/// Whether `character`, between `previous` and `next`, is an invisible one that was probably put
+/// there by mistake
+fn is_invisible(previous: Option<char>, character: char, next: Option<char>) -> bool {
+    match character {
+        // joiners are needed in emoji sequences and some scripts, but not between ASCII
+        '\u{200c}' | '\u{200d}' => {
+            !(previous.is_some_and(|previous| !previous.is_ascii())
+                && next.is_some_and(|next| !next.is_ascii()))
+        }
+        '\u{200b}' | '\u{200e}' | '\u{200f}' | '\u{2060}' | '\u{feff}' => true,
+        _ => false,
+    }
+}
+
+/// Prepares `text` for parsing, skipping a byte order mark at its start, and showing or removing
+/// invisible characters as `invisibles` asks
+pub(crate) fn sanitize(text: &str, invisibles: Invisibles) -> Prepared<'_> {
+    let start = if text.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    };
+    let body = text.get(start..).unwrap_or_default();
+    let mut anchors = vec![(0, start)];
+    if invisibles == Invisibles::Keep {
+        return Prepared {
+            text: Cow::Borrowed(body),
+            anchors,
+        };
+    }
+
+    let mut sanitized = String::with_capacity(body.len());
+    let mut previous = None;
+    for (index, character) in body.char_indices() {
+        let end = index + character.len_utf8();
+        let next = body.get(end..).and_then(|rest| rest.chars().next());
+        if is_invisible(previous, character, next) {
+            if invisibles == Invisibles::Show {
+                // writing to a `String` can't fail
+                let _ = write!(sanitized, "<U+{:04X}>", u32::from(character));
+            }
+            anchors.push((sanitized.len(), start + end));
+        } else {
+            sanitized.push(character);
+        }
+        previous = Some(character);
+    }
+    Prepared {
+        text: Cow::Owned(sanitized),
+        anchors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_sequences_are_found() {
+        assert_eq!(Ok("lorem"), decode(b"lorem"));
+        assert_eq!(Err(InvalidUtf8 { offset: 6 }), decode(b"lorem \xff ipsum"));
+    }
+
+    #[test]
+    fn invalid_sequences_are_replaced() {
+        let (text, error) = decode_lossy(b"caf\xe9 \xe2\x80 ipsum");
+        assert_eq!("caf\u{fffd} \u{fffd} ipsum", text);
+        assert_eq!(Some(InvalidUtf8 { offset: 3 }), error);
+        assert_eq!(None, decode_lossy(b"lorem").1);
+    }
+
+    mod sanitizing {
+        use super::*;
+
+        #[test]
+        fn byte_order_marks_are_skipped() {
+            let sanitized = sanitize("\u{feff}# lorem", Invisibles::Keep);
+            assert_eq!("# lorem", sanitized.text);
+            assert_eq!(5, sanitized.original_offset(2));
+        }
+
+        #[test]
+        fn invisibles_are_shown_or_removed() {
+            let text = "**lorem\u{200b}** ip\u{200d}sum";
+            assert_eq!(
+                "**lorem<U+200B>** ip<U+200D>sum",
+                sanitize(text, Invisibles::Show).text
+            );
+            let sanitized = sanitize(text, Invisibles::Remove);
+            assert_eq!("**lorem** ipsum", sanitized.text);
+            assert_eq!(text.len(), sanitized.original_offset(15));
+            assert_eq!(2, sanitized.original_offset(2));
+        }
+
+        #[test]
+        fn joiners_in_emoji_are_kept() {
+            let family = "👩\u{200d}👧";
+            assert_eq!(family, sanitize(family, Invisibles::Remove).text);
+        }
+    }
+
+    #[cfg(feature = "encoding")]
+    mod encoding {
+        use super::*;
+
+        fn utf_16(text: &str, little_endian: bool) -> Vec<u8> {
+            text.encode_utf16()
+                .flat_map(|unit| {
+                    if little_endian {
+                        unit.to_le_bytes()
+                    } else {
+                        unit.to_be_bytes()
+                    }
+                })
+                .collect()
+        }
+
+        #[test]
+        fn byte_order_marks_are_followed_and_removed() {
+            let bytes = [&[0xff, 0xfe][..], &utf_16("# café", true)].concat();
+            assert_eq!(encoding_rs::UTF_16LE, detect(&bytes));
+            assert_eq!("# café", transcode(&bytes, None));
+            assert_eq!("lorem", transcode(b"\xef\xbb\xbflorem", None));
+        }
+
+        #[test]
+        fn utf_16_is_detected_without_a_byte_order_mark() {
+            assert_eq!("# café", transcode(&utf_16("# café", true), None));
+            assert_eq!("# café", transcode(&utf_16("# café", false), None));
+        }
+
+        #[test]
+        fn invalid_utf_8_is_taken_as_latin_1() {
+            assert_eq!(encoding_rs::UTF_8, detect("café".as_bytes()));
+            assert_eq!("café", transcode(b"caf\xe9", None));
+        }
+
+        #[test]
+        fn encodings_can_be_given() {
+            let latin_1 = Encoding::for_label(b"latin1");
+            assert_eq!("cafÃ©", transcode("café".as_bytes(), latin_1));
+        }
+    }
+
+    mod translating {
+        use super::*;
+
+        #[test]
+        fn offsets_in_translated_lines_stay_on_characters() {
+            let mut translated = Prepared::lines();
+            translated.push_line("# éa", 0);
+            assert_eq!(0, translated.original_boundary(1, "éa"));
+            assert_eq!(2, translated.original_boundary(2, "éa"));
+            assert_eq!(3, translated.original_boundary(5, "éa"));
+        }
+    }
+}
@@ -1,26 +1,871 @@
-mod formatting;
+// parsing and rendering must not panic, whatever the input, so anything that could is written
+// with checked operations instead
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::indexing_slicing,
+        clippy::string_slice,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic
+    )
+)]
+
+mod abbreviations;
+pub mod annotations;
+#[cfg(feature = "asciidoc")]
+mod asciidoc;
+mod autolinks;
+pub mod backends;
+#[cfg(feature = "bidi")]
+mod bidi;
+pub mod code_block;
+pub mod comment;
+pub mod diagnostics;
+#[cfg(feature = "diagrams")]
+mod diagram;
+pub mod document;
+mod entities;
+#[cfg(feature = "net")]
+pub mod fetch;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod formatting;
+mod highlighting;
+pub mod include;
+pub mod inline;
+pub mod input;
+mod links;
+pub mod list;
+pub mod locale;
+#[cfg(feature = "cli")]
+pub mod lsp;
 pub mod markdown;
+#[cfg(feature = "notebook")]
+pub mod notebook;
+mod obsidian;
+pub mod options;
+mod org;
+pub mod pandoc;
+// there is no clock to time anything with in WebAssembly without a host
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod profile;
+#[cfg(feature = "python")]
+mod python;
+pub mod quote;
+pub mod report;
+mod rst;
+#[cfg(feature = "cli")]
+pub mod serve;
+pub mod stream;
+pub mod table;
+pub mod terminal;
+pub mod testing;
+pub mod theme;
+pub mod title;
+mod tokenizer;
+pub mod variables;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod wrapping;
+
+use tracing::debug;
 
-use crate::markdown::{DocumentElement, Paragraph};
+use crate::abbreviations::Abbreviations;
+use crate::backends::{
+    Backend, Epub, Events, Html, Latex, Man, OutputFormat, PandocJson, Speech, Text,
+};
+use crate::code_block::CodeBlock;
+use crate::links::ResolvedLinks;
+use crate::markdown::{DocumentElement, Heading, Paragraph, ThematicBreak};
+use crate::options::{ParserOptions, RenderOptions};
+use crate::table::Table;
 
 #[must_use]
 pub fn parse(text: &str) -> Vec<Box<dyn DocumentElement>> {
+    parse_with_options(text, &ParserOptions::new())
+}
+
+/// Parses `text` as [`parse`] does, with control over how it is parsed. Front matter at the start
+/// of `text` with a title, author, date or language in it becomes a
+/// [`TitleBlock`](title::TitleBlock)
+#[must_use]
+pub fn parse_with_options(text: &str, options: &ParserOptions) -> Vec<Box<dyn DocumentElement>> {
+    let original = text;
+    // hyperlinks with labels are translated into wiki links, as sani has no other links with labels
+    let (translated, options) = match options.input_format {
+        options::InputFormat::Markdown => (None, *options),
+        options::InputFormat::Rst => (Some(rst::to_markdown(text)), options.wiki_links(true)),
+        options::InputFormat::Org => (Some(org::to_markdown(text)), options.wiki_links(true)),
+        #[cfg(feature = "asciidoc")]
+        options::InputFormat::AsciiDoc => {
+            (Some(asciidoc::to_markdown(text)), options.wiki_links(true))
+        }
+    };
+    let text = translated
+        .as_ref()
+        .map_or(text, |translated| &*translated.text);
+    let sanitized = input::sanitize(text, options.invisibles);
+    let uncommented = (options.dialect == options::Dialect::Obsidian)
+        .then(|| obsidian::remove_comments(&sanitized.text));
+    let text = uncommented
+        .as_ref()
+        .map_or(&*sanitized.text, |uncommented| &*uncommented.text);
+    let mut abbreviations = if options.abbreviations {
+        Abbreviations::collect(text)
+    } else {
+        Abbreviations::default()
+    };
+    let mut elements = match title::parse_title_block(text) {
+        Some((title_block, length)) => {
+            let body = text.get(length..).unwrap_or_default();
+            let mut elements: Vec<Box<dyn DocumentElement>> = vec![Box::new(title_block)];
+            for mut element in parse_blocks(body, options, &mut abbreviations) {
+                element.remap_span(&|offset| offset + length);
+                elements.push(element);
+            }
+            elements
+        }
+        None => parse_blocks(text, options, &mut abbreviations),
+    };
+    if let Some(uncommented) = uncommented.filter(input::Prepared::is_shifted) {
+        for element in &mut elements {
+            element.remap_span(&|offset| uncommented.original_offset(offset));
+        }
+    }
+    if sanitized.is_shifted() {
+        for element in &mut elements {
+            element.remap_span(&|offset| sanitized.original_offset(offset));
+        }
+    }
+    if let Some(translated) = translated {
+        for element in &mut elements {
+            element.remap_span(&|offset| translated.original_boundary(offset, original));
+        }
+    }
+    elements
+}
+
+/// Parses `text` as [`parse_with_options`] does, after taking out any front matter (`key: value`
+/// lines between `---` lines) at its start, and filling in `{{name}}` placeholders with the values
+/// `variables` gives them. A title, author, date or language in the front matter still becomes a
+/// [`TitleBlock`](title::TitleBlock). Element spans are still offsets into `text`
+#[must_use]
+pub fn parse_with_variables(
+    text: &str,
+    options: &ParserOptions,
+    variables: &variables::Variables,
+) -> Vec<Box<dyn DocumentElement>> {
+    let substituted = variables::substitute(text, variables);
+    let mut elements = parse_with_options(&substituted.text, options);
+    if substituted.is_shifted() {
+        for element in &mut elements {
+            element.remap_span(&|offset| substituted.original_offset(offset));
+        }
+    }
+    if let Some((title_block, _)) = title::parse_title_block(text) {
+        elements.insert(0, Box::new(title_block));
+    }
+    elements
+}
+
+/// Parses `bytes` as [`parse_with_options`] does, replacing any invalid UTF-8 with U+FFFD (`�`).
+/// Element spans are offsets into the text with the replacements made, as
+/// [`input::decode_lossy`] gives it
+#[must_use]
+pub fn parse_bytes(bytes: &[u8], options: &ParserOptions) -> Vec<Box<dyn DocumentElement>> {
+    parse_with_options(&input::decode_lossy(bytes).0, options)
+}
+
+/// Parses `bytes` as [`parse_with_options`] does, if they are valid UTF-8
+///
+/// # Errors
+///
+/// Returns [`input::InvalidUtf8`], with the offset of the first invalid sequence, if `bytes` isn't
+/// valid UTF-8
+pub fn try_parse_bytes(
+    bytes: &[u8],
+    options: &ParserOptions,
+) -> Result<Vec<Box<dyn DocumentElement>>, input::InvalidUtf8> {
+    Ok(parse_with_options(input::decode(bytes)?, options))
+}
+
+/// Parses `text` into its elements. Lists, blockquotes and fenced code blocks, which can have
+/// blank lines in them, are parsed from wherever they start; the text around them is split into
+/// blocks at blank lines. Element spans are offsets into `text`
+pub(crate) fn parse_blocks(
+    text: &str,
+    options: ParserOptions,
+    abbreviations: &mut Abbreviations,
+) -> Vec<Box<dyn DocumentElement>> {
     let mut elements: Vec<Box<dyn DocumentElement>> = vec![];
+    // the start of the text that hasn't been parsed yet
+    let mut rest_start = 0_usize;
+    let mut line_start = 0_usize;
+    let mut previous_line_blank = true;
+
+    while let Some(rest) = text.get(line_start..).filter(|rest| !rest.is_empty()) {
+        let line = rest.split('\n').next().unwrap_or_default();
+        let interrupts_paragraph = !previous_line_blank;
 
-    for paragraph in text.split("\n\n") {
-        elements.push(Box::new(Paragraph::new(paragraph)));
+        let is_code_block = code_block::is_opening_fence(line);
+        let is_quote = quote::starts_quote(line);
+        let is_comment = comment::starts_comment(line);
+        if is_code_block || is_quote || is_comment || list::starts_list(line, interrupts_paragraph)
+        {
+            // the text before the block is parsed first, so that abbreviations are expanded in
+            // order
+            if let Some(before) = text.get(rest_start..line_start) {
+                parse_blocks_between(before, rest_start, options, abbreviations, &mut elements);
+            }
+            let parsed = if is_code_block {
+                CodeBlock::parse(rest).map(|(code_block, length)| {
+                    let code_block = code_block.at_offset(line_start);
+                    // data is easier to read laid out in a table than as it is written
+                    let element: Box<dyn DocumentElement> = match code_block.to_table() {
+                        Some(table) => Box::new(table),
+                        None => Box::new(code_block),
+                    };
+                    (element, length)
+                })
+            } else if is_comment {
+                comment::parse_comment(rest).map(|(mut comment, length)| {
+                    comment.remap_span(&|offset| offset + line_start);
+                    (Box::new(comment) as Box<dyn DocumentElement>, length)
+                })
+            } else if is_quote {
+                quote::parse_quote(rest, options, abbreviations).map(|(mut quote, length)| {
+                    quote.remap_span(&|offset| offset + line_start);
+                    (Box::new(quote) as Box<dyn DocumentElement>, length)
+                })
+            } else {
+                list::parse_list(rest, interrupts_paragraph, options, abbreviations).map(
+                    |(mut list, length)| {
+                        list.remap_span(&|offset| offset + line_start);
+                        (Box::new(list) as Box<dyn DocumentElement>, length)
+                    },
+                )
+            };
+            if let Some((element, length)) = parsed {
+                debug!(
+                    kind = ?element.kind(),
+                    span = ?(line_start..line_start + length),
+                    "parsed block"
+                );
+                elements.push(element);
+                rest_start = line_start + length;
+                line_start = rest_start + 1;
+                previous_line_blank = true;
+                continue;
+            }
+            debug!(line, "line looked like the start of a block, but isn't one");
+        }
+
+        previous_line_blank = line.trim().is_empty();
+        line_start += line.len() + 1;
+    }
+
+    if rest_start == 0 {
+        // text without any lists, blockquotes or code blocks is parsed as it is, even if empty, to keep its
+        // spacing
+        let mut block_start = 0_usize;
+        for block in text.split("\n\n") {
+            parse_block(block, block_start, options, abbreviations, &mut elements);
+            block_start += block.len() + "\n\n".len();
+        }
+    } else if let Some(after) = text.get(rest_start..) {
+        parse_blocks_between(after, rest_start, options, abbreviations, &mut elements);
     }
 
     elements
 }
 
+/// Parses the blocks of a block that holds others, such as a list item, from its lines with their
+/// indentation or markers taken off. Each line is given with its offset into the text, so that
+/// the blocks' spans can be mapped back onto it. Also returns whether any of the blocks are
+/// separated by blank lines
+pub(crate) fn parse_lines(
+    content: &[(usize, &str)],
+    options: ParserOptions,
+    abbreviations: &mut Abbreviations,
+) -> (Vec<Box<dyn DocumentElement>>, bool) {
+    let lines: Vec<_> = content.iter().map(|(_, line)| *line).collect();
+    let text = lines.join("\n");
+    if text.trim().is_empty() {
+        return (vec![], false);
+    }
+
+    let mut children = parse_blocks(&text, options, abbreviations);
+    let loose = children.windows(2).any(|pair| match pair {
+        [first, second] => text
+            .get(first.span().end..second.span().start)
+            .is_some_and(|between| between.contains("\n\n")),
+        _ => false,
+    });
+
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut line_start = 0;
+    for line in &lines {
+        line_starts.push(line_start);
+        line_start += line.len() + 1;
+    }
+    let map = |position: usize| {
+        let line = line_starts
+            .partition_point(|start| *start <= position)
+            .saturating_sub(1);
+        match (content.get(line), line_starts.get(line)) {
+            (Some((offset, _)), Some(start)) => offset + position - start,
+            _ => position,
+        }
+    };
+    for child in &mut children {
+        child.remap_span(&map);
+    }
+
+    (children, loose)
+}
+
+/// Parses text that comes before, after or between lists, blockquotes and code blocks into its blank-line
+/// delimited blocks. The blank lines separating the text from the blocks around it are skipped.
+/// `offset` is the text's offset into the whole text, for the elements' spans
+fn parse_blocks_between(
+    text: &str,
+    offset: usize,
+    options: ParserOptions,
+    abbreviations: &mut Abbreviations,
+    elements: &mut Vec<Box<dyn DocumentElement>>,
+) {
+    let trimmed = text.trim_start_matches('\n');
+    let offset = offset + text.len() - trimmed.len();
+    let trimmed = trimmed.trim_end_matches('\n');
+    if trimmed.is_empty() {
+        return;
+    }
+
+    let mut block_start = offset;
+    for block in trimmed.split("\n\n") {
+        parse_block(block, block_start, options, abbreviations, elements);
+        block_start += block.len() + "\n\n".len();
+    }
+}
+
+/// Parses a single blank-line-delimited block, which is a paragraph save for any single-line
+/// elements (headings and thematic breaks) and tables, which split it up into their own elements,
+/// and any abbreviation definitions, which are dropped. `block_start` is the block's offset into
+/// the whole text, for the elements' spans
+fn parse_block(
+    block: &str,
+    block_start: usize,
+    options: ParserOptions,
+    abbreviations: &mut Abbreviations,
+    elements: &mut Vec<Box<dyn DocumentElement>>,
+) {
+    let mut paragraph_start = 0_usize;
+    let mut line_start = 0_usize;
+    let mut contains_line_element = false;
+
+    let lines: Vec<_> = block.split_inclusive('\n').collect();
+    let mut index = 0;
+    while let Some(line) = lines.get(index) {
+        let line_content = line.trim_end_matches('\n');
+        let is_definition =
+            options.abbreviations && abbreviations::definition(line_content).is_some();
+        let mut line_count = 1;
+        let element = if is_definition {
+            None
+        } else {
+            parse_line_element(
+                line_content,
+                block_start + line_start,
+                options,
+                abbreviations,
+            )
+            .or_else(|| {
+                let (table, table_lines) = parse_table(lines.get(index..)?, options)?;
+                line_count = table_lines;
+                Some(Box::new(
+                    table
+                        .with_abbreviations(abbreviations)
+                        .at_offset(block_start + line_start),
+                ))
+            })
+        };
+        let length: usize = lines
+            .iter()
+            .skip(index)
+            .take(line_count)
+            .map(|line| line.len())
+            .sum();
+
+        if let Some(element) = &element {
+            debug!(
+                kind = ?element.kind(),
+                span = ?element.span(),
+                "parsed block"
+            );
+        }
+        if is_definition || element.is_some() {
+            // push whatever paragraph text came before the line, minus its trailing newline
+            if paragraph_start != line_start {
+                let text = block
+                    .get(paragraph_start..line_start.saturating_sub(1))
+                    .unwrap_or_default();
+                let paragraph = Paragraph::with_options(text, &options)
+                    .with_abbreviations(abbreviations)
+                    .at_offset(block_start + paragraph_start);
+                debug!(kind = ?paragraph.kind(), span = ?paragraph.span(), "parsed block");
+                elements.push(Box::new(paragraph));
+            }
+            elements.extend(element);
+            contains_line_element = true;
+            paragraph_start = line_start + length;
+        }
+        line_start += length;
+        index += line_count;
+    }
+
+    // a block without any line elements is pushed as-is, even if empty, to preserve spacing
+    if !contains_line_element || paragraph_start != block.len() {
+        let text = block.get(paragraph_start..).unwrap_or_default();
+        let paragraph = Paragraph::with_options(text, &options)
+            .with_abbreviations(abbreviations)
+            .at_offset(block_start + paragraph_start);
+        debug!(kind = ?paragraph.kind(), span = ?paragraph.span(), "parsed block");
+        elements.push(Box::new(paragraph));
+    }
+}
+
+/// Parses an element that takes up exactly one line, if `line` is one. `offset` is the line's
+/// offset into the whole text
+fn parse_line_element(
+    line: &str,
+    offset: usize,
+    options: ParserOptions,
+    abbreviations: &mut Abbreviations,
+) -> Option<Box<dyn DocumentElement>> {
+    if let Some(heading) = Heading::from_line_with_options(line, &options) {
+        return Some(Box::new(
+            heading.with_abbreviations(abbreviations).at_offset(offset),
+        ));
+    }
+    // checked after headings, as neither can be mistaken for the other
+    ThematicBreak::from_line(line).map(|thematic_break| {
+        Box::new(thematic_break.at_offset(offset)) as Box<dyn DocumentElement>
+    })
+}
+
+/// Parses a table starting at the first of `lines`, if one starts there, returning it along with
+/// the number of lines it takes up. A table goes on until the end of its block, or until a line
+/// that is an element of its own
+fn parse_table(lines: &[&str], options: ParserOptions) -> Option<(Table, usize)> {
+    let [header, delimiter, rest @ ..] = lines else {
+        return None;
+    };
+    let rows: Vec<_> = rest
+        .iter()
+        .map(|line| line.trim_end_matches('\n'))
+        .take_while(|line| {
+            !line.trim().is_empty()
+                && Heading::from_line(line).is_none()
+                && ThematicBreak::from_line(line).is_none()
+                && !(options.abbreviations && abbreviations::definition(line).is_some())
+        })
+        .collect();
+    let table = Table::from_lines(
+        header.trim_end_matches('\n'),
+        delimiter.trim_end_matches('\n'),
+        &rows,
+        &options,
+    )?;
+    Some((table, rows.len() + 2))
+}
+
 #[must_use]
-pub fn render(elements: Vec<Box<dyn DocumentElement>>) -> String {
-    let mut output = String::new();
+pub fn render(elements: Vec<Box<dyn DocumentElement>>, options: &RenderOptions) -> String {
+    let mut output = String::with_capacity(estimated_length(&elements));
 
     for element in elements {
-        output += &(element.render() + "\n\n");
+        if element.is_hidden(options) {
+            continue;
+        }
+        element.render_into(options, &mut output);
+        // the last line of the block may have been reset already
+        if options.resets && !output.ends_with(formatting::RESET) {
+            output.push_str(formatting::RESET);
+        }
+        output.push_str("\n\n");
     }
 
     output
 }
+
+/// Cuts rendered output down to its first `max_lines` lines, followed by an ellipsis if anything
+/// was cut off
+#[must_use]
+pub fn truncate_lines(render: &str, max_lines: usize, options: &RenderOptions) -> String {
+    let mut lines = render.lines();
+    let kept: Vec<_> = lines.by_ref().take(max_lines).collect();
+    if lines.next().is_none() {
+        return render.to_owned();
+    }
+
+    let mut truncated = String::new();
+    for line in kept {
+        truncated += line;
+        truncated.push('\n');
+    }
+    truncated += options.theme.glyphs.ellipsis;
+    truncated
+}
+
+/// Renders `elements` in the given output format. Terminal output is the same as that of
+/// [`render`]. As some formats are [binary](OutputFormat::is_binary), such as EPUB, for which
+/// only the XHTML of the book's chapter is given, [`render_bytes`] gives their output proper
+#[must_use]
+#[allow(clippy::needless_pass_by_value)] // taken by value, as `render` takes them
+pub fn render_as(
+    elements: Vec<Box<dyn DocumentElement>>,
+    format: OutputFormat,
+    options: &RenderOptions,
+) -> String {
+    render_elements(&elements, format, options)
+}
+
+/// Renders `elements` as [`render_as`] does, without taking ownership of them
+fn render_elements(
+    elements: &[Box<dyn DocumentElement>],
+    format: OutputFormat,
+    options: &RenderOptions,
+) -> String {
+    let Some(mut backend) = backend_for(format, options) else {
+        let mut output = String::with_capacity(estimated_length(elements));
+        for element in elements {
+            if !element.is_hidden(options) {
+                element.render_into(options, &mut output);
+                output.push_str("\n\n");
+            }
+        }
+        return from_terminal(output, format);
+    };
+    backend.reserve(estimated_length(elements));
+    for element in elements {
+        element.visit(backend.as_mut());
+    }
+    backend.finish()
+}
+
+/// Renders `elements` in the given output format, as [`render_as`] does, except that the output
+/// of binary formats is given in full
+#[must_use]
+pub fn render_bytes(
+    elements: Vec<Box<dyn DocumentElement>>,
+    format: OutputFormat,
+    options: &RenderOptions,
+) -> Vec<u8> {
+    match backend_for(format, options) {
+        Some(mut backend) => {
+            backend.reserve(estimated_length(&elements));
+            for element in &elements {
+                element.visit(backend.as_mut());
+            }
+            backend.finish_bytes()
+        }
+        None => from_terminal(render(elements, options), format).into_bytes(),
+    }
+}
+
+/// How many blocks [`render_to_async`] renders between giving other tasks a chance to run
+#[cfg(feature = "tokio")]
+const BLOCKS_PER_YIELD: usize = 64;
+
+/// Renders `elements` in the given output format, as [`render_bytes`] does, writing the output to
+/// `writer`. Terminal output is written a block at a time; other formats are written once they
+/// are finished. Every few blocks, the task yields, so that a very large document doesn't hold up
+/// the other tasks on its thread
+///
+/// # Errors
+///
+/// Returns any error writing to `writer`
+#[cfg(feature = "tokio")]
+pub async fn render_to_async(
+    elements: &[Box<dyn DocumentElement>],
+    format: OutputFormat,
+    options: &RenderOptions,
+    mut writer: impl tokio::io::AsyncWrite + Unpin,
+) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut backend = backend_for(format, options);
+    // images of terminal output are drawn from all of it at once
+    let is_streamed = format == OutputFormat::Terminal;
+    let mut output = String::new();
+    for (index, element) in elements.iter().enumerate() {
+        if let Some(backend) = &mut backend {
+            element.visit(backend.as_mut());
+        } else if !element.is_hidden(options) {
+            element.render_into(options, &mut output);
+            if options.resets && !output.ends_with(formatting::RESET) {
+                output.push_str(formatting::RESET);
+            }
+            output.push_str("\n\n");
+            if is_streamed {
+                writer.write_all(output.as_bytes()).await?;
+                output.clear();
+            }
+        }
+        if index % BLOCKS_PER_YIELD == BLOCKS_PER_YIELD - 1 {
+            tokio::task::yield_now().await;
+        }
+    }
+    let rest = match backend {
+        Some(mut backend) => backend.finish_bytes(),
+        None => from_terminal(output, format).into_bytes(),
+    };
+    writer.write_all(&rest).await?;
+    writer.flush().await
+}
+
+/// A guess at how long the rendering of `elements` is, so that room for it can be made up front
+/// rather than as it grows: the length of the source they were parsed from, plus a quarter as
+/// much again for escape codes or markup, plus the blank line after each block
+fn estimated_length(elements: &[Box<dyn DocumentElement>]) -> usize {
+    let source: usize = elements.iter().map(|element| element.span().len()).sum();
+    source + source / 4 + 2 * elements.len()
+}
+
+/// Terminal output in `format`, which is either terminal output itself or an image of it
+fn from_terminal(output: String, format: OutputFormat) -> String {
+    match format {
+        #[cfg(feature = "svg")]
+        OutputFormat::Svg => backends::svg_from_terminal(&output),
+        _ => output,
+    }
+}
+
+/// The backend that renders `format`, or [`None`] for terminal output and images of it, which
+/// elements render themselves
+fn backend_for(format: OutputFormat, options: &RenderOptions) -> Option<Box<dyn Backend>> {
+    debug!(?format, "rendering");
+    let backend: Box<dyn Backend> = match format {
+        OutputFormat::Terminal => return None,
+        #[cfg(feature = "svg")]
+        OutputFormat::Svg => return None,
+        OutputFormat::Text => Box::new(Text::new(options)),
+        OutputFormat::Html => Box::new(Html::new()),
+        OutputFormat::Man(section) => Box::new(Man::new(section)),
+        OutputFormat::Latex => Box::new(Latex::new()),
+        OutputFormat::Epub => {
+            let epub = Epub::new().labels(options.labels);
+            Box::new(match &options.language {
+                Some(language) => epub.language(language),
+                None => epub,
+            })
+        }
+        OutputFormat::Events => Box::new(Events::new()),
+        OutputFormat::PandocJson => Box::new(PandocJson::new()),
+        OutputFormat::Speech => Box::new(Speech::new()),
+        #[cfg(feature = "pdf")]
+        OutputFormat::Pdf => Box::new(backends::Pdf::new(options)),
+    };
+    Some(match &options.link_resolver {
+        Some(resolver) => Box::new(ResolvedLinks::new(backend, resolver.clone())),
+        None => backend,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::ElementKind;
+    use crate::theme::{Glyphs, Theme};
+
+    mod truncation {
+        use super::*;
+
+        #[test]
+        fn lines_past_the_limit_are_cut_off() {
+            let options = RenderOptions::new();
+            assert_eq!("a\nb\n…", truncate_lines("a\nb\nc\nd", 2, &options));
+            assert_eq!("a\nb\n", truncate_lines("a\nb\n", 2, &options));
+        }
+
+        #[test]
+        fn ellipsis_follows_the_glyphs() {
+            let options = RenderOptions::new().theme(Theme::dark().glyphs(Glyphs::ascii()));
+            assert_eq!("a\n...", truncate_lines("a\nb", 1, &options));
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    mod rendering_async {
+        use super::*;
+
+        fn render_to_vec(
+            elements: &[Box<dyn DocumentElement>],
+            format: OutputFormat,
+            options: &RenderOptions,
+        ) -> Vec<u8> {
+            let mut output = Vec::new();
+            let rendering = render_to_async(elements, format, options, &mut output);
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(rendering)
+                .unwrap();
+            output
+        }
+
+        #[test]
+        fn output_is_the_same_as_rendering_bytes() {
+            let text = "# lorem\n\n".repeat(BLOCKS_PER_YIELD) + "ipsum *dolor*\n\n- sit\n- amet";
+            let options = RenderOptions::new().width(20);
+            for format in [
+                OutputFormat::Terminal,
+                OutputFormat::Html,
+                OutputFormat::Text,
+            ] {
+                assert_eq!(
+                    render_bytes(parse(&text), format, &options),
+                    render_to_vec(&parse(&text), format, &options)
+                );
+            }
+        }
+
+        #[test]
+        fn rendering_can_be_spawned() {
+            fn assert_send(_: &impl Send) {}
+            let elements = parse("lorem");
+            let options = RenderOptions::new();
+            let mut output = Vec::new();
+            assert_send(&render_to_async(
+                &elements,
+                OutputFormat::Terminal,
+                &options,
+                &mut output,
+            ));
+        }
+    }
+
+    mod capacity {
+        use super::*;
+
+        #[test]
+        fn output_length_is_estimated_from_the_source() {
+            assert_eq!(16, estimated_length(&parse("lorem\n\nipsum")));
+            assert_eq!(0, estimated_length(&[]));
+        }
+
+        #[test]
+        fn room_is_made_for_the_output() {
+            let output = render(parse("lorem *ipsum*"), &RenderOptions::new());
+            assert!(output.capacity() >= estimated_length(&parse("lorem *ipsum*")));
+        }
+    }
+
+    mod bytes {
+        use super::*;
+
+        #[test]
+        fn invalid_utf8_is_replaced_or_reported() {
+            let options = ParserOptions::new();
+            assert_eq!(
+                render(parse("lorem \u{fffd} ipsum"), &RenderOptions::new()),
+                render(
+                    parse_bytes(b"lorem \xff ipsum", &options),
+                    &RenderOptions::new()
+                )
+            );
+            assert_eq!(
+                Some(input::InvalidUtf8 { offset: 6 }),
+                try_parse_bytes(b"lorem \xff ipsum", &options).err()
+            );
+            assert_eq!(1, try_parse_bytes(b"lorem", &options).unwrap().len());
+        }
+    }
+
+    mod block_spans {
+        use super::*;
+
+        fn spans(text: &str) -> Vec<std::ops::Range<usize>> {
+            parse(text).iter().map(|element| element.span()).collect()
+        }
+
+        #[test]
+        fn paragraphs() {
+            assert_eq!(vec![0..5, 7..18], spans("lorem\n\nipsum\ndolor"));
+        }
+
+        #[test]
+        fn heading_splits_paragraph() {
+            assert_eq!(vec![0..5, 6..13, 14..19], spans("lorem\n# ipsum\ndolor"));
+        }
+
+        #[test]
+        fn thematic_break_splits_paragraph() {
+            assert_eq!(vec![0..5, 6..9, 10..15], spans("lorem\n***\nipsum"));
+        }
+
+        #[test]
+        fn spans_are_into_the_unsanitized_text() {
+            assert_eq!(vec![3..8], spans("\u{feff}lorem"));
+            let options = ParserOptions::new().invisibles(options::Invisibles::Remove);
+            let elements = parse_with_options("lo\u{200b}rem\n\nipsum", &options);
+            assert_eq!(
+                vec![0..8, 10..15],
+                elements
+                    .iter()
+                    .map(|element| element.span())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn abbreviation_definitions_are_dropped() {
+            let options = ParserOptions::new().abbreviations(true);
+            let spans = |text| {
+                parse_with_options(text, &options)
+                    .iter()
+                    .map(|element| element.span())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(vec![0..5, 14..19], spans("lorem\n*[A]: b\nipsum"));
+            assert_eq!(vec![0..5], spans("lorem\n\n*[A]: b\n*[C]: d"));
+        }
+
+        #[test]
+        fn table_splits_paragraph() {
+            assert_eq!(
+                vec![0..5, 6..23, 24..31],
+                spans("lorem\na | b\n--|--\n1 | 2\n# ipsum")
+            );
+            assert_eq!(
+                Some(ElementKind::Table),
+                parse("a | b\n--|--").first().map(|element| element.kind())
+            );
+        }
+
+        #[test]
+        fn heading_alone_in_block() {
+            assert_eq!(vec![0..5, 7..14], spans("lorem\n\n# ipsum"));
+        }
+
+        #[test]
+        fn front_matter_with_a_title_is_a_title_block() {
+            let text = "---\ntitle: Lorem\n---\nipsum {{x}}";
+            assert_eq!(vec![0..21, 21..32], spans(text));
+            let variables = variables::Variables::new().define("x", "dolor");
+            let elements = parse_with_variables(text, &ParserOptions::new(), &variables);
+            assert_eq!(
+                vec![0..21, 21..32],
+                elements
+                    .iter()
+                    .map(|element| element.span())
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                Some(ElementKind::TitleBlock),
+                elements.first().map(|element| element.kind())
+            );
+        }
+    }
+}
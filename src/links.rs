@@ -0,0 +1,200 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use crate::backends::Backend;
+use crate::formatting::Format;
+use crate::list::List;
+use crate::table::Table;
+use crate::title::TitleBlock;
+
+/// A function that rewrites the targets of links as they are rendered, such as to turn the page
+/// names of wiki links into URLs or to send links through a proxy. The links it is given are the
+/// ones the parser makes: bare URLs and email addresses, and wiki links
+#[derive(Clone)]
+pub(crate) struct LinkResolver(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl LinkResolver {
+    pub(crate) fn new(resolve: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        Self(Arc::new(resolve))
+    }
+
+    /// Rewrites the link target of each slice that has one
+    pub(crate) fn apply(&self, slices: &[(String, Format)]) -> Vec<(String, Format)> {
+        slices
+            .iter()
+            .map(|(slice, format)| match format.link() {
+                Some(target) => (slice.clone(), format.clone().set_link(&(self.0)(target))),
+                None => (slice.clone(), format.clone()),
+            })
+            .collect()
+    }
+}
+
+impl Debug for LinkResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LinkResolver")
+    }
+}
+
+/// Resolvers are only the same if they are the same function, as closures can't be compared
+impl PartialEq for LinkResolver {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Passes everything on to another backend, with the targets of links rewritten by a
+/// [`LinkResolver`] first
+pub(crate) struct ResolvedLinks {
+    backend: Box<dyn Backend>,
+    resolver: LinkResolver,
+}
+
+impl ResolvedLinks {
+    pub(crate) fn new(backend: Box<dyn Backend>, resolver: LinkResolver) -> Self {
+        Self { backend, resolver }
+    }
+}
+
+impl Backend for ResolvedLinks {
+    fn metadata(&mut self, title_block: &TitleBlock) {
+        self.backend.metadata(title_block);
+    }
+
+    fn paragraph(&mut self, text: &[(String, Format)]) {
+        self.backend.paragraph(&self.resolver.apply(text));
+    }
+
+    fn heading(&mut self, level: u8, text: &[(String, Format)]) {
+        self.backend.heading(level, &self.resolver.apply(text));
+    }
+
+    fn thematic_break(&mut self) {
+        self.backend.thematic_break();
+    }
+
+    fn table(&mut self, table: &Table) {
+        self.backend
+            .table(&table.map_cells(|cell| self.resolver.apply(cell)));
+    }
+
+    fn list_start(&mut self, list: &List) {
+        self.backend.list_start(list);
+    }
+
+    fn list_end(&mut self) {
+        self.backend.list_end();
+    }
+
+    fn item_start(&mut self, index: usize, task: Option<bool>) {
+        self.backend.item_start(index, task);
+    }
+
+    fn item_end(&mut self) {
+        self.backend.item_end();
+    }
+
+    fn quote_start(&mut self) {
+        self.backend.quote_start();
+    }
+
+    fn attribution(&mut self, text: &[(String, Format)]) {
+        self.backend.attribution(&self.resolver.apply(text));
+    }
+
+    fn quote_end(&mut self) {
+        self.backend.quote_end();
+    }
+
+    fn code_block(&mut self, info: &str, code: &str) {
+        self.backend.code_block(info, code);
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.backend.comment(text);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.backend.reserve(additional);
+    }
+
+    fn finish(&mut self) -> String {
+        self.backend.finish()
+    }
+
+    fn finish_bytes(&mut self) -> Vec<u8> {
+        self.backend.finish_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::OutputFormat;
+    use crate::options::{Dialect, ParserOptions, RenderOptions};
+
+    mod targets {
+        use super::*;
+
+        #[test]
+        fn only_link_targets_are_rewritten() {
+            let resolver = LinkResolver::new(|target| format!("https://example.com/{target}"));
+            assert_eq!(
+                vec![
+                    (
+                        "lorem".to_owned(),
+                        Format::new()
+                            .set_bold()
+                            .set_link("https://example.com/ipsum.md")
+                    ),
+                    ("dolor".to_owned(), Format::new()),
+                ],
+                resolver.apply(&[
+                    (
+                        "lorem".to_owned(),
+                        Format::new().set_bold().set_link("ipsum.md")
+                    ),
+                    ("dolor".to_owned(), Format::new()),
+                ])
+            );
+        }
+
+        #[test]
+        fn autolinks_and_wiki_links_are_rewritten() {
+            let options = RenderOptions::new().link_resolver(|target| format!("<{target}>"));
+            let elements = crate::parse_with_options(
+                "[[Lorem]] https://example.com [ipsum](dolor.md)",
+                &ParserOptions::new().dialect(Dialect::Gfm).wiki_links(true),
+            );
+            let events = crate::render_as(elements, OutputFormat::Events, &options);
+            assert!(events.contains("\"target\":\"<Lorem>\""));
+            assert!(events.contains("\"target\":\"<https://example.com>\""));
+            // inline links aren't parsed, so they are left as they are written
+            assert!(events.contains("[ipsum](dolor.md)"));
+            assert!(!events.contains("dolor.md>"));
+        }
+    }
+
+    mod formats {
+        use super::*;
+
+        #[test]
+        fn links_are_resolved_in_every_format() {
+            let options =
+                RenderOptions::new().link_resolver(|target| target.replace("http:", "https:"));
+            let elements = || {
+                crate::parse_with_options(
+                    "http://example.com",
+                    &ParserOptions::new().dialect(Dialect::Gfm),
+                )
+            };
+            assert!(
+                crate::render(elements(), &options).contains("\x1b]8;;https://example.com\x1b\\")
+            );
+            assert!(crate::render_as(elements(), OutputFormat::Html, &options)
+                .contains("<a href=\"https://example.com\">"));
+            assert!(crate::render_as(elements(), OutputFormat::Events, &options)
+                .contains("\"target\":\"https://example.com\""));
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::abbreviations::Abbreviations;
+use crate::backends::Backend;
+use crate::markdown::{DocumentElement, ElementKind, ThematicBreak};
+use crate::options::{ParserOptions, RenderOptions};
+use crate::theme::NumberingStyle;
+
+/// How the items of a list are marked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListKind {
+    /// An unordered list, with each item marked by the same one of `-`, `+` or `*`
+    Bullet(char),
+    /// An ordered list, numbered on from `start`, with each number followed by `delimiter`, which
+    /// is `.` or `)`
+    Ordered { start: u32, delimiter: char },
+}
+
+/// A list, the items of which can hold any blocks, including other lists
+#[derive(Debug, PartialEq)]
+pub struct List {
+    kind: ListKind,
+    tight: bool,
+    items: Vec<ListItem>,
+    span: Range<usize>,
+}
+
+/// An item of a [`List`]
+#[derive(Debug, PartialEq)]
+pub struct ListItem {
+    children: Vec<Box<dyn DocumentElement>>,
+    /// Whether the item is checked, if it is a task list item (`- [ ] lorem` or `- [x] lorem`)
+    task: Option<bool>,
+    span: Range<usize>,
+}
+
+impl List {
+    #[must_use]
+    pub const fn kind(&self) -> ListKind {
+        self.kind
+    }
+
+    /// Whether the list is tight, with no blank lines between its items or between the blocks
+    /// within them, in which case its items are rendered without space between them
+    #[must_use]
+    pub const fn is_tight(&self) -> bool {
+        self.tight
+    }
+
+    #[must_use]
+    pub fn items(&self) -> &[ListItem] {
+        &self.items
+    }
+
+    /// The marker of each item as it is shown: `bullet` for every item of an unordered list, or
+    /// the item's number in the given style and its delimiter for an ordered one. Numbers are
+    /// padded on the left to the same width, so that the content of the items lines up. Task list
+    /// items are marked with one of `checkboxes`, the checked one first, in place of the bullet or
+    /// after the number
+    pub(crate) fn markers(
+        &self,
+        bullet: &str,
+        numbering: NumberingStyle,
+        checkboxes: (&str, &str),
+    ) -> Vec<String> {
+        let markers = match self.kind {
+            ListKind::Bullet(_) => vec![bullet.to_owned(); self.items.len()],
+            ListKind::Ordered { start, delimiter } => {
+                let numbers: Vec<_> = (u64::from(start)..)
+                    .take(self.items.len())
+                    .map(|number| format!("{}{delimiter}", numbering.format(number)))
+                    .collect();
+                // Roman numerals don't get longer as they go up
+                let width = numbers.iter().map(String::len).max().unwrap_or(0);
+                numbers
+                    .into_iter()
+                    .map(|number| format!("{number:>width$}"))
+                    .collect()
+            }
+        };
+        let (checked, unchecked) = checkboxes;
+        markers
+            .into_iter()
+            .zip(&self.items)
+            .map(|(marker, item)| {
+                let checkbox = match item.task {
+                    Some(true) => checked,
+                    Some(false) => unchecked,
+                    None => return marker,
+                };
+                match self.kind {
+                    ListKind::Bullet(_) => checkbox.to_owned(),
+                    ListKind::Ordered { .. } => format!("{marker} {checkbox}"),
+                }
+            })
+            .collect()
+    }
+
+    /// What goes between the items of the list, and between the blocks within each item
+    pub(crate) const fn separator(&self) -> &'static str {
+        if self.tight {
+            "\n"
+        } else {
+            "\n\n"
+        }
+    }
+}
+
+impl ListItem {
+    /// The blocks that make up the item
+    #[must_use]
+    pub fn children(&self) -> &[Box<dyn DocumentElement>] {
+        &self.children
+    }
+
+    /// Whether the item is checked, if it is a task list item (`- [ ] lorem` or `- [x] lorem`)
+    #[must_use]
+    pub const fn task(&self) -> Option<bool> {
+        self.task
+    }
+
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl DocumentElement for List {
+    fn render(&self, options: &RenderOptions) -> String {
+        let markers = self.markers(bullet(options), numbering(options), checkboxes(options));
+        let indent = indent(&markers);
+        let item_options = nested(options, indent);
+
+        self.items
+            .iter()
+            .zip(markers)
+            .map(|(item, marker)| {
+                let content: Vec<_> = item
+                    .children
+                    .iter()
+                    .filter(|child| !child.is_hidden(&item_options))
+                    .map(|child| child.render(&item_options))
+                    .collect();
+                hang(&marker, indent, &content.join(self.separator()))
+            })
+            .collect::<Vec<_>>()
+            .join(self.separator())
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::List
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.list_start(self);
+        for (index, item) in self.items.iter().enumerate() {
+            backend.item_start(index, item.task);
+            for child in &item.children {
+                child.visit(backend);
+            }
+            backend.item_end();
+        }
+        backend.list_end();
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+        for item in &mut self.items {
+            item.span = map(item.span.start)..map(item.span.end);
+            for child in &mut item.children {
+                child.remap_span(map);
+            }
+        }
+    }
+}
+
+/// The bullet for unordered lists at the depth being rendered, which goes through the theme's
+/// bullets, starting again from the first after the last
+pub(crate) fn bullet(options: &RenderOptions) -> &'static str {
+    let bullets = &options.theme.glyphs.bullets;
+    bullets
+        .get(options.list_depth % bullets.len())
+        .copied()
+        .unwrap_or_default()
+}
+
+/// The theme's boxes for checked and unchecked task list items, in that order
+pub(crate) const fn checkboxes(options: &RenderOptions) -> (&'static str, &'static str) {
+    (options.theme.glyphs.checked, options.theme.glyphs.unchecked)
+}
+
+/// How far the content of a list's items is indented, given their markers: past the widest of
+/// them, with a space after it
+pub(crate) fn indent(markers: &[String]) -> usize {
+    markers
+        .iter()
+        .map(|marker| marker.width())
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+/// How ordered lists at the depth being rendered are numbered
+pub(crate) fn numbering(options: &RenderOptions) -> NumberingStyle {
+    options.theme.numbering_style(options.list_depth)
+}
+
+/// The options for rendering the content of a list's items, which are indented by `indent`
+/// columns and nested one list deeper
+pub(crate) fn nested(options: &RenderOptions, indent: usize) -> RenderOptions {
+    let mut options = options.clone().narrowed(indent);
+    options.list_depth += 1;
+    options
+}
+
+/// Hangs rendered content off of a list item's marker: the marker goes before the first line,
+/// and every line after it is indented by `indent` columns, to line up with the first
+pub(crate) fn hang(marker: &str, indent: usize, content: &str) -> String {
+    let padding = " ".repeat(indent);
+    let mut lines = content.split('\n');
+    let first = lines.next().unwrap_or_default();
+    let mut hung = if first.is_empty() {
+        marker.to_owned()
+    } else {
+        format!(
+            "{marker}{}{first}",
+            " ".repeat(indent.saturating_sub(marker.width()))
+        )
+    };
+    for line in lines {
+        hung.push('\n');
+        if !line.is_empty() {
+            hung += &padding;
+            hung += line;
+        }
+    }
+    hung
+}
+
+/// A list item's marker, at the start of a line
+struct Marker {
+    kind: ListKind,
+    /// The offset of the item's content into the line
+    content_start: usize,
+    /// How far the lines after the first must be indented to be part of the item
+    content_indent: usize,
+    /// Whether there is nothing on the line after the marker
+    is_empty: bool,
+}
+
+impl Marker {
+    /// Parses the marker at the start of `line`, if it has one. When the line would interrupt a
+    /// paragraph, only a non-empty item of an unordered list or an ordered list starting from 1
+    /// can start a list, as anything else is too likely to be part of the paragraph's text
+    fn parse(line: &str, interrupts_paragraph: bool) -> Option<Self> {
+        let unindented = line.trim_start_matches(' ');
+        let indent = line.len() - unindented.len();
+        if indent > 3 || ThematicBreak::from_line(line).is_some() {
+            return None;
+        }
+
+        let digits = unindented.len()
+            - unindented
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let (kind, marker_length) = if digits == 0 {
+            let bullet = unindented
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '-' | '+' | '*'))?;
+            (ListKind::Bullet(bullet), 1)
+        } else {
+            let delimiter = unindented
+                .get(digits..)?
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '.' | ')'))?;
+            // longer numbers could overflow, and are unlikely to be meant as lists
+            if digits > 9 {
+                return None;
+            }
+            let start = unindented.get(..digits)?.parse().ok()?;
+            (ListKind::Ordered { start, delimiter }, digits + 1)
+        };
+
+        let after = unindented.get(marker_length..)?;
+        if !(after.is_empty() || after.starts_with(' ')) {
+            return None;
+        }
+        let is_empty = after.trim().is_empty();
+        if interrupts_paragraph
+            && (is_empty || matches!(kind, ListKind::Ordered { start, .. } if start != 1))
+        {
+            return None;
+        }
+        // content indented by five or more spaces past the marker is taken to start after the
+        // first, with the rest of its indentation kept
+        let spaces = after.len() - after.trim_start_matches(' ').len();
+        let spaces = if is_empty || spaces > 4 { 1 } else { spaces };
+
+        let content_indent = indent + marker_length + spaces;
+        Some(Self {
+            kind,
+            content_start: content_indent.min(line.len()),
+            content_indent,
+            is_empty,
+        })
+    }
+
+    /// Whether an item with this marker belongs in the same list as one marked with `kind`
+    fn continues(&self, kind: ListKind) -> bool {
+        match (self.kind, kind) {
+            (ListKind::Bullet(bullet), ListKind::Bullet(other)) => bullet == other,
+            (
+                ListKind::Ordered { delimiter, .. },
+                ListKind::Ordered {
+                    delimiter: other, ..
+                },
+            ) => delimiter == other,
+            _ => false,
+        }
+    }
+}
+
+/// Whether `line` starts a list
+pub(crate) fn starts_list(line: &str, interrupts_paragraph: bool) -> bool {
+    Marker::parse(line, interrupts_paragraph).is_some()
+}
+
+/// Parses a list starting at the first line of `text`, if one starts there, returning it along
+/// with the number of bytes of `text` it takes up, not counting the newline after its last line.
+/// The list goes on for as long as there are items with the same kind of marker, each of which
+/// takes in every line after it that is indented at least as far as its content, along with any
+/// lines that lazily continue a paragraph
+pub(crate) fn parse_list(
+    text: &str,
+    interrupts_paragraph: bool,
+    options: ParserOptions,
+    abbreviations: &mut Abbreviations,
+) -> Option<(List, usize)> {
+    let mut offset = 0;
+    // taken a line at a time, as a list usually ends long before the text does
+    let mut lines = text
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line.trim_end_matches('\n'))
+        })
+        .peekable();
+    let kind = Marker::parse(lines.peek()?.1, interrupts_paragraph)?.kind;
+
+    let mut items = vec![];
+    let mut tight = true;
+    let mut end = 0;
+    let mut blank_before = false;
+    while let Some(&(item_start, line)) = lines.peek() {
+        let Some(marker) = Marker::parse(line, false).filter(|marker| marker.continues(kind))
+        else {
+            break;
+        };
+        if blank_before {
+            tight = false;
+        }
+
+        // the item's lines, with their indentation taken off, and the offset of each into `text`
+        let first = line.get(marker.content_start..).unwrap_or_default();
+        let (task, first) = match task_box(first) {
+            Some((checked, rest)) => (Some(checked), rest),
+            None => (None, first),
+        };
+        let mut content = vec![(item_start + line.len() - first.len(), first)];
+        let mut blanks = 0;
+        lines.next();
+        while let Some(&(line_start, line)) = lines.peek() {
+            let unindented = line.trim_start_matches(' ');
+            let indent = line.len() - unindented.len();
+            let continues_paragraph = blanks == 0
+                && content
+                    .last()
+                    .is_some_and(|(_, line)| !line.trim().is_empty());
+            if unindented.is_empty() {
+                // an item can only start with one blank line, and not if its marker is alone
+                if marker.is_empty && content.len() == 1 {
+                    break;
+                }
+                content.push((line_start, ""));
+                blanks += 1;
+            } else if indent >= marker.content_indent {
+                content.push((
+                    line_start + marker.content_indent,
+                    line.get(marker.content_indent..).unwrap_or_default(),
+                ));
+                blanks = 0;
+            } else if continues_paragraph && is_lazy_continuation(line) {
+                content.push((line_start + indent, unindented));
+            } else {
+                break;
+            }
+            lines.next();
+        }
+        // blank lines at the end of an item come between it and what follows
+        content.truncate(content.len() - blanks);
+        blank_before = blanks > 0;
+
+        let (children, loose) = crate::parse_lines(&content, options, abbreviations);
+        tight &= !loose;
+        let item_end = content
+            .last()
+            .map_or(item_start, |(line_start, line)| line_start + line.len());
+        items.push(ListItem {
+            children,
+            task,
+            span: item_start..item_end,
+        });
+        end = item_end;
+    }
+
+    Some((
+        List {
+            kind,
+            tight,
+            items,
+            span: 0..end,
+        },
+        end,
+    ))
+}
+
+/// Whether the first line of an item's content starts with the box of a task list item, `[ ]` or
+/// `[x]`, followed by a space, and if so, whether it is checked, along with the rest of the line
+fn task_box(line: &str) -> Option<(bool, &str)> {
+    let checked = match line.get(..4)? {
+        "[ ] " => false,
+        "[x] " | "[X] " => true,
+        _ => return None,
+    };
+    Some((checked, line.get(4..)?))
+}
+
+/// Whether `line`, which isn't indented enough to be part of a list item, still continues the
+/// paragraph before it, as it doesn't start a block of its own
+pub(crate) fn is_lazy_continuation(line: &str) -> bool {
+    !starts_list(line, false)
+        && !crate::quote::starts_quote(line)
+        && !crate::code_block::is_opening_fence(line)
+        && crate::markdown::Heading::from_line(line).is_none()
+        && ThematicBreak::from_line(line).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatting::Format;
+    use crate::markdown::Paragraph;
+    use crate::theme::{Glyphs, Theme};
+
+    fn parse(text: &str) -> List {
+        let (list, _) = parse_list(
+            text,
+            false,
+            ParserOptions::new(),
+            &mut Abbreviations::default(),
+        )
+        .unwrap();
+        list
+    }
+
+    fn kinds(item: &ListItem) -> Vec<ElementKind> {
+        item.children().iter().map(|child| child.kind()).collect()
+    }
+
+    mod parsing {
+        use super::*;
+
+        #[test]
+        fn items_end_at_a_different_marker() {
+            let (list, length) = parse_list(
+                "- a\n- b\n+ c",
+                false,
+                ParserOptions::new(),
+                &mut Abbreviations::default(),
+            )
+            .unwrap();
+            assert_eq!(2, list.items().len());
+            assert_eq!(7, length);
+            assert_eq!(ListKind::Bullet('-'), list.kind());
+        }
+
+        #[test]
+        fn ordered_lists_keep_their_start() {
+            assert_eq!(
+                ListKind::Ordered {
+                    start: 3,
+                    delimiter: ')'
+                },
+                parse("3) a\n4) b").kind()
+            );
+        }
+
+        #[test]
+        fn items_hold_multiple_blocks() {
+            let list =
+                parse("1. lorem\n\n   ipsum\n\n   ```\n   code\n   ```\n   - nested\n2. dolor");
+            let item = list.items().first().unwrap();
+            assert_eq!(
+                vec![
+                    ElementKind::Paragraph,
+                    ElementKind::Paragraph,
+                    ElementKind::CodeBlock,
+                    ElementKind::List
+                ],
+                kinds(item)
+            );
+            assert!(!list.is_tight());
+        }
+
+        #[test]
+        fn spans_are_in_the_whole_text() {
+            let list = parse("- lorem\n  ipsum\n\n  dolor\n- sit");
+            let item = list.items().first().unwrap();
+            let spans: Vec<_> = item.children().iter().map(|child| child.span()).collect();
+            assert_eq!(vec![2..15, 19..24], spans);
+            assert_eq!(0..24, item.span());
+            assert_eq!(Some(25..30), list.items().get(1).map(ListItem::span));
+        }
+
+        #[test]
+        fn lazy_continuation_lines() {
+            let list = parse("- lorem\nipsum\n\ndolor");
+            assert_eq!(0..13, list.span());
+            let item = list.items().first().unwrap();
+            assert_eq!(
+                Some("lorem ipsum".to_owned()),
+                item.children()
+                    .first()
+                    .and_then(|child| child.as_paragraph())
+                    .map(Paragraph::plain_text)
+            );
+        }
+
+        #[test]
+        fn tight_and_loose() {
+            assert!(parse("- a\n- b\n  - c\n\n  - d").is_tight());
+            assert!(!parse("- a\n\n- b").is_tight());
+            assert!(!parse("- a\n\n  b").is_tight());
+        }
+
+        #[test]
+        fn task_list_items() {
+            let list = parse("- [x] lorem\n- [ ] ipsum\n- [y] dolor\n- [ ]");
+            let tasks: Vec<_> = list.items().iter().map(ListItem::task).collect();
+            assert_eq!(vec![Some(true), Some(false), None, None], tasks);
+            let item = list.items().first().unwrap();
+            assert_eq!(
+                Some(6..11),
+                item.children().first().map(|child| child.span())
+            );
+        }
+
+        #[test]
+        fn not_lists() {
+            for (text, interrupts_paragraph) in
+                [("-a", false), ("- - -", false), ("2. a", true), ("-", true)]
+            {
+                assert!(!starts_list(text, interrupts_paragraph), "{text}");
+            }
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        fn render(text: &str, width: usize) -> String {
+            let options = RenderOptions::new()
+                .width(width)
+                .theme(Theme::dark().glyphs(Glyphs::ascii()));
+            parse(text).render(&options)
+        }
+
+        #[test]
+        fn hanging_indentation() {
+            assert_eq!(
+                "* lorem ipsum\n  dolor\n* sit",
+                render("- lorem ipsum dolor\n- sit", 14)
+            );
+        }
+
+        #[test]
+        fn task_list_items_are_marked_with_boxes() {
+            assert_eq!(
+                "[x] lorem\n[ ] ipsum\n*   dolor",
+                render("- [x] lorem\n- [ ] ipsum\n- dolor", 20)
+            );
+            assert_eq!(
+                "1. [x] lorem\n2.     ipsum",
+                render("1. [X] lorem\n2. ipsum", 20)
+            );
+        }
+
+        #[test]
+        fn numbers_are_right_aligned() {
+            let text = (1..=10)
+                .map(|number| format!("{number}. a"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let render = render(&text, 20);
+            assert_eq!(Some(" 9. a"), render.lines().nth(8));
+            assert_eq!(Some("10. a"), render.lines().nth(9));
+        }
+
+        #[test]
+        fn numbering_follows_the_depth() {
+            assert_eq!(
+                "3. lorem\n   - ipsum\n      i. dolor\n     ii. sit\n   c) amet",
+                render(
+                    "3. lorem\n   - ipsum\n     1. dolor\n     2. sit\n   3) amet",
+                    40
+                )
+            );
+        }
+
+        #[test]
+        fn nested_blocks_are_indented() {
+            let bold = Format::new().set_bold();
+            assert_eq!(
+                format!(
+                    "* lorem\n\n  - ipsum\n  - {}dolor{}\n\n* sit",
+                    bold.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&bold)
+                ),
+                render("- lorem\n\n  - ipsum\n  - **dolor**\n\n- sit", 20)
+            );
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::env;
+
+/// The text that is generated rather than taken from the document, such as the labels of
+/// annotation callouts, in one language
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Labels {
+    /// The ISO 639-1 code of the language the labels are in
+    pub language: &'static str,
+    /// Before the number of the line an annotation is on
+    pub line: &'static str,
+    /// Before the range of lines an annotation is on
+    pub lines: &'static str,
+    /// The title of a book or PDF whose document has no level 1 heading to take it from
+    pub untitled: &'static str,
+}
+
+/// The languages there are labels for
+const LABELS: [Labels; 7] = [
+    Labels::english(),
+    Labels {
+        language: "de",
+        line: "Zeile",
+        lines: "Zeilen",
+        untitled: "Unbenannt",
+    },
+    Labels {
+        language: "es",
+        line: "línea",
+        lines: "líneas",
+        untitled: "Sin título",
+    },
+    Labels {
+        language: "fr",
+        line: "ligne",
+        lines: "lignes",
+        untitled: "Sans titre",
+    },
+    Labels {
+        language: "it",
+        line: "riga",
+        lines: "righe",
+        untitled: "Senza titolo",
+    },
+    Labels {
+        language: "nl",
+        line: "regel",
+        lines: "regels",
+        untitled: "Naamloos",
+    },
+    Labels {
+        language: "pt",
+        line: "linha",
+        lines: "linhas",
+        untitled: "Sem título",
+    },
+];
+
+impl Labels {
+    #[must_use]
+    pub const fn english() -> Self {
+        Self {
+            language: "en",
+            line: "line",
+            lines: "lines",
+            untitled: "Untitled",
+        }
+    }
+
+    /// The labels for a language, given as an ISO 639-1 code (`de`) or a locale name
+    /// (`de_DE.UTF-8`), if there are any for it
+    #[must_use]
+    pub fn for_language(language: &str) -> Option<Self> {
+        let code = language
+            .split(['_', '-', '.', '@'])
+            .next()?
+            .to_ascii_lowercase();
+        LABELS
+            .iter()
+            .find(|labels| labels.language == code)
+            .copied()
+    }
+
+    /// The labels for the language of the locale set in the environment, going by the
+    /// `LC_ALL`, `LC_MESSAGES` and `LANG` variables in that order, if there are any for it
+    #[must_use]
+    pub fn from_environment() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|variable| env::var(variable).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| Self::for_language(&locale))
+    }
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod languages {
+        use super::*;
+
+        #[test]
+        fn languages_are_found_from_codes_and_locales() {
+            assert_eq!(Some(Labels::english()), Labels::for_language("en"));
+            assert_eq!(
+                Some("Zeile"),
+                Labels::for_language("de_DE.UTF-8").map(|labels| labels.line)
+            );
+            assert_eq!(
+                Some("ligne"),
+                Labels::for_language("FR-ca").map(|labels| labels.line)
+            );
+            assert_eq!(None, Labels::for_language("C"));
+            assert_eq!(None, Labels::for_language(""));
+        }
+    }
+}
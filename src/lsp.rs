@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::slice;
+
+use serde_json::{json, Value};
+
+use crate::backends::OutputFormat;
+use crate::document::{Document, OutlineEntry};
+use crate::options::{ParserOptions, RenderOptions};
+
+/// The kind of symbol headings are given as, which is what other Markdown language servers use
+const STRING_SYMBOL: u8 = 15;
+
+/// The severity diagnostics are published with
+const WARNING: u8 = 2;
+
+/// Error codes from JSON-RPC and the Language Server Protocol
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// Runs a minimal language server for Markdown, reading messages from `input` and writing them to
+/// `output` until the client asks it to exit or closes `input`. The server gives the outline of
+/// a document as its symbols, the diagnostics of `--check` as warnings, and a plain text preview
+/// of the block under the cursor on hover. Returns whether the client asked the server to shut
+/// down before it exited, which is when it should exit successfully
+///
+/// # Errors
+///
+/// Returns an error if reading from `input` or writing to `output` fails
+pub fn run(
+    mut input: impl BufRead,
+    mut output: impl Write,
+    parser_options: &ParserOptions,
+    render_options: &RenderOptions,
+) -> io::Result<bool> {
+    let mut server = Server::new(parser_options, render_options);
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match serde_json::from_slice(&body) {
+            Ok(message) => server.handle(&message),
+            Err(error) => vec![error_response(
+                &Value::Null,
+                PARSE_ERROR,
+                &error.to_string(),
+            )],
+        };
+        for reply in replies {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+    Ok(server.shut_down)
+}
+
+/// Reads the body of the next message, which is framed by a `Content-Length` header, or returns
+/// `None` at the end of `input`
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message without a `Content-Length` header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+fn error_response(id: &Value, code: i32, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// The state of the language server: the documents the client has open
+struct Server<'a> {
+    parser_options: &'a ParserOptions,
+    render_options: &'a RenderOptions,
+    /// The text of each open document, by URI
+    documents: HashMap<String, String>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl<'a> Server<'a> {
+    fn new(parser_options: &'a ParserOptions, render_options: &'a RenderOptions) -> Self {
+        Self {
+            parser_options,
+            render_options,
+            documents: HashMap::new(),
+            shut_down: false,
+            exited: false,
+        }
+    }
+
+    /// Handles a message from the client, returning the messages to send back
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let params = message.get("params").unwrap_or(&Value::Null);
+        let Some(id) = message.get("id") else {
+            return self.notify(method, params).into_iter().collect();
+        };
+
+        if self.shut_down {
+            return vec![error_response(
+                id,
+                INVALID_REQUEST,
+                "the server has shut down",
+            )];
+        }
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    // the whole text of a document is sent whenever it changes
+                    "textDocumentSync": 1,
+                    "documentSymbolProvider": true,
+                    "hoverProvider": true,
+                },
+                "serverInfo": { "name": "sani", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                self.shut_down = true;
+                Value::Null
+            }
+            "textDocument/documentSymbol" => self.symbols(params),
+            "textDocument/hover" => self.hover(params),
+            _ => {
+                return vec![error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("unsupported method `{method}`"),
+                )]
+            }
+        };
+        vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })]
+    }
+
+    /// Handles a notification from the client, returning the diagnostics to publish, if any
+    fn notify(&mut self, method: &str, params: &Value) -> Option<Value> {
+        if method == "exit" {
+            self.exited = true;
+            return None;
+        }
+        let uri = params.pointer("/textDocument/uri")?.as_str()?.to_owned();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.pointer("/textDocument/text")?.as_str()?;
+                self.documents.insert(uri.clone(), text.to_owned());
+            }
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges")?.as_array()?;
+                let text = changes.last()?.get("text")?.as_str()?;
+                self.documents.insert(uri.clone(), text.to_owned());
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return None,
+        }
+        Some(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": self.diagnostics(&uri) },
+        }))
+    }
+
+    /// The open document `params` refer to, along with its text
+    fn document(&self, params: &Value) -> Option<(&str, Document)> {
+        let text = self
+            .documents
+            .get(params.pointer("/textDocument/uri")?.as_str()?)?;
+        Some((
+            text,
+            Document::parse_with_options(text, self.parser_options),
+        ))
+    }
+
+    fn diagnostics(&self, uri: &str) -> Vec<Value> {
+        let Some(text) = self.documents.get(uri) else {
+            return Vec::new();
+        };
+        let document = Document::parse_with_options(text, self.parser_options);
+        crate::diagnostics::check(text, document.elements())
+            .into_iter()
+            .map(|diagnostic| {
+                json!({
+                    "range": range(text, &diagnostic.span),
+                    "severity": WARNING,
+                    "source": "sani",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect()
+    }
+
+    fn symbols(&self, params: &Value) -> Value {
+        let Some((text, document)) = self.document(params) else {
+            return Value::Null;
+        };
+        Value::Array(symbols(
+            text,
+            &document.outline(),
+            text.len(),
+            self.render_options.labels.untitled,
+        ))
+    }
+
+    /// A preview of the block under the cursor, rendered as plain text
+    fn hover(&self, params: &Value) -> Value {
+        let Some((text, document)) = self.document(params) else {
+            return Value::Null;
+        };
+        let Some(offset) = params
+            .get("position")
+            .and_then(|position| offset(text, position))
+        else {
+            return Value::Null;
+        };
+        let Some(element) = document.elements().iter().find(|element| {
+            let span = element.span();
+            span.start <= offset && offset <= span.end
+        }) else {
+            return Value::Null;
+        };
+
+        let preview = crate::render_elements(
+            slice::from_ref(element),
+            OutputFormat::Text,
+            self.render_options,
+        );
+        json!({
+            "contents": { "kind": "plaintext", "value": preview.trim_end() },
+            "range": range(text, &element.span()),
+        })
+    }
+}
+
+/// The symbols for the headings in `outline`, each taking in its section up to the next heading
+/// at the same level or higher (or `end`), and with the headings below it in its section as its
+/// children
+fn symbols(text: &str, outline: &[OutlineEntry], end: usize, untitled: &str) -> Vec<Value> {
+    let mut symbols = Vec::new();
+    let mut index = 0;
+    while let Some(entry) = outline.get(index) {
+        let next = outline
+            .iter()
+            .skip(index + 1)
+            .position(|other| other.level <= entry.level)
+            .map_or(outline.len(), |position| index + 1 + position);
+        let section_end = outline.get(next).map_or(end, |next| next.span.start);
+        let children = outline.get(index + 1..next).unwrap_or_default();
+        // clients refuse symbols without names
+        let name = if entry.text.is_empty() {
+            untitled
+        } else {
+            &entry.text
+        };
+        symbols.push(json!({
+            "name": name,
+            "kind": STRING_SYMBOL,
+            "range": range(text, &(entry.span.start..section_end)),
+            "selectionRange": range(text, &entry.span),
+            "children": self::symbols(text, children, section_end, untitled),
+        }));
+        index = next;
+    }
+    symbols
+}
+
+fn range(text: &str, span: &Range<usize>) -> Value {
+    json!({ "start": position(text, span.start), "end": position(text, span.end) })
+}
+
+/// The position of the byte `offset` in `text`, as the protocol gives them: a zero-based line,
+/// and a column counted in UTF-16 code units
+fn position(text: &str, offset: usize) -> Value {
+    let before = text.get(..offset).unwrap_or(text);
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let character: usize = before
+        .get(line_start..)
+        .unwrap_or_default()
+        .chars()
+        .map(char::len_utf16)
+        .sum();
+    json!({ "line": before.matches('\n').count(), "character": character })
+}
+
+/// The byte offset in `text` of a position from the protocol, which is clamped to the end of its
+/// line
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = usize::try_from(position.get("line")?.as_u64()?).ok()?;
+    let character = usize::try_from(position.get("character")?.as_u64()?).ok()?;
+    let line_start = match line.checked_sub(1) {
+        None => 0,
+        Some(newlines) => text.match_indices('\n').nth(newlines)?.0 + 1,
+    };
+
+    let mut units = 0;
+    for (index, c) in text.get(line_start..)?.char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + index);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(server: &mut Server, text: &str) -> Vec<Value> {
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///lorem.md", "text": text } },
+        }))
+    }
+
+    fn request(server: &mut Server, method: &str, params: &Value) -> Value {
+        let mut replies = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }));
+        replies
+            .pop()
+            .unwrap()
+            .pointer_mut("/result")
+            .unwrap()
+            .take()
+    }
+
+    mod protocol {
+        use super::*;
+
+        #[test]
+        fn positions_count_utf16_code_units() {
+            let text = "lorem\n𝄞 ipsum";
+            assert_eq!(json!({ "line": 1, "character": 3 }), position(text, 11));
+            assert_eq!(
+                Some(11),
+                offset(text, &json!({ "line": 1, "character": 3 }))
+            );
+            assert_eq!(Some(5), offset(text, &json!({ "line": 0, "character": 9 })));
+            assert_eq!(None, offset(text, &json!({ "line": 2, "character": 0 })));
+        }
+
+        #[test]
+        fn messages_are_framed_by_their_length() {
+            let mut output = Vec::new();
+            write_message(&mut output, &json!({ "id": 1 })).unwrap();
+            assert_eq!(b"Content-Length: 8\r\n\r\n{\"id\":1}", output.as_slice());
+            assert_eq!(
+                Some(b"{\"id\":1}".to_vec()),
+                read_message(&mut output.as_slice()).unwrap()
+            );
+            assert_eq!(None, read_message(&mut "".as_bytes()).unwrap());
+        }
+    }
+
+    mod features {
+        use super::*;
+
+        #[test]
+        fn headings_are_nested_symbols() {
+            let (parser_options, render_options) = (ParserOptions::new(), RenderOptions::new());
+            let mut server = Server::new(&parser_options, &render_options);
+            open(&mut server, "# lorem\n\n## ipsum\n\ndolor\n\n# sit");
+            let symbols = request(
+                &mut server,
+                "textDocument/documentSymbol",
+                &json!({ "textDocument": { "uri": "file:///lorem.md" } }),
+            );
+            assert_eq!(2, symbols.as_array().unwrap().len());
+            assert_eq!(Some(&json!("lorem")), symbols.pointer("/0/name"));
+            assert_eq!(Some(&json!("ipsum")), symbols.pointer("/0/children/0/name"));
+            assert_eq!(
+                Some(&json!({ "line": 6, "character": 0 })),
+                symbols.pointer("/0/children/0/range/end")
+            );
+        }
+
+        #[test]
+        fn diagnostics_are_published_and_blocks_previewed() {
+            let (parser_options, render_options) = (ParserOptions::new(), RenderOptions::new());
+            let mut server = Server::new(&parser_options, &render_options);
+            let published = open(&mut server, "# lorem\n\n### *ipsum*");
+            let diagnostics = published.first().unwrap().pointer("/params/diagnostics");
+            assert_eq!(1, diagnostics.and_then(Value::as_array).unwrap().len());
+            assert_eq!(
+                Some(&json!(2)),
+                diagnostics.and_then(|diagnostics| diagnostics.pointer("/0/range/start/line"))
+            );
+
+            let hover = request(
+                &mut server,
+                "textDocument/hover",
+                &json!({
+                    "textDocument": { "uri": "file:///lorem.md" },
+                    "position": { "line": 2, "character": 6 },
+                }),
+            );
+            let preview = hover.pointer("/contents/value").and_then(Value::as_str);
+            assert_eq!(Some("ipsum"), preview.map(str::trim));
+        }
+    }
+}
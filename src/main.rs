@@ -1,22 +1,1161 @@
-use clap::{arg, command};
+use clap::{arg, command, value_parser, ArgAction, ArgMatches, Command};
+use ignore::{DirEntry, WalkBuilder};
+use regex::Regex;
+use sani::annotations::{Annotation, SidecarFormat};
+use sani::backends::OutputFormat;
+use sani::document::Document;
+use sani::formatting::{ColourDepth, SgrStyle};
+use sani::include::IncludeError;
+use sani::locale::Labels;
+use sani::markdown::{Boundary, DocumentElement};
+use sani::options::{
+    Dialect, InputFormat, Invisibles, ParserOptions, RenderOptions, TableOverflow,
+};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use sani::profile::Profile;
+use sani::report::Report;
+use sani::stream::BlockStream;
+use sani::terminal;
+use sani::theme::{Glyphs, Theme};
+use sani::variables::Variables;
+use std::cell::OnceCell;
+use std::convert::Infallible;
 use std::env;
 use std::fs;
-use std::process;
+use std::io::{self, BufRead, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use tracing::Level;
+
+/// The exit status when `--check` finds likely mistakes. Every other status is one of those of
+/// `sysexits.h`, as listed in [`EXIT_STATUS`]
+const FINDINGS: exitcode::ExitCode = 1;
+
+/// The extensions of the files rendered when given a directory
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "md",
+    "markdown",
+    "rst",
+    "org",
+    #[cfg(feature = "asciidoc")]
+    "adoc",
+    #[cfg(feature = "asciidoc")]
+    "asciidoc",
+];
+
+/// The exit statuses sani uses, which scripts can rely on
+const EXIT_STATUS: &str = "\
+Exit status:
+  0   Success
+  1   `--check` found likely mistakes in a document
+  64  The arguments were wrong
+  65  Input couldn't be used, such as an annotations file that doesn't parse or a file that includes itself
+  66  A file couldn't be read
+  69  A URL couldn't be fetched, or a port listened on
+  73  An output file or directory couldn't be written
+  74  Reading or writing failed otherwise";
 
 fn main() {
-    let matches = command!()
-        .arg(arg!(<file> "The file to render"))
-        .get_matches();
-
-    if let Some(file) = matches.get_one::<String>("file") {
-        let Ok(contents) = fs::read_to_string(file) else {
-            eprintln!("unable to read file `{file}`");
-            process::exit(exitcode::UNAVAILABLE);
+    let matches = command().try_get_matches().unwrap_or_else(|error| {
+        // asking for help or the version is reported as an error too, but isn't one
+        let status = if error.use_stderr() {
+            exitcode::USAGE
+        } else {
+            exitcode::OK
         };
-        let parsed = sani::parse(&contents);
-        let render = sani::render(parsed);
+        let _ = error.print();
+        process::exit(status);
+    });
+    init_tracing(&matches);
+    let (matches, debug) = match matches.subcommand() {
+        Some(("debug", debug_matches)) => (debug_matches, true),
+        Some(("split", split_matches)) => {
+            if let Some(path) = split_matches.get_one::<String>("file") {
+                split(Path::new(path), split_matches);
+            }
+            return;
+        }
+        Some(("cat", cat_matches)) => {
+            cat(cat_matches);
+            return;
+        }
+        Some(("lsp", lsp_matches)) => {
+            lsp(lsp_matches);
+            return;
+        }
+        Some(("serve", serve_matches)) => {
+            if let Some(path) = serve_matches.get_one::<String>("file") {
+                serve(Path::new(path), serve_matches);
+            }
+            return;
+        }
+        _ => (&matches, false),
+    };
 
-        println!("{render}");
+    if !debug && matches.get_flag("follow") {
+        follow(matches);
+    } else if let Some(path) = matches.get_one::<String>("file") {
+        run(Path::new(path), matches, debug);
     }
     // note: `clap` will handle the case that no input file was passed in
 }
+
+/// The command-line interface
+fn command() -> Command {
+    let command = command!()
+        .after_help(EXIT_STATUS)
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .arg(arg!([file] "The file to render, a directory to render every Markdown file in, or (if built with the `net` feature) an HTTP(S) URL").required_unless_present("follow"))
+        .arg(
+            arg!(--"number-headings" "Prefix headings with hierarchical section numbers")
+                .global(true),
+        )
+        .arg(
+            arg!(--ascii "Draw decorations such as bullets and rules with plain ASCII characters")
+                .global(true),
+        )
+        .arg(
+            arg!(-w --width <COLUMNS> "Wrap output to the given number of columns")
+                .value_parser(value_parser!(usize))
+                .global(true),
+        )
+        .arg(
+            arg!(--"theme-mode" <MODE> "Use the theme for a light or dark terminal background, or detect which to use")
+                .value_parser(["light", "dark", "auto"])
+                .default_value("auto")
+                .global(true),
+        )
+        .arg(
+            arg!(--"table-overflow" <STRATEGY> "How to fit tables wider than the output: wrap their cells, truncate them, stack each row as a list of `header: content` lines, or wrap unless the columns would get too narrow, and stack otherwise")
+                .value_parser(["auto", "wrap", "truncate", "stack"])
+                .default_value("auto")
+                .global(true),
+        )
+        .arg(
+            arg!(--lang <LANGUAGE> "The language of generated text, such as the labels of annotations, as an ISO 639-1 code; defaults to the locale's. Books record it as the document's language, unless its front matter gives one")
+                .value_parser(|language: &str| {
+                    Labels::for_language(language).ok_or("not one of en, de, es, fr, it, nl or pt")
+                })
+                .global(true),
+        )
+        .arg(arg!(--"show-comments" "Show HTML comments on lines of their own, dimmed, rather than hiding them").global(true))
+        .arg(
+            arg!(--"highlight-across-lines" "Find matches of the pattern to highlight before wrapping, so that matches broken across lines stay highlighted")
+                .global(true),
+        )
+        .arg(
+            arg!(--"highlight-pattern" <REGEX> "Highlight every match of the given regular expression in the output in reverse video")
+                .value_parser(Regex::new)
+                .global(true),
+        )
+        .arg(
+            arg!(--follow "Render standard input as it arrives, such as from `tail -f`, showing each block as soon as the blank line after it does")
+                .conflicts_with_all(["file", "check", "output", "format", "a11y", "max-lines"]),
+        )
+        .arg(arg!(--check "Only check the document for likely mistakes, exiting with an error if there are any").conflicts_with("output"))
+        .arg(arg!(-o --output <PATH> "Write the output to a file, in the format given by its extension (.html, .txt, .tex, .epub, .jsonl for events, .json for pandoc, .pdf, .svg, or a man page section such as .1)"))
+        .arg(
+            arg!(--format <FORMAT> "The format to write the output in, rather than going by the output file's extension")
+                .value_parser([
+                    "terminal",
+                    "text",
+                    "html",
+                    "man",
+                    "latex",
+                    "epub",
+                    "events",
+                    "pandoc-json",
+                    #[cfg(feature = "pdf")]
+                    "pdf",
+                    #[cfg(feature = "svg")]
+                    "svg",
+                    "speech",
+                ])
+                .conflicts_with("check"),
+        )
+        .arg(arg!(--a11y "Write output for screen readers and speech synthesis, saying visual cues such as emphasis, links and quotes in words (the same as `--format speech`)").conflicts_with_all(["check", "format"]))
+        .arg(
+            arg!(--preview "Render for a preview pane, such as fzf's, taking the width from FZF_PREVIEW_COLUMNS (or COLUMNS) and never querying the terminal")
+                .conflicts_with_all(["check", "output"]),
+        )
+        .arg(
+            arg!(--annotations <PATH> "Show the review comments in a JSON or TOML sidecar file (by its extension) as callouts after the lines they are on")
+                .conflicts_with_all(["check", "output", "format"]),
+        );
+    let command = styling_args(code_block_args(parsing_args(excerpt_args(command))));
+    let command = subcommands(reporting_args(command));
+    #[cfg(feature = "hyphenation")]
+    let command = command.arg(arg!(
+        --hyphenate <LANGUAGE> "Hyphenate wrapped words, using the rules of the given language (ISO 639-1 code)"
+    ).global(true));
+    #[cfg(feature = "bidi")]
+    let command = command.arg(
+        arg!(--bidi "Write right-to-left and bidirectional text in the order it is shown in, and right-align right-to-left paragraphs, for terminals that don't reorder text themselves")
+            .global(true),
+    );
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    let command = command.arg(arg!(--profile "Report the time spent parsing and rendering each kind of block, and the slowest blocks with the lines they are on, on standard error").conflicts_with("check"));
+    command
+}
+
+/// Adds the arguments choosing which part of a document is rendered to the command-line interface
+fn excerpt_args(command: Command) -> Command {
+    command
+        .arg(arg!(--summary "Only render headings and the first paragraph under each, marking anything left out with an ellipsis").conflicts_with("check"))
+        .arg(
+            arg!(--from <START> "Only render from the given heading (such as `## Usage`, or just `Usage` at any level) or line number onwards")
+                .value_parser(|boundary: &str| Ok::<_, Infallible>(Boundary::parse(boundary)))
+                .conflicts_with("check"),
+        )
+        .arg(
+            arg!(--to <END> "Only render up to the given heading, leaving it out, or up to and including the given line number")
+                .value_parser(|boundary: &str| Ok::<_, Infallible>(Boundary::parse(boundary)))
+                .conflicts_with("check"),
+        )
+        .arg(
+            arg!(--"max-lines" <LINES> "Only render the first given number of lines, followed by an ellipsis if anything was cut off")
+                .value_parser(value_parser!(usize))
+                .conflicts_with_all(["check", "output"]),
+        )
+}
+
+/// Adds the subcommands to the command-line interface
+fn subcommands(command: Command) -> Command {
+    command
+        .subcommand(
+            Command::new("debug")
+                .about("Print the parsed document tree and an escaped view of the rendered output")
+                .arg(arg!(<file> "The file to debug")),
+        )
+        .subcommand(
+            Command::new("cat")
+                .about("Join documents into one Markdown document, keeping links to their headings working")
+                .arg(arg!(<files>... "The files to join, in order"))
+                .arg(
+                    arg!(--demote <LEVELS> "Move headings down the given number of levels")
+                        .value_parser(value_parser!(u8))
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            Command::new("split")
+                .about("Write each top-level section of a document to a Markdown file of its own, named from its heading")
+                .arg(arg!(<file> "The file to split"))
+                .arg(arg!(--out <DIRECTORY> "The directory to write the sections to").required(true)),
+        )
+        .subcommand(
+            Command::new("lsp").about("Run a language server for Markdown over standard input and output, giving outlines, diagnostics and previews on hover"),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve a live preview of a document as HTML on localhost, which reloads whenever the file changes")
+                .arg(arg!(<file> "The file to preview"))
+                .arg(
+                    arg!(-p --port <PORT> "The port to listen on")
+                        .value_parser(value_parser!(u16))
+                        .default_value("8000"),
+                ),
+        )
+}
+
+/// Adds the arguments controlling how formatting is written for the terminal to the
+/// command-line interface
+fn styling_args(command: Command) -> Command {
+    command
+        .arg(
+            arg!(--"separate-sgr" "Write each formatting code in an escape sequence of its own, for terminals that mishandle sequences that combine them")
+                .global(true),
+        )
+        .arg(
+            arg!(--"sgr-reset" "End formatting with a single reset code at the end of each line and block, for the most compatible output")
+                .global(true),
+        )
+        .arg(
+            arg!(--"colour-depth" <COLOURS> "How many colours the terminal can show, colours beyond which are swapped for the nearest it can; detected from COLORTERM and TERM by default")
+                .value_parser(["16", "256", "24bit", "auto"])
+                .default_value("auto")
+                .global(true),
+        )
+}
+
+/// Adds the arguments controlling what is printed besides the output to the command-line
+/// interface
+fn reporting_args(command: Command) -> Command {
+    command
+        .arg(
+            arg!(-q --quiet "Print nothing but errors: no warnings, no findings from `--check` and no output to standard output, leaving the exit status to tell what happened")
+                .global(true),
+        )
+        .arg(
+            arg!(-v --verbose... "Log how the document is parsed and rendered to standard error, such as where blocks start and end and how tables are fitted; twice for every delimiter matched too")
+                .conflicts_with("quiet")
+                .global(true),
+        )
+}
+
+/// Adds the arguments controlling how documents are read and parsed to the command-line interface
+fn parsing_args(command: Command) -> Command {
+    let command = command
+        .arg(
+            arg!(--abbreviations "Underline abbreviations defined with `*[ABBR]: expansion` lines, and expand them on first use")
+                .global(true),
+        )
+        .arg(
+            arg!(--entities "Decode HTML entities and numeric character references, such as `&amp;`")
+                .global(true),
+        )
+        .arg(
+            arg!(--input <FORMAT> "The markup language documents are written in, rather than going by their extensions (`.rst` for reStructuredText, `.org` for Org, `.adoc` for AsciiDoc if built with the `asciidoc` feature, and Markdown otherwise), where `pandoc-json` is pandoc's JSON document model, as written by `pandoc -t json`")
+                .value_parser([
+                    "markdown",
+                    "rst",
+                    #[cfg(feature = "asciidoc")]
+                    "asciidoc",
+                    "org",
+                    "pandoc-json",
+                ])
+                .global(true),
+        )
+        .arg(
+            arg!(--dialect <DIALECT> "The flavour of Markdown to parse, where `gfm` (GitHub Flavored Markdown) also makes links of bare URLs and email addresses, and allows strikethrough with a single tilde, and `obsidian` has wiki links, embeds of other notes (`![[Page]]`), `%%comments%%` and callouts (`> [!tip] Title`)")
+                .value_parser(["sani", "gfm", "obsidian"])
+                .default_value("sani")
+                .global(true),
+        )
+        .arg(
+            arg!(--"show-invisibles" "Show zero-width and other invisible characters as their code points, such as `<U+200B>`")
+                .global(true),
+        )
+        .arg(
+            arg!(--"remove-invisibles" "Remove zero-width and other invisible characters before parsing, so they can't get in the way of formatting")
+                .conflicts_with("show-invisibles")
+                .global(true),
+        )
+        .arg(
+            arg!(--"wiki-links" "Make links of `[[Page]]` and `[[Page|label]]` wiki links, which link to the page name as it is written")
+                .global(true),
+        )
+        .arg(
+            arg!(--"wiki-link-url" <TEMPLATE> "Link wiki links to the URL or path given, with `{page}` in place of the page name, such as `https://wiki.example.com/{page}`; implies `--wiki-links`")
+                .global(true),
+        )
+        .arg(
+            arg!(--variables "Fill in `{{name}}` placeholders from the front matter (`key: value` lines between `---` lines) and environment variables, leaving the front matter out")
+                .global(true),
+        )
+        .arg(
+            arg!(--define <DEFINITION> "Fill in `{{name}}` placeholders with a value given as `name=value`, overriding any from the front matter or environment; implies `--variables`")
+                .value_parser(|definition: &str| {
+                    definition
+                        .split_once('=')
+                        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                        .ok_or("expected `name=value`")
+                })
+                .action(ArgAction::Append)
+                .global(true),
+        )
+        .arg(
+            arg!(--"no-includes" "Leave `@include(path)` directives as they are, rather than reading the files they name")
+                .global(true),
+        )
+        .arg(
+            arg!(--rev <REVISION> "Render the file as it was at the given git revision (such as a commit, branch or tag), leaving `@include(path)` directives as they are")
+                // git would take a revision starting with `-` as one of its own options
+                .value_parser(|revision: &str| {
+                    if revision.starts_with('-') {
+                        Err("revisions can't start with `-`")
+                    } else {
+                        Ok(revision.to_owned())
+                    }
+                })
+                .global(true),
+        );
+    #[cfg(feature = "notebook")]
+    let command = command.arg(
+        arg!(--"notebook-outputs" "Show the outputs of the code cells of Jupyter notebooks (`.ipynb` files) after them, as far as they are text")
+            .global(true),
+    );
+    #[cfg(feature = "encoding")]
+    let command = command.arg(
+        arg!(--encoding <ENCODING> "Read files in the given encoding (such as `utf-16le` or `latin1`), rather than guessing it")
+            .value_parser(|label: &str| {
+                sani::input::Encoding::for_label(label.as_bytes()).ok_or("unknown encoding")
+            })
+            .global(true),
+    );
+    command
+}
+
+/// Adds the arguments controlling how code blocks are shown to the command-line interface
+fn code_block_args(command: Command) -> Command {
+    let command = command
+        .arg(arg!(--"line-numbers" "Number the lines of code blocks").global(true))
+        .arg(
+            arg!(--"code-plain" "Show code blocks without indentation, line numbers or highlighting, and without wrapping them, so they can be copied cleanly")
+                .global(true),
+        );
+    #[cfg(feature = "diagrams")]
+    let command = command.arg(
+        arg!(--diagrams "Draw mermaid and graphviz code blocks as text art, if mermaid-ascii and graph-easy are installed")
+            .global(true),
+    );
+    command
+}
+
+/// Renders (or debugs, or checks) the file or directory at `path`, as the arguments ask
+fn run(path: &Path, matches: &ArgMatches, debug: bool) {
+    let is_directory = path.is_dir();
+    if is_directory && matches.contains_id("rev") {
+        fail(
+            &Report::error("`--rev` can only be used to render a single file"),
+            exitcode::USAGE,
+        );
+    }
+    if is_directory && matches.contains_id("annotations") {
+        fail(
+            &Report::error("`--annotations` can only be used to render a single file"),
+            exitcode::USAGE,
+        );
+    }
+    let files = if is_directory {
+        markdown_files(path)
+    } else {
+        vec![path.to_owned()]
+    };
+
+    // only worked out when needed, as detecting the theme can mean querying the terminal
+    let options = OnceCell::new();
+    let options = || options.get_or_init(|| render_options(matches));
+    // the `debug` subcommand doesn't take an output file
+    let output = matches.try_get_one::<String>("output").ok().flatten();
+    let a11y = matches.try_get_one::<bool>("a11y").ok().flatten() == Some(&true);
+    let format = match matches.try_get_one::<String>("format").ok().flatten() {
+        _ if a11y => OutputFormat::Speech,
+        Some(format) => match format.as_str() {
+            "text" => OutputFormat::Text,
+            "html" => OutputFormat::Html,
+            "man" => OutputFormat::Man(1),
+            "latex" => OutputFormat::Latex,
+            "epub" => OutputFormat::Epub,
+            "events" => OutputFormat::Events,
+            "pandoc-json" => OutputFormat::PandocJson,
+            "speech" => OutputFormat::Speech,
+            #[cfg(feature = "pdf")]
+            "pdf" => OutputFormat::Pdf,
+            #[cfg(feature = "svg")]
+            "svg" => OutputFormat::Svg,
+            _ => OutputFormat::Terminal,
+        },
+        None => output
+            .map_or(Ok(OutputFormat::Terminal), |path| {
+                OutputFormat::from_path(Path::new(path))
+            })
+            .unwrap_or_else(|error| fail(&Report::error(error.to_string()), exitcode::USAGE)),
+    };
+    if format.is_binary() && (output.is_none() || is_directory) {
+        fail(
+            &Report::error(
+                "binary output formats can only be written for a single file, with `--output`",
+            ),
+            exitcode::USAGE,
+        );
+    }
+
+    let annotations = matches
+        .try_get_one::<String>("annotations")
+        .ok()
+        .flatten()
+        .map(|path| read_annotations(Path::new(path)));
+
+    let mut render = Vec::new();
+    let mut has_diagnostics = false;
+    for file in &files {
+        let (contents, parsed) = parse_file(file, matches);
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        if matches.try_get_one::<bool>("profile").ok().flatten() == Some(&true) {
+            let parser_options = parser_options(matches).input_format(input_format(file, matches));
+            let profile = Profile::measure(&contents, &parser_options, options());
+            eprintln!("{}: {profile}", file.display());
+        }
+        if debug {
+            print_debug(&parsed, options());
+        } else if matches.get_flag("check") {
+            has_diagnostics |= check(&file.display().to_string(), &contents, &parsed, matches);
+        } else {
+            if is_directory && format == OutputFormat::Terminal {
+                let banner = sani::markdown::render_banner(&file.display().to_string(), options());
+                render.extend(banner.bytes().chain(*b"\n\n"));
+            }
+            render.extend(render_file(
+                matches,
+                &contents,
+                parsed,
+                format,
+                annotations.as_deref(),
+                options(),
+            ));
+        }
+    }
+
+    if has_diagnostics {
+        process::exit(FINDINGS);
+    }
+    if !debug && !matches.get_flag("check") {
+        if let Some(max_lines) = matches.get_one::<usize>("max-lines") {
+            render = sani::truncate_lines(&String::from_utf8_lossy(&render), *max_lines, options())
+                .into_bytes();
+        }
+        if output.is_some() || !is_quiet(matches) {
+            write_output(output, &render);
+        }
+    }
+}
+
+fn render_options(matches: &ArgMatches) -> RenderOptions {
+    let theme = match matches.get_one::<String>("theme-mode").map(String::as_str) {
+        Some("light") => Theme::light(),
+        Some("dark") => Theme::dark(),
+        // a preview pane's output isn't shown until sani exits, so there's no waiting on a reply
+        // from the terminal
+        _ if is_preview(matches) => {
+            terminal::background_from_environment().map_or_else(Theme::dark, Theme::for_background)
+        }
+        _ => terminal::detect_background().map_or_else(Theme::dark, Theme::for_background),
+    };
+
+    let theme = if matches.get_flag("ascii") {
+        theme.glyphs(Glyphs::ascii())
+    } else {
+        theme
+    };
+
+    let colour_depth = match matches
+        .get_one::<String>("colour-depth")
+        .map(String::as_str)
+    {
+        Some("16") => ColourDepth::Standard,
+        Some("256") => ColourDepth::Indexed,
+        Some("24bit") => ColourDepth::TrueColour,
+        _ => terminal::colour_depth_from_environment(),
+    };
+
+    let mut options = RenderOptions::new().colour_depth(colour_depth).theme(theme);
+    if matches.get_flag("separate-sgr") {
+        options = options.sgr_style(SgrStyle::Separate);
+    }
+    options = options
+        .resets(matches.get_flag("sgr-reset"))
+        .highlight_across_lines(matches.get_flag("highlight-across-lines"))
+        .show_comments(matches.get_flag("show-comments"))
+        .labels(
+            matches
+                .get_one::<Labels>("lang")
+                .copied()
+                .or_else(Labels::from_environment)
+                .unwrap_or_default(),
+        );
+    if let Some(labels) = matches.get_one::<Labels>("lang") {
+        options = options.language(labels.language);
+    }
+    if let Some(width) = matches.get_one::<usize>("width") {
+        options = options.width(*width);
+    } else if let Some(width) = is_preview(matches).then(preview_width).flatten() {
+        options = options.width(width);
+    }
+    let table_overflow = match matches
+        .get_one::<String>("table-overflow")
+        .map(String::as_str)
+    {
+        Some("wrap") => TableOverflow::Wrap,
+        Some("truncate") => TableOverflow::Truncate,
+        Some("stack") => TableOverflow::Stack,
+        _ => TableOverflow::Auto,
+    };
+    options = options
+        .table_overflow(table_overflow)
+        .line_numbers(matches.get_flag("line-numbers"))
+        .plain_code_blocks(matches.get_flag("code-plain"));
+    if let Some(template) = matches.get_one::<String>("wiki-link-url") {
+        let template = template.clone();
+        // bare URLs and email addresses are always given a scheme, which page names don't have
+        options = options.link_resolver(move |target| {
+            if target.contains("://") || target.starts_with("mailto:") {
+                target.to_owned()
+            } else {
+                template.replace("{page}", &target.replace(' ', "%20"))
+            }
+        });
+    }
+    if let Some(pattern) = matches.get_one::<Regex>("highlight-pattern") {
+        options = options.highlight(pattern.clone());
+    }
+    #[cfg(feature = "diagrams")]
+    {
+        options = options.draw_diagrams(matches.get_flag("diagrams"));
+    }
+    #[cfg(feature = "hyphenation")]
+    if let Some(language) = matches.get_one::<String>("hyphenate") {
+        let Some(lang) = <[u8; 2]>::try_from(language.as_bytes())
+            .ok()
+            .and_then(sani::options::Lang::from_iso)
+        else {
+            fail(
+                &Report::error(format!(
+                    "no hyphenation rules available for language `{language}`"
+                )),
+                exitcode::USAGE,
+            );
+        };
+        options = options.hyphenate(lang);
+    }
+    #[cfg(feature = "bidi")]
+    {
+        options = options.bidi(matches.get_flag("bidi"));
+    }
+
+    options
+}
+
+/// Whether `--preview` was given, which the `debug` subcommand doesn't take
+fn is_preview(matches: &ArgMatches) -> bool {
+    matches
+        .try_get_one::<bool>("preview")
+        .ok()
+        .flatten()
+        .is_some_and(|preview| *preview)
+}
+
+/// The width of the preview pane, as given by fzf or, failing that, the shell
+fn preview_width() -> Option<usize> {
+    ["FZF_PREVIEW_COLUMNS", "COLUMNS"]
+        .into_iter()
+        .find_map(|variable| env::var(variable).ok()?.parse().ok())
+}
+
+/// Logs the decisions made in parsing and rendering to standard error, in as much detail as
+/// `--verbose` asks for
+fn init_tracing(matches: &ArgMatches) {
+    let level = match matches.get_count("verbose") {
+        0 => return,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_max_level(level)
+        .without_time()
+        .with_target(false)
+        .init();
+}
+
+/// Prints `report` and exits with `status`
+fn fail(report: &Report, status: exitcode::ExitCode) -> ! {
+    eprintln!("{report}");
+    process::exit(status);
+}
+
+/// Whether `--quiet` was given, so that nothing but errors should be printed
+fn is_quiet(matches: &ArgMatches) -> bool {
+    matches.get_flag("quiet")
+}
+
+/// Reports any diagnostics for the parsed document, unless the arguments ask for quiet, returning
+/// whether there were any
+fn check(
+    file: &str,
+    contents: &str,
+    elements: &[Box<dyn DocumentElement>],
+    matches: &ArgMatches,
+) -> bool {
+    let diagnostics = sani::diagnostics::check(contents, elements);
+    if is_quiet(matches) {
+        return !diagnostics.is_empty();
+    }
+    for diagnostic in &diagnostics {
+        eprintln!("{}\n", diagnostic.report(file, contents));
+    }
+    !diagnostics.is_empty()
+}
+
+/// The documents in `directory` and its subdirectories, going by [`DOCUMENT_EXTENSIONS`] (and
+/// Jupyter notebooks, with the `notebook` feature), in order of their paths. Hidden files and anything ignored by a
+/// `.gitignore` file are left out
+fn markdown_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = WalkBuilder::new(directory)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(DirEntry::into_path)
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| DOCUMENT_EXTENSIONS.contains(&extension))
+                || is_notebook(path)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Reads and parses `file` as the arguments ask, returning its contents (with any includes
+/// expanded) along with the parsed elements
+fn parse_file(file: &Path, matches: &ArgMatches) -> (String, Vec<Box<dyn DocumentElement>>) {
+    let contents = contents(file, matches);
+    let parsed = parse(&contents, file, matches);
+    (contents, parsed)
+}
+
+/// Parses `contents`, read from `file`, as the arguments ask
+fn parse(contents: &str, file: &Path, matches: &ArgMatches) -> Vec<Box<dyn DocumentElement>> {
+    let parser_options = parser_options(matches).input_format(input_format(file, matches));
+    let mut parsed = match variables(matches) {
+        Some(variables) => sani::parse_with_variables(contents, &parser_options, &variables),
+        None => sani::parse_with_options(contents, &parser_options),
+    };
+    if matches.get_flag("number-headings") {
+        sani::markdown::number_headings(&mut parsed);
+    }
+    parsed
+}
+
+/// Why a document couldn't be read, and the status to exit with for it
+type ReadError = (Report<'static>, exitcode::ExitCode);
+
+/// Reads `file` as the arguments ask, with any includes expanded
+fn contents(file: &Path, matches: &ArgMatches) -> String {
+    try_contents(file, matches).unwrap_or_else(|(report, status)| fail(&report, status))
+}
+
+/// Reads `file` as [`contents`] does, returning why it couldn't rather than exiting, for the
+/// preview server, which shows the error and carries on
+fn try_contents(file: &Path, matches: &ArgMatches) -> Result<String, ReadError> {
+    let (contents, is_local) = read_file(file, matches)?;
+    if !is_local || matches.get_flag("no-includes") {
+        return Ok(contents);
+    }
+    let dialect = matches.get_one::<String>("dialect").map(String::as_str);
+    let expanded = if dialect == Some("obsidian") {
+        sani::include::expand_embeds(&contents, file)
+    } else {
+        sani::include::expand_includes(&contents, file)
+    };
+    expanded.map_err(|error| {
+        let status = match error {
+            IncludeError::Io { .. } => exitcode::NOINPUT,
+            IncludeError::Cycle { .. } => exitcode::DATAERR,
+        };
+        (Report::error(error.to_string()), status)
+    })
+}
+
+/// Reads `file` from the server if it is a URL, from git if the arguments give a revision, or from
+/// the working tree otherwise, returning its contents along with whether it was read from the
+/// working tree, which is where included files would be read from
+fn read_file(file: &Path, matches: &ArgMatches) -> Result<(String, bool), ReadError> {
+    let revision = matches.get_one::<String>("rev");
+    let fetched = fetch_url(file).transpose()?;
+    let is_local = fetched.is_none() && revision.is_none();
+    let contents = fetched
+        .or_else(|| match revision {
+            Some(revision) => git_show(file, revision),
+            None => fs::read(file).ok(),
+        })
+        .map(|bytes| decode(file, &bytes, matches));
+    let Some(contents) = contents else {
+        let report = Report::error(format!("unable to read file `{}`", file.display()));
+        return Err((report, exitcode::NOINPUT));
+    };
+    let contents = if is_pandoc_json(matches) {
+        sani::pandoc::to_markdown(&contents).map_err(|error| {
+            let report = Report::error(format!("`{}` is {error}", file.display()));
+            (report, exitcode::DATAERR)
+        })?
+    } else {
+        from_notebook(file, contents, matches)?
+    };
+    Ok((contents, is_local))
+}
+
+/// Whether the arguments say documents are pandoc's JSON, which is turned into Markdown as it is
+/// read
+fn is_pandoc_json(matches: &ArgMatches) -> bool {
+    matches
+        .get_one::<String>("input")
+        .is_some_and(|input| input == "pandoc-json")
+}
+
+/// Turns `contents` into Markdown if `file` is a Jupyter notebook, going by its extension
+#[cfg(feature = "notebook")]
+fn from_notebook(file: &Path, contents: String, matches: &ArgMatches) -> Result<String, ReadError> {
+    if !is_notebook(file) {
+        return Ok(contents);
+    }
+    sani::notebook::to_markdown(&contents, matches.get_flag("notebook-outputs")).map_err(|error| {
+        let report = Report::error(format!("`{}` is an {error}", file.display()));
+        (report, exitcode::DATAERR)
+    })
+}
+
+#[cfg(not(feature = "notebook"))]
+#[allow(clippy::unnecessary_wraps)] // to match the version that reads notebooks
+fn from_notebook(
+    _file: &Path,
+    contents: String,
+    _matches: &ArgMatches,
+) -> Result<String, ReadError> {
+    Ok(contents)
+}
+
+/// Whether `file` is a Jupyter notebook, going by its extension
+fn is_notebook(file: &Path) -> bool {
+    cfg!(feature = "notebook")
+        && file
+            .extension()
+            .is_some_and(|extension| extension == "ipynb")
+}
+
+/// The options for parsing documents that the arguments give
+fn parser_options(matches: &ArgMatches) -> ParserOptions {
+    let dialect = match matches.get_one::<String>("dialect").map(String::as_str) {
+        Some("gfm") => Dialect::Gfm,
+        Some("obsidian") => Dialect::Obsidian,
+        _ => Dialect::Sani,
+    };
+    ParserOptions::new()
+        .dialect(dialect)
+        .entities(matches.get_flag("entities"))
+        .abbreviations(matches.get_flag("abbreviations"))
+        .wiki_links(
+            // links in pandoc's JSON are turned into wiki links
+            dialect == Dialect::Obsidian
+                || is_pandoc_json(matches)
+                || matches.get_flag("wiki-links")
+                || matches.contains_id("wiki-link-url"),
+        )
+        .invisibles(if matches.get_flag("show-invisibles") {
+            Invisibles::Show
+        } else if matches.get_flag("remove-invisibles") {
+            Invisibles::Remove
+        } else {
+            Invisibles::Keep
+        })
+}
+
+/// The markup language `file` is written in: the one the arguments give, or else the one its
+/// extension is for
+fn input_format(file: &Path, matches: &ArgMatches) -> InputFormat {
+    let format = matches
+        .get_one::<String>("input")
+        .map(String::as_str)
+        .or_else(|| file.extension()?.to_str());
+    match format {
+        Some("rst") => InputFormat::Rst,
+        Some("org") => InputFormat::Org,
+        #[cfg(feature = "asciidoc")]
+        Some("asciidoc" | "adoc") => InputFormat::AsciiDoc,
+        _ => InputFormat::Markdown,
+    }
+}
+
+/// Prints the files the arguments give joined into one document, with their headings moved down
+/// as many levels as the arguments ask
+fn cat(matches: &ArgMatches) {
+    let texts: Vec<_> = matches
+        .get_many::<String>("files")
+        .into_iter()
+        .flatten()
+        .map(|file| contents(Path::new(file), matches))
+        .collect();
+    let texts: Vec<_> = texts.iter().map(String::as_str).collect();
+    let demote = matches.get_one::<u8>("demote").copied().unwrap_or_default();
+    let joined = sani::document::concatenate(&texts, &parser_options(matches), demote);
+    if !is_quiet(matches) {
+        write_output(None, joined.as_bytes());
+    }
+}
+
+/// The values for placeholders that the arguments give, if they ask for placeholders to be filled
+/// in at all
+fn variables(matches: &ArgMatches) -> Option<Variables> {
+    let definitions = matches.get_many::<(String, String)>("define");
+    if definitions.is_none() && !matches.get_flag("variables") {
+        return None;
+    }
+    let variables = definitions
+        .into_iter()
+        .flatten()
+        .fold(Variables::new(), |variables, (name, value)| {
+            variables.define(name, value)
+        });
+    Some(variables.environment(true))
+}
+
+/// Writes each top-level section of `file` to a Markdown file of its own in the directory the
+/// arguments give, named from the slug of its heading, with its source text exactly as it was
+fn split(file: &Path, matches: &ArgMatches) {
+    let Some(directory) = matches.get_one::<String>("out").map(Path::new) else {
+        return;
+    };
+    let (contents, _) =
+        read_file(file, matches).unwrap_or_else(|(report, status)| fail(&report, status));
+    let document = Document::parse_with_options(&contents, &parser_options(matches));
+    if fs::create_dir_all(directory).is_err() {
+        fail(
+            &Report::error(format!(
+                "unable to create directory `{}`",
+                directory.display()
+            )),
+            exitcode::CANTCREAT,
+        );
+    }
+    for section in document.sections(&contents) {
+        let name = if section.slug.is_empty() {
+            "index"
+        } else {
+            &section.slug
+        };
+        let path = directory.join(format!("{name}.md"));
+        if fs::write(&path, section.text).is_err() {
+            fail(
+                &Report::error(format!("unable to write file `{}`", path.display())),
+                exitcode::CANTCREAT,
+            );
+        }
+    }
+}
+
+/// Runs the language server on standard input and output until the client asks it to exit
+fn lsp(matches: &ArgMatches) {
+    let shut_down = sani::lsp::run(
+        io::stdin().lock(),
+        io::stdout().lock(),
+        &parser_options(matches),
+        &render_options(matches),
+    )
+    .unwrap_or_else(|error| {
+        fail(&Report::error(error.to_string()), exitcode::IOERR);
+    });
+    if !shut_down {
+        // as the protocol asks of a server told to exit without being shut down first
+        process::exit(1);
+    }
+}
+
+/// Renders standard input a run of blocks at a time, as each is completed by a blank line, without
+/// rendering again what has already been shown
+fn follow(matches: &ArgMatches) {
+    let parser_options = parser_options(matches);
+    let options = render_options(matches);
+    let mut blocks = BlockStream::new();
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap_or_else(|error| {
+            fail(
+                &Report::error(format!("unable to read standard input: {error}")),
+                exitcode::IOERR,
+            );
+        });
+        let Some(text) = blocks.push_line(&line) else {
+            continue;
+        };
+        let render = sani::render(sani::parse_with_options(&text, &parser_options), &options);
+        if stdout
+            .write_all(render.as_bytes())
+            .and_then(|()| stdout.flush())
+            .is_err()
+        {
+            // whatever was reading the output has gone, such as a pager that was quit
+            return;
+        }
+    }
+    if let Some(text) = blocks.finish() {
+        let render = sani::render(sani::parse_with_options(&text, &parser_options), &options);
+        // there's nowhere left to report a failure to write to standard output
+        let _ = stdout.write_all(render.as_bytes());
+    }
+}
+
+/// Serves a live preview of `file` rendered as HTML on the port the arguments give, reading it
+/// afresh, as the other commands read it, for every request so that the page can reload as soon
+/// as it changes
+fn serve(file: &Path, matches: &ArgMatches) {
+    if matches.contains_id("rev") {
+        fail(
+            &Report::error("`--rev` can't be used with `serve`, which previews the working tree"),
+            exitcode::USAGE,
+        );
+    }
+    let port = matches.get_one::<u16>("port").copied().unwrap_or(8000);
+    // only local connections are accepted, as a preview is for whoever is editing the file
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|error| {
+        fail(
+            &Report::error(format!("unable to listen on port {port}: {error}")),
+            exitcode::UNAVAILABLE,
+        );
+    });
+    if !is_quiet(matches) {
+        eprintln!(
+            "serving a preview of `{}` at http://localhost:{port}/",
+            file.display()
+        );
+    }
+
+    let options = render_options(matches);
+    let source = || try_contents(file, matches).map_err(|(report, _)| report);
+    let render = |contents: &str| {
+        sani::render_as(parse(contents, file, matches), OutputFormat::Html, &options)
+    };
+    let title = file.file_name().unwrap_or_default().to_string_lossy();
+    sani::serve::serve(&listener, &title, source, render);
+}
+
+/// Downloads the document if `file` is actually an HTTP(S) URL, returning `None` if it isn't one
+#[cfg(feature = "net")]
+fn fetch_url(file: &Path) -> Option<Result<Vec<u8>, ReadError>> {
+    let url = file.to_str().filter(|file| sani::fetch::is_url(file))?;
+    Some(
+        sani::fetch::fetch(url)
+            .map_err(|error| (Report::error(error.to_string()), exitcode::UNAVAILABLE)),
+    )
+}
+
+#[cfg(not(feature = "net"))]
+const fn fetch_url(_file: &Path) -> Option<Result<Vec<u8>, ReadError>> {
+    None
+}
+
+/// Reads `file` as it was at `revision`, with `git show`. Any error from git is passed on
+fn git_show(file: &Path, revision: &str) -> Option<Vec<u8>> {
+    let directory = file
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = file.file_name()?.to_str()?;
+    let output = process::Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .arg("show")
+        .arg(format!("{revision}:./{name}"))
+        .stderr(Stdio::inherit())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(output.stdout)
+}
+
+/// Decodes the contents of `file`, replacing invalid UTF-8 with U+FFFD (`�`) and warning about it,
+/// so that files with mixed encodings still render
+#[cfg(not(feature = "encoding"))]
+fn decode(file: &Path, bytes: &[u8], matches: &ArgMatches) -> String {
+    let (text, error) = sani::input::decode_lossy(bytes);
+    if let Some(error) = error.filter(|_| !is_quiet(matches)) {
+        let report = Report::warning(format!("`{}` has {error}", file.display()))
+            .hint("invalid sequences are shown as `\u{fffd}`; build with the `encoding` feature to read files in other encodings");
+        eprintln!("{report}");
+    }
+    text.into_owned()
+}
+
+/// Decodes the contents of a file from the encoding the arguments give, or the one it seems to be
+/// in
+#[cfg(feature = "encoding")]
+fn decode(_file: &Path, bytes: &[u8], matches: &ArgMatches) -> String {
+    let encoding = matches
+        .get_one::<&'static sani::input::Encoding>("encoding")
+        .copied();
+    sani::input::transcode(bytes, encoding).into_owned()
+}
+
+/// Renders a document parsed from `contents` in `format`, as the arguments ask, with any
+/// annotations shown after the lines they are on
+fn render_file(
+    matches: &ArgMatches,
+    contents: &str,
+    mut elements: Vec<Box<dyn DocumentElement>>,
+    format: OutputFormat,
+    annotations: Option<&[Annotation]>,
+    options: &RenderOptions,
+) -> Vec<u8> {
+    let from = matches.get_one::<Boundary>("from");
+    let to = matches.get_one::<Boundary>("to");
+    if from.is_some() || to.is_some() {
+        elements = sani::markdown::excerpt(elements, contents, from, to)
+            .unwrap_or_else(|error| fail(&Report::error(error.to_string()), exitcode::USAGE));
+    }
+    if matches.get_flag("summary") {
+        elements = sani::markdown::summarise(elements);
+    }
+    match annotations {
+        Some(annotations) => {
+            sani::annotations::render_annotated(contents, elements, annotations, options)
+                .into_bytes()
+        }
+        None => sani::render_bytes(elements, format, options),
+    }
+}
+
+/// Reads the sidecar file of annotations at `path`, as JSON unless its extension is `.toml`
+fn read_annotations(path: &Path) -> Vec<Annotation> {
+    let Ok(text) = fs::read_to_string(path) else {
+        fail(
+            &Report::error(format!("unable to read file `{}`", path.display())),
+            exitcode::NOINPUT,
+        );
+    };
+    let format = if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        SidecarFormat::Toml
+    } else {
+        SidecarFormat::Json
+    };
+    sani::annotations::parse_sidecar(&text, format).unwrap_or_else(|error| {
+        let report = Report::error(error.to_string());
+        let name = path.display().to_string();
+        let report = match error.span(&text) {
+            Some(span) => report.source(&name, &text, span),
+            None => report,
+        };
+        fail(&report, exitcode::DATAERR);
+    })
+}
+
+/// Writes rendered output to the file at `path`, or to standard output if there isn't one
+fn write_output(path: Option<&String>, render: &[u8]) {
+    if let Some(path) = path {
+        if fs::write(path, render).is_err() {
+            fail(
+                &Report::error(format!("unable to write file `{path}`")),
+                exitcode::CANTCREAT,
+            );
+        }
+    } else {
+        let mut stdout = io::stdout().lock();
+        // there's nowhere left to report a failure to write to standard output
+        let _ = stdout
+            .write_all(render)
+            .and_then(|()| stdout.write_all(b"\n"));
+    }
+}
+
+/// Prints the tree of each parsed element, followed by its rendered output with the escape
+/// character shown as `\e` so that the formatting codes are visible
+fn print_debug(elements: &[Box<dyn DocumentElement>], options: &RenderOptions) {
+    for element in elements {
+        println!("{element:#?}");
+        println!("rendered:");
+        for line in element.render(options).lines() {
+            println!("    {}", sani::testing::visible_escapes(line));
+        }
+        println!();
+    }
+}
@@ -1,115 +1,777 @@
+use std::any::Any;
+use std::fmt::{self, Debug, Display};
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::abbreviations::Abbreviations;
+use crate::backends::Backend;
+use crate::code_block::CodeBlock;
+use crate::comment::Comment;
 use crate::formatting::Format;
+use crate::inline::{self, parse_inline};
+use crate::list::List;
+use crate::options::{ParserOptions, RenderOptions};
+use crate::quote::Blockquote;
+use crate::table::Table;
+use crate::title::TitleBlock;
+use crate::wrapping::wrap;
+
+/// What kind of element a [`DocumentElement`] is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ElementKind {
+    Paragraph,
+    Heading,
+    ThematicBreak,
+    Table,
+    List,
+    CodeBlock,
+    Blockquote,
+    Ellipsis,
+    Comment,
+    TitleBlock,
+}
+
+/// A block of a parsed document. Elements are `Send` and `Sync`, so that documents can be parsed
+/// on one thread and rendered on another, or shared between threads, without a lock
+pub trait DocumentElement: Debug + Any + Send + Sync {
+    fn render(&self, options: &RenderOptions) -> String;
+
+    /// Renders the element onto the end of `output`, as [`render`](Self::render) does. Elements
+    /// that are rendered line by line write their lines straight into `output`, rather than into
+    /// a `String` of their own
+    fn render_into(&self, options: &RenderOptions, output: &mut String) {
+        output.push_str(&self.render(options));
+    }
+
+    /// Whether the element is left out of the output when rendering with `options`, as comments
+    /// are unless they are asked for
+    fn is_hidden(&self, _options: &RenderOptions) -> bool {
+        false
+    }
 
-pub trait DocumentElement {
-    fn render(&self) -> String;
+    fn kind(&self) -> ElementKind;
+
+    /// Renders the element into a non-terminal backend, by calling the backend's method for the
+    /// element's kind
+    fn visit(&self, backend: &mut dyn Backend);
+
+    /// The byte range of the source text the element was parsed from
+    fn span(&self) -> Range<usize>;
+
+    /// Passes the element's span, and those of any elements within it, through `map`. Elements
+    /// parsed out of text that was taken from the document, such as the content of a list item,
+    /// are mapped back onto the document this way
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize);
+
+    /// Gives access to the element as a [`Heading`], if it is one
+    fn as_heading(&self) -> Option<&Heading> {
+        None
+    }
+
+    /// Gives mutable access to the element as a [`Heading`], if it is one. Used by post-parse
+    /// passes (such as heading numbering) that need to alter headings in place
+    fn as_heading_mut(&mut self) -> Option<&mut Heading> {
+        None
+    }
+
+    /// Gives access to the element as a [`Paragraph`], if it is one
+    fn as_paragraph(&self) -> Option<&Paragraph> {
+        None
+    }
 }
 
+impl dyn DocumentElement {
+    /// Gives access to the element as [`Any`], for code that deals in elements generically
+    #[must_use]
+    pub fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    /// Gives access to the element as its concrete type, if it is a `T`
+    #[must_use]
+    pub fn downcast_ref<T: DocumentElement>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+/// Elements are equal if they are of the same type, with the same content and span
+impl PartialEq for dyn DocumentElement {
+    fn eq(&self, other: &Self) -> bool {
+        fn downcast_eq<T: DocumentElement + PartialEq>(
+            element: &dyn DocumentElement,
+            other: &dyn DocumentElement,
+        ) -> bool {
+            matches!(
+                (element.downcast_ref::<T>(), other.downcast_ref::<T>()),
+                (Some(element), Some(other)) if element == other
+            )
+        }
+
+        self.kind() == other.kind()
+            && match self.kind() {
+                ElementKind::Paragraph => downcast_eq::<Paragraph>(self, other),
+                ElementKind::Heading => downcast_eq::<Heading>(self, other),
+                ElementKind::ThematicBreak => downcast_eq::<ThematicBreak>(self, other),
+                ElementKind::Ellipsis => downcast_eq::<Ellipsis>(self, other),
+                ElementKind::Table => downcast_eq::<Table>(self, other),
+                ElementKind::List => downcast_eq::<List>(self, other),
+                ElementKind::CodeBlock => downcast_eq::<CodeBlock>(self, other),
+                ElementKind::Blockquote => downcast_eq::<Blockquote>(self, other),
+                ElementKind::Comment => downcast_eq::<Comment>(self, other),
+                ElementKind::TitleBlock => downcast_eq::<TitleBlock>(self, other),
+            }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Paragraph {
     render_slices: Vec<(String, Format)>,
+    span: Range<usize>,
 }
 
 impl Paragraph {
     #[must_use]
     pub fn new(text: &str) -> Self {
-        let mut render_slices = Vec::new();
-        let mut current_format = Format::new();
-
-        let mut current_slice_start = 0_usize;
-
-        let mut char_indices = text.char_indices();
-
-        while let Some((char_index, current_char)) = char_indices.next() {
-            match current_char {
-                '\\' => {
-                    // '\': character escape
-                    let (next_char_index, _) = char_indices
-                        .next()
-                        // doing this allows the handling of the case of EOL after a backslash without any special cases
-                        // it does it by tricking the final slice pusher outside the loop into thinking
-                        // that there is nothing more to add (otherwise it would add the backslash at the end)
-                        .unwrap_or((char_index + 1, '\\'));
-                    #[allow(clippy::indexing_slicing)]
-                    render_slices.push((
-                        text[current_slice_start..char_index].to_owned(),
-                        current_format,
-                    ));
-                    current_slice_start = next_char_index;
-                }
-                '\n' => {
-                    // '\n': newline (replace with space)
-                    #[allow(clippy::indexing_slicing)]
-                    let slice = text[current_slice_start..char_index].to_owned() + " ";
-                    render_slices.push((slice, current_format));
-                    current_slice_start = char_index + 1;
-                }
-                '*' => {
-                    // bold or italic
-                    // both cases require the current slice to be pushed
-                    #[allow(clippy::indexing_slicing)]
-                    render_slices.push((
-                        text[current_slice_start..char_index].to_owned(),
-                        current_format,
-                    ));
-                    if let Some((next_char_index, '*')) = char_indices.next() {
-                        // '**': toggle the bold format
-                        current_slice_start = next_char_index + 1; // leapfrog the second asterisk
-                        current_format.toggle_bold();
-                    } else {
-                        // '*': toggle the italic format
-                        current_slice_start = char_index + 1; // leapfrog the asterisk
-                        current_format.toggle_italic();
-                    }
-                }
-                '~' => {
-                    // strikethrough or just a tilde
-                    if let Some((next_char_index, '~')) = char_indices.next() {
-                        // '~~': toggle the strikethrough format
-                        #[allow(clippy::indexing_slicing)]
-                        render_slices.push((
-                            text[current_slice_start..char_index].to_owned(),
-                            current_format,
-                        ));
-                        current_slice_start = next_char_index + 1; // leapfrog the second tilde
-                        current_format.toggle_strikethrough();
-                    }
-                }
-                _other_char => (),
+        Self::with_options(text, &ParserOptions::new())
+    }
+
+    /// Parses a paragraph, with control over how the inline text is parsed
+    #[must_use]
+    pub fn with_options(text: &str, options: &ParserOptions) -> Self {
+        Self {
+            render_slices: inline::to_slices(&parse_inline(text, options)),
+            span: 0..text.len(),
+        }
+    }
+
+    /// Marks up the abbreviations in the paragraph
+    #[must_use]
+    pub(crate) fn with_abbreviations(mut self, abbreviations: &mut Abbreviations) -> Self {
+        self.render_slices = abbreviations.apply(self.render_slices);
+        self
+    }
+
+    /// The paragraph's text with all markup removed
+    #[must_use]
+    pub fn plain_text(&self) -> String {
+        self.render_slices
+            .iter()
+            .map(|(slice, _)| slice.as_str())
+            .collect()
+    }
+
+    /// Moves the paragraph's span along by `offset` bytes, for when `text` wasn't the whole
+    /// document
+    #[must_use]
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        self.span = self.span.start + offset..self.span.end + offset;
+        self
+    }
+}
+
+impl DocumentElement for Paragraph {
+    fn render(&self, options: &RenderOptions) -> String {
+        render_lines(&self.render_slices, options)
+    }
+
+    fn render_into(&self, options: &RenderOptions, output: &mut String) {
+        write_lines(&self.render_slices, options, output);
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::Paragraph
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        // empty paragraphs only exist to keep the spacing of terminal output
+        if !self.render_slices.is_empty() {
+            backend.paragraph(&self.render_slices);
+        }
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+
+    fn as_paragraph(&self) -> Option<&Paragraph> {
+        Some(self)
+    }
+}
+
+/// Splits an ATX heading line (`## Heading ##`) into its level and its text, without the opening
+/// or closing sequences, returning `None` if the line isn't a heading
+pub(crate) fn atx_heading(line: &str) -> Option<(u8, &str)> {
+    // up to three spaces of indentation are allowed before the opening sequence
+    let unindented = line.trim_start_matches(' ');
+    if line.len() - unindented.len() > 3 {
+        return None;
+    }
+
+    let content = unindented.trim_start_matches('#');
+    let level = unindented.len() - content.len();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    // the opening sequence must be followed by whitespace or the end of the line
+    if !(content.is_empty() || content.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    // strip the optional closing sequence, provided it is separated from the text by a space
+    let mut content = content.trim();
+    let without_closing_sequence = content.trim_end_matches('#');
+    if without_closing_sequence.is_empty() || without_closing_sequence.ends_with([' ', '\t']) {
+        content = without_closing_sequence.trim_end();
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // guaranteed to be in 1..=6 by this point
+    Some((level as u8, content))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Heading {
+    level: u8,
+    number: Option<String>,
+    render_slices: Vec<(String, Format)>,
+    span: Range<usize>,
+}
+
+impl Heading {
+    /// Parses an ATX heading (`# Heading`) from a single line, returning `None` if the line isn't
+    /// one
+    #[must_use]
+    pub fn from_line(line: &str) -> Option<Self> {
+        Self::from_line_with_options(line, &ParserOptions::new())
+    }
+
+    /// As [`Heading::from_line`], with control over how the heading text is parsed
+    #[must_use]
+    pub fn from_line_with_options(line: &str, options: &ParserOptions) -> Option<Self> {
+        let (level, content) = atx_heading(line)?;
+        Some(Self {
+            level,
+            number: None,
+            render_slices: Paragraph::with_options(content, options).render_slices,
+            span: 0..line.len(),
+        })
+    }
+
+    /// Moves the heading's span along by `offset` bytes, for when `line` wasn't the whole
+    /// document
+    #[must_use]
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        self.span = self.span.start + offset..self.span.end + offset;
+        self
+    }
+
+    /// Marks up the abbreviations in the heading
+    #[must_use]
+    pub(crate) fn with_abbreviations(mut self, abbreviations: &mut Abbreviations) -> Self {
+        self.render_slices = abbreviations.apply(self.render_slices);
+        self
+    }
+
+    #[must_use]
+    pub const fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// The heading's text with all markup removed, not including its number
+    #[must_use]
+    pub fn plain_text(&self) -> String {
+        self.render_slices
+            .iter()
+            .map(|(slice, _)| slice.as_str())
+            .collect()
+    }
+
+    /// The heading's text, preceded by its number if it has one
+    fn numbered_slices(&self) -> Vec<(String, Format)> {
+        self.number
+            .iter()
+            .map(|number| (format!("{number} "), Format::new()))
+            .chain(self.render_slices.iter().cloned())
+            .collect()
+    }
+
+    /// The section number (e.g. `1.2.1`) displayed before the heading text, if it has one
+    #[must_use]
+    pub fn number(&self) -> Option<&str> {
+        self.number.as_deref()
+    }
+
+    /// Sets the section number (e.g. `1.2.1`) displayed before the heading text
+    pub fn set_number(&mut self, number: String) {
+        self.number = Some(number);
+    }
+}
+
+impl DocumentElement for Heading {
+    fn render(&self, options: &RenderOptions) -> String {
+        let mut output = String::new();
+        self.render_into(options, &mut output);
+        output
+    }
+
+    fn render_into(&self, options: &RenderOptions, output: &mut String) {
+        // the theme's formatting for the heading level goes on top of any inline formatting
+        let heading_format = options.theme.heading_format(self.level);
+        let slices: Vec<_> = self
+            .numbered_slices()
+            .into_iter()
+            .map(|(slice, format)| (slice, format.union(&heading_format)))
+            .collect();
+
+        write_lines(&slices, options, output);
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::Heading
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.heading(self.level, &self.numbered_slices());
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+
+    fn as_heading(&self) -> Option<&Heading> {
+        Some(self)
+    }
+
+    fn as_heading_mut(&mut self) -> Option<&mut Heading> {
+        Some(self)
+    }
+}
+
+/// The width of horizontal rules when no wrapping width is set
+pub(crate) const DEFAULT_RULE_WIDTH: usize = 80;
+
+/// A horizontal rule (`---`, `***` or `___`)
+#[derive(Debug, PartialEq, Eq)]
+pub struct ThematicBreak {
+    span: Range<usize>,
+}
+
+impl ThematicBreak {
+    /// Parses a thematic break from a single line, returning `None` if the line isn't one. A
+    /// thematic break is three or more of the same one of `-`, `*` or `_`, optionally separated by
+    /// spaces or tabs
+    #[must_use]
+    pub fn from_line(line: &str) -> Option<Self> {
+        // up to three spaces of indentation are allowed, as for headings
+        let unindented = line.trim_start_matches(' ');
+        if line.len() - unindented.len() > 3 {
+            return None;
+        }
+
+        let mut characters = unindented.chars().filter(|c| !matches!(c, ' ' | '\t'));
+        let marker = characters.next().filter(|c| matches!(c, '-' | '*' | '_'))?;
+        let mut count = 1_usize;
+        for character in characters {
+            if character != marker {
+                return None;
+            }
+            count += 1;
+        }
+
+        (count >= 3).then_some(Self {
+            span: 0..line.len(),
+        })
+    }
+
+    /// Moves the thematic break's span along by `offset` bytes, for when `line` wasn't the whole
+    /// document
+    #[must_use]
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        self.span = self.span.start + offset..self.span.end + offset;
+        self
+    }
+}
+
+impl DocumentElement for ThematicBreak {
+    fn render(&self, options: &RenderOptions) -> String {
+        render_rule(options)
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::ThematicBreak
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.thematic_break();
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+}
+
+/// Marks where content has been left out, as in a [summary](summarise)
+#[derive(Debug, PartialEq, Eq)]
+pub struct Ellipsis {
+    span: Range<usize>,
+}
+
+impl DocumentElement for Ellipsis {
+    fn render(&self, options: &RenderOptions) -> String {
+        options.theme.glyphs.ellipsis.to_owned()
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::Ellipsis
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.paragraph(&[("…".to_owned(), Format::new())]);
+    }
+
+    /// The span of the content left out
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+}
+
+/// Cuts `elements` down to a summary of the document: its headings, each followed by only the
+/// first paragraph under it. An [`Ellipsis`] is put wherever anything else has been left out
+#[must_use]
+pub fn summarise(elements: Vec<Box<dyn DocumentElement>>) -> Vec<Box<dyn DocumentElement>> {
+    let mut summary: Vec<Box<dyn DocumentElement>> = Vec::new();
+    let mut has_paragraph = false;
+    let mut omitted: Option<Range<usize>> = None;
+
+    for element in elements {
+        if element.as_heading().is_some() {
+            if let Some(span) = omitted.take() {
+                summary.push(Box::new(Ellipsis { span }));
+            }
+            has_paragraph = false;
+            summary.push(element);
+        } else if let Some(paragraph) = element.as_paragraph() {
+            // empty paragraphs only add spacing, which a summary can do without
+            if paragraph.plain_text().trim().is_empty() {
+                continue;
             }
+            if has_paragraph {
+                let span = element.span();
+                omitted = Some(omitted.map_or(span.clone(), |omitted| omitted.start..span.end));
+            } else {
+                has_paragraph = true;
+                summary.push(element);
+            }
+        } else {
+            let span = element.span();
+            omitted = Some(omitted.map_or(span.clone(), |omitted| omitted.start..span.end));
+        }
+    }
+    if let Some(span) = omitted {
+        summary.push(Box::new(Ellipsis { span }));
+    }
+
+    summary
+}
+
+/// Where an [excerpt] of a document starts or ends
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    /// The first heading with the given text. Written with its `#`s (`## Usage`), it only matches
+    /// a heading of that level
+    Heading(String),
+    /// A line of the source, counting from 1
+    Line(usize),
+}
+
+impl Boundary {
+    /// Reads a boundary given by the user: a line number if it is one, or a heading otherwise
+    #[must_use]
+    pub fn parse(boundary: &str) -> Self {
+        boundary
+            .parse()
+            .map_or_else(|_| Self::Heading(boundary.to_owned()), Self::Line)
+    }
+
+    /// Whether `heading` is the heading this boundary is at
+    fn is_at(heading: &Heading, query: &str) -> bool {
+        let text = query.trim_start_matches('#');
+        let level = query.len() - text.len();
+        (level == 0 || level == usize::from(heading.level()))
+            && heading.plain_text().trim() == text.trim()
+    }
+}
+
+/// The error given when a heading an [excerpt] starts or ends at isn't in the document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeadingNotFound {
+    pub heading: String,
+}
+
+impl Display for HeadingNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no heading `{}` in the document", self.heading)
+    }
+}
+
+impl std::error::Error for HeadingNotFound {}
+
+/// Cuts `elements`, parsed from `text`, down to those between two boundaries: from the heading or
+/// line `from` up to (but not including) the heading `to`, or up to and including the line `to`.
+/// The document is excerpted from its start or to its end if either is left out. Elements that
+/// are only partly in the range, such as a paragraph the line `from` is in the middle of, are kept
+/// whole. The heading `to` is looked for after the start of the excerpt
+///
+/// # Errors
+///
+/// Returns [`HeadingNotFound`] if either boundary is a heading that isn't in the document
+pub fn excerpt(
+    elements: Vec<Box<dyn DocumentElement>>,
+    text: &str,
+    from: Option<&Boundary>,
+    to: Option<&Boundary>,
+) -> Result<Vec<Box<dyn DocumentElement>>, HeadingNotFound> {
+    let find_heading = |query: &str, after: usize| {
+        elements
+            .iter()
+            .filter_map(|element| element.as_heading())
+            .find(|heading| heading.span().start >= after && Boundary::is_at(heading, query))
+            .map(|heading| heading.span().start)
+            .ok_or_else(|| HeadingNotFound {
+                heading: query.to_owned(),
+            })
+    };
+    // the offset of the start of the given line, or the end of the text if it has fewer lines
+    let line_start = |line: usize| {
+        if line <= 1 {
+            return 0;
         }
+        text.match_indices('\n')
+            .nth(line - 2)
+            .map_or(text.len(), |(index, _)| index + 1)
+    };
+
+    let start = match from {
+        None => 0,
+        Some(Boundary::Heading(heading)) => find_heading(heading, 0)?,
+        Some(Boundary::Line(line)) => line_start(*line),
+    };
+    let end = match to {
+        None => usize::MAX,
+        Some(Boundary::Heading(heading)) => find_heading(heading, start + 1)?,
+        Some(Boundary::Line(line)) => line_start(line + 1),
+    };
+    Ok(elements
+        .into_iter()
+        .filter(|element| {
+            let span = element.span();
+            span.end > start && span.start < end
+        })
+        .collect())
+}
+
+/// Numbers every heading in `elements` hierarchically (`1`, `1.1`, `1.1.2`, ...). Numbering is
+/// relative to the shallowest heading level present, so a document using only `##` and `###`
+/// headings is still numbered from `1`
+pub fn number_headings(elements: &mut [Box<dyn DocumentElement>]) {
+    let Some(top_level) = elements
+        .iter_mut()
+        .filter_map(|element| element.as_heading_mut())
+        .map(|heading| heading.level)
+        .min()
+    else {
+        return;
+    };
 
-        // push the final slice to the render components, provided that there is something to push
-        // in the first place
-        if current_slice_start != text.len() {
-            #[allow(clippy::indexing_slicing)]
-            render_slices.push((text[current_slice_start..].to_owned(), current_format));
+    let mut counters = [0_usize; 6];
+    for heading in elements
+        .iter_mut()
+        .filter_map(|element| element.as_heading_mut())
+    {
+        // at most 5, as levels are in 1..=6
+        let depth = usize::from(heading.level.saturating_sub(top_level));
+        let Some((numbered, deeper)) = counters.split_at_mut_checked(depth + 1) else {
+            continue;
+        };
+        if let Some(counter) = numbered.last_mut() {
+            *counter += 1;
         }
+        deeper.fill(0);
+        heading.set_number(
+            numbered
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("."),
+        );
+    }
+}
+
+/// Draws a horizontal rule across the width set in `options`
+pub(crate) fn render_rule(options: &RenderOptions) -> String {
+    let rule = options.theme.glyphs.rule;
+    let width = options.width.unwrap_or(DEFAULT_RULE_WIDTH);
+    rule.repeat(width / rule.width().max(1))
+}
 
-        // remove any empty slices
-        render_slices.retain(|elem| !elem.0.is_empty());
+/// Renders a horizontal rule with `title` set into it, in bold, for separating documents rendered
+/// one after another
+#[must_use]
+pub fn render_banner(title: &str, options: &RenderOptions) -> String {
+    let rule = options.theme.glyphs.rule;
+    let rule_width = rule.width().max(1);
+    let width = options.width.unwrap_or(DEFAULT_RULE_WIDTH);
+    let fill_width = width.saturating_sub(2 * rule_width + title.width() + 2);
 
-        Self { render_slices }
+    let bold = Format::new().set_bold();
+    format!(
+        "{} {}{title}{} {}",
+        rule.repeat(2),
+        bold.get_codes_for_format_change(&Format::new()),
+        Format::new().get_codes_for_format_change(&bold),
+        rule.repeat(fill_width / rule_width)
+    )
+}
+
+/// Calls `inspect` with the plain text and source span of every non-empty paragraph in
+/// `elements`, in order. This is a hook for checks that work on prose, such as spell-checking
+pub fn inspect_paragraphs(
+    elements: &[Box<dyn DocumentElement>],
+    mut inspect: impl FnMut(&str, Range<usize>),
+) {
+    for paragraph in elements.iter().filter_map(|element| element.as_paragraph()) {
+        let text = paragraph.plain_text();
+        if !text.is_empty() {
+            inspect(&text, paragraph.span());
+        }
     }
 }
 
-impl DocumentElement for Paragraph {
-    fn render(&self) -> String {
-        let mut render = String::new();
-        let mut previous_format = Format::new();
+/// Wraps a sequence of formatted slices according to `options` and renders each resulting line,
+/// with each inline role, such as emphasis or code, in the theme's formatting for it
+pub(crate) fn render_lines(slices: &[(String, Format)], options: &RenderOptions) -> String {
+    let mut output = String::new();
+    write_lines(slices, options, &mut output);
+    output
+}
 
-        for (slice, format) in &self.render_slices {
-            render += &(format.get_codes_for_format_change(previous_format) + slice);
-            previous_format = *format;
+/// Renders a sequence of formatted slices as [`render_lines`] does, onto the end of `output`. When
+/// decorations are continued, matches of the pattern to highlight are found before the slices are
+/// wrapped, so that a match broken across lines is highlighted on each of them
+pub(crate) fn write_lines(
+    slices: &[(String, Format)],
+    options: &RenderOptions,
+    output: &mut String,
+) {
+    let slices = with_role_formats(slices, options);
+    let highlight = options.highlight.as_ref();
+    let (slices, highlighted) = match highlight.filter(|_| options.highlight_across_lines) {
+        Some(highlight) => (highlight.apply(slices), true),
+        None => (slices, false),
+    };
+    let lines = wrap(&slices, options);
+    #[cfg(feature = "bidi")]
+    let lines = crate::bidi::aligned(lines, &slices, options);
+    for (index, line) in lines.into_iter().enumerate() {
+        if index > 0 {
+            output.push('\n');
         }
-        // close up any hanging formatting
-        render += &Format::new().get_codes_for_format_change(previous_format);
+        if highlighted {
+            write_slices(&line, options, output);
+        } else {
+            write_line(line, options, output);
+        }
+    }
+}
+
+/// Adds the theme's formatting for the inline roles of each slice, such as emphasis or code, and
+/// resolves the targets of links with the options' resolver, if they have one
+pub(crate) fn with_role_formats(
+    slices: &[(String, Format)],
+    options: &RenderOptions,
+) -> Vec<(String, Format)> {
+    let resolved = options
+        .link_resolver
+        .as_ref()
+        .map(|resolver| resolver.apply(slices));
+    let slices = resolved.as_deref().unwrap_or(slices);
+    slices
+        .iter()
+        .map(|(slice, format)| {
+            if format.roles().is_empty() {
+                (slice.clone(), format.clone())
+            } else {
+                (
+                    slice.clone(),
+                    format.union(&options.theme.role_format(format.roles())),
+                )
+            }
+        })
+        .collect()
+}
+
+/// Renders a single line of formatted slices, with any matches of the pattern to highlight
+/// highlighted
+pub(crate) fn render_line(line: Vec<(String, Format)>, options: &RenderOptions) -> String {
+    let mut output = String::new();
+    write_line(line, options, &mut output);
+    output
+}
+
+/// Renders a single line of formatted slices as [`render_line`] does, onto the end of `output`
+pub(crate) fn write_line(
+    line: Vec<(String, Format)>,
+    options: &RenderOptions,
+    output: &mut String,
+) {
+    match &options.highlight {
+        Some(highlight) => write_slices(&highlight.apply(line), options, output),
+        None => write_slices(&line, options, output),
+    }
+}
+
+/// Renders a sequence of formatted slices onto the end of `output`, emitting the minimal
+/// formatting codes between them and closing up any formatting left open at the end
+fn write_slices(slices: &[(String, Format)], options: &RenderOptions, output: &mut String) {
+    let no_format = Format::new();
+    let mut previous_format = &no_format;
 
-        render
+    // writing to a `String` can't fail
+    for (slice, format) in slices {
+        let _ = format.write_codes_for_format_change(previous_format, options.sgr_style, output);
+        output.push_str(slice);
+        previous_format = format;
     }
+    // close up any hanging formatting
+    let _ = if options.resets {
+        previous_format.write_reset_codes(output)
+    } else {
+        no_format.write_codes_for_format_change(previous_format, options.sgr_style, output)
+    };
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::formatting::{Colour, Roles};
+    use crate::theme::Theme;
 
     mod paragraph_parsing {
         use super::*;
@@ -135,6 +797,51 @@ mod tests {
             );
         }
 
+        #[test]
+        fn escaped_punctuation_is_literal() {
+            let paragraph = Paragraph::new(r"\*lorem\* \~~ipsum\~~ \# \`dolor\` \[sit\]");
+            assert_eq!(
+                "*lorem* ~~ipsum~~ # `dolor` [sit]",
+                paragraph
+                    .render_slices
+                    .iter()
+                    .map(|(slice, _)| slice.as_str())
+                    .collect::<String>()
+            );
+            assert!(paragraph
+                .render_slices
+                .iter()
+                .all(|(_, format)| *format == Format::new()));
+        }
+
+        #[test]
+        fn backslash_before_a_letter_is_literal() {
+            let paragraph = Paragraph::new(r"C:\dolor");
+            assert_eq!(
+                vec![(r"C:\dolor".to_owned(), Format::new())],
+                paragraph.render_slices
+            );
+        }
+
+        #[test]
+        fn entities_are_decoded_when_enabled() {
+            let text = "&copy; lorem &amp; *ipsum*";
+            assert_eq!(
+                vec![
+                    ("© lorem & ".to_owned(), Format::new()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
+                ],
+                Paragraph::with_options(text, &ParserOptions::new().entities(true)).render_slices
+            );
+            assert_eq!(
+                vec![
+                    ("&copy; lorem &amp; ".to_owned(), Format::new()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
+                ],
+                Paragraph::new(text).render_slices
+            );
+        }
+
         #[test]
         fn newline_becomes_space() {
             let paragraph = Paragraph::new("lorem\nipsum");
@@ -147,12 +854,20 @@ mod tests {
             );
         }
 
+        #[test]
+        fn code_takes_the_theme_formatting() {
+            assert_eq!(
+                "lorem \x1b[33m*ipsum*\x1b[39m",
+                Paragraph::new("lorem `*ipsum*`").render(&RenderOptions::new())
+            );
+        }
+
         #[test]
         fn bold_at_start_of_paragraph() {
             let paragraph = Paragraph::new(r"**lorem** ipsum");
             assert_eq!(
                 vec![
-                    ("lorem".to_owned(), Format::new().set_bold()),
+                    ("lorem".to_owned(), Format::new().set_roles(Roles::STRONG)),
                     (" ipsum".to_owned(), Format::new()),
                 ],
                 paragraph.render_slices
@@ -165,7 +880,7 @@ mod tests {
             assert_eq!(
                 vec![
                     ("lorem ".to_owned(), Format::new()),
-                    ("ipsum".to_owned(), Format::new().set_bold()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::STRONG)),
                     (" dolor".to_owned(), Format::new()),
                 ],
                 paragraph.render_slices
@@ -178,7 +893,7 @@ mod tests {
             assert_eq!(
                 vec![
                     ("lorem ".to_owned(), Format::new()),
-                    ("ipsum".to_owned(), Format::new().set_bold()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::STRONG)),
                 ],
                 paragraph.render_slices
             );
@@ -189,7 +904,7 @@ mod tests {
             let paragraph = Paragraph::new("*lorem* ipsum");
             assert_eq!(
                 vec![
-                    ("lorem".to_owned(), Format::new().set_italic()),
+                    ("lorem".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
                     (" ipsum".to_owned(), Format::new()),
                 ],
                 paragraph.render_slices
@@ -202,7 +917,7 @@ mod tests {
             assert_eq!(
                 vec![
                     ("lorem ".to_owned(), Format::new()),
-                    ("ipsum".to_owned(), Format::new().set_italic()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
                     (" dolor".to_owned(), Format::new()),
                 ],
                 paragraph.render_slices
@@ -215,7 +930,7 @@ mod tests {
             assert_eq!(
                 vec![
                     ("lorem ".to_owned(), Format::new()),
-                    ("ipsum".to_owned(), Format::new().set_italic()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
                 ],
                 paragraph.render_slices
             );
@@ -227,7 +942,10 @@ mod tests {
             assert_eq!(
                 vec![
                     ("lorem ".to_owned(), Format::new()),
-                    (" ipsum ".to_owned(), Format::new().set_italic()),
+                    (
+                        " ipsum ".to_owned(),
+                        Format::new().set_roles(Roles::EMPHASIS)
+                    ),
                     (" dolor".to_owned(), Format::new()),
                 ],
                 paragraph.render_slices
@@ -239,7 +957,7 @@ mod tests {
             let paragraph = Paragraph::new(r"~~lorem~~ ipsum");
             assert_eq!(
                 vec![
-                    ("lorem".to_owned(), Format::new().set_strikethrough()),
+                    ("lorem".to_owned(), Format::new().set_roles(Roles::DELETED)),
                     (" ipsum".to_owned(), Format::new()),
                 ],
                 paragraph.render_slices
@@ -252,7 +970,7 @@ mod tests {
             assert_eq!(
                 vec![
                     ("lorem ".to_owned(), Format::new()),
-                    ("ipsum".to_owned(), Format::new().set_strikethrough()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::DELETED)),
                     (" dolor".to_owned(), Format::new()),
                 ],
                 paragraph.render_slices
@@ -265,7 +983,7 @@ mod tests {
             assert_eq!(
                 vec![
                     ("lorem ".to_owned(), Format::new()),
-                    ("ipsum".to_owned(), Format::new().set_strikethrough()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::DELETED)),
                 ],
                 paragraph.render_slices
             );
@@ -280,14 +998,59 @@ mod tests {
             );
         }
 
+        #[test]
+        fn tilde_before_asterisk() {
+            let paragraph = Paragraph::new("~*lorem*");
+            assert_eq!(
+                vec![
+                    ("~".to_owned(), Format::new()),
+                    ("lorem".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
+                ],
+                paragraph.render_slices
+            );
+        }
+
+        #[test]
+        fn asterisk_before_newline() {
+            let paragraph = Paragraph::new("*lorem*\nipsum");
+            assert_eq!(
+                vec![
+                    ("lorem".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
+                    (" ".to_owned(), Format::new()),
+                    ("ipsum".to_owned(), Format::new()),
+                ],
+                paragraph.render_slices
+            );
+        }
+
+        #[test]
+        fn asterisk_before_escape() {
+            let paragraph = Paragraph::new(r"*lorem*\*");
+            assert_eq!(
+                vec![
+                    ("lorem".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
+                    ("*".to_owned(), Format::new()),
+                ],
+                paragraph.render_slices
+            );
+        }
+
         #[test]
         fn two_overlapping_formats() {
             let paragraph = Paragraph::new(r"**lorem *ipsum** dolor*");
             assert_eq!(
                 vec![
-                    ("lorem ".to_owned(), Format::new().set_bold()),
-                    ("ipsum".to_owned(), Format::new().set_bold().set_italic()),
-                    (" dolor".to_owned(), Format::new().set_italic()),
+                    ("lorem ".to_owned(), Format::new().set_roles(Roles::STRONG)),
+                    (
+                        "ipsum".to_owned(),
+                        Format::new()
+                            .set_roles(Roles::STRONG)
+                            .set_roles(Roles::EMPHASIS)
+                    ),
+                    (
+                        " dolor".to_owned(),
+                        Format::new().set_roles(Roles::EMPHASIS)
+                    ),
                 ],
                 paragraph.render_slices
             );
@@ -298,9 +1061,14 @@ mod tests {
             let paragraph = Paragraph::new(r"**lorem *ipsum* dolor**");
             assert_eq!(
                 vec![
-                    ("lorem ".to_owned(), Format::new().set_bold()),
-                    ("ipsum".to_owned(), Format::new().set_bold().set_italic()),
-                    (" dolor".to_owned(), Format::new().set_bold()),
+                    ("lorem ".to_owned(), Format::new().set_roles(Roles::STRONG)),
+                    (
+                        "ipsum".to_owned(),
+                        Format::new()
+                            .set_roles(Roles::STRONG)
+                            .set_roles(Roles::EMPHASIS)
+                    ),
+                    (" dolor".to_owned(), Format::new().set_roles(Roles::STRONG)),
                 ],
                 paragraph.render_slices
             );
@@ -311,14 +1079,24 @@ mod tests {
             let paragraph = Paragraph::new(r"**lorem *ipsum* ~~dolor** sit amet~~");
             assert_eq!(
                 vec![
-                    ("lorem ".to_owned(), Format::new().set_bold()),
-                    ("ipsum".to_owned(), Format::new().set_bold().set_italic()),
-                    (" ".to_owned(), Format::new().set_bold()),
+                    ("lorem ".to_owned(), Format::new().set_roles(Roles::STRONG)),
+                    (
+                        "ipsum".to_owned(),
+                        Format::new()
+                            .set_roles(Roles::STRONG)
+                            .set_roles(Roles::EMPHASIS)
+                    ),
+                    (" ".to_owned(), Format::new().set_roles(Roles::STRONG)),
                     (
                         "dolor".to_owned(),
-                        Format::new().set_bold().set_strikethrough()
+                        Format::new()
+                            .set_roles(Roles::STRONG)
+                            .set_roles(Roles::DELETED)
+                    ),
+                    (
+                        " sit amet".to_owned(),
+                        Format::new().set_roles(Roles::DELETED)
                     ),
-                    (" sit amet".to_owned(), Format::new().set_strikethrough()),
                 ],
                 paragraph.render_slices
             );
@@ -331,6 +1109,7 @@ mod tests {
         #[test]
         fn escaped_character_mid_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ipsum ".to_owned(), Format::new()),
                     (r"\dolor sit amet".to_owned(), Format::new()),
@@ -338,24 +1117,29 @@ mod tests {
             };
             assert_eq!(
                 r"lorem ipsum \dolor sit amet".to_owned(),
-                paragraph.render(),
+                paragraph.render(&RenderOptions::new()),
             );
         }
 
         #[test]
         fn bold_at_start_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem".to_owned(), Format::new().set_bold()),
                     (" ipsum".to_owned(), Format::new()),
                 ],
             };
-            assert_eq!("\x1b[1mlorem\x1b[22m ipsum".to_owned(), paragraph.render());
+            assert_eq!(
+                "\x1b[1mlorem\x1b[22m ipsum".to_owned(),
+                paragraph.render(&RenderOptions::new())
+            );
         }
 
         #[test]
         fn bold_in_the_middle_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new()),
                     ("ipsum".to_owned(), Format::new().set_bold()),
@@ -364,35 +1148,44 @@ mod tests {
             };
             assert_eq!(
                 "lorem \x1b[1mipsum\x1b[22m dolor".to_owned(),
-                paragraph.render()
+                paragraph.render(&RenderOptions::new())
             );
         }
 
         #[test]
         fn bold_at_end_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new()),
                     ("ipsum".to_owned(), Format::new().set_bold()),
                 ],
             };
-            assert_eq!("lorem \x1b[1mipsum\x1b[22m".to_owned(), paragraph.render());
+            assert_eq!(
+                "lorem \x1b[1mipsum\x1b[22m".to_owned(),
+                paragraph.render(&RenderOptions::new())
+            );
         }
 
         #[test]
         fn italic_at_start_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem".to_owned(), Format::new().set_italic()),
                     (" ipsum".to_owned(), Format::new()),
                 ],
             };
-            assert_eq!("\x1b[3mlorem\x1b[23m ipsum".to_owned(), paragraph.render());
+            assert_eq!(
+                "\x1b[3mlorem\x1b[23m ipsum".to_owned(),
+                paragraph.render(&RenderOptions::new())
+            );
         }
 
         #[test]
         fn italic_in_the_middle_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new()),
                     ("ipsum".to_owned(), Format::new().set_italic()),
@@ -401,24 +1194,29 @@ mod tests {
             };
             assert_eq!(
                 "lorem \x1b[3mipsum\x1b[23m dolor".to_owned(),
-                paragraph.render()
+                paragraph.render(&RenderOptions::new())
             );
         }
 
         #[test]
         fn italic_at_end_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new()),
                     ("ipsum".to_owned(), Format::new().set_italic()),
                 ],
             };
-            assert_eq!("lorem \x1b[3mipsum\x1b[23m".to_owned(), paragraph.render());
+            assert_eq!(
+                "lorem \x1b[3mipsum\x1b[23m".to_owned(),
+                paragraph.render(&RenderOptions::new())
+            );
         }
 
         #[test]
         fn italic_with_asterisks_surrounded_by_spaces() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new()),
                     (" ipsum ".to_owned(), Format::new().set_italic()),
@@ -427,24 +1225,29 @@ mod tests {
             };
             assert_eq!(
                 "lorem \x1b[3m ipsum \x1b[23m dolor".to_owned(),
-                paragraph.render()
+                paragraph.render(&RenderOptions::new())
             );
         }
 
         #[test]
         fn strikethrough_at_start_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem".to_owned(), Format::new().set_strikethrough()),
                     (" ipsum".to_owned(), Format::new()),
                 ],
             };
-            assert_eq!("\x1b[9mlorem\x1b[29m ipsum".to_owned(), paragraph.render());
+            assert_eq!(
+                "\x1b[9mlorem\x1b[29m ipsum".to_owned(),
+                paragraph.render(&RenderOptions::new())
+            );
         }
 
         #[test]
         fn strikethrough_in_the_middle_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new()),
                     ("ipsum".to_owned(), Format::new().set_strikethrough()),
@@ -453,24 +1256,29 @@ mod tests {
             };
             assert_eq!(
                 "lorem \x1b[9mipsum\x1b[29m dolor".to_owned(),
-                paragraph.render()
+                paragraph.render(&RenderOptions::new())
             );
         }
 
         #[test]
         fn strikethrough_at_end_of_paragraph() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new()),
                     ("ipsum".to_owned(), Format::new().set_strikethrough()),
                 ],
             };
-            assert_eq!("lorem \x1b[9mipsum\x1b[29m".to_owned(), paragraph.render());
+            assert_eq!(
+                "lorem \x1b[9mipsum\x1b[29m".to_owned(),
+                paragraph.render(&RenderOptions::new())
+            );
         }
 
         #[test]
         fn two_overlapping_formats() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new().set_bold()),
                     ("ipsum".to_owned(), Format::new().set_bold().set_italic()),
@@ -479,13 +1287,14 @@ mod tests {
             };
             assert_eq!(
                 "\x1b[1mlorem \x1b[3mipsum\x1b[22m dolor\x1b[23m".to_owned(),
-                paragraph.render()
+                paragraph.render(&RenderOptions::new())
             );
         }
 
         #[test]
         fn enclosed_formats() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new().set_bold()),
                     ("ipsum".to_owned(), Format::new().set_bold().set_italic()),
@@ -494,13 +1303,14 @@ mod tests {
             };
             assert_eq!(
                 "\x1b[1mlorem \x1b[3mipsum\x1b[23m dolor\x1b[22m".to_owned(),
-                paragraph.render()
+                paragraph.render(&RenderOptions::new())
             );
         }
 
         #[test]
         fn enclosed_and_overlapping_formats() {
             let paragraph = Paragraph {
+                span: 0..0,
                 render_slices: vec![
                     ("lorem ".to_owned(), Format::new().set_bold()),
                     ("ipsum".to_owned(), Format::new().set_bold().set_italic()),
@@ -515,8 +1325,491 @@ mod tests {
             assert_eq!(
                 "\x1b[1mlorem \x1b[3mipsum\x1b[23m \x1b[9mdolor\x1b[22m sit amet\x1b[29m"
                     .to_owned(),
-                paragraph.render()
+                paragraph.render(&RenderOptions::new())
+            );
+        }
+
+        #[test]
+        fn inline_roles_are_shown_in_the_theme_formatting_for_them() {
+            let paragraph = Paragraph::new("*lorem* **ipsum** ~~dolor~~");
+            assert_eq!(
+                "\x1b[3mlorem\x1b[23m \x1b[1mipsum\x1b[22m \x1b[9mdolor\x1b[29m",
+                paragraph.render(&RenderOptions::new())
+            );
+            let theme = Theme::dark()
+                .strong(Format::new().set_foreground(Colour::Red))
+                .emphasis(Format::new().set_underline());
+            assert_eq!(
+                "\x1b[4mlorem\x1b[24m \x1b[31mipsum\x1b[39m \x1b[9mdolor\x1b[29m",
+                paragraph.render(&RenderOptions::new().theme(theme))
+            );
+        }
+
+        #[test]
+        fn outer_colours_are_restored_after_nested_ones() {
+            // each slice has the whole of its formatting, so leaving a nested construct
+            // re-emits the colour of the one around it, rather than just ending the inner colour
+            let quote = Format::new().set_background(Colour::Blue);
+            let link = quote.clone().set_foreground(Colour::Cyan);
+            let paragraph = Paragraph {
+                span: 0..0,
+                render_slices: vec![
+                    ("lorem ".to_owned(), link.clone()),
+                    (
+                        "ipsum".to_owned(),
+                        link.clone().set_bold().set_foreground(Colour::Yellow),
+                    ),
+                    (" dolor".to_owned(), link),
+                    (" sit".to_owned(), quote),
+                ],
+            };
+            assert_eq!(
+                "\x1b[36;44mlorem \x1b[1;33mipsum\x1b[22;36m dolor\x1b[39m sit\x1b[49m",
+                paragraph.render(&RenderOptions::new())
+            );
+        }
+    }
+
+    mod wrapped_rendering {
+        use super::*;
+
+        #[test]
+        fn paragraph_lines_are_joined_with_newlines() {
+            let paragraph = Paragraph::new("lorem ipsum dolor");
+            assert_eq!(
+                "lorem\nipsum\ndolor".to_owned(),
+                paragraph.render(&RenderOptions::new().width(5))
+            );
+        }
+
+        #[test]
+        fn formatting_is_closed_and_reopened_at_line_breaks() {
+            let paragraph = Paragraph::new("**lorem ipsum**");
+            assert_eq!(
+                "\x1b[1mlorem\x1b[22m\n\x1b[1mipsum\x1b[22m".to_owned(),
+                paragraph.render(&RenderOptions::new().width(5))
+            );
+        }
+
+        #[test]
+        fn highlights_can_continue_across_line_breaks() {
+            let paragraph = Paragraph::new("lorem ipsum");
+            let options = RenderOptions::new()
+                .width(5)
+                .highlight(regex::Regex::new("m i").unwrap());
+            assert_eq!("lorem\nipsum".to_owned(), paragraph.render(&options));
+            assert_eq!(
+                "lore\x1b[7mm\x1b[27m\n\x1b[7mi\x1b[27mpsum".to_owned(),
+                paragraph.render(&options.highlight_across_lines(true))
+            );
+        }
+
+        #[test]
+        fn numbered_heading_is_wrapped_with_its_number() {
+            let mut heading = Heading::from_line("# lorem").unwrap();
+            heading.set_number("1.1".to_owned());
+            let theme = Theme::dark().heading(1, Format::new().set_bold());
+            assert_eq!(
+                "\x1b[1m1.1\x1b[22m\n\x1b[1mlorem\x1b[22m".to_owned(),
+                heading.render(&RenderOptions::new().width(5).theme(theme))
+            );
+        }
+    }
+
+    mod heading_parsing {
+        use super::*;
+
+        #[test]
+        fn heading_levels() {
+            for level in 1..=6 {
+                let heading = Heading::from_line(&format!("{} lorem", "#".repeat(level)))
+                    .expect("line should have been parsed as a heading");
+                assert_eq!(level, usize::from(heading.level));
+            }
+        }
+
+        #[test]
+        fn too_many_hashes_is_not_a_heading() {
+            assert!(Heading::from_line("####### lorem").is_none());
+        }
+
+        #[test]
+        fn no_space_after_hashes_is_not_a_heading() {
+            assert!(Heading::from_line("#lorem").is_none());
+        }
+
+        #[test]
+        fn four_spaces_of_indentation_is_not_a_heading() {
+            assert!(Heading::from_line("    # lorem").is_none());
+            assert!(Heading::from_line("   # lorem").is_some());
+        }
+
+        #[test]
+        fn heading_text() {
+            let heading = Heading::from_line("# lorem ipsum").unwrap();
+            assert_eq!(
+                vec![("lorem ipsum".to_owned(), Format::new())],
+                heading.render_slices
+            );
+        }
+
+        #[test]
+        fn closing_sequence_is_removed() {
+            let heading = Heading::from_line("## lorem ipsum ##").unwrap();
+            assert_eq!(
+                vec![("lorem ipsum".to_owned(), Format::new())],
+                heading.render_slices
+            );
+        }
+
+        #[test]
+        fn hashes_attached_to_text_are_kept() {
+            let heading = Heading::from_line("# lorem#").unwrap();
+            assert_eq!(
+                vec![("lorem#".to_owned(), Format::new())],
+                heading.render_slices
+            );
+        }
+
+        #[test]
+        fn inline_formatting_within_heading() {
+            let heading = Heading::from_line("# lorem *ipsum*").unwrap();
+            assert_eq!(
+                vec![
+                    ("lorem ".to_owned(), Format::new()),
+                    ("ipsum".to_owned(), Format::new().set_roles(Roles::EMPHASIS)),
+                ],
+                heading.render_slices
+            );
+        }
+    }
+
+    mod heading_rendering {
+        use super::*;
+
+        #[test]
+        fn unnumbered_heading() {
+            let heading = Heading::from_line("# lorem").unwrap();
+            assert_eq!(
+                "\x1b[1;7mlorem\x1b[22;27m".to_owned(),
+                heading.render(&RenderOptions::new())
+            );
+        }
+
+        #[test]
+        fn numbered_heading() {
+            let mut heading = Heading::from_line("## lorem").unwrap();
+            heading.set_number("1.2".to_owned());
+            assert_eq!(
+                "\x1b[1;36m1.2 lorem\x1b[22;39m".to_owned(),
+                heading.render(&RenderOptions::new())
+            );
+        }
+
+        #[test]
+        fn heading_format_comes_from_theme() {
+            let heading = Heading::from_line("## lorem").unwrap();
+            assert_eq!(
+                "\x1b[1;34mlorem\x1b[22;39m".to_owned(),
+                heading.render(&RenderOptions::new().theme(Theme::light()))
+            );
+        }
+
+        #[test]
+        fn inline_formatting_is_combined_with_heading_format() {
+            let heading = Heading::from_line("### lorem *ipsum*").unwrap();
+            assert_eq!(
+                "\x1b[1mlorem \x1b[3mipsum\x1b[22;23m".to_owned(),
+                heading.render(&RenderOptions::new())
+            );
+        }
+    }
+
+    mod thematic_breaks {
+        use super::*;
+        use crate::theme::Glyphs;
+
+        #[test]
+        fn thematic_break_lines() {
+            for line in ["---", "***", "___", " - - -", "   *****", "_ _\t_ _"] {
+                assert!(ThematicBreak::from_line(line).is_some(), "{line:?}");
+            }
+        }
+
+        #[test]
+        fn not_thematic_breaks() {
+            for line in ["--", "-*-", "    ---", "--- lorem", "+++", "==="] {
+                assert!(ThematicBreak::from_line(line).is_none(), "{line:?}");
+            }
+        }
+
+        #[test]
+        fn rule_fills_the_width() {
+            let rule = ThematicBreak::from_line("---").unwrap();
+            assert_eq!("─".repeat(12), rule.render(&RenderOptions::new().width(12)));
+            assert_eq!(
+                "-".repeat(DEFAULT_RULE_WIDTH),
+                rule.render(&RenderOptions::new().theme(Theme::dark().glyphs(Glyphs::ascii())))
+            );
+        }
+    }
+
+    mod paragraph_inspection {
+        use super::*;
+
+        #[test]
+        fn plain_text_drops_markup() {
+            assert_eq!(
+                "lorem ipsum dolor",
+                Paragraph::new("**lorem** *ipsum*\n~~dolor~~").plain_text()
+            );
+        }
+
+        #[test]
+        fn only_non_empty_paragraphs_are_inspected() {
+            let elements: Vec<Box<dyn DocumentElement>> = vec![
+                Box::new(Paragraph::new("*lorem*")),
+                Box::new(Heading::from_line("# ipsum").unwrap()),
+                Box::new(Paragraph::new("")),
+                Box::new(Paragraph::new("dolor").at_offset(20)),
+            ];
+
+            let mut inspected = Vec::new();
+            inspect_paragraphs(&elements, |text, span| {
+                inspected.push((text.to_owned(), span));
+            });
+            assert_eq!(
+                vec![("lorem".to_owned(), 0..7), ("dolor".to_owned(), 20..25)],
+                inspected
+            );
+        }
+    }
+
+    mod element_identity {
+        use super::*;
+
+        #[test]
+        fn kinds_and_downcasting() {
+            let elements = crate::parse("lorem\n# ipsum\n---");
+            assert_eq!(
+                vec![
+                    ElementKind::Paragraph,
+                    ElementKind::Heading,
+                    ElementKind::ThematicBreak
+                ],
+                elements
+                    .iter()
+                    .map(|element| element.kind())
+                    .collect::<Vec<_>>()
+            );
+            let heading = elements.get(1).map(Box::as_ref);
+            assert_eq!(
+                Some(1),
+                heading
+                    .and_then(<dyn DocumentElement>::downcast_ref::<Heading>)
+                    .map(Heading::level)
+            );
+            assert!(heading
+                .and_then(<dyn DocumentElement>::downcast_ref::<Paragraph>)
+                .is_none());
+        }
+
+        #[test]
+        fn equality() {
+            assert!(crate::parse("lorem\n# *ipsum*") == crate::parse("lorem\n# *ipsum*"));
+            assert!(crate::parse("lorem\n# *ipsum*") != crate::parse("lorem\n# ipsum"));
+            // the same content in a different place isn't the same element
+            assert!(crate::parse("lorem").get(..1) != crate::parse("\n\nlorem").get(1..));
+        }
+    }
+
+    mod banners {
+        use super::*;
+        use crate::theme::{Glyphs, Theme};
+
+        #[test]
+        fn title_is_set_into_a_rule() {
+            let options = RenderOptions::new()
+                .width(20)
+                .theme(Theme::dark().glyphs(Glyphs::ascii()));
+            assert_eq!(
+                "-- \x1b[1mdocs/a.md\x1b[22m -------",
+                render_banner("docs/a.md", &options)
+            );
+        }
+
+        #[test]
+        fn long_titles_overflow() {
+            let options = RenderOptions::new().width(5);
+            assert_eq!(
+                "── \x1b[1mlorem ipsum\x1b[22m ",
+                render_banner("lorem ipsum", &options)
+            );
+        }
+    }
+
+    mod summaries {
+        use super::*;
+
+        fn contents(elements: &[Box<dyn DocumentElement>]) -> Vec<String> {
+            elements
+                .iter()
+                .map(|element| {
+                    if let Some(heading) = element.as_heading() {
+                        format!("# {}", heading.plain_text())
+                    } else if let Some(paragraph) = element.as_paragraph() {
+                        paragraph.plain_text()
+                    } else {
+                        "…".to_owned()
+                    }
+                })
+                .collect()
+        }
+
+        #[test]
+        fn first_paragraph_under_each_heading() {
+            let text = "intro\n\nmore\n\n# a\n\n---\n\nfirst\n\nsecond\n\n## b\n\n## c\n\nonly";
+            assert_eq!(
+                vec!["intro", "…", "# a", "first", "…", "# b", "# c", "only"],
+                contents(&summarise(crate::parse(text)))
+            );
+        }
+
+        #[test]
+        fn ellipsis_spans_what_was_left_out() {
+            let summary = summarise(crate::parse("# a\n\nfirst\n\nsecond\n\nthird\n\n\n\n"));
+            assert_eq!(
+                vec![0..3, 5..10, 12..25],
+                summary
+                    .iter()
+                    .map(|element| element.span())
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    mod excerpts {
+        use super::*;
+
+        const TEXT: &str =
+            "intro\n\n## Usage\n\nlorem\nipsum\n\n### Options\n\ndolor\n\n## License\n\nsit";
+
+        fn contents(from: Option<&str>, to: Option<&str>) -> Result<String, HeadingNotFound> {
+            let from = from.map(Boundary::parse);
+            let to = to.map(Boundary::parse);
+            Ok(
+                excerpt(crate::parse(TEXT), TEXT, from.as_ref(), to.as_ref())?
+                    .iter()
+                    .map(|element| {
+                        element.as_heading().map_or_else(
+                            || element.as_paragraph().unwrap().plain_text(),
+                            |heading| format!("# {}", heading.plain_text()),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            )
+        }
+
+        #[test]
+        fn between_contents() {
+            assert_eq!(
+                Ok("# Usage | lorem ipsum | # Options | dolor".to_owned()),
+                contents(Some("## Usage"), Some("## License"))
+            );
+            assert_eq!(
+                Ok("# Options | dolor | # License | sit".to_owned()),
+                contents(Some("Options"), None)
+            );
+            assert_eq!(Ok("intro".to_owned()), contents(None, Some("Usage")));
+        }
+
+        #[test]
+        fn headings_must_be_at_the_level_given() {
+            assert_eq!(
+                Err(HeadingNotFound {
+                    heading: "# Usage".to_owned()
+                }),
+                contents(Some("# Usage"), None)
             );
         }
+
+        #[test]
+        fn between_lines() {
+            // the paragraph the range starts in the middle of is kept whole
+            assert_eq!(
+                Ok("lorem ipsum | # Options".to_owned()),
+                contents(Some("6"), Some("8"))
+            );
+            assert_eq!(Ok("sit".to_owned()), contents(Some("14"), Some("100")));
+        }
+    }
+
+    mod heading_numbering {
+        use super::*;
+
+        fn numbers(elements: &mut [Box<dyn DocumentElement>]) -> Vec<Option<String>> {
+            elements
+                .iter_mut()
+                .filter_map(|element| element.as_heading_mut())
+                .map(|heading| heading.number.clone())
+                .collect()
+        }
+
+        fn headings(lines: &[&str]) -> Vec<Box<dyn DocumentElement>> {
+            lines
+                .iter()
+                .map(|line| -> Box<dyn DocumentElement> {
+                    Box::new(Heading::from_line(line).unwrap())
+                })
+                .collect()
+        }
+
+        #[test]
+        fn hierarchical_numbers() {
+            let mut elements = headings(&["# a", "## b", "### c", "### d", "## e", "# f", "## g"]);
+            number_headings(&mut elements);
+            assert_eq!(
+                vec!["1", "1.1", "1.1.1", "1.1.2", "1.2", "2", "2.1"]
+                    .into_iter()
+                    .map(|number| Some(number.to_owned()))
+                    .collect::<Vec<_>>(),
+                numbers(&mut elements)
+            );
+        }
+
+        #[test]
+        fn numbering_is_relative_to_shallowest_level() {
+            let mut elements = headings(&["## a", "### b", "## c"]);
+            number_headings(&mut elements);
+            assert_eq!(
+                vec![
+                    Some("1".to_owned()),
+                    Some("1.1".to_owned()),
+                    Some("2".to_owned())
+                ],
+                numbers(&mut elements)
+            );
+        }
+
+        #[test]
+        fn skipped_levels_are_numbered_zero() {
+            let mut elements = headings(&["# a", "### b"]);
+            number_headings(&mut elements);
+            assert_eq!(
+                vec![Some("1".to_owned()), Some("1.0.1".to_owned())],
+                numbers(&mut elements)
+            );
+        }
+
+        #[test]
+        fn paragraphs_are_left_alone() {
+            let mut elements: Vec<Box<dyn DocumentElement>> = vec![
+                Box::new(Paragraph::new("lorem")),
+                Box::new(Heading::from_line("# ipsum").unwrap()),
+            ];
+            number_headings(&mut elements);
+            assert_eq!(vec![Some("1".to_owned())], numbers(&mut elements));
+        }
     }
 }
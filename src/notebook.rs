@@ -0,0 +1,205 @@
+use std::fmt::{self, Display};
+
+use serde::Deserialize;
+
+use crate::code_block::fenced;
+
+/// A Jupyter notebook that couldn't be read
+#[derive(Debug)]
+pub struct NotebookError(serde_json::Error);
+
+impl Display for NotebookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid notebook: {}", self.0)
+    }
+}
+
+impl std::error::Error for NotebookError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+#[derive(Deserialize)]
+struct Notebook {
+    cells: Vec<Cell>,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Default, Deserialize)]
+struct Metadata {
+    kernelspec: Option<KernelSpec>,
+    language_info: Option<LanguageInfo>,
+}
+
+#[derive(Deserialize)]
+struct KernelSpec {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LanguageInfo {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "cell_type", rename_all = "lowercase")]
+enum Cell {
+    Markdown {
+        source: Text,
+    },
+    Code {
+        source: Text,
+        #[serde(default)]
+        outputs: Vec<Output>,
+    },
+    Raw {},
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "output_type", rename_all = "snake_case")]
+enum Output {
+    Stream { text: Text },
+    ExecuteResult { data: Data },
+    DisplayData { data: Data },
+    Error { ename: String, evalue: String },
+}
+
+#[derive(Deserialize)]
+struct Data {
+    #[serde(rename = "text/plain")]
+    text: Option<Text>,
+}
+
+/// Text in a notebook, which is either a single string or a list of lines
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Whole(String),
+    Lines(Vec<String>),
+}
+
+impl Text {
+    fn join(self) -> String {
+        match self {
+            Self::Whole(text) => text,
+            Self::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+impl Output {
+    /// The output as plain text, if it has any
+    fn text(self) -> Option<String> {
+        match self {
+            Self::Stream { text } => Some(text.join()),
+            Self::ExecuteResult { data } | Self::DisplayData { data } => Some(data.text?.join()),
+            // the traceback is left out, as it is full of escape codes and repeats the code
+            Self::Error { ename, evalue } => Some(format!("{ename}: {evalue}")),
+        }
+    }
+}
+
+/// Turns the Jupyter notebook (`.ipynb`) in `json` into Markdown: each Markdown cell as it is,
+/// and each code cell as a fenced code block in the notebook's language, followed by a block of
+/// its outputs if `outputs` is set. Raw cells, which are meant for other formats, are left out,
+/// as are outputs without a plain text form, such as images
+///
+/// # Errors
+///
+/// Returns a [`NotebookError`] if `json` isn't a notebook
+pub fn to_markdown(json: &str, outputs: bool) -> Result<String, NotebookError> {
+    let notebook: Notebook = serde_json::from_str(json).map_err(NotebookError)?;
+    let metadata = notebook.metadata;
+    let language = metadata
+        .language_info
+        .and_then(|info| info.name)
+        .or_else(|| metadata.kernelspec?.language)
+        .unwrap_or_default();
+
+    let mut blocks = Vec::new();
+    for cell in notebook.cells {
+        match cell {
+            Cell::Markdown { source } => blocks.push(source.join()),
+            Cell::Code {
+                source,
+                outputs: cell_outputs,
+            } => {
+                blocks.push(fenced(&language, &source.join()));
+                if outputs {
+                    let mut text = String::new();
+                    for output in cell_outputs.into_iter().filter_map(Output::text) {
+                        text += &output;
+                        if !text.ends_with('\n') {
+                            text.push('\n');
+                        }
+                    }
+                    if !text.trim().is_empty() {
+                        blocks.push(fenced("text", &text));
+                    }
+                }
+            }
+            Cell::Raw {} => {}
+        }
+    }
+    Ok(blocks.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+        "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Lorem\n", "ipsum *dolor*"]},
+            {
+                "cell_type": "code",
+                "metadata": {},
+                "execution_count": 1,
+                "source": "print('sit')\n1 / 0",
+                "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["sit\n"]},
+                    {
+                        "output_type": "error",
+                        "ename": "ZeroDivisionError",
+                        "evalue": "division by zero",
+                        "traceback": ["\u001b[0;31m..."]
+                    }
+                ]
+            },
+            {"cell_type": "raw", "metadata": {}, "source": "amet"}
+        ],
+        "metadata": {"kernelspec": {"name": "python3", "language": "python"}},
+        "nbformat": 4,
+        "nbformat_minor": 5
+    }"##;
+
+    mod cells {
+        use super::*;
+
+        #[test]
+        fn cells_become_prose_and_code_blocks() {
+            assert_eq!(
+                "# Lorem\nipsum *dolor*\n\n```python\nprint('sit')\n1 / 0\n```",
+                to_markdown(NOTEBOOK, false).unwrap()
+            );
+        }
+
+        #[test]
+        fn outputs_follow_their_cells() {
+            assert!(to_markdown(NOTEBOOK, true)
+                .unwrap()
+                .ends_with("\n```\n\n```text\nsit\nZeroDivisionError: division by zero\n```"));
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        #[test]
+        fn other_json_is_an_error() {
+            assert!(to_markdown(r#"{"annotations": []}"#, false).is_err());
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+
+use memchr::{memchr, memchr3};
+
+use crate::code_block::CodeBlock;
+use crate::input::Prepared;
+
+/// Takes the comments (`%%lorem%%`) out of `text`. A comment on lines of its own is taken out
+/// along with the newline after it, so that it doesn't leave a blank line behind to split the
+/// paragraph around it. A `%%` that is never closed is left as it is, and so is any in fenced code
+/// blocks or code spans, which are found as the parser finds them
+pub(crate) fn remove_comments(text: &str) -> Prepared<'_> {
+    let mut anchors = vec![(0, 0)];
+    if !text.contains("%%") {
+        return Prepared {
+            text: Cow::Borrowed(text),
+            anchors,
+        };
+    }
+
+    let mut uncommented = String::with_capacity(text.len());
+    // the start of the text that hasn't been copied yet, and of the text that hasn't been looked
+    // through yet
+    let mut copied = 0;
+    let mut position = fenced_code_end(text, 0);
+    while let Some(found) = text
+        .get(position..)
+        .and_then(|rest| memchr3(b'%', b'`', b'\n', rest.as_bytes()))
+    {
+        let start = position + found;
+        let rest = text.get(start..).unwrap_or_default();
+        if rest.starts_with('\n') {
+            position = fenced_code_end(text, start + 1);
+        } else if rest.starts_with('`') {
+            position = code_span_end(text, start);
+        } else if let Some(close) = rest.strip_prefix("%%").map(|inner| inner.find("%%")) {
+            let Some(close) = close else {
+                break;
+            };
+            let mut end = start + "%%".len() + close + "%%".len();
+            let before = text.get(..start).unwrap_or_default();
+            let after = text.get(end..).unwrap_or_default();
+            let on_own_lines =
+                (before.is_empty() || before.ends_with('\n')) && after.starts_with('\n');
+            if on_own_lines {
+                end += 1;
+            }
+            uncommented += text.get(copied..start).unwrap_or_default();
+            anchors.push((uncommented.len(), end));
+            copied = end;
+            position = if on_own_lines {
+                fenced_code_end(text, end)
+            } else {
+                end
+            };
+        } else {
+            position = start + 1;
+        }
+    }
+    uncommented += text.get(copied..).unwrap_or_default();
+
+    Prepared {
+        text: Cow::Owned(uncommented),
+        anchors,
+    }
+}
+
+/// The end of the fenced code block that starts at `line_start`, not counting the newline after
+/// its closing fence, or `line_start` itself if no code block starts there
+fn fenced_code_end(text: &str, line_start: usize) -> usize {
+    text.get(line_start..)
+        .and_then(CodeBlock::parse)
+        .map_or(line_start, |(_, length)| line_start + length)
+}
+
+/// The end of the code span that starts with the backtick at `start`, which is closed by the next
+/// run of as many backticks before the end of the paragraph, or the end of the opening backticks
+/// if it isn't closed, as then they are just text
+fn code_span_end(text: &str, start: usize) -> usize {
+    let rest = text.get(start..).unwrap_or_default();
+    let length = rest.len() - rest.trim_start_matches('`').len();
+    let content = rest.get(length..).unwrap_or_default();
+    let paragraph = content.split("\n\n").next().unwrap_or_default();
+    let mut search_start = 0;
+    while let Some(run_start) = paragraph
+        .get(search_start..)
+        .and_then(|text| memchr(b'`', text.as_bytes()))
+    {
+        let run_start = search_start + run_start;
+        let run = paragraph.get(run_start..).unwrap_or_default();
+        let run_length = run.len() - run.trim_start_matches('`').len();
+        if run_length == length {
+            return start + length + run_start + run_length;
+        }
+        search_start = run_start + run_length;
+    }
+    start + length
+}
+
+/// The type and title of a callout from the first line of a blockquote's content
+/// (`[!tip] Lorem ipsum`), if it starts one. The title is empty if none is given. Whether the
+/// callout is folded (`[!tip]-`) makes no difference, as there's no unfolding it in a terminal
+pub(crate) fn callout(line: &str) -> Option<(&str, &str)> {
+    let (kind, title) = line.trim().strip_prefix("[!")?.split_once(']')?;
+    let title = title.strip_prefix(['+', '-']).unwrap_or(title).trim_start();
+    let is_kind = kind
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    (!kind.is_empty() && is_kind).then_some((kind, title))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod comments {
+        use super::*;
+
+        #[test]
+        fn comments_are_taken_out() {
+            let prepared =
+                remove_comments("lorem %%ipsum%% dolor\n%%\nsit\n%%\namet %% consectetur");
+            assert_eq!("lorem  dolor\namet %% consectetur", prepared.text);
+            assert_eq!(17, prepared.original_offset(8));
+            assert_eq!(32, prepared.original_offset(13));
+        }
+
+        #[test]
+        fn code_is_left_as_it_is() {
+            let text =
+                "Format with `%%d` here.\n\n```c\nprintf(\"50%%\"); printf(\"%%s\");\n```\n\n\
+                        lorem %%ipsum%% ``a %% ` b``\n\nend %%";
+            assert_eq!(
+                "Format with `%%d` here.\n\n```c\nprintf(\"50%%\"); printf(\"%%s\");\n```\n\n\
+                 lorem  ``a %% ` b``\n\nend %%",
+                remove_comments(text).text
+            );
+        }
+
+        #[test]
+        fn comments_can_have_backticks_in_them() {
+            assert_eq!(
+                "lorem  dolor `sit`",
+                remove_comments("lorem %%ipsum ` %% dolor `sit`").text
+            );
+        }
+    }
+
+    mod callouts {
+        use super::*;
+
+        #[test]
+        fn callouts_have_a_type_and_maybe_a_title() {
+            assert_eq!(Some(("tip", "")), callout("[!tip]"));
+            assert_eq!(
+                Some(("warning", "Lorem ipsum")),
+                callout("[!warning]- Lorem ipsum")
+            );
+            assert_eq!(None, callout("[!] lorem"));
+            assert_eq!(None, callout("[lorem] ipsum"));
+        }
+    }
+}
@@ -0,0 +1,405 @@
+#[cfg(feature = "hyphenation")]
+pub use hypher::Lang;
+
+use regex::Regex;
+
+use crate::formatting::{ColourDepth, SgrStyle};
+use crate::highlighting::Highlight;
+use crate::links::LinkResolver;
+use crate::locale::Labels;
+use crate::theme::Theme;
+
+/// Options controlling how parsed elements are rendered
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(clippy::struct_excessive_bools)] // each turns a way of rendering on or off
+pub struct RenderOptions {
+    pub(crate) width: Option<usize>,
+    pub(crate) theme: Theme,
+    #[cfg(feature = "hyphenation")]
+    pub(crate) hyphenation: Option<Lang>,
+    #[cfg(feature = "bidi")]
+    pub(crate) bidi: bool,
+    pub(crate) highlight: Option<Highlight>,
+    pub(crate) link_resolver: Option<LinkResolver>,
+    pub(crate) table_overflow: TableOverflow,
+    pub(crate) sgr_style: SgrStyle,
+    pub(crate) colour_depth: ColourDepth,
+    pub(crate) resets: bool,
+    pub(crate) highlight_across_lines: bool,
+    pub(crate) show_comments: bool,
+    pub(crate) labels: Labels,
+    pub(crate) language: Option<String>,
+    pub(crate) code_blocks: CodeBlockOptions,
+    /// How many lists deep the element being rendered is, for choosing its bullets
+    pub(crate) list_depth: usize,
+}
+
+impl RenderOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps rendered text so that no line is wider than `width` columns. Without this, each
+    /// block is rendered as a single line and wrapping is left to the terminal
+    #[must_use]
+    pub const fn width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Takes `columns` off the wrapping width, if there is one, for rendering the content of a
+    /// block that uses those columns itself, such as for indentation or a border. Nested blocks
+    /// narrow the width again, so that their content never overflows the outermost width
+    #[must_use]
+    pub const fn narrowed(mut self, columns: usize) -> Self {
+        if let Some(width) = self.width {
+            self.width = Some(width.saturating_sub(columns));
+        }
+        self
+    }
+
+    /// Sets the theme used to format the rendered elements. Defaults to [`Theme::dark`]
+    #[must_use]
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme.downgraded(self.colour_depth);
+        self
+    }
+
+    /// Highlights every match of `pattern` in the rendered text in reverse video, on top of the
+    /// text's own formatting. Matches are found within each rendered line
+    #[must_use]
+    pub fn highlight(mut self, pattern: Regex) -> Self {
+        self.highlight = Some(Highlight::new(pattern));
+        self
+    }
+
+    /// Rewrites the target of every link with `resolve` as it is rendered, in whichever format,
+    /// such as to turn the page names of [wiki links](ParserOptions::wiki_links) into URLs or to
+    /// send links through a proxy. The links rewritten are bare URLs and email addresses, in the
+    /// [`Dialect::Gfm`] dialect, and wiki links; inline links (`[text](url)`) aren't parsed, and
+    /// are left as they are written
+    #[must_use]
+    pub fn link_resolver(
+        mut self,
+        resolve: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.link_resolver = Some(LinkResolver::new(resolve));
+        self
+    }
+
+    /// Finds matches of the pattern to [`highlight`](Self::highlight) in whole blocks, rather than
+    /// line by line, so that a match broken across lines by wrapping is highlighted on each of them
+    /// (and `^` and `$` match at the start and end of blocks, rather than of lines). Formatting
+    /// codes, and the bars down the side of blockquotes, are repeated at the start of every line
+    /// either way
+    #[must_use]
+    pub const fn highlight_across_lines(mut self, highlight_across_lines: bool) -> Self {
+        self.highlight_across_lines = highlight_across_lines;
+        self
+    }
+
+    /// Shows HTML comments (`<!-- lorem -->`) on lines of their own, in the theme's formatting for
+    /// them, rather than leaving them out
+    #[must_use]
+    pub const fn show_comments(mut self, show_comments: bool) -> Self {
+        self.show_comments = show_comments;
+        self
+    }
+
+    /// Sets the text generated for the output, such as the labels of annotation callouts.
+    /// Defaults to [`Labels::english`]
+    #[must_use]
+    pub const fn labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the language the document is written in, as a language tag such as `en-GB`, for
+    /// formats that record it. A `lang` in the document's front matter takes precedence
+    #[must_use]
+    pub fn language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_owned());
+        self
+    }
+
+    /// Sets how tables too wide for the [`width`](Self::width) are narrowed to fit. Defaults to
+    /// [`TableOverflow::Auto`]
+    #[must_use]
+    pub const fn table_overflow(mut self, table_overflow: TableOverflow) -> Self {
+        self.table_overflow = table_overflow;
+        self
+    }
+
+    /// Sets how the SGR parameters of each change of formatting are written. Defaults to
+    /// [`SgrStyle::Combined`], which makes for smaller output
+    #[must_use]
+    pub const fn sgr_style(mut self, sgr_style: SgrStyle) -> Self {
+        self.sgr_style = sgr_style;
+        self
+    }
+
+    /// Sets how many colours the terminal can show. Colours of the theme it can't show are swapped
+    /// for the nearest ones it can, rather than written in codes it doesn't understand. Defaults
+    /// to [`ColourDepth::TrueColour`], with which every colour is written as it is given
+    #[must_use]
+    pub fn colour_depth(mut self, colour_depth: ColourDepth) -> Self {
+        self.colour_depth = colour_depth;
+        self.theme = self.theme.downgraded(colour_depth);
+        self
+    }
+
+    /// Ends the formatting left at the end of each line with a single reset code (`\x1b[0m`)
+    /// rather than with the codes that end each attribute, and ends each block with a reset too,
+    /// for the most compatible output
+    #[must_use]
+    pub const fn resets(mut self, resets: bool) -> Self {
+        self.resets = resets;
+        self
+    }
+
+    /// Numbers the lines of code blocks, in a gutter down their left side. Lines broken to fit
+    /// the width are only numbered once
+    #[must_use]
+    pub const fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.code_blocks.line_numbers = line_numbers;
+        self
+    }
+
+    /// Shows code blocks just as they are written, without indentation, line numbers or
+    /// highlighted lines, and without breaking long lines, so that they can be copied cleanly out
+    /// of the terminal. Lines wider than the terminal are left for it to deal with
+    #[must_use]
+    pub const fn plain_code_blocks(mut self, plain_code_blocks: bool) -> Self {
+        self.code_blocks.plain = plain_code_blocks;
+        self
+    }
+
+    /// Draws `mermaid` and `graphviz` (or `dot`) code blocks as text art, with the `mermaid-ascii`
+    /// and `graph-easy` programs. Blocks are shown as code if the program isn't installed or
+    /// can't draw them
+    #[cfg(feature = "diagrams")]
+    #[must_use]
+    pub const fn draw_diagrams(mut self, draw_diagrams: bool) -> Self {
+        self.code_blocks.diagrams = draw_diagrams;
+        self
+    }
+
+    /// Hyphenates words that would otherwise overflow a wrapped line, using the hyphenation
+    /// patterns of `lang`. Only has an effect when a [`width`](Self::width) is set
+    #[cfg(feature = "hyphenation")]
+    #[must_use]
+    pub const fn hyphenate(mut self, lang: Lang) -> Self {
+        self.hyphenation = Some(lang);
+        self
+    }
+
+    /// Writes right-to-left and bidirectional text in the order it is shown in, reordering each
+    /// wrapped line with the Unicode bidirectional algorithm, and right-aligns paragraphs that are
+    /// right-to-left. This is for terminals that show text in the order it is stored, as those that
+    /// reorder it themselves would reorder it a second time
+    #[cfg(feature = "bidi")]
+    #[must_use]
+    pub const fn bidi(mut self, bidi: bool) -> Self {
+        self.bidi = bidi;
+        self
+    }
+}
+
+/// Options controlling how code blocks are rendered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct CodeBlockOptions {
+    pub(crate) line_numbers: bool,
+    pub(crate) plain: bool,
+    #[cfg(feature = "diagrams")]
+    pub(crate) diagrams: bool,
+}
+
+/// The ways of fitting a table that is too wide into the wrapping width
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TableOverflow {
+    /// Wraps cells, unless that would leave columns too narrow to read, in which case rows are
+    /// stacked
+    #[default]
+    Auto,
+    /// Wraps the content of cells within narrower columns
+    Wrap,
+    /// Cuts the content of cells off at the width of narrower columns, ending them with an
+    /// ellipsis
+    Truncate,
+    /// Lays out each row as a record of its own, with a `header: content` line for each cell
+    Stack,
+}
+
+/// The flavours of Markdown syntax that can be parsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// Sani's own syntax
+    #[default]
+    Sani,
+    /// GitHub Flavored Markdown, which also makes links of bare URLs and email addresses, and
+    /// allows strikethrough with a single tilde (`~lorem~`)
+    Gfm,
+    /// The syntax of Obsidian notes, which also has wiki links (`[[Page]]`), embeds of other
+    /// notes (`![[Page]]`, when includes are [expanded](crate::include::expand_embeds)), comments
+    /// (`%%lorem%%`), which are left out, and callouts (`> [!tip] Title`)
+    Obsidian,
+}
+
+/// The markup languages documents can be written in, all of which are parsed into the same
+/// elements. Those other than Markdown are translated into Markdown first, so only what they have
+/// in common with it is understood
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    #[default]
+    Markdown,
+    /// reStructuredText: section titles, emphasis, literal blocks (after `::` or in a
+    /// `code-block` directive), lists and links. Other directives and comments are left out
+    Rst,
+    /// AsciiDoc: section titles, bold, italic and monospace text, lists, listing and literal
+    /// blocks, and links
+    #[cfg(feature = "asciidoc")]
+    AsciiDoc,
+    /// Org: headlines, emphasis, source, example and quote blocks, lists, tables and links, with
+    /// a title, author or date among the keywords at the start made into a title block
+    Org,
+}
+
+/// What to do with invisible characters, such as zero-width spaces, which are easy to paste in
+/// from the web without noticing, and can stop the syntax around them from being recognised
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Invisibles {
+    /// Leave them as they are
+    #[default]
+    Keep,
+    /// Replace each with its code point, such as `<U+200B>`
+    Show,
+    /// Take them out before parsing
+    Remove,
+}
+
+/// Options controlling how Markdown text is parsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // each turns an extension to the syntax on or off
+pub struct ParserOptions {
+    pub(crate) input_format: InputFormat,
+    pub(crate) dialect: Dialect,
+    pub(crate) single_tilde_strikethrough: bool,
+    pub(crate) entities: bool,
+    pub(crate) abbreviations: bool,
+    pub(crate) invisibles: Invisibles,
+    pub(crate) wiki_links: bool,
+}
+
+impl ParserOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the markup language to parse. Defaults to [`InputFormat::Markdown`]. Element spans are
+    /// still offsets into the text as it was given
+    #[must_use]
+    pub const fn input_format(mut self, input_format: InputFormat) -> Self {
+        self.input_format = input_format;
+        self
+    }
+
+    /// Sets the dialect of Markdown to parse. Defaults to [`Dialect::Sani`]. This also sets
+    /// [`single_tilde_strikethrough`](Self::single_tilde_strikethrough) and
+    /// [`wiki_links`](Self::wiki_links) to match the dialect
+    #[must_use]
+    pub const fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self.single_tilde_strikethrough = matches!(dialect, Dialect::Gfm);
+        self.wiki_links = matches!(dialect, Dialect::Obsidian);
+        self
+    }
+
+    /// Allows strikethrough with a single tilde (`~lorem~`) as well as with two (`~~lorem~~`).
+    /// Without this, a single tilde is just a tilde
+    #[must_use]
+    pub const fn single_tilde_strikethrough(mut self, single_tilde_strikethrough: bool) -> Self {
+        self.single_tilde_strikethrough = single_tilde_strikethrough;
+        self
+    }
+
+    /// Decodes entity and numeric character references (`&amp;`, `&copy;`, `&#x1F600;`) in inline
+    /// text into the characters they stand for. Without this, they are shown as written
+    #[must_use]
+    pub const fn entities(mut self, entities: bool) -> Self {
+        self.entities = entities;
+        self
+    }
+
+    /// Enables abbreviation definitions (`*[HTML]: HyperText Markup Language`). Definition lines
+    /// are removed, and every use of a defined abbreviation is given a dotted underline, with the
+    /// expansion shown after its first use
+    #[must_use]
+    pub const fn abbreviations(mut self, abbreviations: bool) -> Self {
+        self.abbreviations = abbreviations;
+        self
+    }
+
+    /// Makes links of wiki links, `[[Page]]` and `[[Page|label]]`, as used by wikis and notes apps.
+    /// They link to the page name as it is written, which a
+    /// [link resolver](RenderOptions::link_resolver) can make into a URL or path
+    #[must_use]
+    pub const fn wiki_links(mut self, wiki_links: bool) -> Self {
+        self.wiki_links = wiki_links;
+        self
+    }
+
+    /// Sets what to do with invisible characters. Defaults to [`Invisibles::Keep`]. Joiners between
+    /// characters outside ASCII, as in emoji sequences and some scripts, are always kept, and a
+    /// byte order mark at the start of the text is always skipped. Element spans are still offsets
+    /// into the text as it was given
+    #[must_use]
+    pub const fn invisibles(mut self, invisibles: Invisibles) -> Self {
+        self.invisibles = invisibles;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod width {
+        use super::*;
+
+        #[test]
+        fn narrowing() {
+            let options = RenderOptions::new().width(10);
+            assert_eq!(Some(6), options.clone().narrowed(2).narrowed(2).width);
+            assert_eq!(Some(0), options.narrowed(12).width);
+            assert_eq!(None, RenderOptions::new().narrowed(2).width);
+        }
+    }
+
+    mod themes {
+        use super::*;
+
+        #[test]
+        fn themes_are_downgraded_whichever_is_set_first() {
+            use crate::formatting::{Colour, Format};
+
+            let theme = Theme::dark().code(Format::new().set_foreground(Colour::Rgb(250, 20, 30)));
+            let expected = Theme::dark().code(Format::new().set_foreground(Colour::BrightRed));
+            assert_eq!(
+                expected,
+                RenderOptions::new()
+                    .theme(theme.clone())
+                    .colour_depth(ColourDepth::Standard)
+                    .theme
+            );
+            assert_eq!(
+                expected,
+                RenderOptions::new()
+                    .colour_depth(ColourDepth::Standard)
+                    .theme(theme)
+                    .theme
+            );
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use crate::code_block::fence_for;
+use crate::input::{constrained, lines, Prepared};
+
+/// The emphasis markers and the Markdown each is translated into. Underlining has no Markdown,
+/// so it is left as plain text
+const EMPHASIS: [(char, &str); 6] = [
+    ('*', "**"),
+    ('/', "*"),
+    ('=', "`"),
+    ('~', "`"),
+    ('+', "~~"),
+    ('_', ""),
+];
+
+/// The keywords at the start of a document that are kept, in its front matter, so that they
+/// become a [title block](crate::title::TitleBlock)
+const TITLE_KEYWORDS: [&str; 3] = ["title", "author", "date"];
+
+/// Parses a keyword line (`#+TITLE: Lorem`), returning the keyword, in lowercase, and its value
+fn keyword(line: &str) -> Option<(String, &str)> {
+    let (keyword, value) = line.trim_start().strip_prefix("#+")?.split_once(':')?;
+    (!keyword.is_empty() && !keyword.contains(char::is_whitespace))
+        .then(|| (keyword.to_lowercase(), value.trim()))
+}
+
+/// Parses a headline (`** TODO Lorem :tag:`), returning its level and its title without any tags
+fn headline(line: &str) -> Option<(usize, &str)> {
+    let title = line.trim_start_matches('*');
+    let level = line.len() - title.len();
+    let title = title.strip_prefix(' ')?.trim();
+    let title = match title.rsplit_once(char::is_whitespace) {
+        Some((before, tags)) if tags.len() > 2 && tags.starts_with(':') && tags.ends_with(':') => {
+            before.trim_end()
+        }
+        _ => title,
+    };
+    (level > 0 && !title.is_empty()).then_some((level, title))
+}
+
+/// Whether `line` begins a block (`#+BEGIN_SRC`) of the given type, returning what follows the
+/// type, such as the language of a source block
+fn begin<'a>(line: &'a str, block: &str) -> Option<&'a str> {
+    let rest = line.trim_start().get(.."#+begin_".len() + block.len())?;
+    rest.eq_ignore_ascii_case(&format!("#+begin_{block}"))
+        .then(|| {
+            line.trim_start()
+                .get(rest.len()..)
+                .unwrap_or_default()
+                .trim()
+        })
+}
+
+/// Whether `line` ends a block of the given type (`#+END_SRC`)
+fn is_end(line: &str, block: &str) -> bool {
+    line.trim().eq_ignore_ascii_case(&format!("#+end_{block}"))
+}
+
+/// Parses a link at the start of `text` (`[[target][label]]` or `[[target]]`), returning its
+/// length, target and label
+fn link(text: &str) -> Option<(usize, &str, &str)> {
+    let inner = text.strip_prefix("[[")?;
+    let end = inner.find("]]")?;
+    let inner = inner.get(..end)?;
+    let (target, label) = inner.split_once("][").unwrap_or((inner, ""));
+    (!target.is_empty()).then_some((end + 4, target, label))
+}
+
+/// Translates the inline markup of a line of Org into Markdown: emphasis as [`EMPHASIS`] gives,
+/// and links into wiki links (`[[target|label]]`)
+fn inline(line: &str) -> String {
+    let mut markdown = String::with_capacity(line.len());
+    let mut rest = line;
+    let mut previous = None;
+    while let Some(c) = rest.chars().next() {
+        let at_boundary = previous.is_none_or(|previous: char| {
+            previous.is_whitespace() || matches!(previous, '-' | '(' | '{' | '\'' | '"')
+        });
+        let mut translated = link(rest).map(|(length, target, label)| {
+            let markup = if label.is_empty() {
+                format!("[[{target}]]")
+            } else {
+                format!("[[{target}|{label}]]")
+            };
+            (length, markup)
+        });
+        if let Some(&(mark, delimiter)) = EMPHASIS
+            .iter()
+            .find(|(mark, _)| *mark == c)
+            .filter(|_| at_boundary && translated.is_none())
+        {
+            translated = constrained(rest, mark).map(|content| {
+                // verbatim text and code are left as they are
+                let translated = if delimiter == "`" {
+                    content.to_owned()
+                } else {
+                    inline(content)
+                };
+                (
+                    content.len() + 2,
+                    format!("{delimiter}{translated}{delimiter}"),
+                )
+            });
+        }
+
+        if let Some((length, translated)) = translated {
+            markdown.push_str(&translated);
+            previous = rest.get(..length).and_then(|markup| markup.chars().last());
+            rest = rest.get(length..).unwrap_or_default();
+        } else {
+            markdown.push(c);
+            previous = Some(c);
+            rest = rest.get(c.len_utf8()..).unwrap_or_default();
+        }
+    }
+    markdown
+}
+
+/// Translates basic Org into Markdown, a line at a time so that element spans can be mapped back
+/// onto it. Headlines become headings, without their tags; source and example blocks become fenced
+/// code blocks, and quote blocks blockquotes; the separators of tables are translated; and a
+/// title, author or date among the keywords at the start becomes front matter. Other keywords,
+/// comments and drawers (such as `:PROPERTIES:`) are left out
+pub(crate) fn to_markdown(text: &str) -> Prepared<'static> {
+    let lines = lines(text);
+    let mut markdown = Prepared::lines();
+    let mut index = 0;
+
+    let header: Vec<_> = lines
+        .iter()
+        .enumerate()
+        .take_while(|(_, (_, line))| line.trim().is_empty() || keyword(line).is_some())
+        .filter_map(|(index, (offset, line))| Some((index, *offset, keyword(line)?)))
+        .filter(|(_, _, (keyword, _))| TITLE_KEYWORDS.contains(&keyword.as_str()))
+        .collect();
+    if let Some(&(last_index, _, _)) = header.last() {
+        // the closing line stands for the end of the last keyword, so the title block spans them
+        let last = lines
+            .get(last_index)
+            .map_or(0, |(offset, line)| offset + line.len());
+        // the anchors have to go on increasing, so the lines up to the last of these keywords
+        // are skipped below, rather than any blank lines before them being pushed after them
+        markdown.push_line("---", 0);
+        for (_, offset, (keyword, value)) in &header {
+            markdown.push_line(&format!("{keyword}: {value}"), *offset);
+        }
+        markdown.push_line("---", last);
+        index = last_index + 1;
+    }
+
+    while let Some(&(offset, line)) = lines.get(index) {
+        index += 1;
+
+        let block = ["src", "example"]
+            .into_iter()
+            .find_map(|block| Some((block, begin(line, block)?)));
+        if let Some((block, arguments)) = block {
+            let language = match block {
+                "src" => arguments.split_whitespace().next().unwrap_or_default(),
+                _ => "",
+            };
+            let end = lines
+                .iter()
+                .skip(index)
+                .position(|(_, line)| is_end(line, block))
+                .map_or(lines.len(), |length| index + length);
+            let content = lines.get(index..end).unwrap_or_default();
+            let close = lines.get(end).map_or(text.len(), |(offset, _)| *offset);
+            index = end + 1;
+            let code: Vec<_> = content.iter().map(|(_, line)| *line).collect();
+            let fence = fence_for(&code.join("\n"));
+            markdown.push_line(&format!("{fence}{language}"), offset);
+            for (offset, line) in content {
+                markdown.push_line(line, *offset);
+            }
+            markdown.push_line(&fence, close);
+            continue;
+        }
+        if begin(line, "quote").is_some() {
+            while let Some(&(offset, line)) = lines.get(index) {
+                index += 1;
+                if is_end(line, "quote") {
+                    break;
+                }
+                markdown.push_line(&format!("> {}", inline(line.trim())), offset);
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.eq_ignore_ascii_case(":properties:") || trimmed.eq_ignore_ascii_case(":logbook:")
+        {
+            index = lines
+                .iter()
+                .skip(index)
+                .position(|(_, line)| line.trim().eq_ignore_ascii_case(":end:"))
+                .map_or(lines.len(), |length| index + length + 1);
+            continue;
+        }
+        if keyword(line).is_some() || trimmed == "#" || trimmed.starts_with("# ") {
+            continue;
+        }
+
+        if trimmed.len() >= 5 && trimmed.chars().all(|c| c == '-') {
+            markdown.push_line("---", offset);
+        } else if let Some((level, title)) = headline(line) {
+            markdown.push_line(
+                &format!("{} {}", "#".repeat(level.min(6)), inline(title)),
+                offset,
+            );
+        } else if trimmed.starts_with("|-") {
+            markdown.push_line(&line.replace('+', "|"), offset);
+        } else {
+            markdown.push_line(&inline(line), offset);
+        }
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translate(text: &str) -> String {
+        to_markdown(text).text.into_owned()
+    }
+
+    mod blocks {
+        use super::*;
+
+        #[test]
+        fn headlines_become_headings_without_tags() {
+            assert_eq!(
+                "# Lorem\n## TODO Ipsum\n",
+                translate("* Lorem\n** TODO Ipsum   :work:urgent:\n")
+            );
+        }
+
+        #[test]
+        fn source_blocks_become_fenced_code() {
+            assert_eq!(
+                "```python\nprint(1)\n```\nlorem\n",
+                translate("#+BEGIN_SRC python :results output\nprint(1)\n#+END_SRC\nlorem\n")
+            );
+        }
+    }
+
+    mod front_matter {
+        use super::*;
+
+        #[test]
+        fn front_matter_after_blank_lines_keeps_anchors_in_order() {
+            let text = "\r\n#+TITLE: Lorem\n\nipsum\n";
+            let markdown = to_markdown(text);
+            assert_eq!("---\ntitle: Lorem\n---\n\nipsum\n", markdown.text);
+            assert!(markdown
+                .anchors
+                .windows(2)
+                .all(|pair| matches!(pair, [(_, a), (_, b)] if a <= b)));
+            let elements = crate::parse_with_options(
+                text,
+                &crate::options::ParserOptions::new()
+                    .input_format(crate::options::InputFormat::Org),
+            );
+            assert!(elements
+                .iter()
+                .all(|element| text.get(element.span()).is_some()));
+        }
+
+        #[test]
+        fn leading_keywords_become_front_matter() {
+            let markdown =
+                to_markdown("#+TITLE: Lorem\n#+OPTIONS: toc:nil\n\n# a comment\nipsum\n");
+            assert_eq!("---\ntitle: Lorem\n---\n\nipsum\n", markdown.text);
+            assert_eq!(47, markdown.original_offset(22));
+        }
+    }
+
+    mod inline {
+        use super::*;
+
+        #[test]
+        fn inline_markup() {
+            assert_eq!(
+                "**lorem** *ipsum* `dolor` `s*t` ~~amet~~ consectetur [[https://example.com|adipiscing]] 1/2/3",
+                inline("*lorem* /ipsum/ =dolor= ~s*t~ +amet+ _consectetur_ [[https://example.com][adipiscing]] 1/2/3")
+            );
+        }
+    }
+}
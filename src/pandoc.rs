@@ -0,0 +1,423 @@
+use std::fmt::{self, Display};
+
+use serde_json::Value;
+
+use crate::code_block::fence_for;
+
+/// Pandoc JSON that couldn't be read
+#[derive(Debug)]
+pub enum PandocError {
+    Json(serde_json::Error),
+    /// The JSON is valid, but isn't a pandoc document, which has a list of `blocks`
+    NotADocument,
+}
+
+impl Display for PandocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "invalid pandoc JSON: {error}"),
+            Self::NotADocument => f.write_str("not a pandoc document, as it has no `blocks`"),
+        }
+    }
+}
+
+impl std::error::Error for PandocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(error) => Some(error),
+            Self::NotADocument => None,
+        }
+    }
+}
+
+/// The type of a pandoc element, and its contents
+fn element(value: &Value) -> (&str, &Value) {
+    (
+        value.get("t").and_then(Value::as_str).unwrap_or_default(),
+        value.get("c").unwrap_or(&Value::Null),
+    )
+}
+
+/// The items of an array in pandoc's JSON, or none if it isn't one
+fn items(value: &Value) -> &[Value] {
+    value.as_array().map_or(&[], Vec::as_slice)
+}
+
+/// Escapes the characters in `text` that would otherwise be taken as Markdown
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '~' | '`' | '[' | ']' | '&' | '|' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Writes `code` as a code span, with one more backtick around it than the longest run in it, so
+/// that none can end it
+fn code_span(code: &str) -> String {
+    let longest = code
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest + 1);
+    format!("{fence}{code}{fence}")
+}
+
+/// The text of `inlines` without any of their formatting
+fn plain(inlines: &Value) -> String {
+    items(inlines)
+        .iter()
+        .map(|inline| match element(inline) {
+            ("Str", text) => text.as_str().unwrap_or_default().to_owned(),
+            ("Space" | "SoftBreak" | "LineBreak", _) => " ".to_owned(),
+            ("Code" | "Math", contents) => items(contents)
+                .get(1)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned(),
+            (
+                "Emph" | "Strong" | "Strikeout" | "Underline" | "SmallCaps" | "Superscript"
+                | "Subscript",
+                contents,
+            ) => plain(contents),
+            ("Span" | "Link" | "Image" | "Quoted" | "Cite", contents) => {
+                plain(items(contents).get(1).unwrap_or(&Value::Null))
+            }
+            _ => String::new(),
+        })
+        .collect()
+}
+
+/// Writes an inline as Markdown. Links become wiki links (`[[target|label]]`), and images their
+/// descriptions, while footnotes and raw inlines are left out
+fn inline(inline: &Value) -> String {
+    let (kind, contents) = element(inline);
+    let second = items(contents).get(1).unwrap_or(&Value::Null);
+    match kind {
+        "Str" => escape(contents.as_str().unwrap_or_default()),
+        "Space" | "SoftBreak" => " ".to_owned(),
+        "LineBreak" => "\\\n".to_owned(),
+        "Emph" => format!("*{}*", inlines(contents)),
+        "Strong" => format!("**{}**", inlines(contents)),
+        "Strikeout" => format!("~~{}~~", inlines(contents)),
+        "Underline" | "SmallCaps" | "Superscript" | "Subscript" => inlines(contents),
+        "Span" | "Cite" | "Image" => inlines(second),
+        "Quoted" => {
+            let double =
+                element(items(contents).first().unwrap_or(&Value::Null)).0 == "DoubleQuote";
+            if double {
+                format!("“{}”", inlines(second))
+            } else {
+                format!("‘{}’", inlines(second))
+            }
+        }
+        "Code" | "Math" => code_span(second.as_str().unwrap_or_default()),
+        "Link" => {
+            let target = items(contents)
+                .get(2)
+                .and_then(|target| target.get(0))
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let label = plain(second);
+            if label.is_empty() || label == target {
+                format!("[[{target}]]")
+            } else {
+                format!("[[{target}|{label}]]")
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Writes a list of inlines as Markdown
+fn inlines(inlines: &Value) -> String {
+    items(inlines).iter().map(inline).collect()
+}
+
+/// Puts `prefix` before the first line of `text`, and `indent` before each line after it that
+/// isn't blank
+fn hang(prefix: &str, indent: &str, text: &str) -> String {
+    let mut lines = text.split('\n');
+    let mut hung = format!("{prefix}{}", lines.next().unwrap_or_default());
+    for line in lines {
+        hung.push('\n');
+        if !line.is_empty() {
+            hung += indent;
+            hung += line;
+        }
+    }
+    hung
+}
+
+/// Writes the items of a list, each with its marker, one after another if the list is tight
+fn list(items_: &[Value], markers: impl Iterator<Item = String>) -> String {
+    let tight = items_
+        .iter()
+        .all(|item| items(item).iter().all(|block| element(block).0 != "Para"));
+    let written: Vec<_> = items_
+        .iter()
+        .zip(markers)
+        .map(|(item, marker)| {
+            let indent = " ".repeat(marker.len());
+            hang(&marker, &indent, &blocks(items(item)))
+        })
+        .collect();
+    written.join(if tight { "\n" } else { "\n\n" })
+}
+
+/// Writes a table as a pipe table, with the text of each cell on one line
+fn table(contents: &[Value]) -> Option<String> {
+    let alignments: Vec<_> = items(contents.get(2)?)
+        .iter()
+        .map(
+            |column| match element(items(column).first().unwrap_or(&Value::Null)).0 {
+                "AlignLeft" => ":--",
+                "AlignCenter" => ":-:",
+                "AlignRight" => "--:",
+                _ => "---",
+            },
+        )
+        .collect();
+    let rows = |rows: &Value| -> Vec<Vec<String>> {
+        items(rows)
+            .iter()
+            .map(|row| {
+                items(items(row).get(1).unwrap_or(&Value::Null))
+                    .iter()
+                    .map(|cell| {
+                        let cell = items(items(cell).get(4).unwrap_or(&Value::Null));
+                        blocks(cell).replace('\n', " ")
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    let mut head = rows(items(contents.get(3)?).get(1)?);
+    let mut body: Vec<_> = items(contents.get(4)?)
+        .iter()
+        .flat_map(|body| {
+            let body = items(body);
+            let mut body_rows = rows(body.get(2).unwrap_or(&Value::Null));
+            body_rows.extend(rows(body.get(3).unwrap_or(&Value::Null)));
+            body_rows
+        })
+        .collect();
+    let header = if head.is_empty() {
+        vec![String::new(); alignments.len()]
+    } else {
+        body.splice(0..0, head.drain(1..));
+        head.swap_remove(0)
+    };
+    let line = |cells: &[String]| format!("| {} |", cells.join(" | "));
+    let mut lines = vec![
+        line(&header),
+        line(
+            &alignments
+                .iter()
+                .map(|a| (*a).to_owned())
+                .collect::<Vec<_>>(),
+        ),
+    ];
+    lines.extend(body.iter().map(|row| line(row)));
+    Some(lines.join("\n"))
+}
+
+/// Writes a block as Markdown, or gives `None` for one that has no Markdown, such as raw blocks
+/// for other formats
+fn block(block: &Value) -> Option<String> {
+    let (kind, contents) = element(block);
+    let parts = items(contents);
+    Some(match kind {
+        "Plain" | "Para" => inlines(contents),
+        "Header" => {
+            let level = parts.first()?.as_u64()?.clamp(1, 6);
+            let level = usize::try_from(level).ok()?;
+            format!("{} {}", "#".repeat(level), inlines(parts.get(2)?))
+        }
+        "CodeBlock" => {
+            let language = items(items(parts.first()?).get(1)?)
+                .first()
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let code = parts.get(1)?.as_str()?;
+            let fence = fence_for(code);
+            format!("{fence}{language}\n{code}\n{fence}")
+        }
+        "RawBlock" => {
+            let format = parts.first()?.as_str()?;
+            let text = parts.get(1)?.as_str()?;
+            let is_comment = format == "html" && text.trim_start().starts_with("<!--");
+            (format == "markdown" || is_comment).then(|| text.to_owned())?
+        }
+        "BlockQuote" => hang("> ", "> ", &blocks(parts)).replace("\n\n", "\n>\n"),
+        "BulletList" => list(parts, std::iter::repeat("- ".to_owned())),
+        "OrderedList" => {
+            let attributes = items(parts.first()?);
+            let start = attributes.first().and_then(Value::as_u64).unwrap_or(1);
+            let delimiter = match element(attributes.get(2).unwrap_or(&Value::Null)).0 {
+                "OneParen" | "TwoParens" => ')',
+                _ => '.',
+            };
+            list(
+                items(parts.get(1)?),
+                (start..).map(|number| format!("{number}{delimiter} ")),
+            )
+        }
+        "HorizontalRule" => "---".to_owned(),
+        "Table" => table(parts)?,
+        "Div" => blocks(items(parts.get(1)?)),
+        "Figure" => blocks(items(parts.get(2)?)),
+        "LineBlock" => parts.iter().map(inlines).collect::<Vec<_>>().join("\\\n"),
+        "DefinitionList" => parts
+            .iter()
+            .map(|entry| {
+                let entry = items(entry);
+                let term = format!("**{}**", inlines(entry.first().unwrap_or(&Value::Null)));
+                let definitions = items(entry.get(1).unwrap_or(&Value::Null))
+                    .iter()
+                    .map(|definition| hang("> ", "> ", &blocks(items(definition))));
+                std::iter::once(term)
+                    .chain(definitions)
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => return None,
+    })
+}
+
+/// Writes blocks as Markdown, with blank lines between them
+fn blocks(blocks: &[Value]) -> String {
+    blocks
+        .iter()
+        .filter_map(block)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The text of a value in a document's metadata
+fn meta_text(value: &Value) -> String {
+    match element(value) {
+        ("MetaString", text) => text.as_str().unwrap_or_default().to_owned(),
+        ("MetaInlines", contents) => plain(contents),
+        ("MetaBlocks", contents) => blocks(items(contents)),
+        ("MetaList", contents) => items(contents)
+            .iter()
+            .map(meta_text)
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => String::new(),
+    }
+}
+
+/// Turns pandoc's JSON representation of a document, as written by `pandoc -t json`, into
+/// Markdown. The title, author and date in its metadata become front matter, so that they are
+/// shown as a [title block](crate::title::TitleBlock). Links become wiki links
+/// (`[[target|label]]`), so should be parsed with [wiki links](crate::options::ParserOptions::wiki_links)
+/// on. Anything without Markdown, such as footnotes and raw blocks for other formats, is left out
+///
+/// # Errors
+///
+/// Returns a [`PandocError`] if `json` isn't valid JSON, or isn't a pandoc document
+pub fn to_markdown(json: &str) -> Result<String, PandocError> {
+    let document: Value = serde_json::from_str(json).map_err(PandocError::Json)?;
+    let body = blocks(
+        document
+            .get("blocks")
+            .and_then(Value::as_array)
+            .ok_or(PandocError::NotADocument)?,
+    );
+    let front_matter: Vec<_> = ["title", "author", "date"]
+        .into_iter()
+        .filter_map(|key| {
+            let value = meta_text(document.get("meta")?.get(key)?);
+            (!value.is_empty()).then(|| format!("{key}: {}", value.replace('\n', " ")))
+        })
+        .collect();
+    Ok(if front_matter.is_empty() {
+        body
+    } else {
+        format!("---\n{}\n---\n{body}", front_matter.join("\n"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod documents {
+        use super::*;
+
+        #[test]
+        fn documents_become_markdown() {
+            let json = r#"{
+                "pandoc-api-version": [1, 23, 1],
+                "meta": {"title": {"t": "MetaInlines", "c": [{"t": "Str", "c": "Lorem"}]}},
+                "blocks": [
+                    {"t": "Header", "c": [2, ["ipsum", [], []], [{"t": "Str", "c": "Ipsum"}]]},
+                    {"t": "Para", "c": [
+                        {"t": "Emph", "c": [{"t": "Str", "c": "dolor*"}]},
+                        {"t": "Space"},
+                        {"t": "Link", "c": [["", [], []], [{"t": "Str", "c": "sit"}], ["https://example.com", ""]]}
+                    ]},
+                    {"t": "BulletList", "c": [
+                        [{"t": "Plain", "c": [{"t": "Str", "c": "amet"}]}],
+                        [{"t": "CodeBlock", "c": [["", ["rust"], []], "fn main() {}"]}]
+                    ]},
+                    {"t": "BlockQuote", "c": [
+                        {"t": "Para", "c": [{"t": "Str", "c": "consectetur"}]},
+                        {"t": "Para", "c": [{"t": "Code", "c": [["", [], []], "a`b"]}]}
+                    ]}
+                ]
+            }"#;
+            assert_eq!(
+                concat!(
+                    "---\ntitle: Lorem\n---\n",
+                    "## Ipsum\n\n",
+                    "*dolor\\** [[https://example.com|sit]]\n\n",
+                    "- amet\n- ```rust\n  fn main() {}\n  ```\n\n",
+                    "> consectetur\n>\n> ``a`b``",
+                ),
+                to_markdown(json).unwrap()
+            );
+        }
+
+        #[test]
+        fn tables_become_pipe_tables() {
+            let cell = |text: &str| {
+                serde_json::json!([["", [], []], {"t": "AlignDefault"}, 1, 1,
+                    [{"t": "Plain", "c": [{"t": "Str", "c": text}]}]])
+            };
+            let table = serde_json::json!({"t": "Table", "c": [
+                ["", [], []],
+                [null, []],
+                [[{"t": "AlignLeft"}, {"t": "ColWidthDefault"}], [{"t": "AlignRight"}, {"t": "ColWidthDefault"}]],
+                [["", [], []], [[["", [], []], [cell("a"), cell("b")]]]],
+                [[["", [], []], 0, [], [[["", [], []], [cell("1"), cell("2")]]]]],
+                [["", [], []], []]
+            ]});
+            assert_eq!(
+                Some("| a | b |\n| :-- | --: |\n| 1 | 2 |".to_owned()),
+                block(&table)
+            );
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        #[test]
+        fn other_json_is_an_error() {
+            assert!(matches!(
+                to_markdown(r#"{"cells": []}"#),
+                Err(PandocError::NotADocument)
+            ));
+            assert!(matches!(to_markdown("{"), Err(PandocError::Json(_))));
+        }
+    }
+}
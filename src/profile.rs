@@ -0,0 +1,229 @@
+use std::fmt::{self, Display};
+use std::hint::black_box;
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
+use crate::markdown::ElementKind;
+use crate::options::{ParserOptions, RenderOptions};
+
+/// How many of the slowest blocks a [`Profile`] lists when shown
+const SLOWEST: usize = 5;
+
+/// How long a block of a document took to parse and render
+#[derive(Clone, Debug)]
+pub struct BlockTiming {
+    pub kind: ElementKind,
+    /// The lines of the document the block is on, counting from one
+    pub lines: RangeInclusive<usize>,
+    /// How long the block takes to parse on its own, apart from the rest of the document
+    pub parse: Duration,
+    pub render: Duration,
+}
+
+impl BlockTiming {
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.parse + self.render
+    }
+}
+
+/// The time spent on the blocks of one kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KindTiming {
+    pub kind: ElementKind,
+    pub blocks: usize,
+    pub parse: Duration,
+    pub render: Duration,
+}
+
+/// How long parsing and rendering a document for the terminal took, as a whole and block by
+/// block, for finding out what makes a document slow. Shown, it is a report of the time spent on
+/// each kind of block and on the slowest blocks, with the lines they are on
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub parse: Duration,
+    pub render: Duration,
+    pub blocks: Vec<BlockTiming>,
+}
+
+/// The one-based line of `text` that the byte at `offset` is on
+fn line_of(text: &str, offset: usize) -> usize {
+    let before = text.get(..offset).unwrap_or(text);
+    before.matches('\n').count() + 1
+}
+
+/// What a kind of block is called in a profile
+const fn name(kind: ElementKind) -> &'static str {
+    match kind {
+        ElementKind::Paragraph => "paragraph",
+        ElementKind::Heading => "heading",
+        ElementKind::ThematicBreak => "thematic break",
+        ElementKind::Table => "table",
+        ElementKind::List => "list",
+        ElementKind::CodeBlock => "code block",
+        ElementKind::Blockquote => "blockquote",
+        ElementKind::Ellipsis => "ellipsis",
+        ElementKind::Comment => "comment",
+        ElementKind::TitleBlock => "title block",
+    }
+}
+
+impl Profile {
+    /// Parses `text` and renders it for the terminal, timing the whole document and then each of
+    /// its blocks
+    #[must_use]
+    pub fn measure(text: &str, parser_options: &ParserOptions, options: &RenderOptions) -> Self {
+        let start = Instant::now();
+        let elements = crate::parse_with_options(text, parser_options);
+        let parse = start.elapsed();
+
+        let mut output = String::new();
+        let blocks = elements
+            .iter()
+            .map(|element| {
+                let span = element.span();
+                let source = text.get(span.clone()).unwrap_or_default();
+                let start = Instant::now();
+                black_box(crate::parse_with_options(source, parser_options));
+                let parse = start.elapsed();
+
+                let start = Instant::now();
+                if !element.is_hidden(options) {
+                    element.render_into(options, &mut output);
+                }
+                let render = start.elapsed();
+                output.clear();
+
+                let last = span.end.saturating_sub(1).max(span.start);
+                BlockTiming {
+                    kind: element.kind(),
+                    lines: line_of(text, span.start)..=line_of(text, last),
+                    parse,
+                    render,
+                }
+            })
+            .collect();
+
+        let start = Instant::now();
+        black_box(crate::render(elements, options));
+        let render = start.elapsed();
+        Self {
+            parse,
+            render,
+            blocks,
+        }
+    }
+
+    /// The time spent on each kind of block, slowest first
+    #[must_use]
+    pub fn by_kind(&self) -> Vec<KindTiming> {
+        let mut kinds: Vec<KindTiming> = Vec::new();
+        for block in &self.blocks {
+            if let Some(kind) = kinds.iter_mut().find(|kind| kind.kind == block.kind) {
+                kind.blocks += 1;
+                kind.parse += block.parse;
+                kind.render += block.render;
+            } else {
+                kinds.push(KindTiming {
+                    kind: block.kind,
+                    blocks: 1,
+                    parse: block.parse,
+                    render: block.render,
+                });
+            }
+        }
+        kinds.sort_by_key(|kind| std::cmp::Reverse(kind.parse + kind.render));
+        kinds
+    }
+
+    /// The `count` slowest blocks, slowest first
+    #[must_use]
+    pub fn slowest(&self, count: usize) -> Vec<&BlockTiming> {
+        let mut blocks: Vec<_> = self.blocks.iter().collect();
+        blocks.sort_by_key(|block| std::cmp::Reverse(block.total()));
+        blocks.truncate(count);
+        blocks
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |duration: Duration| format!("{duration:.2?}");
+        writeln!(
+            f,
+            "parsing took {}, rendering {}",
+            time(self.parse),
+            time(self.render)
+        )?;
+
+        writeln!(
+            f,
+            "\n{:<16}{:>8}{:>12}{:>12}",
+            "kind", "blocks", "parsing", "rendering"
+        )?;
+        for kind in self.by_kind() {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>12}{:>12}",
+                name(kind.kind),
+                kind.blocks,
+                time(kind.parse),
+                time(kind.render)
+            )?;
+        }
+
+        writeln!(f, "\nslowest blocks:")?;
+        for block in self.slowest(SLOWEST) {
+            let lines = if block.lines.start() == block.lines.end() {
+                format!("line {}", block.lines.start())
+            } else {
+                format!("lines {}-{}", block.lines.start(), block.lines.end())
+            };
+            writeln!(
+                f,
+                "  {lines:<16}{:<16}{:>12}",
+                name(block.kind),
+                time(block.total())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod timing {
+        use super::*;
+
+        #[test]
+        fn blocks_are_timed_with_their_lines() {
+            let profile = Profile::measure(
+                "# Lorem\n\nipsum\ndolor\n\n```\nsit\n```\n\namet",
+                &ParserOptions::new(),
+                &RenderOptions::new(),
+            );
+            let blocks: Vec<_> = profile
+                .blocks
+                .iter()
+                .map(|block| (block.kind, block.lines.clone()))
+                .collect();
+            assert_eq!(
+                vec![
+                    (ElementKind::Heading, 1..=1),
+                    (ElementKind::Paragraph, 3..=4),
+                    (ElementKind::CodeBlock, 6..=8),
+                    (ElementKind::Paragraph, 10..=10),
+                ],
+                blocks
+            );
+            let kinds = profile.by_kind();
+            assert_eq!(3, kinds.len());
+            assert!(kinds
+                .iter()
+                .any(|kind| kind.kind == ElementKind::Paragraph && kind.blocks == 2));
+            assert_eq!(2, profile.slowest(2).len());
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Bindings for Python, as the `sani` module, built with `maturin build` (which `pyproject.toml`
+//! sets up with this feature)
+
+use pyo3::prelude::*;
+
+use crate::backends::OutputFormat;
+use crate::document::Document;
+use crate::options::RenderOptions;
+
+/// A parsed document. It is never changed once parsed, so it can be shared between Python threads
+/// and rendered from any of them, with the GIL released while it is rendered
+#[pyclass(name = "Document", module = "sani", frozen)]
+struct PyDocument(Document);
+
+#[pymethods]
+impl PyDocument {
+    /// The document's headings, in order, as `(text, level, slug)` tuples
+    fn outline(&self) -> Vec<(String, u8, String)> {
+        self.0
+            .outline()
+            .into_iter()
+            .map(|entry| (entry.text, entry.level, entry.slug))
+            .collect()
+    }
+}
+
+/// Parses `text` into a document
+#[pyfunction]
+fn parse(text: &str) -> PyDocument {
+    PyDocument(Document::parse(text))
+}
+
+/// Renders `document` with formatting as ANSI escape codes if `ansi` is true, or as plain text
+/// otherwise, wrapped to `width` columns if it is given. Other Python threads carry on while it is
+/// rendered
+#[pyfunction]
+#[pyo3(signature = (document, *, ansi = true, width = None))]
+fn render(py: Python<'_>, document: &PyDocument, ansi: bool, width: Option<usize>) -> String {
+    let format = if ansi {
+        OutputFormat::Terminal
+    } else {
+        OutputFormat::Text
+    };
+    let options = width.map_or_else(RenderOptions::new, |width| {
+        RenderOptions::new().width(width)
+    });
+    let document = &document.0;
+    py.detach(|| document.render(format, &options))
+}
+
+#[pymodule]
+fn sani(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyDocument>()?;
+    module.add_function(wrap_pyfunction!(parse, module)?)?;
+    module.add_function(wrap_pyfunction!(render, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,422 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::abbreviations::Abbreviations;
+use crate::backends::Backend;
+use crate::formatting::{Format, Roles};
+use crate::inline::{self, parse_inline};
+use crate::markdown::{
+    render_line, render_lines, with_role_formats, DocumentElement, ElementKind, DEFAULT_RULE_WIDTH,
+};
+use crate::obsidian;
+use crate::options::{Dialect, ParserOptions, RenderOptions};
+use crate::wrapping::wrap;
+
+/// A blockquote (`> lorem`), which can hold any blocks, and can end with an attribution of who
+/// is being quoted. In the Obsidian dialect, it can be a [`Callout`]
+#[derive(Debug, PartialEq)]
+pub struct Blockquote {
+    children: Vec<Box<dyn DocumentElement>>,
+    attribution: Option<Vec<(String, Format)>>,
+    callout: Option<Callout>,
+    span: Range<usize>,
+}
+
+/// A blockquote set apart as a note, tip, warning or the like (`> [!tip] Title`), which is shown
+/// with its title in bold at the top
+#[derive(Debug, PartialEq)]
+pub struct Callout {
+    kind: String,
+    title: Vec<(String, Format)>,
+}
+
+impl Callout {
+    /// The type of callout, as it is written, such as `tip`
+    #[must_use]
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The callout's title, which is its type, capitalised, if it isn't given one
+    #[must_use]
+    pub fn title(&self) -> &[(String, Format)] {
+        &self.title
+    }
+}
+
+impl Blockquote {
+    /// The blocks that make up the quote, not including its attribution
+    #[must_use]
+    pub fn children(&self) -> &[Box<dyn DocumentElement>] {
+        &self.children
+    }
+
+    /// Who or what the quote is attributed to, if anything, without the dash before it
+    #[must_use]
+    pub fn attribution(&self) -> Option<&[(String, Format)]> {
+        self.attribution.as_deref()
+    }
+
+    #[must_use]
+    pub const fn callout(&self) -> Option<&Callout> {
+        self.callout.as_ref()
+    }
+}
+
+impl DocumentElement for Blockquote {
+    fn render(&self, options: &RenderOptions) -> String {
+        let inner_options = inner(options);
+        let mut blocks: Vec<_> = self
+            .children
+            .iter()
+            .filter(|child| !child.is_hidden(&inner_options))
+            .map(|child| child.render(&inner_options))
+            .collect();
+        let content = match &self.attribution {
+            Some(attribution) => {
+                let lines: Vec<_> = attribution_lines(attribution, &inner_options)
+                    .into_iter()
+                    .map(|line| render_line(line, &inner_options))
+                    .collect();
+                // the attribution goes right under the quote, rather than being a block of its own
+                let quote = blocks.join("\n\n");
+                blocks = vec![quote, lines.join("\n")];
+                blocks.retain(|block| !block.is_empty());
+                blocks.join("\n")
+            }
+            None => blocks.join("\n\n"),
+        };
+        // a callout's title goes right above its content
+        let content = match &self.callout {
+            Some(callout) => {
+                let title = render_lines(&callout.title, &inner_options);
+                [title, content]
+                    .into_iter()
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            None => content,
+        };
+        bar(options.theme.glyphs.quote_bar, &content)
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::Blockquote
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.quote_start();
+        if let Some(callout) = &self.callout {
+            backend.paragraph(&callout.title);
+        }
+        for child in &self.children {
+            child.visit(backend);
+        }
+        if let Some(attribution) = &self.attribution {
+            backend.attribution(attribution);
+        }
+        backend.quote_end();
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+        for child in &mut self.children {
+            child.remap_span(map);
+        }
+    }
+}
+
+/// The options for rendering the content of a blockquote, which is narrowed by the bar down its
+/// side and the space after it
+pub(crate) fn inner(options: &RenderOptions) -> RenderOptions {
+    options.clone().narrowed(2)
+}
+
+/// Draws `bar` down the side of rendered content, with a space between it and each line
+pub(crate) fn bar(bar: &str, content: &str) -> String {
+    content
+        .split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                bar.to_owned()
+            } else {
+                format!("{bar} {line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lays out a quote's attribution in italics after a dash, right-aligned to the wrapping width, or
+/// to the width of rules if there isn't one
+pub(crate) fn attribution_lines(
+    attribution: &[(String, Format)],
+    options: &RenderOptions,
+) -> Vec<Vec<(String, Format)>> {
+    let slices: Vec<_> = [(format!("{} ", options.theme.glyphs.dash), Format::new())]
+        .into_iter()
+        .chain(with_role_formats(attribution, options))
+        .map(|(slice, format)| (slice, format.set_italic()))
+        .collect();
+    let width = options.width.unwrap_or(DEFAULT_RULE_WIDTH);
+
+    wrap(&slices, options)
+        .into_iter()
+        .map(|mut line| {
+            let line_width: usize = line.iter().map(|(slice, _)| slice.width()).sum();
+            if line_width < width {
+                line.insert(0, (" ".repeat(width - line_width), Format::new()));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Whether `line` starts a blockquote
+pub(crate) fn starts_quote(line: &str) -> bool {
+    quote_content(line).is_some()
+}
+
+/// The content of a line of a blockquote, after its `>` marker and the space after that, if
+/// there is one, along with the offset of the content into the line
+fn quote_content(line: &str) -> Option<(usize, &str)> {
+    let unindented = line.trim_start_matches(' ');
+    if line.len() - unindented.len() > 3 {
+        return None;
+    }
+    let content = unindented.strip_prefix('>')?;
+    let content = content.strip_prefix(' ').unwrap_or(content);
+    Some((line.len() - content.len(), content))
+}
+
+/// The attribution on a line of a blockquote's content, after the dash (`—` or `--`) it starts
+/// with, if it is one
+fn attribution(content: &str) -> Option<&str> {
+    let author = content
+        .strip_prefix('—')
+        .or_else(|| content.strip_prefix("--"))?;
+    (author.starts_with(' ') && !author.trim().is_empty()).then(|| author.trim())
+}
+
+/// Parses a blockquote starting at the first line of `text`, if one starts there, returning it
+/// along with the number of bytes of `text` it takes up, not counting the newline after its last
+/// line. The quote goes on for as long as there are lines marked with `>`, along with any lines
+/// that lazily continue a paragraph. Its attribution is either given explicitly, on a line
+/// starting with `>--`, or taken from its last line, if that starts with a dash after other
+/// content
+pub(crate) fn parse_quote(
+    text: &str,
+    options: ParserOptions,
+    abbreviations: &mut Abbreviations,
+) -> Option<(Blockquote, usize)> {
+    if !starts_quote(text.split('\n').next().unwrap_or_default()) {
+        return None;
+    }
+
+    // the quote's lines, with their markers taken off, and the offset of each into `text`
+    let mut content: Vec<(usize, &str)> = vec![];
+    let mut explicit_attribution = None;
+    let mut end = 0;
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let line_content = line.trim_end_matches('\n');
+        if let Some((offset, quoted)) = quote_content(line_content) {
+            let explicit = line_content
+                .trim_start()
+                .strip_prefix(">--")
+                .filter(|author| !author.starts_with('-'));
+            match explicit {
+                Some(author) if !author.trim().is_empty() => {
+                    explicit_attribution = Some(author.trim());
+                }
+                _ => content.push((line_start + offset, quoted)),
+            }
+        } else {
+            let unindented = line_content.trim_start_matches(' ');
+            let continues_paragraph = content
+                .last()
+                .is_some_and(|(_, line)| !line.trim().is_empty());
+            if unindented.is_empty()
+                || !continues_paragraph
+                || !crate::list::is_lazy_continuation(line_content)
+            {
+                break;
+            }
+            content.push((
+                line_start + line_content.len() - unindented.len(),
+                unindented,
+            ));
+        }
+        end = line_start + line_content.len();
+        line_start += line.len();
+    }
+
+    let mut attribution_text = explicit_attribution;
+    if attribution_text.is_none() && content.len() > 1 {
+        if let Some(author) = content.last().and_then(|(_, line)| attribution(line)) {
+            attribution_text = Some(author);
+            content.pop();
+            while content
+                .last()
+                .is_some_and(|(_, line)| line.trim().is_empty())
+            {
+                content.pop();
+            }
+        }
+    }
+    let attribution = attribution_text
+        .map(|author| abbreviations.apply(inline::to_slices(&parse_inline(author, &options))));
+    let callout = content
+        .first()
+        .filter(|_| options.dialect == Dialect::Obsidian)
+        .and_then(|(_, line)| obsidian::callout(line))
+        .map(|(kind, title)| {
+            let title = if title.is_empty() {
+                let mut characters = kind.chars();
+                characters
+                    .next()
+                    .map(|first| first.to_uppercase().chain(characters).collect())
+                    .unwrap_or_default()
+            } else {
+                title.to_owned()
+            };
+            Callout {
+                kind: kind.to_owned(),
+                title: inline::to_slices(&parse_inline(&title, &options))
+                    .into_iter()
+                    .map(|(slice, format)| (slice, format.set_roles(Roles::STRONG)))
+                    .collect(),
+            }
+        });
+    if callout.is_some() {
+        content.remove(0);
+    }
+    let (children, _) = crate::parse_lines(&content, options, abbreviations);
+
+    Some((
+        Blockquote {
+            children,
+            attribution,
+            callout,
+            span: 0..end,
+        },
+        end,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{Glyphs, Theme};
+
+    fn parse(text: &str) -> (Blockquote, usize) {
+        parse_quote(text, ParserOptions::new(), &mut Abbreviations::default()).unwrap()
+    }
+
+    fn attribution_text(quote: &Blockquote) -> Option<String> {
+        quote
+            .attribution()
+            .map(|slices| slices.iter().map(|(slice, _)| slice.as_str()).collect())
+    }
+
+    mod parsing {
+        use super::*;
+
+        #[test]
+        fn quotes_end_at_unmarked_lines() {
+            let (quote, length) = parse("> lorem\n>\n> ipsum\ndolor\n\nsit");
+            assert_eq!(2, quote.children().len());
+            assert_eq!(23, length);
+            assert_eq!(
+                vec![2..7, 12..23],
+                quote
+                    .children()
+                    .iter()
+                    .map(|child| child.span())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn trailing_dash_lines_are_attributions() {
+            let (quote, _) = parse("> lorem ipsum\n>\n> — *Cicero*");
+            assert_eq!(Some("Cicero".to_owned()), attribution_text(&quote));
+            assert_eq!(1, quote.children().len());
+
+            let (quote, _) = parse("> lorem\n> -- Cicero");
+            assert_eq!(Some("Cicero".to_owned()), attribution_text(&quote));
+        }
+
+        #[test]
+        fn explicit_attributions() {
+            let (quote, _) = parse(">-- Cicero\n> lorem");
+            assert_eq!(Some("Cicero".to_owned()), attribution_text(&quote));
+            assert_eq!(1, quote.children().len());
+        }
+
+        #[test]
+        fn dashes_alone_are_not_attributions() {
+            let (quote, _) = parse("> — lorem");
+            assert_eq!(None, attribution_text(&quote));
+            let (quote, _) = parse("> lorem\n> --");
+            assert_eq!(None, attribution_text(&quote));
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        #[test]
+        fn callouts_have_their_title_in_bold_above_them() {
+            let options = RenderOptions::new().theme(Theme::dark().glyphs(Glyphs::ascii()));
+            let bold = Format::new().set_bold();
+            let callout = |text| {
+                let options = ParserOptions::new().dialect(Dialect::Obsidian);
+                let (quote, _) = parse_quote(text, options, &mut Abbreviations::default()).unwrap();
+                quote.render(&RenderOptions::new().theme(Theme::dark().glyphs(Glyphs::ascii())))
+            };
+            assert_eq!(
+                format!(
+                    "| {}Lorem ipsum{}\n| dolor",
+                    bold.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&bold)
+                ),
+                callout("> [!tip]- Lorem ipsum\n> dolor")
+            );
+            assert_eq!(
+                format!(
+                    "| {}Warning{}",
+                    bold.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&bold)
+                ),
+                callout("> [!warning]")
+            );
+            // other dialects leave them as they are
+            assert_eq!("| [!tip] lorem", parse("> [!tip] lorem").0.render(&options));
+        }
+
+        #[test]
+        fn attribution_is_right_aligned_in_italics() {
+            let italic = Format::new().set_italic();
+            let options = RenderOptions::new()
+                .width(20)
+                .theme(Theme::dark().glyphs(Glyphs::ascii()));
+            let (quote, _) = parse("> lorem\n>\n> ipsum\n> — Cicero");
+            assert_eq!(
+                format!(
+                    "| lorem\n|\n| ipsum\n|{}{}-- Cicero{}",
+                    " ".repeat(10),
+                    italic.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&italic)
+                ),
+                quote.render(&options)
+            );
+        }
+    }
+}
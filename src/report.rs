@@ -0,0 +1,153 @@
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+/// How serious a [`Report`] is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
+}
+
+/// A message for the user about something that went wrong, shown in the manner of a compiler's
+/// errors: if it is about a place in a file, the line it is on is shown with the place
+/// underlined, and it can give a hint about what to do
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report<'a> {
+    severity: Severity,
+    message: String,
+    source: Option<Source<'a>>,
+    hint: Option<String>,
+}
+
+/// The place in a file that a [`Report`] is about
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Source<'a> {
+    name: &'a str,
+    text: &'a str,
+    span: Range<usize>,
+}
+
+impl<'a> Report<'a> {
+    #[must_use]
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message.into())
+    }
+
+    #[must_use]
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message.into())
+    }
+
+    const fn new(severity: Severity, message: String) -> Self {
+        Self {
+            severity,
+            message,
+            source: None,
+            hint: None,
+        }
+    }
+
+    /// Points the report at the byte range `span` of `text`, the contents of the file called
+    /// `name`
+    #[must_use]
+    pub fn source(mut self, name: &'a str, text: &'a str, span: Range<usize>) -> Self {
+        self.source = Some(Source { name, text, span });
+        self
+    }
+
+    /// Suggests what to do about the problem
+    #[must_use]
+    pub fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        // the width of the line number, with a space after it
+        let mut gutter = "  ".to_owned();
+        if let Some(Source { name, text, span }) = &self.source {
+            let before = text.get(..span.start).unwrap_or(text);
+            let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+            let line_number = before.matches('\n').count() + 1;
+            let line_end = text
+                .get(line_start..)
+                .and_then(|rest| rest.find('\n'))
+                .map_or(text.len(), |end| line_start + end);
+            let line = text.get(line_start..line_end).unwrap_or_default();
+            let leading = before.get(line_start..).unwrap_or_default();
+            // a span running onto later lines is underlined to the end of its first
+            let underlined = text
+                .get(span.start..span.end.min(line_end))
+                .unwrap_or_default();
+
+            let number = line_number.to_string();
+            gutter = " ".repeat(number.len() + 1);
+            let column = leading.chars().count() + 1;
+            write!(
+                f,
+                "\n{}--> {name}:{line_number}:{column}",
+                " ".repeat(number.len())
+            )?;
+            write!(f, "\n{gutter}|\n{number} | {line}")?;
+            write!(
+                f,
+                "\n{gutter}| {}{}",
+                " ".repeat(leading.width()),
+                "^".repeat(underlined.width().max(1))
+            )?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, "\n{gutter}= hint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod places {
+        use super::*;
+
+        #[test]
+        fn places_are_underlined() {
+            let text = "lorem\n\nipsum *dolor\nsit";
+            let report = Report::warning("unclosed emphasis")
+                .source("lorem.md", text, 13..21)
+                .hint("close it");
+            assert_eq!(
+                "warning: unclosed emphasis\n --> lorem.md:3:7\n  |\n3 | ipsum *dolor\n  |       ^^^^^^\n  = hint: close it",
+                report.to_string()
+            );
+        }
+
+        #[test]
+        fn places_are_only_shown_if_there_is_one() {
+            assert_eq!(
+                "error: unable to read file `lorem.md`",
+                Report::error("unable to read file `lorem.md`").to_string()
+            );
+            assert_eq!(
+                "error: lorem\n --> -:1:6\n  |\n1 | lorem\n  |      ^",
+                Report::error("lorem")
+                    .source("-", "lorem", 5..5)
+                    .to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::fmt::Write;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::code_block::fence_for;
+use crate::input::{lines, Prepared};
+
+/// The characters that section titles can be underlined and overlined with
+const ADORNMENTS: &str = "=-`:'\"~^_*+#<>.";
+
+/// The directives whose content is a block of code, with its language as their argument
+const CODE_DIRECTIVES: [&str; 3] = ["code-block", "code", "sourcecode"];
+
+/// The directives for admonitions, which are shown as blockquotes headed with their name
+const ADMONITIONS: [&str; 9] = [
+    "attention",
+    "caution",
+    "danger",
+    "error",
+    "hint",
+    "important",
+    "note",
+    "tip",
+    "warning",
+];
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The character `line` is made up of, if it is an adornment: a run of a single punctuation
+/// character
+fn adornment(line: &str) -> Option<char> {
+    let line = line.trim_end();
+    let first = line.chars().next().filter(|c| ADORNMENTS.contains(*c))?;
+    (line.len() >= 2 && line.chars().all(|c| c == first)).then_some(first)
+}
+
+/// The style of a section title, which sets its level: the character it is adorned with, and
+/// whether it is overlined as well as underlined
+type Style = (char, bool);
+
+/// Parses a section title starting at `line`, with the two lines after it, returning the style of
+/// its adornment, the title and how many lines it takes up
+fn section<'a>(
+    line: &'a str,
+    next: Option<&'a str>,
+    third: Option<&'a str>,
+) -> Option<(Style, &'a str, usize)> {
+    let next = next?;
+    if let (Some(over), Some(under)) = (adornment(line), third.and_then(adornment)) {
+        let title = next.trim();
+        if over == under && !title.is_empty() && line.trim_end().width() >= title.width() {
+            return Some(((over, true), title, 3));
+        }
+    }
+    let under = adornment(next)?;
+    let title = line.trim_end();
+    let is_title = adornment(line).is_none()
+        && indentation(line) == 0
+        && !title.is_empty()
+        && next.trim_end().width() >= title.width();
+    is_title.then_some(((under, false), title, 2))
+}
+
+/// The end of the block indented more than `indent` that starts at `start`, leaving out any blank
+/// lines at its end
+fn indented_block(lines: &[(usize, &str)], start: usize, indent: usize) -> usize {
+    let mut end = start;
+    for (index, (_, line)) in lines.iter().enumerate().skip(start) {
+        if is_blank(line) {
+            continue;
+        }
+        if indentation(line) <= indent {
+            break;
+        }
+        end = index + 1;
+    }
+    end
+}
+
+/// Adds `lines` to `markdown` as a fenced code block in `language`, with their common indentation
+/// taken off
+fn push_code(markdown: &mut Prepared<'static>, language: &str, lines: &[(usize, &str)]) {
+    let dedent = lines
+        .iter()
+        .filter(|(_, line)| !is_blank(line))
+        .map(|(_, line)| indentation(line))
+        .min()
+        .unwrap_or_default();
+    let code: Vec<_> = lines
+        .iter()
+        .map(|(offset, line)| (offset + dedent, line.get(dedent..).unwrap_or_default()))
+        .collect();
+    let fence = fence_for(
+        &code
+            .iter()
+            .map(|(_, line)| *line)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    let (start, end) = match (lines.first(), lines.last()) {
+        (Some((start, _)), Some((offset, line))) => (*start, offset + line.len()),
+        _ => return,
+    };
+    markdown.push_line(&format!("{fence}{language}"), start);
+    for (offset, line) in code {
+        markdown.push_line(line, offset);
+    }
+    markdown.push_line(&fence, end);
+}
+
+/// Whether `name` can be the name of a role, as in `` :code:`lorem` ``
+fn is_role(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+}
+
+/// Translates the inline markup of a line of reStructuredText into Markdown. Inline literals and
+/// emphasis are written the same way in both; interpreted text is written as emphasis, or as the
+/// code, emphasis or strong emphasis its role asks for, and hyperlink references with embedded
+/// URLs (`` `text <url>`_ ``) become wiki links (`[[url|text]]`)
+fn inline(line: &str) -> String {
+    let mut markdown = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(['\\', '`', ':']) {
+        markdown.push_str(rest.get(..start).unwrap_or_default());
+        rest = rest.get(start..).unwrap_or_default();
+
+        // escapes are written the same way in Markdown
+        if rest.starts_with('\\') {
+            let length = rest.chars().nth(1).map_or(0, char::len_utf8) + 1;
+            markdown.push_str(rest.get(..length).unwrap_or(rest));
+            rest = rest.get(length..).unwrap_or_default();
+            continue;
+        }
+        if rest.starts_with("``") {
+            let length = rest
+                .get(2..)
+                .and_then(|literal| literal.find("``"))
+                .map_or(rest.len(), |end| end + 4);
+            markdown.push_str(rest.get(..length).unwrap_or(rest));
+            rest = rest.get(length..).unwrap_or_default();
+            continue;
+        }
+
+        let role = rest
+            .strip_prefix(':')
+            .and_then(|role| role.split_once(":`"))
+            .filter(|(role, _)| is_role(role));
+        let (role, text) = if let Some((role, text)) = role {
+            (Some(role), text)
+        } else if let Some(text) = rest.strip_prefix('`') {
+            (None, text)
+        } else {
+            markdown.push(':');
+            rest = rest.get(1..).unwrap_or_default();
+            continue;
+        };
+        let Some((content, after)) = text.split_once('`') else {
+            markdown.push_str(rest);
+            rest = "";
+            continue;
+        };
+        let (label, target) = match content.strip_suffix('>').and_then(|c| c.rsplit_once('<')) {
+            Some((label, target)) if !label.trim().is_empty() => (label.trim_end(), Some(target)),
+            Some((_, target)) => (target, Some(target)),
+            None => (content, None),
+        };
+        rest = after;
+        // writing to a `String` can't fail
+        if role.is_none() && rest.starts_with('_') {
+            rest = rest.trim_start_matches('_');
+            let _ = match target {
+                Some(target) => write!(markdown, "[[{target}|{label}]]"),
+                None => write!(markdown, "{label}"),
+            };
+            continue;
+        }
+        let delimiter = match role {
+            Some("code" | "literal" | "samp" | "file" | "command") => "`",
+            Some("strong") => "**",
+            Some(
+                "emphasis" | "title-reference" | "title" | "t" | "dfn" | "term" | "ref" | "doc",
+            )
+            | None => "*",
+            Some(_) => "",
+        };
+        let _ = write!(markdown, "{delimiter}{label}{delimiter}");
+    }
+    markdown.push_str(rest);
+    markdown
+}
+
+/// Translates the list marker at the start of `line`, if it is one Markdown doesn't have: an
+/// auto-numbered item (`#.`) or an enumerator in parentheses (`(1)`)
+fn list_marker(line: &str) -> Option<String> {
+    let indent = indentation(line);
+    let unindented = line.get(indent..)?;
+    if let Some(item) = unindented.strip_prefix("#. ") {
+        return Some(format!("{}1. {item}", " ".repeat(indent)));
+    }
+    let (number, item) = unindented.strip_prefix('(')?.split_once(") ")?;
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+        .then(|| format!("{}{number}) {item}", " ".repeat(indent)))
+}
+
+/// Translates basic reStructuredText into Markdown, a line at a time so that element spans can
+/// be mapped back onto it. Section titles become headings, with their levels in the order their
+/// styles are first used; transitions become thematic breaks; literal blocks, whether after a
+/// paragraph ending with `::` or in a `code-block` directive, become fenced code blocks; and
+/// admonitions become blockquotes. Other directives, comments and hyperlink targets are left out
+pub(crate) fn to_markdown(text: &str) -> Prepared<'static> {
+    let lines = lines(text);
+    let mut markdown = Prepared::lines();
+    let mut styles: Vec<Style> = Vec::new();
+    let mut after_blank = true;
+    let mut literal_next = false;
+    let mut index = 0;
+    while let Some(&(offset, line)) = lines.get(index) {
+        let next = lines.get(index + 1).map(|(_, line)| *line);
+        let third = lines.get(index + 2).map(|(_, line)| *line);
+
+        if is_blank(line) {
+            markdown.push_line("", offset);
+            after_blank = true;
+            index += 1;
+            continue;
+        }
+        if literal_next && indentation(line) > 0 {
+            let end = indented_block(&lines, index, 0);
+            push_code(&mut markdown, "", lines.get(index..end).unwrap_or_default());
+            literal_next = false;
+            index = end;
+            continue;
+        }
+        literal_next = false;
+
+        if let Some((style, title, length)) = section(line, next, third).filter(|_| after_blank) {
+            if !styles.contains(&style) {
+                styles.push(style);
+            }
+            let level = styles.iter().position(|known| *known == style).unwrap_or(0) + 1;
+            let title = inline(title);
+            markdown.push_line(&format!("{} {title}", "#".repeat(level.min(6))), offset);
+            index += length;
+            continue;
+        }
+        if adornment(line).is_some()
+            && line.trim_end().len() >= 4
+            && after_blank
+            && next.is_none_or(is_blank)
+        {
+            markdown.push_line("---", offset);
+            index += 1;
+            continue;
+        }
+        after_blank = false;
+
+        if let Some(markup) = line
+            .trim_start()
+            .strip_prefix("..")
+            .filter(|markup| markup.is_empty() || markup.starts_with(' '))
+        {
+            let indent = indentation(line);
+            let end = indented_block(&lines, index + 1, indent);
+            let body = lines.get(index + 1..end).unwrap_or_default();
+            let (name, argument) = markup.split_once("::").unwrap_or_default();
+            let name = name.trim();
+            // options come first in a directive's body, before a blank line
+            let content_start = body
+                .iter()
+                .position(|(_, line)| !is_blank(line) && !line.trim_start().starts_with(':'))
+                .unwrap_or(body.len());
+            let content = body.get(content_start..).unwrap_or_default();
+            if CODE_DIRECTIVES.contains(&name) {
+                let language = argument.split_whitespace().next().unwrap_or_default();
+                push_code(&mut markdown, language, content);
+            } else if ADMONITIONS.contains(&name) {
+                let mut heading = name.to_owned();
+                if let Some(first) = heading.get_mut(..1) {
+                    first.make_ascii_uppercase();
+                }
+                markdown.push_line(&format!("> **{heading}**"), offset);
+                markdown.push_line(">", offset);
+                let argument = argument.trim();
+                if !argument.is_empty() {
+                    markdown.push_line(&format!("> {}", inline(argument)), offset);
+                }
+                for (offset, line) in content {
+                    markdown.push_line(&format!("> {}", inline(line.trim())), *offset);
+                }
+            }
+            index = end;
+            continue;
+        }
+
+        let line = list_marker(line).unwrap_or_else(|| line.to_owned());
+        let line = match line.trim_end().strip_suffix("::") {
+            Some(paragraph) => {
+                literal_next = true;
+                if paragraph.trim().is_empty() {
+                    index += 1;
+                    continue;
+                } else if paragraph.ends_with(char::is_whitespace) {
+                    paragraph.trim_end().to_owned()
+                } else {
+                    format!("{paragraph}:")
+                }
+            }
+            None => line,
+        };
+        markdown.push_line(&inline(&line), offset);
+        index += 1;
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translate(text: &str) -> String {
+        to_markdown(text).text.into_owned()
+    }
+
+    mod blocks {
+        use super::*;
+
+        #[test]
+        fn section_levels_follow_the_order_of_their_styles() {
+            assert_eq!(
+                "# Lorem\n\n## Ipsum\n\n# Dolor\n",
+                translate("=====\nLorem\n=====\n\nIpsum\n-----\n\n=====\nDolor\n=====\n")
+            );
+        }
+
+        #[test]
+        fn literal_blocks_become_fenced_code() {
+            assert_eq!(
+                "Lorem:\n\n```\nipsum\n  dolor\n```\n\nsit\n",
+                translate("Lorem::\n\n    ipsum\n      dolor\n\nsit\n")
+            );
+            assert_eq!(
+                "```python\nprint(1)\n```\n",
+                translate(".. code-block:: python\n   :linenos:\n\n   print(1)\n")
+            );
+        }
+
+        #[test]
+        fn comments_are_left_out_and_admonitions_quoted() {
+            assert_eq!(
+                "> **Note**\n>\n> Lorem\n> *ipsum*\n",
+                translate(".. a comment\n   over two lines\n.. note::\n\n   Lorem\n   *ipsum*\n")
+            );
+        }
+    }
+
+    mod inline {
+        use super::*;
+
+        #[test]
+        fn inline_markup() {
+            assert_eq!(
+                "*lorem* **ipsum** ``dolor`` [[https://example.com|sit]] `amet` *consectetur*",
+                inline("*lorem* **ipsum** ``dolor`` `sit <https://example.com>`_ :code:`amet` `consectetur`")
+            );
+        }
+    }
+
+    mod spans {
+        use super::*;
+
+        #[test]
+        fn spans_point_into_the_original_text() {
+            let markdown = to_markdown("Lorem\n=====\n\nipsum\n");
+            assert_eq!("# Lorem\n\nipsum\n", markdown.text);
+            assert_eq!(13, markdown.original_offset(9));
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt::Display;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::backends::escape;
+
+/// How often a preview page asks whether the document has changed, in milliseconds
+pub const POLL_INTERVAL: u32 = 500;
+
+/// How long a connection may wait to send a request or take its response before it is dropped, so
+/// that a client that stops part way can't hold up the previews of every other
+#[cfg(not(test))]
+const TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(test)]
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A response to a request for a preview
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: status.to_owned(),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+/// Serves live previews of a document over HTTP, one request at a time, for as long as
+/// `listener` accepts connections. `source` reads the document afresh for every request, and
+/// `render` renders it as HTML. `/` is a page with the rendered document, which polls `/version`
+/// for a hash of the source and reloads itself once it changes. A source that can't be read is
+/// shown as its error until it can be again
+pub fn serve<E: Display>(
+    listener: &TcpListener,
+    title: &str,
+    source: impl Fn() -> Result<String, E>,
+    render: impl Fn(&str) -> String,
+) {
+    for stream in listener.incoming() {
+        // a connection that goes wrong only fails its own request
+        let _ = stream.and_then(|stream| handle(stream, title, &source, &render));
+    }
+}
+
+fn handle<E: Display>(
+    mut stream: TcpStream,
+    title: &str,
+    source: impl Fn() -> Result<String, E>,
+    render: impl Fn(&str) -> String,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut request = BufReader::new(&stream);
+    let mut request_line = String::new();
+    request.read_line(&mut request_line)?;
+    // the headers say nothing a preview needs, but are read so the client isn't cut off
+    let mut header = String::new();
+    while request.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut words = request_line.split_whitespace();
+    let response = match (words.next(), words.next()) {
+        (Some("GET"), Some(target)) => respond(target, title, &source(), render),
+        (Some(_), Some(_)) => Response::error("405 Method Not Allowed"),
+        _ => Response::error("400 Bad Request"),
+    };
+    response.write_to(&mut stream)?;
+    stream.flush()
+}
+
+/// The response to a `GET` request for `target`, given the source as it is now
+fn respond<E: Display>(
+    target: &str,
+    title: &str,
+    source: &Result<String, E>,
+    render: impl Fn(&str) -> String,
+) -> Response {
+    let path = target.split_once('?').map_or(target, |(path, _)| path);
+    let version = match source {
+        Ok(text) => version(text),
+        Err(error) => version(&error.to_string()),
+    };
+    match path {
+        "/" | "/index.html" => {
+            let body = match source {
+                Ok(text) => render(text),
+                Err(error) => format!("<pre>{}</pre>\n", escape(&error.to_string())),
+            };
+            Response::ok("text/html; charset=utf-8", page(title, &body, version))
+        }
+        "/version" => Response::ok("text/plain; charset=utf-8", version.to_string()),
+        _ => Response::error("404 Not Found"),
+    }
+}
+
+/// A hash of the source, which changes whenever the source does
+fn version(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A page showing the rendered `body`, which reloads itself once the version of the source is no
+/// longer `version`
+fn page(title: &str, body: &str, version: u64) -> String {
+    format!(
+        include_str!("serve/page.html"),
+        title = escape(title),
+        body = body,
+        version = version,
+        interval = POLL_INTERVAL
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> String {
+        format!("<p>{text}</p>\n")
+    }
+
+    mod pages {
+        use super::*;
+
+        #[test]
+        fn pages_reload_once_the_source_changes() {
+            let source: Result<_, io::Error> = Ok("lorem".to_owned());
+            let response = respond("/?reload", "a & b", &source, render);
+            assert_eq!("200 OK", response.status);
+            assert!(response.body.contains("<title>a &amp; b</title>"));
+            assert!(response.body.contains("<p>lorem</p>"));
+            assert!(response
+                .body
+                .contains(&format!("!== \"{}\"", version("lorem"))));
+
+            let response = respond("/version", "", &source, render);
+            assert_eq!(version("lorem").to_string(), response.body);
+            assert_ne!(version("lorem"), version("ipsum"));
+        }
+
+        #[test]
+        fn errors_are_shown_in_place_of_the_document() {
+            let source: Result<String, _> = Err(io::Error::other("<gone>"));
+            let response = respond("/", "", &source, render);
+            assert!(response.body.contains("<pre>&lt;gone&gt;</pre>"));
+            assert_eq!(
+                Response::error("404 Not Found"),
+                respond("/lorem", "", &source, render)
+            );
+        }
+    }
+
+    mod connections {
+        use super::*;
+
+        #[test]
+        fn silent_connections_time_out() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            let source = || Ok::<_, io::Error>(String::new());
+            let error = handle(stream, "", source, render).unwrap_err();
+            assert!(matches!(
+                error.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ));
+            drop(client);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::code_block::{is_closing_fence, opening_fence};
+
+/// Gathers Markdown that arrives a line at a time, such as from `tail -f`, into runs of complete
+/// blocks that can be rendered as soon as they are whole. A block is complete at the blank line
+/// after it, unless that line is inside a fenced code block, so anything after the last blank line
+/// waits for more to arrive. Parts of a loose list, with blank lines between its items, are given
+/// as separate runs
+#[derive(Debug, Default)]
+pub struct BlockStream {
+    pending: String,
+    /// The fence of the code block that is open, if there is one
+    fence: Option<String>,
+}
+
+impl BlockStream {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a line, without its line ending, returning the blocks it completes, if it is the blank
+    /// line after them
+    pub fn push_line(&mut self, line: &str) -> Option<String> {
+        match &self.fence {
+            Some(fence) if is_closing_fence(line, fence) => self.fence = None,
+            Some(_) => {}
+            None if line.trim().is_empty() => {
+                return (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending));
+            }
+            None => self.fence = opening_fence(line).map(|(fence, ..)| fence.to_owned()),
+        }
+        self.pending.push_str(line);
+        self.pending.push('\n');
+        None
+    }
+
+    /// Ends the input, returning the blocks that were still waiting for a blank line after them, if
+    /// there are any
+    #[must_use]
+    pub fn finish(self) -> Option<String> {
+        (!self.pending.is_empty()).then_some(self.pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The runs of blocks `lines` are given in, one at a time
+    fn runs(lines: &[&str]) -> Vec<String> {
+        let mut stream = BlockStream::new();
+        let mut runs: Vec<_> = lines
+            .iter()
+            .filter_map(|line| stream.push_line(line))
+            .collect();
+        runs.extend(stream.finish());
+        runs
+    }
+
+    mod blocks {
+        use super::*;
+
+        #[test]
+        fn blocks_end_at_blank_lines() {
+            assert_eq!(
+                vec!["# Lorem\n", "ipsum\ndolor\n", "sit\n"],
+                runs(&["# Lorem", "", "", "ipsum", "dolor", "  ", "sit"])
+            );
+        }
+
+        #[test]
+        fn code_blocks_are_kept_whole() {
+            assert_eq!(
+                vec!["````rust\nlet a = 1;\n\n```\nlet b = 2;\n````\n", "lorem\n"],
+                runs(&[
+                    "````rust",
+                    "let a = 1;",
+                    "",
+                    "```",
+                    "let b = 2;",
+                    "````",
+                    "",
+                    "lorem"
+                ])
+            );
+        }
+    }
+}
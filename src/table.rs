@@ -0,0 +1,783 @@
+use std::mem;
+use std::ops::Range;
+
+use tracing::debug;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::abbreviations::Abbreviations;
+use crate::backends::Backend;
+use crate::formatting::Format;
+use crate::inline::{self, parse_inline};
+use crate::markdown::{with_role_formats, write_line, DocumentElement, ElementKind};
+use crate::options::{ParserOptions, RenderOptions, TableOverflow};
+use crate::wrapping::wrap;
+
+/// The narrowest that the `auto` overflow strategy will wrap a column to, short of the column's
+/// own width. Tables that can't fit with their columns this wide are stacked instead
+const MIN_WRAPPED_WIDTH: usize = 10;
+
+/// The formatted text of a table cell, or a line of a laid-out table
+type Cell = Vec<(String, Format)>;
+
+/// How the content of a table column is aligned, as set by colons in the delimiter row
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alignment {
+    /// No alignment was given, which is shown as left-aligned
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// A pipe table, as in GitHub Flavored Markdown: a header row, a delimiter row setting the
+/// alignment of each column, and any number of body rows
+#[derive(Debug, PartialEq)]
+pub struct Table {
+    alignments: Vec<Alignment>,
+    header: Vec<Cell>,
+    rows: Vec<Vec<Cell>>,
+    span: Range<usize>,
+}
+
+impl Table {
+    /// Parses a table from its header row, delimiter row and body rows, returning `None` if
+    /// `header` and `delimiter` aren't a header row and a delimiter row with the same number of
+    /// cells. Body rows with too few cells are filled out with empty ones, and any cells past the
+    /// number in the header are dropped
+    #[must_use]
+    pub fn from_lines(
+        header: &str,
+        delimiter: &str,
+        rows: &[&str],
+        options: &ParserOptions,
+    ) -> Option<Self> {
+        if !header.contains('|') {
+            return None;
+        }
+        let alignments = delimiter_row(delimiter)?;
+        let header_cells = split_row(header);
+        if header_cells.len() != alignments.len() {
+            return None;
+        }
+
+        let parse_cells = |cells: Vec<String>| -> Vec<Cell> {
+            let mut cells: Vec<_> = cells
+                .iter()
+                .take(alignments.len())
+                .map(|cell| inline::to_slices(&parse_inline(cell, options)))
+                .collect();
+            cells.resize(alignments.len(), Cell::new());
+            cells
+        };
+        // every line but the last is followed by a newline
+        let length = [header, delimiter]
+            .iter()
+            .chain(rows)
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            - 1;
+        Some(Self {
+            header: parse_cells(header_cells),
+            rows: rows.iter().map(|row| parse_cells(split_row(row))).collect(),
+            alignments,
+            span: 0..length,
+        })
+    }
+
+    /// Makes a table of delimiter-separated values, such as CSV, with the first record as its
+    /// header. Fields are plain text, and can be quoted as in CSV. Columns of numbers are
+    /// right-aligned. Returns `None` if there are no records
+    #[must_use]
+    pub fn from_delimited(text: &str, delimiter: char) -> Option<Self> {
+        let mut records = split_records(text, delimiter).into_iter();
+        let header = records.next()?;
+        let columns = header.len();
+        let to_cells = |record: Vec<String>| -> Vec<Cell> {
+            let mut cells: Vec<_> = record
+                .into_iter()
+                .take(columns)
+                .map(|field| {
+                    if field.is_empty() {
+                        Cell::new()
+                    } else {
+                        vec![(field, Format::new())]
+                    }
+                })
+                .collect();
+            cells.resize(columns, Cell::new());
+            cells
+        };
+        let rows: Vec<_> = records.map(to_cells).collect();
+
+        let alignments = (0..columns)
+            .map(|column| {
+                let mut fields = rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .filter(|cell| !cell.is_empty())
+                    .peekable();
+                let is_numeric = fields.peek().is_some()
+                    && fields.all(|cell| {
+                        cell.iter()
+                            .map(|(slice, _)| slice.as_str())
+                            .collect::<String>()
+                            .parse::<f64>()
+                            .is_ok()
+                    });
+                if is_numeric {
+                    Alignment::Right
+                } else {
+                    Alignment::None
+                }
+            })
+            .collect();
+
+        Some(Self {
+            alignments,
+            header: to_cells(header),
+            rows,
+            span: 0..0,
+        })
+    }
+
+    /// Sets the table's span, for tables that weren't parsed from their own lines
+    #[must_use]
+    pub(crate) fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Marks up the abbreviations in the table's cells, in reading order
+    #[must_use]
+    pub(crate) fn with_abbreviations(mut self, abbreviations: &mut Abbreviations) -> Self {
+        for cell in self.header.iter_mut().chain(self.rows.iter_mut().flatten()) {
+            *cell = abbreviations.apply(mem::take(cell));
+        }
+        self
+    }
+
+    /// A copy of the table with `map` applied to each of its cells
+    #[must_use]
+    pub(crate) fn map_cells(&self, map: impl Fn(&Cell) -> Cell) -> Self {
+        Self {
+            alignments: self.alignments.clone(),
+            header: self.header.iter().map(&map).collect(),
+            rows: self
+                .rows
+                .iter()
+                .map(|row| row.iter().map(&map).collect())
+                .collect(),
+            span: self.span.clone(),
+        }
+    }
+
+    /// Moves the table's span along by `offset` bytes, for when its lines weren't the whole
+    /// document
+    #[must_use]
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        self.span = self.span.start + offset..self.span.end + offset;
+        self
+    }
+
+    #[must_use]
+    pub fn alignments(&self) -> &[Alignment] {
+        &self.alignments
+    }
+
+    /// The cells of the header row
+    #[must_use]
+    pub fn header(&self) -> &[Cell] {
+        &self.header
+    }
+
+    /// The cells of each body row, of which there are always as many as in the header
+    #[must_use]
+    pub fn rows(&self) -> &[Vec<Cell>] {
+        &self.rows
+    }
+
+    /// Lays the table out as lines of formatted slices, with the header in bold and the columns
+    /// separated by the theme's bar. Tables too wide for the width set in `options` are narrowed
+    /// as its [table overflow strategy](TableOverflow) asks
+    pub(crate) fn layout(&self, options: &RenderOptions) -> Vec<Cell> {
+        let bold = Format::new().set_bold();
+        let header: Vec<Cell> = self
+            .header
+            .iter()
+            .map(|cell| {
+                let cell: Cell = cell
+                    .iter()
+                    .map(|(slice, format)| (slice.clone(), format.union(&bold)))
+                    .collect();
+                with_role_formats(&cell, options)
+            })
+            .collect();
+        let rows: Vec<Vec<Cell>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| with_role_formats(cell, options))
+                    .collect()
+            })
+            .collect();
+
+        let widths: Vec<usize> = (0..self.alignments.len())
+            .map(|column| {
+                std::iter::once(&header)
+                    .chain(&rows)
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| cell_width(cell))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let separators = separator_width(options) * self.alignments.len().saturating_sub(1);
+        let Some(available) = options
+            .width
+            .filter(|width| widths.iter().sum::<usize>() + separators > *width)
+        else {
+            return self.grid(&header, &rows, &widths, false, options);
+        };
+
+        let strategy = match options.table_overflow {
+            TableOverflow::Auto => {
+                let minimum: usize = widths
+                    .iter()
+                    .map(|width| (*width).min(MIN_WRAPPED_WIDTH))
+                    .sum();
+                if minimum + separators <= available {
+                    TableOverflow::Wrap
+                } else {
+                    TableOverflow::Stack
+                }
+            }
+            strategy => strategy,
+        };
+        debug!(
+            ?strategy,
+            width = available,
+            "table is too wide for the output"
+        );
+        match strategy {
+            TableOverflow::Stack => stack(&header, &rows, options),
+            strategy => {
+                let widths = shrink(widths, available.saturating_sub(separators));
+                let truncate = strategy == TableOverflow::Truncate;
+                self.grid(&header, &rows, &widths, truncate, options)
+            }
+        }
+    }
+
+    /// Lays the table out in columns of the given widths, wrapping or truncating cells that are
+    /// too wide for them, with a rule under the header
+    fn grid(
+        &self,
+        header: &[Cell],
+        rows: &[Vec<Cell>],
+        widths: &[usize],
+        truncate: bool,
+        options: &RenderOptions,
+    ) -> Vec<Cell> {
+        let glyphs = options.theme.glyphs;
+        let rule_width = glyphs.rule.width().max(1);
+        let rule = widths
+            .iter()
+            .map(|width| glyphs.rule.repeat(width / rule_width))
+            .collect::<Vec<_>>()
+            .join(&format!(
+                "{}{}{}",
+                glyphs.rule, glyphs.junction, glyphs.rule
+            ));
+
+        let mut lines = self.row_lines(header, widths, truncate, options);
+        lines.push(vec![(rule, Format::new())]);
+        for row in rows {
+            lines.extend(self.row_lines(row, widths, truncate, options));
+        }
+        lines
+    }
+
+    /// Lays out a single row, which takes up as many lines as its tallest cell
+    fn row_lines(
+        &self,
+        row: &[Cell],
+        widths: &[usize],
+        truncate: bool,
+        options: &RenderOptions,
+    ) -> Vec<Cell> {
+        let cells: Vec<Vec<Cell>> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| {
+                if truncate {
+                    vec![truncate_cell(cell, *width, options.theme.glyphs.ellipsis)]
+                } else {
+                    wrap(cell, &options.clone().width(*width))
+                }
+            })
+            .collect();
+        let height = cells.iter().map(Vec::len).max().unwrap_or_default();
+
+        let separator = format!(" {} ", options.theme.glyphs.quote_bar);
+        (0..height)
+            .map(|index| {
+                let mut line = Cell::new();
+                for (column, ((cell, width), alignment)) in
+                    cells.iter().zip(widths).zip(&self.alignments).enumerate()
+                {
+                    if column > 0 {
+                        line.push((separator.clone(), Format::new()));
+                    }
+                    let content = cell.get(index).cloned().unwrap_or_default();
+                    let padding = width.saturating_sub(cell_width(&content));
+                    let (before, after) = match alignment {
+                        Alignment::None | Alignment::Left => (0, padding),
+                        Alignment::Center => (padding / 2, padding - padding / 2),
+                        Alignment::Right => (padding, 0),
+                    };
+                    line.push((" ".repeat(before), Format::new()));
+                    line.extend(content);
+                    line.push((" ".repeat(after), Format::new()));
+                }
+                // nothing follows the last column, so the spaces left at the end are dropped
+                while let Some((slice, format)) = line.last_mut() {
+                    if *format != Format::new() {
+                        break;
+                    }
+                    slice.truncate(slice.trim_end().len());
+                    if !slice.is_empty() {
+                        break;
+                    }
+                    line.pop();
+                }
+                line.retain(|(slice, _)| !slice.is_empty());
+                line
+            })
+            .collect()
+    }
+}
+
+impl DocumentElement for Table {
+    fn render(&self, options: &RenderOptions) -> String {
+        let mut output = String::new();
+        self.render_into(options, &mut output);
+        output
+    }
+
+    fn render_into(&self, options: &RenderOptions, output: &mut String) {
+        for (index, line) in self.layout(options).into_iter().enumerate() {
+            if index > 0 {
+                output.push('\n');
+            }
+            write_line(line, options, output);
+        }
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::Table
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.table(self);
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+}
+
+/// Splits a table row into the text of its cells. Leading and trailing pipes are optional, and
+/// an escaped pipe (`\|`) is part of a cell rather than the end of one
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line
+        .strip_suffix('|')
+        .filter(|rest| !rest.ends_with('\\'))
+        .unwrap_or(line);
+
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut characters = line.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some('|') => cell.push('|'),
+                Some(next) => {
+                    cell.push('\\');
+                    cell.push(next);
+                }
+                None => cell.push('\\'),
+            },
+            '|' => cells.push(mem::take(&mut cell).trim().to_owned()),
+            _ => cell.push(character),
+        }
+    }
+    cells.push(cell.trim().to_owned());
+    cells
+}
+
+/// Splits delimiter-separated values into records of fields, with the whitespace around each
+/// field trimmed. A field can be quoted with double quotes, inside of which the delimiter and line
+/// breaks are part of the field, and two double quotes stand for one. Blank lines are skipped
+fn split_records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut characters = text.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' if quoted => {
+                if characters.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            '\n' if !quoted => {
+                record.push(mem::take(&mut field));
+                records.push(mem::take(&mut record));
+            }
+            '\r' if !quoted => {}
+            _ if character == delimiter && !quoted => record.push(mem::take(&mut field)),
+            _ => field.push(character),
+        }
+    }
+    record.push(field);
+    records.push(record);
+
+    records
+        .into_iter()
+        .filter(|record| record.iter().any(|field| !field.trim().is_empty()))
+        .map(|record| {
+            record
+                .into_iter()
+                .map(|field| field.trim().to_owned())
+                .collect()
+        })
+        .collect()
+}
+
+/// Parses the alignments of a delimiter row (`| :--- | :---: | ---: |`), returning `None` if
+/// `line` isn't one
+fn delimiter_row(line: &str) -> Option<Vec<Alignment>> {
+    split_row(line)
+        .iter()
+        .map(|cell| {
+            let left = cell.starts_with(':');
+            let right = cell.ends_with(':') && cell.len() > 1;
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|character| character == '-') {
+                return None;
+            }
+            Some(match (left, right) {
+                (false, false) => Alignment::None,
+                (true, false) => Alignment::Left,
+                (true, true) => Alignment::Center,
+                (false, true) => Alignment::Right,
+            })
+        })
+        .collect()
+}
+
+fn cell_width(cell: &[(String, Format)]) -> usize {
+    cell.iter().map(|(slice, _)| slice.width()).sum()
+}
+
+/// The width of the space and bar between two columns
+fn separator_width(options: &RenderOptions) -> usize {
+    options.theme.glyphs.quote_bar.width() + 2
+}
+
+/// Narrows columns of the given widths until they add up to no more than `available`, by taking
+/// from the widest column each time, so that narrow columns are left alone for as long as possible
+fn shrink(mut widths: Vec<usize>, available: usize) -> Vec<usize> {
+    while widths.iter().sum::<usize>() > available {
+        let Some(widest) = widths
+            .iter_mut()
+            .filter(|width| **width > 1)
+            .max_by_key(|width| **width)
+        else {
+            break;
+        };
+        *widest -= 1;
+    }
+    widths
+}
+
+/// Cuts a cell down to `width` columns, ending it with `ellipsis` if anything was cut off
+fn truncate_cell(cell: &[(String, Format)], width: usize, ellipsis: &str) -> Cell {
+    if cell_width(cell) <= width {
+        return cell.to_vec();
+    }
+    let budget = width.saturating_sub(ellipsis.width());
+
+    let mut truncated = Cell::new();
+    let mut used = 0;
+    for (slice, format) in cell {
+        let mut kept = String::new();
+        for character in slice.chars() {
+            used += character.width().unwrap_or_default();
+            if used > budget {
+                truncated.push((kept, format.clone()));
+                truncated.push((ellipsis.to_owned(), format.clone()));
+                truncated.retain(|(slice, _)| !slice.is_empty());
+                return truncated;
+            }
+            kept.push(character);
+        }
+        truncated.push((kept, format.clone()));
+    }
+    truncated
+}
+
+/// Lays out each row as a record of its own, with a line for each cell, headed by the column's
+/// header, and a blank line between records
+fn stack(header: &[Cell], rows: &[Vec<Cell>], options: &RenderOptions) -> Vec<Cell> {
+    let mut lines = vec![];
+    for (index, row) in rows.iter().enumerate() {
+        if index > 0 {
+            lines.push(Cell::new());
+        }
+        for (heading, cell) in header.iter().zip(row) {
+            let mut slices = heading.clone();
+            slices.push((": ".to_owned(), Format::new().set_bold()));
+            slices.extend(cell.iter().cloned());
+            lines.extend(wrap(&slices, options));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatting::Roles;
+    use crate::theme::{Glyphs, Theme};
+
+    fn table(text: &str) -> Table {
+        let lines: Vec<_> = text.lines().collect();
+        Table::from_lines(
+            lines.first().unwrap(),
+            lines.get(1).unwrap(),
+            lines.get(2..).unwrap_or_default(),
+            &ParserOptions::new(),
+        )
+        .unwrap()
+    }
+
+    /// Lays `text` out as plain text, with ASCII glyphs
+    fn layout(text: &str, options: RenderOptions) -> Vec<String> {
+        let options = options.theme(Theme::dark().glyphs(Glyphs::ascii()));
+        table(text)
+            .layout(&options)
+            .iter()
+            .map(|line| line.iter().map(|(slice, _)| slice.as_str()).collect())
+            .collect()
+    }
+
+    mod parsing {
+        use super::*;
+
+        #[test]
+        fn alignments() {
+            assert_eq!(
+                [
+                    Alignment::None,
+                    Alignment::Left,
+                    Alignment::Center,
+                    Alignment::Right
+                ],
+                table("a | b | c | d\n--- | :-- | :-: | --:").alignments()
+            );
+        }
+
+        #[test]
+        fn not_tables() {
+            let options = ParserOptions::new();
+            // no pipe in the header
+            assert!(Table::from_lines("lorem", "---", &[], &options).is_none());
+            // the delimiter row has a different number of cells
+            assert!(Table::from_lines("a | b", "---", &[], &options).is_none());
+            // the delimiter row isn't one
+            assert!(Table::from_lines("a | b", "-- | x", &[], &options).is_none());
+        }
+
+        #[test]
+        fn rows_are_evened_out() {
+            let table = table("| a | b |\n|---|---|\n| 1 |\n| 1 | 2 | 3 |");
+            assert_eq!(
+                Some(vec![vec![("1".to_owned(), Format::new())], vec![]]),
+                table.rows().first().cloned()
+            );
+            assert_eq!(Some(2), table.rows().get(1).map(Vec::len));
+        }
+
+        #[test]
+        fn escaped_pipes() {
+            assert_eq!(
+                vec![vec![("a|b".to_owned(), Format::new())]],
+                table("| a\\|b |\n| --- |").header()
+            );
+        }
+
+        #[test]
+        fn cells_are_inline_markup() {
+            assert_eq!(
+                vec![vec![(
+                    "a".to_owned(),
+                    Format::new().set_roles(Roles::STRONG)
+                )]],
+                table("| **a** |\n| --- |").header()
+            );
+        }
+    }
+
+    mod delimited {
+        use super::*;
+
+        fn text(cells: &[Cell]) -> Vec<String> {
+            cells
+                .iter()
+                .map(|cell| cell.iter().map(|(slice, _)| slice.as_str()).collect())
+                .collect()
+        }
+
+        #[test]
+        fn quoted_fields() {
+            let table = Table::from_delimited(
+                "name, quote\n\nCicero, \"lorem, \"\"ipsum\"\"\ndolor\"",
+                ',',
+            )
+            .unwrap();
+            assert_eq!(vec!["name", "quote"], text(table.header()));
+            assert_eq!(
+                vec![vec!["Cicero", "lorem, \"ipsum\"\ndolor"]],
+                table.rows().iter().map(|row| text(row)).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn numeric_columns_are_right_aligned() {
+            let table = Table::from_delimited("a\tb\tc\n1\tx\t\n-2.5\t3\t", '\t').unwrap();
+            assert_eq!(
+                [Alignment::Right, Alignment::None, Alignment::None],
+                table.alignments()
+            );
+            assert!(Table::from_delimited("\n", ',').is_none());
+        }
+    }
+
+    mod layout {
+        use super::*;
+
+        const TABLE: &str =
+            "name | description\n--- | ---\nlorem | ipsum dolor sit amet consectetur";
+
+        #[test]
+        fn fitting_tables_are_as_wide_as_their_content() {
+            assert_eq!(
+                vec![
+                    "name  | description",
+                    "------+---------------------------------",
+                    "lorem | ipsum dolor sit amet consectetur",
+                ],
+                layout(TABLE, RenderOptions::new())
+            );
+        }
+
+        #[test]
+        fn alignment() {
+            assert_eq!(
+                vec![
+                    "left | center | right",
+                    "-----+--------+------",
+                    "a    |   b    |     c",
+                ],
+                layout(
+                    "left | center | right\n:-- | :-: | --:\na | b | c",
+                    RenderOptions::new()
+                )
+            );
+        }
+
+        #[test]
+        fn wrap() {
+            assert_eq!(
+                vec![
+                    "name  | description",
+                    "------+-------------",
+                    "lorem | ipsum dolor",
+                    "      | sit amet",
+                    "      | consectetur",
+                ],
+                layout(
+                    TABLE,
+                    RenderOptions::new()
+                        .width(20)
+                        .table_overflow(TableOverflow::Wrap)
+                )
+            );
+        }
+
+        #[test]
+        fn truncate() {
+            assert_eq!(
+                vec![
+                    "name  | descrip...",
+                    "------+-----------",
+                    "lorem | ipsum d..."
+                ],
+                layout(
+                    TABLE,
+                    RenderOptions::new()
+                        .width(18)
+                        .table_overflow(TableOverflow::Truncate)
+                )
+            );
+        }
+
+        #[test]
+        fn stack() {
+            assert_eq!(
+                vec![
+                    "name: lorem",
+                    "description: ipsum",
+                    "dolor sit amet",
+                    "consectetur",
+                ],
+                layout(
+                    TABLE,
+                    RenderOptions::new()
+                        .width(18)
+                        .table_overflow(TableOverflow::Stack)
+                )
+            );
+        }
+
+        #[test]
+        fn auto_wraps_unless_columns_would_get_too_narrow() {
+            let options = RenderOptions::new().width(20);
+            assert_eq!(
+                layout(TABLE, options.clone().table_overflow(TableOverflow::Wrap)),
+                layout(TABLE, options)
+            );
+            let options = RenderOptions::new().width(12);
+            assert_eq!(
+                layout(TABLE, options.clone().table_overflow(TableOverflow::Stack)),
+                layout(TABLE, options)
+            );
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use crate::formatting::ColourDepth;
+
+/// The brightness of a terminal's background colour
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+/// Works out whether the terminal has a light or dark background. The `COLORFGBG` environment
+/// variable set by some terminals is used if present; otherwise, provided that standard output is
+/// a terminal, the terminal is asked for its background colour directly (OSC 11). Returns `None`
+/// if neither gives an answer
+#[must_use]
+pub fn detect_background() -> Option<Background> {
+    if let Some(background) = background_from_environment() {
+        return Some(background);
+    }
+
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    query_background_colour().and_then(|response| background_from_osc_11_response(&response))
+}
+
+/// Works out whether the terminal has a light or dark background from the `COLORFGBG`
+/// environment variable alone, without asking the terminal, which can take a moment
+#[must_use]
+pub fn background_from_environment() -> Option<Background> {
+    env::var("COLORFGBG")
+        .ok()
+        .and_then(|value| background_from_colorfgbg(&value))
+}
+
+/// Works out how many colours the terminal can show from the `COLORTERM` and `TERM` environment
+/// variables. Terminals that say nothing of it are taken to show only the standard colours
+#[must_use]
+pub fn colour_depth_from_environment() -> ColourDepth {
+    colour_depth(
+        env::var("COLORTERM").ok().as_deref(),
+        env::var("TERM").ok().as_deref(),
+    )
+}
+
+/// Interprets the values of `COLORTERM` and `TERM`, if they are set
+fn colour_depth(colorterm: Option<&str>, term: Option<&str>) -> ColourDepth {
+    if matches!(colorterm, Some("truecolor" | "24bit"))
+        || term.is_some_and(|term| term.ends_with("-direct"))
+    {
+        ColourDepth::TrueColour
+    } else if term.is_some_and(|term| term.contains("256color")) {
+        ColourDepth::Indexed
+    } else {
+        ColourDepth::Standard
+    }
+}
+
+/// Interprets a `COLORFGBG` value (`fg;bg`, or `fg;default;bg` in some terminals), in which the
+/// background is given as one of the 16 standard colour indices
+fn background_from_colorfgbg(value: &str) -> Option<Background> {
+    let background = value.rsplit(';').next()?.parse::<u8>().ok()?;
+    match background {
+        0..=6 | 8 => Some(Background::Dark),
+        7 | 9..=15 => Some(Background::Light),
+        _ => None,
+    }
+}
+
+/// Interprets a terminal's reply to an OSC 11 query, of the form `ESC ] 11 ; rgb:R/G/B ST`, in
+/// which each component is given by one to four hex digits
+fn background_from_osc_11_response(response: &str) -> Option<Background> {
+    let (_, colour) = response.split_once("rgb:")?;
+    let colour = colour.trim_end_matches(['\x07', '\x1b', '\\']);
+
+    let mut components = colour.split('/').map(|component| {
+        let digits = u32::try_from(component.len())
+            .ok()
+            .filter(|len| (1..=4).contains(len))?;
+        let value = u32::from_str_radix(component, 16).ok()?;
+        Some(f64::from(value) / f64::from(16_u32.pow(digits) - 1))
+    });
+    let (red, green, blue) = (
+        components.next()??,
+        components.next()??,
+        components.next()??,
+    );
+
+    // relative luminance, as per ITU-R BT.709
+    let luminance = 0.0722_f64.mul_add(blue, 0.2126_f64.mul_add(red, 0.7152 * green));
+    if luminance > 0.5 {
+        Some(Background::Light)
+    } else {
+        Some(Background::Dark)
+    }
+}
+
+/// Asks the terminal for its background colour with an OSC 11 query, returning the raw reply.
+/// Terminals that don't understand the query simply don't reply, so reading the reply gives up
+/// after a tenth of a second
+#[cfg(unix)]
+fn query_background_colour() -> Option<String> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+
+    // SAFETY: `termios` is a plain C struct, for which all zeroes is a valid value, and it is
+    // overwritten by `tcgetattr` before being used anyway
+    let mut original_attributes = unsafe { std::mem::zeroed::<libc::termios>() };
+    // SAFETY: `fd` belongs to `tty`, which stays open for the duration of this function
+    if unsafe { libc::tcgetattr(fd, &raw mut original_attributes) } != 0 {
+        return None;
+    }
+
+    // turn off line buffering and echoing so that the reply can be read (and isn't displayed),
+    // and make reads time out after a tenth of a second without any input
+    let mut query_attributes = original_attributes;
+    query_attributes.c_lflag &= !(libc::ICANON | libc::ECHO);
+    for (index, value) in [(libc::VMIN, 0), (libc::VTIME, 1)] {
+        if let Some(control) = query_attributes.c_cc.get_mut(index) {
+            *control = value;
+        }
+    }
+    // SAFETY: as above
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw const query_attributes) } != 0 {
+        return None;
+    }
+
+    let reply = tty
+        .write_all(b"\x1b]11;?\x1b\\")
+        .and_then(|()| tty.flush())
+        .ok()
+        .and_then(|()| {
+            let mut reply = Vec::new();
+            let mut byte = [0_u8; 1];
+            while tty.read(&mut byte).ok()? == 1 {
+                reply.extend_from_slice(&byte);
+                // the reply is terminated by either BEL or ST (`ESC \`)
+                if byte == [0x07] || reply.ends_with(b"\x1b\\") {
+                    return String::from_utf8(reply).ok();
+                }
+                if reply.len() > 64 {
+                    break;
+                }
+            }
+            None
+        });
+
+    // SAFETY: as above
+    unsafe {
+        libc::tcsetattr(fd, libc::TCSANOW, &raw const original_attributes);
+    }
+
+    reply
+}
+
+#[cfg(not(unix))]
+fn query_background_colour() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod depth {
+        use super::*;
+
+        #[test]
+        fn colour_depth_is_taken_from_the_environment() {
+            assert_eq!(
+                ColourDepth::TrueColour,
+                colour_depth(Some("truecolor"), Some("xterm-256color"))
+            );
+            assert_eq!(
+                ColourDepth::TrueColour,
+                colour_depth(None, Some("xterm-direct"))
+            );
+            assert_eq!(
+                ColourDepth::Indexed,
+                colour_depth(Some(""), Some("screen-256color"))
+            );
+            assert_eq!(ColourDepth::Standard, colour_depth(None, Some("xterm")));
+            assert_eq!(ColourDepth::Standard, colour_depth(None, None));
+        }
+    }
+
+    mod colorfgbg {
+        use super::*;
+
+        #[test]
+        fn dark_background() {
+            assert_eq!(Some(Background::Dark), background_from_colorfgbg("15;0"));
+        }
+
+        #[test]
+        fn light_background() {
+            assert_eq!(Some(Background::Light), background_from_colorfgbg("0;15"));
+            assert_eq!(Some(Background::Light), background_from_colorfgbg("0;7"));
+        }
+
+        #[test]
+        fn three_part_value() {
+            assert_eq!(
+                Some(Background::Light),
+                background_from_colorfgbg("0;default;15")
+            );
+        }
+
+        #[test]
+        fn unknown_background() {
+            assert_eq!(None, background_from_colorfgbg("7;default"));
+            assert_eq!(None, background_from_colorfgbg("0;42"));
+            assert_eq!(None, background_from_colorfgbg(""));
+        }
+    }
+
+    mod osc_11_response {
+        use super::*;
+
+        #[test]
+        fn white_background_terminated_by_st() {
+            assert_eq!(
+                Some(Background::Light),
+                background_from_osc_11_response("\x1b]11;rgb:ffff/ffff/ffff\x1b\\")
+            );
+        }
+
+        #[test]
+        fn black_background_terminated_by_bel() {
+            assert_eq!(
+                Some(Background::Dark),
+                background_from_osc_11_response("\x1b]11;rgb:0000/0000/0000\x07")
+            );
+        }
+
+        #[test]
+        fn two_digit_components() {
+            assert_eq!(
+                Some(Background::Dark),
+                background_from_osc_11_response("\x1b]11;rgb:1e/1e/2e\x07")
+            );
+            assert_eq!(
+                Some(Background::Light),
+                background_from_osc_11_response("\x1b]11;rgb:fd/f6/e3\x07")
+            );
+        }
+
+        #[test]
+        fn green_weighs_more_than_blue() {
+            assert_eq!(
+                Some(Background::Light),
+                background_from_osc_11_response("\x1b]11;rgb:0000/ffff/0000\x07")
+            );
+            assert_eq!(
+                Some(Background::Dark),
+                background_from_osc_11_response("\x1b]11;rgb:0000/0000/ffff\x07")
+            );
+        }
+
+        #[test]
+        fn malformed_responses() {
+            assert_eq!(None, background_from_osc_11_response(""));
+            assert_eq!(
+                None,
+                background_from_osc_11_response("\x1b]11;rgb:ff/ff\x07")
+            );
+            assert_eq!(
+                None,
+                background_from_osc_11_response("\x1b]11;rgb:fffff/0/0\x07")
+            );
+            assert_eq!(
+                None,
+                background_from_osc_11_response("\x1b]11;rgb:zz/0/0\x07")
+            );
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Helpers for snapshot testing how documents render, for programs that embed sani. Everything is
+//! rendered with the same [`options`], which nothing in the environment (the terminal, its
+//! background or the locale) changes, and terminal output has its escape characters shown as
+//! `\e`, so that snapshots stay the same from one machine to the next and can be read in a diff:
+//!
+//! ```
+//! assert_eq!("\\e[3mlorem\\e[23m ipsum", sani::testing::render("*lorem* ipsum"));
+//! // or, with insta: insta::assert_snapshot!(sani::testing::render(README));
+//! ```
+
+use crate::backends::OutputFormat;
+use crate::formatting::ColourDepth;
+use crate::locale::Labels;
+use crate::options::{ParserOptions, RenderOptions};
+use crate::theme::Theme;
+
+/// The number of columns output is wrapped to
+pub const WIDTH: usize = 80;
+
+/// The options everything is rendered with: the dark theme, wrapped to [`WIDTH`] columns, with
+/// English labels and with every colour written as it is given
+#[must_use]
+pub fn options() -> RenderOptions {
+    RenderOptions::new()
+        .width(WIDTH)
+        .colour_depth(ColourDepth::TrueColour)
+        .theme(Theme::dark())
+        .labels(Labels::english())
+}
+
+/// Renders `text` for the terminal, without the blank line after the last block
+#[must_use]
+pub fn render(text: &str) -> String {
+    render_with(text, &ParserOptions::new())
+}
+
+/// Renders `text` for the terminal as [`render`] does, parsing it with `parser_options`
+#[must_use]
+pub fn render_with(text: &str, parser_options: &ParserOptions) -> String {
+    let output = crate::render(crate::parse_with_options(text, parser_options), &options());
+    visible_escapes(output.trim_end_matches('\n'))
+}
+
+/// Renders `text` in the given output format. Binary formats give the text their output is made
+/// from, as [`render_as`](crate::render_as) does
+#[must_use]
+pub fn render_as(text: &str, format: OutputFormat) -> String {
+    visible_escapes(&crate::render_as(crate::parse(text), format, &options()))
+}
+
+/// Shows the escape characters in `text` as `\e`, so that formatting codes can be read
+#[must_use]
+pub fn visible_escapes(text: &str) -> String {
+    text.replace('\x1b', "\\e")
+}
+
+/// Checks that every SGR attribute switched on in `render` is switched off again, and never
+/// switched off without having been switched on, for fuzzing and property tests. Codes that set
+/// several parameters at once, such as `\x1b[22;23m`, are taken apart
+///
+/// # Errors
+///
+/// Returns what is wrong with the formatting codes in `render`, if anything is
+pub fn check_balanced_sgr_codes(render: &str) -> Result<(), String> {
+    // bold, italic, strikethrough, underline, inverse, foreground colour, background colour
+    let mut active = [false; 7];
+
+    for code in render.split("\x1b[").skip(1) {
+        let (parameters, _) = code
+            .split_once('m')
+            .ok_or_else(|| format!("unterminated SGR code in {render:?}"))?;
+        let mut parameters = parameters.split(';');
+        while let Some(parameter) = parameters.next() {
+            let parameter: u8 = parameter
+                .parse()
+                .map_err(|_| format!("bad SGR code in {render:?}"))?;
+            let (attribute, on) = match parameter {
+                1 => (0, true),
+                22 => (0, false),
+                3 => (1, true),
+                23 => (1, false),
+                9 => (2, true),
+                29 => (2, false),
+                4 => (3, true),
+                24 => (3, false),
+                7 => (4, true),
+                27 => (4, false),
+                30..=37 | 90..=97 => (5, true),
+                39 => (5, false),
+                40..=47 | 100..=107 => (6, true),
+                49 => (6, false),
+                38 | 48 => {
+                    // an indexed colour has one more parameter, and an RGB one three
+                    let extra = match parameters.next() {
+                        Some("5") => 1,
+                        Some("2") => 3,
+                        _ => return Err(format!("bad colour in {render:?}")),
+                    };
+                    if parameters.by_ref().take(extra).count() != extra {
+                        return Err(format!("bad colour in {render:?}"));
+                    }
+                    (if parameter == 38 { 5 } else { 6 }, true)
+                }
+                _ => return Err(format!("unknown SGR code in {render:?}")),
+            };
+            let Some(state) = active.get_mut(attribute) else {
+                continue;
+            };
+            // colours may be switched straight from one to another
+            if on == *state && attribute < 5 {
+                let switched = if on { "on" } else { "off" };
+                return Err(format!(
+                    "attribute {attribute} switched {switched} twice in {render:?}"
+                ));
+            }
+            *state = on;
+        }
+    }
+
+    if active.contains(&true) {
+        return Err(format!("formatting left open in {render:?}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod wrapping {
+        use super::*;
+
+        #[test]
+        fn output_is_wrapped_to_a_fixed_width() {
+            let text = "lorem ".repeat(20);
+            let output = render(&text);
+            assert!(output.lines().all(|line| line.len() <= WIDTH));
+            assert_eq!(2, output.lines().count());
+        }
+    }
+
+    mod formatting_codes {
+        use super::*;
+
+        #[test]
+        fn formatting_codes_are_visible() {
+            assert_eq!("\\e[1;7mlorem\\e[22;27m", render("# lorem"));
+            assert_eq!("<p>lorem</p>\n", render_as("lorem", OutputFormat::Html));
+        }
+
+        #[test]
+        fn combined_sgr_codes_are_balanced() {
+            assert!(check_balanced_sgr_codes("\x1b[1;3mlorem\x1b[22;23m").is_ok());
+            assert!(check_balanced_sgr_codes("\x1b[38;5;1ma\x1b[38;2;1;2;3mb\x1b[39m").is_ok());
+            assert!(check_balanced_sgr_codes("\x1b[1;3mlorem\x1b[22m").is_err());
+            assert!(check_balanced_sgr_codes("lorem\x1b[23m").is_err());
+            assert!(check_balanced_sgr_codes("\x1b[1").is_err());
+        }
+    }
+}
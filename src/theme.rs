@@ -0,0 +1,572 @@
+use crate::formatting::{Colour, ColourDepth, Format, Roles};
+use crate::terminal::Background;
+
+/// The formatting applied to each kind of element when rendering
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    headings: [Format; 6],
+    title: Format,
+    byline: Format,
+    emphasis: Format,
+    strong: Format,
+    deleted: Format,
+    code: Format,
+    link: Format,
+    gutter: Format,
+    comment: Format,
+    highlighted_line: Format,
+    annotation: Format,
+    numbering: [NumberingStyle; 3],
+    pub(crate) glyphs: Glyphs,
+}
+
+/// How the items of an ordered list are numbered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumberingStyle {
+    /// `1`, `2`, `3`
+    #[default]
+    Arabic,
+    /// `a`, `b`, `c`, going on to `aa` after `z`
+    LowerAlpha,
+    /// `A`, `B`, `C`, going on to `AA` after `Z`
+    UpperAlpha,
+    /// `i`, `ii`, `iii`
+    LowerRoman,
+    /// `I`, `II`, `III`
+    UpperRoman,
+}
+
+impl NumberingStyle {
+    /// Writes `number` in this style. Numbers that can't be written in it, which are 0 for
+    /// letters, and 0 or anything over 3999 for Roman numerals, are written in Arabic numerals
+    #[must_use]
+    pub fn format(self, number: u64) -> String {
+        match self {
+            Self::LowerAlpha if number > 0 => alphabetic(number),
+            Self::UpperAlpha if number > 0 => alphabetic(number).to_uppercase(),
+            Self::LowerRoman if (1..=3999).contains(&number) => roman(number),
+            Self::UpperRoman if (1..=3999).contains(&number) => roman(number).to_uppercase(),
+            _ => number.to_string(),
+        }
+    }
+}
+
+/// Writes a positive number in letters, as spreadsheet columns are: `a` to `z`, then `aa` to `zz`,
+/// and so on
+fn alphabetic(mut number: u64) -> String {
+    let mut letters = vec![];
+    while number > 0 {
+        number -= 1;
+        letters.push(char::from(
+            b'a' + u8::try_from(number % 26).unwrap_or_default(),
+        ));
+        number /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Writes a number from 1 to 3999 in lowercase Roman numerals
+fn roman(mut number: u64) -> String {
+    const NUMERALS: [(u64, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            roman += numeral;
+            number -= value;
+        }
+    }
+    roman
+}
+
+/// The characters used to draw decorations such as list bullets and horizontal rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Glyphs {
+    pub(crate) bullets: [&'static str; 3],
+    pub(crate) quote_bar: &'static str,
+    pub(crate) rule: &'static str,
+    pub(crate) junction: &'static str,
+    pub(crate) checked: &'static str,
+    pub(crate) unchecked: &'static str,
+    pub(crate) ellipsis: &'static str,
+    pub(crate) dash: &'static str,
+}
+
+impl Glyphs {
+    /// Unicode bullets and box-drawing characters. This is the default
+    #[must_use]
+    pub const fn unicode() -> Self {
+        Self {
+            bullets: ["•", "◦", "▪"],
+            quote_bar: "│",
+            rule: "─",
+            junction: "┼",
+            checked: "☑",
+            unchecked: "☐",
+            ellipsis: "…",
+            dash: "—",
+        }
+    }
+
+    /// Plain ASCII equivalents of the default glyphs, for terminals (and logs) that can't display
+    /// anything else
+    #[must_use]
+    pub const fn ascii() -> Self {
+        Self {
+            bullets: ["*", "-", "+"],
+            quote_bar: "|",
+            rule: "-",
+            junction: "+",
+            checked: "[x]",
+            unchecked: "[ ]",
+            ellipsis: "...",
+            dash: "--",
+        }
+    }
+
+    /// Sets the bullets of unordered list items. Nested lists use the next bullet along, going
+    /// back to the first after the third
+    #[must_use]
+    pub const fn bullets(mut self, bullets: [&'static str; 3]) -> Self {
+        self.bullets = bullets;
+        self
+    }
+
+    /// Sets the bar drawn down the side of blockquotes
+    #[must_use]
+    pub const fn quote_bar(mut self, quote_bar: &'static str) -> Self {
+        self.quote_bar = quote_bar;
+        self
+    }
+
+    /// Sets the character repeated to draw horizontal rules
+    #[must_use]
+    pub const fn rule(mut self, rule: &'static str) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    /// Sets the character drawn where the rule under a table's header crosses the bars between
+    /// its columns
+    #[must_use]
+    pub const fn junction(mut self, junction: &'static str) -> Self {
+        self.junction = junction;
+        self
+    }
+
+    /// Sets the boxes drawn for checked and unchecked task list items
+    #[must_use]
+    pub const fn checkboxes(mut self, checked: &'static str, unchecked: &'static str) -> Self {
+        self.checked = checked;
+        self.unchecked = unchecked;
+        self
+    }
+
+    /// Sets the ellipsis that marks where content has been left out of a summary or cut off
+    #[must_use]
+    pub const fn ellipsis(mut self, ellipsis: &'static str) -> Self {
+        self.ellipsis = ellipsis;
+        self
+    }
+
+    /// Sets the dash before the attribution of a blockquote
+    #[must_use]
+    pub const fn dash(mut self, dash: &'static str) -> Self {
+        self.dash = dash;
+        self
+    }
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Self::unicode()
+    }
+}
+
+/// Ordered lists are numbered, then lettered, then numbered in Roman numerals as they nest
+const DEFAULT_NUMBERING: [NumberingStyle; 3] = [
+    NumberingStyle::Arabic,
+    NumberingStyle::LowerAlpha,
+    NumberingStyle::LowerRoman,
+];
+
+impl Theme {
+    /// The default theme for terminals with a dark background
+    #[must_use]
+    pub const fn dark() -> Self {
+        Self {
+            headings: [
+                Format::new().set_bold().set_inverse(),
+                Format::new().set_bold().set_foreground(Colour::Cyan),
+                Format::new().set_bold(),
+                Format::new().set_foreground(Colour::Cyan),
+                Format::new().set_italic(),
+                Format::new()
+                    .set_italic()
+                    .set_foreground(Colour::BrightBlack),
+            ],
+            title: Format::new().set_bold(),
+            byline: Format::new().set_dim(),
+            emphasis: Format::new().set_italic(),
+            strong: Format::new().set_bold(),
+            deleted: Format::new().set_strikethrough(),
+            code: Format::new().set_foreground(Colour::Yellow),
+            link: Format::new(),
+            gutter: Format::new().set_dim(),
+            comment: Format::new().set_dim(),
+            highlighted_line: Format::new().set_background(Colour::BrightBlack),
+            annotation: Format::new().set_foreground(Colour::BrightGreen),
+            numbering: DEFAULT_NUMBERING,
+            glyphs: Glyphs::unicode(),
+        }
+    }
+
+    /// The default theme for terminals with a light background, which swaps out colours that are
+    /// hard to read against white
+    #[must_use]
+    pub const fn light() -> Self {
+        Self {
+            headings: [
+                Format::new().set_bold().set_inverse(),
+                Format::new().set_bold().set_foreground(Colour::Blue),
+                Format::new().set_bold(),
+                Format::new().set_foreground(Colour::Blue),
+                Format::new().set_italic(),
+                Format::new()
+                    .set_italic()
+                    .set_foreground(Colour::BrightBlack),
+            ],
+            title: Format::new().set_bold(),
+            byline: Format::new().set_dim(),
+            emphasis: Format::new().set_italic(),
+            strong: Format::new().set_bold(),
+            deleted: Format::new().set_strikethrough(),
+            code: Format::new().set_foreground(Colour::Magenta),
+            link: Format::new(),
+            gutter: Format::new().set_dim(),
+            comment: Format::new().set_dim(),
+            highlighted_line: Format::new().set_background(Colour::White),
+            annotation: Format::new().set_foreground(Colour::Green),
+            numbering: DEFAULT_NUMBERING,
+            glyphs: Glyphs::unicode(),
+        }
+    }
+
+    /// The default theme for the given terminal background
+    #[must_use]
+    pub const fn for_background(background: Background) -> Self {
+        match background {
+            Background::Light => Self::light(),
+            Background::Dark => Self::dark(),
+        }
+    }
+
+    /// Sets the formatting of headings of the given level (1 to 6). Out-of-range levels are
+    /// ignored
+    #[must_use]
+    pub fn heading(mut self, level: u8, format: Format) -> Self {
+        if let Some(heading_format) = self.headings.get_mut(usize::from(level).wrapping_sub(1)) {
+            *heading_format = format;
+        }
+        self
+    }
+
+    /// Sets the formatting of the title in a title block, which is given in front matter
+    #[must_use]
+    pub fn title(mut self, format: Format) -> Self {
+        self.title = format;
+        self
+    }
+
+    /// Sets the formatting of the author and date under the title in a title block
+    #[must_use]
+    pub fn byline(mut self, format: Format) -> Self {
+        self.byline = format;
+        self
+    }
+
+    /// Sets the formatting of emphasised text
+    #[must_use]
+    pub fn emphasis(mut self, format: Format) -> Self {
+        self.emphasis = format;
+        self
+    }
+
+    /// Sets the formatting of strongly emphasised text
+    #[must_use]
+    pub fn strong(mut self, format: Format) -> Self {
+        self.strong = format;
+        self
+    }
+
+    /// Sets the formatting of text marked as deleted
+    #[must_use]
+    pub fn deleted(mut self, format: Format) -> Self {
+        self.deleted = format;
+        self
+    }
+
+    /// Sets the formatting of code spans
+    #[must_use]
+    pub fn code(mut self, format: Format) -> Self {
+        self.code = format;
+        self
+    }
+
+    /// Sets the formatting of the text of links, which by default is left as it is, as terminals
+    /// that support hyperlinks show them in a style of their own
+    #[must_use]
+    pub fn link(mut self, format: Format) -> Self {
+        self.link = format;
+        self
+    }
+
+    /// Sets the formatting of the line numbers down the side of code blocks
+    #[must_use]
+    pub fn gutter(mut self, format: Format) -> Self {
+        self.gutter = format;
+        self
+    }
+
+    /// Sets the formatting of HTML comments, when they are shown
+    #[must_use]
+    pub fn comment(mut self, format: Format) -> Self {
+        self.comment = format;
+        self
+    }
+
+    /// Sets the formatting applied on top of the code formatting of highlighted lines of code
+    /// blocks
+    #[must_use]
+    pub fn highlighted_line(mut self, format: Format) -> Self {
+        self.highlighted_line = format;
+        self
+    }
+
+    /// Sets the formatting of annotation callouts
+    #[must_use]
+    pub fn annotation(mut self, format: Format) -> Self {
+        self.annotation = format;
+        self
+    }
+
+    /// Swaps the colours of every format of the theme for the nearest ones a terminal that can
+    /// show `depth` colours has
+    #[must_use]
+    pub fn downgraded(mut self, depth: ColourDepth) -> Self {
+        self.headings = self.headings.map(|format| format.downgraded(depth));
+        self.title = self.title.downgraded(depth);
+        self.byline = self.byline.downgraded(depth);
+        self.emphasis = self.emphasis.downgraded(depth);
+        self.strong = self.strong.downgraded(depth);
+        self.deleted = self.deleted.downgraded(depth);
+        self.code = self.code.downgraded(depth);
+        self.link = self.link.downgraded(depth);
+        self.gutter = self.gutter.downgraded(depth);
+        self.comment = self.comment.downgraded(depth);
+        self.highlighted_line = self.highlighted_line.downgraded(depth);
+        self.annotation = self.annotation.downgraded(depth);
+        self
+    }
+
+    /// Sets how ordered lists are numbered at each depth of nesting, going back to the first
+    /// style after the third. Depth counts every list, ordered or not, as it does for bullets
+    #[must_use]
+    pub const fn numbering(mut self, numbering: [NumberingStyle; 3]) -> Self {
+        self.numbering = numbering;
+        self
+    }
+
+    /// Sets the glyphs used to draw decorations
+    #[must_use]
+    pub const fn glyphs(mut self, glyphs: Glyphs) -> Self {
+        self.glyphs = glyphs;
+        self
+    }
+
+    pub(crate) fn heading_format(&self, level: u8) -> Format {
+        self.headings
+            .get(usize::from(level).wrapping_sub(1))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The formatting of every one of `roles`, such as emphasis or code, together
+    pub(crate) fn role_format(&self, roles: Roles) -> Format {
+        [
+            (Roles::CODE, &self.code),
+            (Roles::STRONG, &self.strong),
+            (Roles::EMPHASIS, &self.emphasis),
+            (Roles::DELETED, &self.deleted),
+            (Roles::LINK, &self.link),
+        ]
+        .into_iter()
+        .filter(|(role, _)| roles.contains(*role))
+        .fold(Format::new(), |all, (_, format)| all.union(format))
+    }
+
+    pub(crate) const fn title_format(&self) -> &Format {
+        &self.title
+    }
+
+    pub(crate) const fn byline_format(&self) -> &Format {
+        &self.byline
+    }
+
+    pub(crate) const fn gutter_format(&self) -> &Format {
+        &self.gutter
+    }
+
+    pub(crate) const fn comment_format(&self) -> &Format {
+        &self.comment
+    }
+
+    pub(crate) const fn highlighted_line_format(&self) -> &Format {
+        &self.highlighted_line
+    }
+
+    pub(crate) const fn annotation_format(&self) -> &Format {
+        &self.annotation
+    }
+
+    pub(crate) fn numbering_style(&self, depth: usize) -> NumberingStyle {
+        self.numbering
+            .get(depth % self.numbering.len())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod headings {
+        use super::*;
+
+        #[test]
+        fn heading_levels_have_distinct_formats() {
+            let theme = Theme::dark();
+            for level in 1..6 {
+                assert_ne!(theme.heading_format(level), theme.heading_format(level + 1));
+            }
+        }
+
+        #[test]
+        fn light_theme_avoids_cyan() {
+            let theme = Theme::light();
+            for level in 1..=6 {
+                let format = theme.heading_format(level);
+                assert_ne!(format, format.clone().set_foreground(Colour::Cyan));
+            }
+        }
+
+        #[test]
+        fn heading_format_can_be_overridden() {
+            let format = Format::new().set_underline();
+            assert_eq!(
+                format,
+                Theme::dark().heading(3, format.clone()).heading_format(3)
+            );
+        }
+
+        #[test]
+        fn out_of_range_heading_levels_are_ignored() {
+            let format = Format::new().set_underline();
+            assert_eq!(
+                Theme::dark(),
+                Theme::dark().heading(0, format.clone()).heading(7, format)
+            );
+            assert_eq!(Format::new(), Theme::dark().heading_format(0));
+        }
+    }
+
+    mod roles {
+        use super::*;
+
+        #[test]
+        fn roles_are_given_their_formats_together() {
+            let theme = Theme::dark().link(Format::new().set_underline());
+            assert_eq!(
+                Format::new().set_bold().set_underline(),
+                theme.role_format(Roles::STRONG | Roles::LINK)
+            );
+            assert_eq!(Format::new(), theme.role_format(Roles::empty()));
+        }
+    }
+
+    mod numbering {
+        use super::*;
+
+        #[test]
+        fn numbering_styles() {
+            let format = |style: NumberingStyle, numbers: &[u64]| {
+                numbers
+                    .iter()
+                    .map(|number| style.format(*number))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                vec!["a", "z", "aa", "zz", "aaa"],
+                format(NumberingStyle::LowerAlpha, &[1, 26, 27, 702, 703])
+            );
+            assert_eq!(
+                vec!["IV", "IX", "XIV", "MCMXCIX"],
+                format(NumberingStyle::UpperRoman, &[4, 9, 14, 1999])
+            );
+            assert_eq!(
+                vec!["0", "4000"],
+                format(NumberingStyle::LowerRoman, &[0, 4000])
+            );
+            assert_eq!(vec!["0"], format(NumberingStyle::UpperAlpha, &[0]));
+        }
+
+        #[test]
+        fn numbering_goes_back_to_the_first_style() {
+            let theme = Theme::dark();
+            assert_eq!(NumberingStyle::LowerRoman, theme.numbering_style(2));
+            assert_eq!(NumberingStyle::Arabic, theme.numbering_style(3));
+        }
+    }
+
+    mod glyphs {
+        use super::*;
+
+        #[test]
+        fn ascii_glyphs_are_ascii() {
+            let glyphs = Glyphs::ascii();
+            assert!(glyphs
+                .bullets
+                .iter()
+                .chain([
+                    &glyphs.quote_bar,
+                    &glyphs.rule,
+                    &glyphs.junction,
+                    &glyphs.checked,
+                    &glyphs.unchecked,
+                    &glyphs.ellipsis,
+                    &glyphs.dash
+                ])
+                .all(|glyph| glyph.is_ascii()));
+        }
+    }
+}
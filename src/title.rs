@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::backends::Backend;
+use crate::formatting::{Format, Roles};
+use crate::markdown::{render_rule, write_line, DocumentElement, ElementKind, DEFAULT_RULE_WIDTH};
+use crate::options::RenderOptions;
+use crate::variables::front_matter;
+use crate::wrapping::wrap;
+
+/// The title, author and date given in a document's front matter, shown at the top of the
+/// document between two rules, centred, so that the title needn't be repeated as a heading. The
+/// language (`lang`) isn't shown, but is given to formats that record it
+#[derive(Debug, PartialEq, Eq)]
+pub struct TitleBlock {
+    title: Option<String>,
+    author: Option<String>,
+    date: Option<String>,
+    language: Option<String>,
+    span: Range<usize>,
+}
+
+impl TitleBlock {
+    #[must_use]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    #[must_use]
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    #[must_use]
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    /// The language the document is written in, as a language tag such as `en-GB`
+    #[must_use]
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// The author and date, whichever of them are given, joined by `dash`
+    fn byline(&self, dash: &str) -> Option<String> {
+        let parts: Vec<_> = [&self.author, &self.date]
+            .into_iter()
+            .filter_map(Option::as_deref)
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(&format!(" {dash} ")))
+    }
+}
+
+/// Writes `text` in `format`, wrapped and with each line centred in the width of the output
+fn write_centred(text: &str, format: &Format, options: &RenderOptions, output: &mut String) {
+    let width = options.width.unwrap_or(DEFAULT_RULE_WIDTH);
+    for line in wrap(&[(text.to_owned(), format.clone())], options) {
+        let line_width: usize = line.iter().map(|(slice, _)| slice.width()).sum();
+        output.push('\n');
+        output.push_str(&" ".repeat(width.saturating_sub(line_width) / 2));
+        write_line(line, options, output);
+    }
+}
+
+impl DocumentElement for TitleBlock {
+    fn render(&self, options: &RenderOptions) -> String {
+        let mut output = String::new();
+        self.render_into(options, &mut output);
+        output
+    }
+
+    fn render_into(&self, options: &RenderOptions, output: &mut String) {
+        let theme = &options.theme;
+        output.push_str(&render_rule(options));
+        if let Some(title) = &self.title {
+            write_centred(title, theme.title_format(), options, output);
+        }
+        if let Some(byline) = self.byline(theme.glyphs.dash) {
+            write_centred(&byline, theme.byline_format(), options, output);
+        }
+        output.push('\n');
+        output.push_str(&render_rule(options));
+    }
+
+    /// Hidden if there is only a language, which there is nothing to show for
+    fn is_hidden(&self, _options: &RenderOptions) -> bool {
+        self.title.is_none() && self.author.is_none() && self.date.is_none()
+    }
+
+    fn kind(&self) -> ElementKind {
+        ElementKind::TitleBlock
+    }
+
+    fn visit(&self, backend: &mut dyn Backend) {
+        backend.metadata(self);
+        if let Some(title) = &self.title {
+            backend.heading(1, &[(title.clone(), Format::new())]);
+        }
+        if let Some(byline) = self.byline("—") {
+            backend.paragraph(&[(byline, Format::new().set_roles(Roles::EMPHASIS))]);
+        }
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    fn remap_span(&mut self, map: &dyn Fn(usize) -> usize) {
+        self.span = map(self.span.start)..map(self.span.end);
+    }
+}
+
+/// Parses a title block out of the front matter at the start of `text`, if there is front matter
+/// with a title, author, date or language in it, returning it along with the length of the front
+/// matter
+pub(crate) fn parse_title_block(text: &str) -> Option<(TitleBlock, usize)> {
+    let (length, values) = front_matter(text)?;
+    let value = |key| values.get(key).map(|value: &&str| (*value).to_owned());
+    let block = TitleBlock {
+        title: value("title"),
+        author: value("author"),
+        date: value("date"),
+        language: value("lang"),
+        span: 0..length,
+    };
+    (!block.is_hidden(&RenderOptions::default()) || block.language.is_some())
+        .then_some((block, length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod parsing {
+        use super::*;
+
+        #[test]
+        fn only_front_matter_with_a_title_author_date_or_language_is_a_title_block() {
+            let (block, length) =
+                parse_title_block("---\ntitle: Lorem\nlayout: post\n---\nipsum").unwrap();
+            assert_eq!(34, length);
+            assert_eq!(Some("Lorem"), block.title());
+            assert_eq!(None, parse_title_block("---\nlayout: post\n---\nipsum"));
+            assert_eq!(None, parse_title_block("# Lorem"));
+
+            let (block, _) = parse_title_block("---\nlang: la\n---\n").unwrap();
+            assert_eq!(Some("la"), block.language());
+            assert!(block.is_hidden(&RenderOptions::new()));
+        }
+    }
+
+    mod rendering {
+        use super::*;
+
+        #[test]
+        fn title_blocks_are_centred_between_rules() {
+            let (block, _) =
+                parse_title_block("---\ntitle: Lorem\nauthor: Cicero\ndate: 45 BC\n---\n").unwrap();
+            let options = RenderOptions::new().width(20);
+            let bold = Format::new().set_bold();
+            let dim = Format::new().set_dim();
+            assert_eq!(
+                format!(
+                    "{rule}\n       {}Lorem{}\n   {}Cicero — 45 BC{}\n{rule}",
+                    bold.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&bold),
+                    dim.get_codes_for_format_change(&Format::new()),
+                    Format::new().get_codes_for_format_change(&dim),
+                    rule = "─".repeat(20)
+                ),
+                block.render(&options)
+            );
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use memchr::{memchr, memchr3};
+
+use crate::entities;
+
+/// A single piece of inline markup or text, as produced by [`Tokenizer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A run of literal text
+    Text(&'a str),
+    /// A backslash-escaped character, which is always literal
+    Escaped(&'a str),
+    /// A decoded entity or numeric character reference, such as `&amp;` or `&#x1F600;`
+    Entity(char),
+    /// `*`
+    Emphasis,
+    /// `**`
+    Strong,
+    /// `~~`
+    Strikethrough,
+    /// The content of a code span (`` `code` ``), exactly as it appears between the backticks
+    Code(&'a str),
+    /// A newline, which continues the paragraph on the same line
+    SoftBreak,
+    /// A backslash-escaped newline, which is kept as a line break
+    HardBreak,
+}
+
+/// Splits inline text up into [`Token`]s. This is the only place where backslash escapes are
+/// handled, so that escaping works the same wherever inline text appears. Any ASCII punctuation
+/// character can be escaped, as per the spec, while a backslash before anything else is just a
+/// backslash
+pub struct Tokenizer<'a> {
+    text: &'a str,
+    /// The byte offset of the next character to be read
+    position: usize,
+    /// Where the next byte of each group in [`SPECIAL_BYTES`] was found, or the end of the text
+    /// if there is none. A group is only searched for again once it has been passed, so that
+    /// each is searched for once over the whole text rather than once per token
+    next_special: [Option<usize>; 2],
+    decode_entities: bool,
+    single_tilde_strikethrough: bool,
+}
+
+/// The bytes that could start a token other than plain text, in groups of three to be searched
+/// for together. All of them are ASCII, so they are always at a character boundary
+const SPECIAL_BYTES: [[u8; 3]; 2] = [[b'\\', b'\n', b'*'], [b'~', b'&', b'`']];
+
+impl<'a> Tokenizer<'a> {
+    pub const fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            position: 0,
+            next_special: [None; 2],
+            decode_entities: false,
+            single_tilde_strikethrough: false,
+        }
+    }
+
+    /// Decodes entity and numeric character references into the characters they stand for,
+    /// rather than leaving them as text
+    pub const fn decode_entities(mut self, decode_entities: bool) -> Self {
+        self.decode_entities = decode_entities;
+        self
+    }
+
+    /// Makes a single tilde a strikethrough delimiter, as well as a pair of them
+    pub const fn single_tilde_strikethrough(mut self, single_tilde_strikethrough: bool) -> Self {
+        self.single_tilde_strikethrough = single_tilde_strikethrough;
+        self
+    }
+
+    /// The byte offset in the text of the next token
+    pub const fn offset(&self) -> usize {
+        self.position
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text.get(self.position..)?.chars().next()
+    }
+
+    /// Reads the next character if it is `expected`, returning whether it was
+    fn next_is(&mut self, expected: char) -> bool {
+        let is_expected = self.peek() == Some(expected);
+        if is_expected {
+            self.position += expected.len_utf8();
+        }
+        is_expected
+    }
+
+    /// The offset of the first special byte at or after `from`, or the end of the text
+    fn next_special(&mut self, from: usize) -> usize {
+        let bytes = self.text.as_bytes();
+        let mut nearest = bytes.len();
+        for (next, [first, second, third]) in self.next_special.iter_mut().zip(SPECIAL_BYTES) {
+            let found = match *next {
+                Some(found) if found >= from => found,
+                _ => bytes
+                    .get(from..)
+                    .and_then(|rest| memchr3(first, second, third, rest))
+                    .map_or(bytes.len(), |index| from + index),
+            };
+            *next = Some(found);
+            nearest = nearest.min(found);
+        }
+        nearest
+    }
+
+    /// Returns the text from `start` up to (but not including) the next character that could
+    /// begin a token of its own
+    fn text_run(&mut self, start: usize) -> &'a str {
+        self.position = self.next_special(self.position);
+        self.text.get(start..self.position).unwrap_or_default()
+    }
+
+    /// Reads a code span starting with the backtick at `start`. The span ends at the next run of
+    /// exactly as many backticks as it started with, so that the code can contain shorter runs.
+    /// Without such a run, the opening backticks are just text
+    fn code_span(&mut self, start: usize) -> Token<'a> {
+        let mut length = 1;
+        while self.next_is('`') {
+            length += 1;
+        }
+        let content_start = start + length;
+        let backticks = self.text.get(start..content_start).unwrap_or_default();
+
+        let rest = self.text.get(content_start..).unwrap_or_default();
+        let mut search_start = 0;
+        let closing = loop {
+            let Some(run_start) = rest
+                .get(search_start..)
+                .and_then(|text| memchr(b'`', text.as_bytes()))
+            else {
+                break None;
+            };
+            let run_start = search_start + run_start;
+            let run_length = rest
+                .get(run_start..)
+                .map_or(0, |run| run.len() - run.trim_start_matches('`').len());
+            if run_length == length {
+                break Some(run_start);
+            }
+            search_start = run_start + run_length;
+        };
+
+        let Some(closing) = closing else {
+            return Token::Text(backticks);
+        };
+        self.position = content_start + closing + length;
+        Token::Code(rest.get(..closing).unwrap_or_default())
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let char_index = self.position;
+        let current_char = self.peek()?;
+        self.position += current_char.len_utf8();
+
+        let token = match current_char {
+            '\\' => match self.peek().filter(|next_char| is_escapable(*next_char)) {
+                Some('\n') => {
+                    self.position += 1;
+                    Token::HardBreak
+                }
+                Some(next_char) => {
+                    let start = self.position;
+                    self.position += next_char.len_utf8();
+                    Token::Escaped(self.text.get(start..self.position).unwrap_or_default())
+                }
+                // a backslash at the very end escapes nothing, and is dropped
+                None if self.position >= self.text.len() => return None,
+                None => Token::Text(self.text_run(char_index)),
+            },
+            '\n' => Token::SoftBreak,
+            '*' => {
+                if self.next_is('*') {
+                    Token::Strong
+                } else {
+                    Token::Emphasis
+                }
+            }
+            '&' if self.decode_entities => {
+                match self.text.get(char_index..).and_then(entities::decode) {
+                    Some((character, length)) => {
+                        self.position = char_index + length;
+                        Token::Entity(character)
+                    }
+                    None => Token::Text(self.text_run(char_index)),
+                }
+            }
+            '~' => {
+                let is_pair = self.next_is('~');
+                if is_pair || self.single_tilde_strikethrough {
+                    Token::Strikethrough
+                } else {
+                    // a lone tilde is just a tilde
+                    Token::Text(self.text_run(char_index))
+                }
+            }
+            '`' => self.code_span(char_index),
+            _ => Token::Text(self.text_run(char_index)),
+        };
+
+        Some(token)
+    }
+}
+
+const fn is_escapable(character: char) -> bool {
+    character.is_ascii_punctuation() || character == '\n'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<Token<'_>> {
+        Tokenizer::new(text).collect()
+    }
+
+    mod text {
+        use super::*;
+
+        #[test]
+        fn plain_text_is_one_token() {
+            assert_eq!(vec![Token::Text("lorem ipsum")], tokens("lorem ipsum"));
+        }
+
+        #[test]
+        fn empty_text_has_no_tokens() {
+            assert_eq!(Vec::<Token<'_>>::new(), tokens(""));
+        }
+
+        #[test]
+        fn text_runs_end_at_any_special_character() {
+            assert_eq!(
+                vec![
+                    Token::Text("lorem"),
+                    Token::Emphasis,
+                    Token::Text("ipsum"),
+                    Token::Text("~dolor"),
+                    Token::Emphasis,
+                    Token::Text("sit"),
+                    Token::Text("`"),
+                    Token::Text(" amet"),
+                    Token::SoftBreak,
+                    Token::Text("consectetur"),
+                ],
+                tokens("lorem*ipsum~dolor*sit` amet\nconsectetur")
+            );
+        }
+
+        #[test]
+        fn multibyte_text() {
+            assert_eq!(
+                vec![Token::Text("日本"), Token::Emphasis, Token::Text("é")],
+                tokens("日本*é")
+            );
+        }
+    }
+
+    mod escapes {
+        use super::*;
+
+        #[test]
+        fn escaped_punctuation() {
+            for punctuation in ["*", "~", "#", "`", "[", "]", "\\", "!", "_", "<", "&"] {
+                assert_eq!(
+                    vec![Token::Escaped(punctuation)],
+                    tokens(&format!("\\{punctuation}"))
+                );
+            }
+        }
+
+        #[test]
+        fn backslash_before_other_characters_is_literal() {
+            assert_eq!(vec![Token::Text("\\lorem")], tokens("\\lorem"));
+            assert_eq!(vec![Token::Text("\\é")], tokens("\\é"));
+        }
+
+        #[test]
+        fn escaped_newline_is_a_hard_break() {
+            assert_eq!(
+                vec![Token::Text("lorem"), Token::HardBreak, Token::Text("ipsum")],
+                tokens("lorem\\\nipsum")
+            );
+        }
+
+        #[test]
+        fn trailing_backslash_is_dropped() {
+            assert_eq!(vec![Token::Text("lorem")], tokens("lorem\\"));
+        }
+
+        #[test]
+        fn escaped_delimiters_do_not_pair_up() {
+            assert_eq!(
+                vec![Token::Escaped("*"), Token::Emphasis, Token::Text("lorem")],
+                tokens("\\**lorem")
+            );
+            assert_eq!(
+                vec![Token::Escaped("~"), Token::Text("~lorem")],
+                tokens("\\~~lorem")
+            );
+        }
+    }
+
+    mod entities {
+        use super::*;
+
+        fn decoded_tokens(text: &str) -> Vec<Token<'_>> {
+            Tokenizer::new(text).decode_entities(true).collect()
+        }
+
+        #[test]
+        fn references_are_decoded() {
+            assert_eq!(
+                vec![
+                    Token::Text("lorem "),
+                    Token::Entity('&'),
+                    Token::Text(" ipsum "),
+                    Token::Entity('©'),
+                    Token::Entity('😀'),
+                ],
+                decoded_tokens("lorem &amp; ipsum &#169;&#x1F600;")
+            );
+        }
+
+        #[test]
+        fn references_are_left_alone_unless_enabled() {
+            assert_eq!(
+                vec![Token::Text("lorem "), Token::Text("&amp; ipsum")],
+                tokens("lorem &amp; ipsum")
+            );
+        }
+
+        #[test]
+        fn unknown_references_are_text() {
+            assert_eq!(
+                vec![Token::Text("&lorem; "), Token::Text("& ipsum")],
+                decoded_tokens("&lorem; & ipsum")
+            );
+        }
+
+        #[test]
+        fn escaped_ampersand_is_not_decoded() {
+            assert_eq!(
+                vec![Token::Escaped("&"), Token::Text("amp;")],
+                decoded_tokens("\\&amp;")
+            );
+        }
+    }
+
+    mod delimiters {
+        use super::*;
+
+        #[test]
+        fn emphasis_and_strong() {
+            assert_eq!(
+                vec![
+                    Token::Strong,
+                    Token::Text("lorem"),
+                    Token::Strong,
+                    Token::Emphasis,
+                    Token::Text("ipsum"),
+                    Token::Emphasis,
+                ],
+                tokens("**lorem***ipsum*")
+            );
+        }
+
+        #[test]
+        fn strikethrough() {
+            assert_eq!(
+                vec![
+                    Token::Strikethrough,
+                    Token::Text("lorem"),
+                    Token::Strikethrough
+                ],
+                tokens("~~lorem~~")
+            );
+        }
+
+        #[test]
+        fn lone_tilde_is_text() {
+            assert_eq!(
+                vec![Token::Text("~lorem"), Token::Text("~")],
+                tokens("~lorem~")
+            );
+        }
+
+        #[test]
+        fn single_tilde_strikethrough() {
+            assert_eq!(
+                vec![
+                    Token::Strikethrough,
+                    Token::Text("lorem"),
+                    Token::Strikethrough,
+                    Token::Strikethrough,
+                ],
+                Tokenizer::new("~lorem~~~")
+                    .single_tilde_strikethrough(true)
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn code_spans() {
+            assert_eq!(
+                vec![
+                    Token::Text("lorem "),
+                    Token::Code("*ipsum*"),
+                    Token::Text(" dolor")
+                ],
+                tokens("lorem `*ipsum*` dolor")
+            );
+            assert_eq!(
+                vec![Token::Code(" a`b``c "), Token::Text("d")],
+                tokens("``` a`b``c ```d")
+            );
+        }
+
+        #[test]
+        fn unclosed_backticks_are_text() {
+            assert_eq!(
+                vec![
+                    Token::Text("``"),
+                    Token::Text("lorem"),
+                    Token::Code("ipsum")
+                ],
+                tokens("``lorem`ipsum`")
+            );
+        }
+
+        #[test]
+        fn backslashes_are_literal_in_code_spans() {
+            assert_eq!(vec![Token::Code("a\\"), Token::Text("b")], tokens("`a\\`b"));
+        }
+
+        #[test]
+        fn newline_is_a_soft_break() {
+            assert_eq!(
+                vec![Token::Text("lorem"), Token::SoftBreak, Token::Text("ipsum")],
+                tokens("lorem\nipsum")
+            );
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+
+use crate::input::Prepared;
+
+/// Values for `{{name}}` placeholders in a document. Placeholders are filled in from the values
+/// defined here first, then from the document's front matter, and then, if enabled, from the
+/// environment. Placeholders without a value are left as they are
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Variables {
+    defined: HashMap<String, String>,
+    environment: bool,
+}
+
+impl Variables {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines the variable `name`, overriding any value given for it in front matter or the
+    /// environment
+    #[must_use]
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defined.insert(name.into(), value.into());
+        self
+    }
+
+    /// Fills in placeholders without any other value from environment variables of the same
+    /// name. Off by default, as it lets a document show whatever is in the environment
+    #[must_use]
+    pub const fn environment(mut self, environment: bool) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// The value of the variable `name`, given the values in the document's front matter
+    fn get<'a>(
+        &'a self,
+        name: &str,
+        front_matter: &'a HashMap<&str, &str>,
+    ) -> Option<Cow<'a, str>> {
+        if let Some(value) = self.defined.get(name) {
+            return Some(Cow::Borrowed(value));
+        }
+        if let Some(value) = front_matter.get(name) {
+            return Some(Cow::Borrowed(value));
+        }
+        self.environment
+            .then(|| env::var(name).ok())
+            .flatten()
+            .map(Cow::Owned)
+    }
+}
+
+/// Finds the front matter at the start of `text`: `key: value` lines between two `---` lines.
+/// Returns the length of the front matter, including the newline after it, along with the values
+/// in it. Lines that aren't simple `key: value` pairs, such as nested YAML, are skipped
+pub(crate) fn front_matter(text: &str) -> Option<(usize, HashMap<&str, &str>)> {
+    let mut lines = text.split_inclusive('\n');
+    let first_line = lines.next()?;
+    if first_line.trim_end() != "---" {
+        return None;
+    }
+
+    let mut values = HashMap::new();
+    let mut length = first_line.len();
+    for line in lines {
+        length += line.len();
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            return Some((length, values));
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if key.is_empty() || key.starts_with([' ', '\t', '-', '#']) {
+            continue;
+        }
+        let value = value.trim();
+        let value = [('"', '"'), ('\'', '\'')]
+            .into_iter()
+            .find_map(|(open, close)| value.strip_prefix(open)?.strip_suffix(close))
+            .unwrap_or(value);
+        values.entry(key.trim()).or_insert(value);
+    }
+    // without a closing line, it was never front matter
+    None
+}
+
+/// Whether `name` can be the name of a variable
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Prepares `text` for parsing, taking out any front matter at its start and filling in
+/// `{{name}}` placeholders (which can have spaces inside the braces) with the values of the
+/// variables they name
+pub(crate) fn substitute<'a>(text: &'a str, variables: &Variables) -> Prepared<'a> {
+    let (start, front_matter) = front_matter(text).unwrap_or_default();
+    let body = text.get(start..).unwrap_or_default();
+    let mut anchors = vec![(0, start)];
+    if !body.contains("{{") {
+        return Prepared {
+            text: Cow::Borrowed(body),
+            anchors,
+        };
+    }
+
+    let mut substituted = String::with_capacity(body.len());
+    let mut position = 0;
+    while let Some(open) = body.get(position..).and_then(|rest| rest.find("{{")) {
+        let open = position + open;
+        let inner_start = open + "{{".len();
+        let Some(close) = body.get(inner_start..).and_then(|rest| rest.find("}}")) else {
+            break;
+        };
+        let end = inner_start + close + "}}".len();
+        let name = body
+            .get(inner_start..inner_start + close)
+            .unwrap_or_default()
+            .trim();
+        let value = is_name(name)
+            .then(|| variables.get(name, &front_matter))
+            .flatten();
+        substituted += body.get(position..open).unwrap_or_default();
+        match value {
+            Some(value) => {
+                substituted += &value;
+                anchors.push((substituted.len(), start + end));
+            }
+            None => substituted += body.get(open..end).unwrap_or_default(),
+        }
+        position = end;
+    }
+    substituted += body.get(position..).unwrap_or_default();
+
+    Prepared {
+        text: Cow::Owned(substituted),
+        anchors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod front_matter {
+        use super::*;
+
+        #[test]
+        fn front_matter_values() {
+            let (length, values) = front_matter(
+                "---\ntitle: \"Lorem: Ipsum\"\nauthors:\n  - Cicero\n---\n# {{title}}",
+            )
+            .unwrap();
+            assert_eq!(50, length);
+            assert_eq!(Some(&"Lorem: Ipsum"), values.get("title"));
+            assert_eq!(None, values.get("  - Cicero"));
+            assert_eq!(None, front_matter("---\nlorem: ipsum\n"));
+            assert_eq!(None, front_matter("lorem\n---\n"));
+        }
+
+        #[test]
+        fn definitions_override_front_matter() {
+            let variables = Variables::new().define("name", "lorem");
+            assert_eq!(
+                "lorem",
+                substitute("---\nname: ipsum\n---\n{{name}}", &variables).text
+            );
+        }
+    }
+
+    mod placeholders {
+        use super::*;
+
+        #[test]
+        fn placeholders_are_filled_in() {
+            let variables = Variables::new().define("version", "2.0");
+            let prepared = substitute(
+                "---\nname: sani\n---\n{{name}} {{ version }} {{other}}",
+                &variables,
+            );
+            assert_eq!("sani 2.0 {{other}}", prepared.text);
+            assert_eq!(42, prepared.original_offset(9));
+        }
+
+        #[test]
+        fn environment_is_only_read_when_enabled() {
+            let text = "{{PATH}}";
+            assert_eq!(text, substitute(text, &Variables::new()).text);
+            assert_ne!(
+                text,
+                substitute(text, &Variables::new().environment(true)).text
+            );
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Bindings for JavaScript, for previewing documents in a browser. To build them, run
+//! `cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+//! and then `wasm-bindgen` on the resulting `sani.wasm`
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::backends::OutputFormat;
+use crate::options::RenderOptions;
+
+/// Parses `text` and returns its structure as a JSON array of the events written by the
+/// [events](OutputFormat::Events) output format
+#[must_use]
+#[wasm_bindgen(js_name = parseToJson)]
+pub fn parse_to_json(text: &str) -> String {
+    let events = crate::render_as(
+        crate::parse(text),
+        OutputFormat::Events,
+        &RenderOptions::new(),
+    );
+    format!("[{}]", events.lines().collect::<Vec<_>>().join(","))
+}
+
+/// Parses `text` and renders it as an HTML fragment
+#[must_use]
+#[wasm_bindgen(js_name = renderToHtml)]
+pub fn render_to_html(text: &str) -> String {
+    crate::render_as(
+        crate::parse(text),
+        OutputFormat::Html,
+        &RenderOptions::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod events {
+        use super::*;
+
+        #[test]
+        fn json_is_an_array_of_events() {
+            assert_eq!(
+                concat!(
+                    "[{\"event\":\"start\",\"tag\":\"paragraph\"},",
+                    "{\"event\":\"text\",\"text\":\"lorem\"},",
+                    "{\"event\":\"end\",\"tag\":\"paragraph\"}]",
+                ),
+                parse_to_json("lorem")
+            );
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::mem;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::formatting::Format;
+use crate::options::RenderOptions;
+
+/// A point in a word where it may be broken, with a hyphen, if it doesn't fit on a line. It is
+/// never shown otherwise
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// A run of formatted fragments, such as a single line of output or a single word
+type Fragments = Vec<(String, Format)>;
+
+enum Token {
+    Word(Fragments),
+    Space(Fragments),
+    /// A hard line break (from an escaped newline)
+    Break,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharKind {
+    Word,
+    Space,
+    Break,
+}
+
+impl CharKind {
+    const fn of(character: char) -> Self {
+        match character {
+            // a non-breaking space (U+00A0) is deliberately left as part of the word around it
+            ' ' => Self::Space,
+            '\n' => Self::Break,
+            _ => Self::Word,
+        }
+    }
+}
+
+/// Breaks a sequence of formatted slices up into lines no wider than the width set in `options`,
+/// breaking between words where possible. Words that don't fit are broken at a soft hyphen
+/// (U+00AD) if they have one, or hyphenated if hyphenation is enabled, and those too long to fit
+/// on a line of their own are broken wherever they overflow otherwise. Without a width,
+/// everything is returned as a single line. Soft hyphens are removed from the result either way,
+/// and with [`bidi`](RenderOptions::bidi) on, each line is put into the order it is shown in
+pub fn wrap(slices: &[(String, Format)], options: &RenderOptions) -> Vec<Fragments> {
+    let Some(width) = options.width else {
+        return in_display_order(slices, remove_soft_hyphens(vec![slices.to_vec()]), options);
+    };
+    // a zero width would make it impossible to make progress
+    let width = width.max(1);
+
+    let mut lines = vec![];
+    let mut line = vec![];
+    let mut line_width = 0_usize;
+    let mut space = vec![];
+
+    for token in tokenise(slices) {
+        let mut word = match token {
+            Token::Space(fragments) => {
+                space = fragments;
+                continue;
+            }
+            Token::Break => {
+                lines.push(mem::take(&mut line));
+                line_width = 0;
+                space.clear();
+                continue;
+            }
+            Token::Word(fragments) => fragments,
+        };
+
+        loop {
+            // spaces are only kept between words on the same line, never at the start of one
+            let space_width = if line.is_empty() {
+                0
+            } else {
+                fragments_width(&space)
+            };
+            let word_width = fragments_width(&word);
+
+            if line_width + space_width + word_width <= width {
+                if !line.is_empty() {
+                    line.append(&mut space);
+                }
+                line.append(&mut word);
+                line_width += space_width + word_width;
+                break;
+            }
+
+            let available = width.saturating_sub(line_width + space_width);
+            if let Some((offset, hyphenated)) =
+                break_point(&word, available, line.is_empty(), options)
+            {
+                let (mut head, tail) = split_fragments(mem::take(&mut word), offset);
+                if hyphenated {
+                    let hyphen_format = head
+                        .last()
+                        .map_or_else(Format::new, |(_, format)| format.clone());
+                    head.push(("-".to_owned(), hyphen_format));
+                }
+                if !line.is_empty() {
+                    line.append(&mut space);
+                }
+                line.extend(head);
+                word = tail;
+            }
+            lines.push(mem::take(&mut line));
+            line_width = 0;
+        }
+        space.clear();
+    }
+
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    in_display_order(slices, remove_soft_hyphens(lines), options)
+}
+
+/// Puts each of the lines wrapped from `slices` into the order it is shown in, if the options ask
+/// for bidirectional text to be reordered
+#[cfg(feature = "bidi")]
+fn in_display_order(
+    slices: &[(String, Format)],
+    lines: Vec<Fragments>,
+    options: &RenderOptions,
+) -> Vec<Fragments> {
+    if !options.bidi {
+        return lines;
+    }
+    let right_to_left = crate::bidi::is_right_to_left(slices);
+    lines
+        .into_iter()
+        .map(|line| crate::bidi::reorder(&line, right_to_left))
+        .collect()
+}
+
+#[cfg(not(feature = "bidi"))]
+fn in_display_order(
+    _slices: &[(String, Format)],
+    lines: Vec<Fragments>,
+    _options: &RenderOptions,
+) -> Vec<Fragments> {
+    lines
+}
+
+/// Removes every soft hyphen from `lines`, along with any fragments left empty by doing so
+fn remove_soft_hyphens(mut lines: Vec<Fragments>) -> Vec<Fragments> {
+    for line in &mut lines {
+        for (fragment, _) in line.iter_mut() {
+            fragment.retain(|c| c != SOFT_HYPHEN);
+        }
+        line.retain(|(fragment, _)| !fragment.is_empty());
+    }
+    lines
+}
+
+/// Splits the slices into words, runs of spaces and hard line breaks, keeping the formatting of
+/// each fragment intact (a single word may be made up of several differently-formatted fragments)
+fn tokenise(slices: &[(String, Format)]) -> Vec<Token> {
+    let mut tokens = vec![];
+
+    for (slice, format) in slices {
+        let mut run_start = 0_usize;
+        let mut chars = slice.char_indices().peekable();
+        while let Some((char_index, current_char)) = chars.next() {
+            let kind = CharKind::of(current_char);
+            let run_continues = kind != CharKind::Break
+                && chars
+                    .peek()
+                    .is_some_and(|(_, next_char)| CharKind::of(*next_char) == kind);
+            if run_continues {
+                continue;
+            }
+
+            let run_end = char_index + current_char.len_utf8();
+            let fragment = (
+                slice.get(run_start..run_end).unwrap_or_default().to_owned(),
+                format.clone(),
+            );
+            run_start = run_end;
+
+            match (tokens.last_mut(), kind) {
+                (Some(Token::Space(fragments)), CharKind::Space)
+                | (Some(Token::Word(fragments)), CharKind::Word) => fragments.push(fragment),
+                (_, CharKind::Space) => tokens.push(Token::Space(vec![fragment])),
+                (_, CharKind::Word) => tokens.push(Token::Word(vec![fragment])),
+                (_, CharKind::Break) => tokens.push(Token::Break),
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Finds where to break a word that doesn't fit into the `available` columns left on a line,
+/// returning the byte offset to break at and whether a hyphen should be inserted there. The word
+/// is broken at a soft hyphen or hyphenated if possible; failing that, it is broken wherever it
+/// overflows, but only if `overflowing` (i.e. it doesn't fit on a line of its own either)
+#[allow(unused_variables)] // `options` is only used when hyphenation is enabled
+fn break_point(
+    word: &[(String, Format)],
+    available: usize,
+    overflowing: bool,
+    options: &RenderOptions,
+) -> Option<(usize, bool)> {
+    let text = plain_text(word);
+
+    if let Some(offset) = soft_hyphen_point(&text, available) {
+        return Some((offset, true));
+    }
+
+    #[cfg(feature = "hyphenation")]
+    if let Some(lang) = options.hyphenation {
+        if let Some(offset) = hyphenation_point(&text, available, lang) {
+            return Some((offset, true));
+        }
+    }
+
+    if !overflowing {
+        return None;
+    }
+
+    // fit as many characters as possible, but always at least one so that wrapping progresses
+    let mut offset = 0_usize;
+    let mut used_width = 0_usize;
+    for (char_index, current_char) in text.char_indices() {
+        let char_width = current_char.width().unwrap_or(0);
+        if char_index != 0 && used_width + char_width > available {
+            break;
+        }
+        used_width += char_width;
+        offset = char_index + current_char.len_utf8();
+    }
+
+    Some((offset, false))
+}
+
+/// Finds the byte offset of the latest soft hyphen in `word` that leaves room for the hyphen shown
+/// in its place within `available` columns
+fn soft_hyphen_point(word: &str, available: usize) -> Option<usize> {
+    word.match_indices(SOFT_HYPHEN)
+        .map(|(offset, _)| offset)
+        // breaking at the very start of the word would leave nothing but the hyphen
+        .filter(|offset| *offset > 0)
+        .take_while(|offset| {
+            word.get(..*offset)
+                .is_some_and(|head| head.width() < available)
+        })
+        .last()
+}
+
+/// Finds the byte offset of the latest hyphenation point in `word` that leaves room for the
+/// hyphen itself within `available` columns. Only a purely alphabetic core is hyphenated, so
+/// surrounding punctuation is left alone and things like URLs are never broken up
+#[cfg(feature = "hyphenation")]
+fn hyphenation_point(word: &str, available: usize, lang: crate::options::Lang) -> Option<usize> {
+    let core = word.trim_matches(|c: char| !c.is_alphabetic());
+    if core.is_empty() || !core.chars().all(char::is_alphabetic) {
+        return None;
+    }
+    let core_start = word.len() - word.trim_start_matches(|c: char| !c.is_alphabetic()).len();
+
+    let mut best = None;
+    let mut offset = core_start;
+    let mut syllables = hypher::hyphenate(core, lang).peekable();
+    while let Some(syllable) = syllables.next() {
+        // there's no hyphenation point after the last syllable
+        if syllables.peek().is_none() {
+            break;
+        }
+        offset += syllable.len();
+        match word.get(..offset) {
+            Some(head) if head.width() < available => best = Some(offset),
+            _ => break,
+        }
+    }
+
+    best
+}
+
+/// Splits formatted fragments in two at a byte offset into their combined text
+fn split_fragments(fragments: Fragments, offset: usize) -> (Fragments, Fragments) {
+    let mut head = vec![];
+    let mut tail = vec![];
+    let mut fragment_start = 0_usize;
+
+    for (mut fragment, format) in fragments {
+        let fragment_end = fragment_start + fragment.len();
+        if fragment_end <= offset {
+            head.push((fragment, format));
+        } else if fragment_start >= offset {
+            tail.push((fragment, format));
+        } else {
+            let rest = fragment.split_off(offset - fragment_start);
+            head.push((fragment, format.clone()));
+            tail.push((rest, format));
+        }
+        fragment_start = fragment_end;
+    }
+
+    (head, tail)
+}
+
+fn plain_text(fragments: &[(String, Format)]) -> String {
+    fragments.iter().map(|(slice, _)| slice.as_str()).collect()
+}
+
+fn fragments_width(fragments: &[(String, Format)]) -> usize {
+    fragments.iter().map(|(slice, _)| slice.width()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain_lines(lines: &[Vec<(String, Format)>]) -> Vec<String> {
+        lines.iter().map(|line| plain_text(line)).collect()
+    }
+
+    mod wrapping {
+        use super::*;
+
+        #[test]
+        fn no_width_gives_a_single_line() {
+            let slices = vec![("lorem ipsum dolor sit amet".to_owned(), Format::new())];
+            assert_eq!(vec![slices.clone()], wrap(&slices, &RenderOptions::new()));
+        }
+
+        #[test]
+        fn breaks_between_words() {
+            let slices = vec![("lorem ipsum dolor sit amet".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(11));
+            assert_eq!(
+                vec!["lorem ipsum", "dolor sit", "amet"],
+                plain_lines(&lines)
+            );
+        }
+
+        #[test]
+        fn spaces_at_line_boundaries_are_dropped() {
+            let slices = vec![("  lorem   ipsum  ".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(5));
+            assert_eq!(vec!["lorem", "ipsum"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn spaces_between_words_are_kept() {
+            let slices = vec![("lorem  ipsum".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(20));
+            assert_eq!(vec!["lorem  ipsum"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn overlong_word_is_broken() {
+            let slices = vec![("lorem loremipsumdolor".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(8));
+            assert_eq!(vec!["lorem", "loremips", "umdolor"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn formatting_is_kept_across_lines() {
+            let slices = vec![
+                ("lorem ".to_owned(), Format::new()),
+                ("ipsum dolor".to_owned(), Format::new().set_bold()),
+            ];
+            let lines = wrap(&slices, &RenderOptions::new().width(11));
+            assert_eq!(
+                vec![
+                    vec![
+                        ("lorem".to_owned(), Format::new()),
+                        (" ".to_owned(), Format::new()),
+                        ("ipsum".to_owned(), Format::new().set_bold()),
+                    ],
+                    vec![("dolor".to_owned(), Format::new().set_bold())],
+                ],
+                lines
+            );
+        }
+
+        #[test]
+        fn word_made_of_several_formats_is_kept_together() {
+            let slices = vec![
+                ("lorem ip".to_owned(), Format::new()),
+                ("sum".to_owned(), Format::new().set_italic()),
+            ];
+            let lines = wrap(&slices, &RenderOptions::new().width(8));
+            assert_eq!(
+                vec![
+                    vec![("lorem".to_owned(), Format::new())],
+                    vec![
+                        ("ip".to_owned(), Format::new()),
+                        ("sum".to_owned(), Format::new().set_italic()),
+                    ],
+                ],
+                lines
+            );
+        }
+
+        #[test]
+        fn wide_characters_are_measured_by_columns() {
+            let slices = vec![("日本語".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(4));
+            assert_eq!(vec!["日本", "語"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn hard_line_breaks_are_kept() {
+            let slices = vec![("lorem\nipsum dolor".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(20));
+            assert_eq!(vec!["lorem", "ipsum dolor"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn hard_line_break_resets_line_width() {
+            let slices = vec![("lorem\nipsum dolor".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(11));
+            assert_eq!(vec!["lorem", "ipsum dolor"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn non_breaking_spaces_are_never_broken_at() {
+            let slices = vec![("lorem ipsum\u{a0}dolor".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(13));
+            assert_eq!(vec!["lorem", "ipsum\u{a0}dolor"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn soft_hyphens_are_hidden_unless_broken_at() {
+            let slices = vec![("lorem ex\u{ad}ten\u{ad}sive".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(20));
+            assert_eq!(vec!["lorem extensive"], plain_lines(&lines));
+            let lines = wrap(&slices, &RenderOptions::new());
+            assert_eq!(vec!["lorem extensive"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn words_are_broken_at_the_latest_soft_hyphen_that_fits() {
+            let slices = vec![("lorem ex\u{ad}ten\u{ad}sive".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(12));
+            assert_eq!(vec!["lorem exten-", "sive"], plain_lines(&lines));
+            let lines = wrap(&slices, &RenderOptions::new().width(9));
+            assert_eq!(vec!["lorem ex-", "tensive"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn zero_width_still_makes_progress() {
+            let slices = vec![("lorem".to_owned(), Format::new())];
+            let lines = wrap(&slices, &RenderOptions::new().width(0));
+            assert_eq!(vec!["l", "o", "r", "e", "m"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn empty_input_gives_one_empty_line() {
+            let lines = wrap(&[], &RenderOptions::new().width(10));
+            assert_eq!(vec![String::new()], plain_lines(&lines));
+        }
+    }
+
+    #[cfg(feature = "hyphenation")]
+    mod hyphenation {
+        use super::*;
+        use crate::options::Lang;
+
+        #[test]
+        fn word_is_hyphenated_to_fill_the_line() {
+            let slices = vec![("lorem extensive".to_owned(), Format::new())];
+            let lines = wrap(
+                &slices,
+                &RenderOptions::new().width(12).hyphenate(Lang::English),
+            );
+            assert_eq!(vec!["lorem exten-", "sive"], plain_lines(&lines));
+        }
+
+        #[test]
+        fn hyphen_takes_on_the_format_of_the_word() {
+            let slices = vec![("extensive".to_owned(), Format::new().set_bold())];
+            let lines = wrap(
+                &slices,
+                &RenderOptions::new().width(6).hyphenate(Lang::English),
+            );
+            assert_eq!(
+                vec![
+                    vec![
+                        ("exten".to_owned(), Format::new().set_bold()),
+                        ("-".to_owned(), Format::new().set_bold()),
+                    ],
+                    vec![("sive".to_owned(), Format::new().set_bold())],
+                ],
+                lines
+            );
+        }
+
+        #[test]
+        fn punctuation_is_not_hyphenated() {
+            let slices = vec![("lorem https://example.com".to_owned(), Format::new())];
+            let lines = wrap(
+                &slices,
+                &RenderOptions::new().width(12).hyphenate(Lang::English),
+            );
+            assert_eq!(
+                vec!["lorem", "https://exam", "ple.com"],
+                plain_lines(&lines)
+            );
+        }
+
+        #[test]
+        fn trailing_punctuation_is_kept_with_the_tail() {
+            let slices = vec![("lorem extensive.".to_owned(), Format::new())];
+            let lines = wrap(
+                &slices,
+                &RenderOptions::new().width(12).hyphenate(Lang::English),
+            );
+            assert_eq!(vec!["lorem exten-", "sive."], plain_lines(&lines));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use proptest::prelude::*;
+use sani::backends::OutputFormat;
+use sani::document::Document;
+use sani::markdown::DocumentElement;
+use sani::options::{
+    Dialect, InputFormat, Invisibles, ParserOptions, RenderOptions, TableOverflow,
+};
+
+/// Pieces of text that are wider or longer than they look, or change length when their case is
+/// changed, mixed with markup that the parsers slice around
+const PIECES: &[&str] = &[
+    "é",
+    "e\u{301}",
+    "日本語",
+    "👩\u{200D}👩\u{200D}👧",
+    "🇬🇧",
+    "\u{200B}",
+    "\u{FEFF}",
+    "\u{202E}",
+    "\u{AD}",
+    "İ",
+    "ß",
+    "ﬃ",
+    "\u{10FFFF}",
+    "\r\n",
+    "\t",
+    " ",
+    "\n",
+    "\n\n",
+    "*",
+    "**",
+    "_",
+    "~",
+    "~~",
+    "`",
+    "```",
+    "~~~",
+    "\\",
+    "[[",
+    "]]",
+    "|",
+    "|-",
+    "# ",
+    "###### ",
+    "> ",
+    "> [!",
+    "- ",
+    "1. ",
+    "(1) ",
+    "#. ",
+    "---",
+    "<!--",
+    "-->",
+    "%%",
+    "::",
+    "..",
+    "=",
+    "== ",
+    "* ",
+    "+",
+    "/",
+    ":",
+    "&amp;",
+    "&#x1F600;",
+    "&#",
+    "*[",
+    "]: ",
+    "#+TITLE: ",
+    "#+BEGIN_SRC",
+    "#+END_SRC",
+    ".. code-block:: ",
+    "   ",
+    "https://",
+    "{{",
+    "}}",
+];
+
+/// Text made of [`PIECES`], which stresses the byte offsets the parsers work with
+fn adversarial_text() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(PIECES), 0..48).prop_map(|pieces| pieces.concat())
+}
+
+/// The combinations of parser options that are tried on every input
+fn parser_options() -> Vec<ParserOptions> {
+    let formats = [
+        InputFormat::Markdown,
+        InputFormat::Rst,
+        InputFormat::Org,
+        #[cfg(feature = "asciidoc")]
+        InputFormat::AsciiDoc,
+    ];
+    let mut options = Vec::new();
+    for format in formats {
+        options.push(ParserOptions::new().input_format(format));
+        options.push(
+            ParserOptions::new()
+                .input_format(format)
+                .dialect(Dialect::Obsidian)
+                .entities(true)
+                .abbreviations(true)
+                .invisibles(Invisibles::Show),
+        );
+    }
+    options.push(
+        ParserOptions::new()
+            .dialect(Dialect::Gfm)
+            .invisibles(Invisibles::Remove),
+    );
+    options
+}
+
+/// The output formats that are given as text
+const TEXT_FORMATS: [OutputFormat; 8] = [
+    OutputFormat::Terminal,
+    OutputFormat::Text,
+    OutputFormat::Html,
+    OutputFormat::Man(1),
+    OutputFormat::Latex,
+    OutputFormat::Events,
+    OutputFormat::PandocJson,
+    OutputFormat::Speech,
+];
+
+/// Checks that every element's span, and those of the elements inside it, can be sliced out of
+/// `text`
+fn assert_spans_slice(
+    text: &str,
+    elements: &[Box<dyn DocumentElement>],
+) -> Result<(), TestCaseError> {
+    for element in elements {
+        let span = element.span();
+        prop_assert!(
+            text.get(span.clone()).is_some(),
+            "bad span {:?} of {:?} in {:?}",
+            span,
+            element.kind(),
+            text
+        );
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn adversarial_text_parses_and_renders_without_panicking(
+        text in adversarial_text(),
+        width in prop_oneof![Just(None), (0_usize..12).prop_map(Some)],
+    ) {
+        let mut options = RenderOptions::new()
+            .line_numbers(true)
+            .show_comments(true)
+            .table_overflow(TableOverflow::Truncate);
+        if let Some(width) = width {
+            options = options.width(width);
+        }
+        #[cfg(feature = "bidi")]
+        {
+            options = options.bidi(true);
+        }
+        for parser_options in parser_options() {
+            let mut elements = sani::parse_with_options(&text, &parser_options);
+            assert_spans_slice(&text, &elements)?;
+            sani::markdown::number_headings(&mut elements);
+            for format in TEXT_FORMATS {
+                let _ = sani::render_as(sani::parse_with_options(&text, &parser_options), format, &options);
+            }
+            let _ = sani::render_bytes(elements, OutputFormat::Epub, &options);
+        }
+    }
+
+    #[test]
+    fn documents_split_without_panicking(text in adversarial_text()) {
+        let document = Document::parse(&text);
+        let _ = document.outline();
+        for section in document.sections(&text) {
+            prop_assert!(text.contains(section.text));
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes_parse_without_panicking(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+        let elements = sani::parse_bytes(&bytes, &ParserOptions::new());
+        let text = sani::input::decode_lossy(&bytes).0;
+        assert_spans_slice(&text, &elements)?;
+        let _ = sani::render(elements, &RenderOptions::new().width(5));
+    }
+
+    #[test]
+    fn streamed_text_renders_without_panicking(text in adversarial_text()) {
+        let mut stream = sani::stream::BlockStream::new();
+        let mut runs: Vec<_> = text.lines().filter_map(|line| stream.push_line(line)).collect();
+        runs.extend(stream.finish());
+        for run in runs {
+            let _ = sani::render(sani::parse(&run), &RenderOptions::new());
+        }
+    }
+}
+
+#[test]
+fn every_prefix_of_multibyte_markup_parses() {
+    let text = "# 日本 *é\u{301}* ~~👩\u{200D}👧~~ `ß`\n\n> [!note] İ\n> ﬃ\n\n- \u{200B}[[ページ|é]]\n\n```\n🇬🇧\n```\n";
+    for (end, _) in text.char_indices() {
+        let prefix = text.get(..end).unwrap_or_default();
+        for parser_options in parser_options() {
+            let elements = sani::parse_with_options(prefix, &parser_options);
+            for element in &elements {
+                assert!(
+                    prefix.get(element.span()).is_some(),
+                    "bad span {:?} with {parser_options:?} in {prefix:?}",
+                    element.span()
+                );
+            }
+            let _ = sani::render(elements, &RenderOptions::new().width(3));
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Runs sani with arguments that it has to turn down before doing anything with them
+
+use std::process::Command;
+
+/// Runs sani with `arguments`, returning its exit code and what it wrote to standard error
+fn run(arguments: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_sani"))
+        .args(arguments)
+        .env_clear()
+        .output()
+        .expect("unable to run sani");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn revisions_cant_be_git_options() {
+    for revision in ["--rev=--output=/dev/null", "--rev=-p"] {
+        let (code, stderr) = run(&[revision, "README.md"]);
+        assert_eq!(Some(64), code, "{stderr}");
+        assert!(
+            stderr.contains("revisions can't start with `-`"),
+            "{stderr}"
+        );
+    }
+}
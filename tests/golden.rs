@@ -0,0 +1,106 @@
+//! Runs sani over each Markdown file in `tests/golden` and compares what it writes with the
+//! `.ansi` file of the same name next to it. Set `SANI_UPDATE_GOLDEN=1` to write the output to the
+//! `.ansi` files instead, once a change to the output has been checked
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The arguments every file is rendered with, so that nothing is detected from the terminal or
+/// the environment
+const ARGUMENTS: [&str; 8] = [
+    "--width",
+    "80",
+    "--theme-mode",
+    "dark",
+    "--colour-depth",
+    "24bit",
+    "--lang",
+    "en",
+];
+
+fn render(path: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_sani"))
+        .args(ARGUMENTS)
+        .arg(path)
+        .env_clear()
+        .output()
+        .expect("unable to run sani");
+    assert!(
+        output.status.success(),
+        "sani failed on {}: {}",
+        path.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("sani wrote invalid UTF-8")
+}
+
+/// Describes the first line that differs between `expected` and `actual`, with escape characters
+/// shown as `\e`
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for number in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => break,
+            (expected, actual) if expected != actual => {
+                let visible = |line: Option<&str>| {
+                    line.map_or_else(|| "<none>".to_owned(), |line| line.replace('\x1b', "\\e"))
+                };
+                return format!(
+                    "line {number}\n  expected: {}\n  actual:   {}",
+                    visible(expected),
+                    visible(actual)
+                );
+            }
+            _ => {}
+        }
+    }
+    "the line endings".to_owned()
+}
+
+#[test]
+fn output_matches_golden_files() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = env::var_os("SANI_UPDATE_GOLDEN").is_some_and(|value| value != "0");
+
+    let mut documents: Vec<PathBuf> = fs::read_dir(&directory)
+        .expect("unable to read the golden file directory")
+        .map(|entry| entry.expect("unable to read a directory entry").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "md"))
+        .collect();
+    documents.sort();
+    assert!(!documents.is_empty(), "no documents to render");
+
+    let mut failures = Vec::new();
+    for document in &documents {
+        let output = render(document);
+        let golden = document.with_extension("ansi");
+        if update {
+            fs::write(&golden, output).expect("unable to write a golden file");
+            continue;
+        }
+
+        let Ok(expected) = fs::read_to_string(&golden) else {
+            failures.push(format!(
+                "{}: no golden file; run with SANI_UPDATE_GOLDEN=1 to write it",
+                golden.display()
+            ));
+            continue;
+        };
+        if output != expected {
+            failures.push(format!(
+                "{} differs at {}",
+                golden.display(),
+                first_difference(&expected, &output)
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "output doesn't match the golden files (run with SANI_UPDATE_GOLDEN=1 to update them if the change is intended):\n\n{}",
+        failures.join("\n\n")
+    );
+}
@@ -0,0 +1,63 @@
+use proptest::prelude::*;
+use sani::options::RenderOptions;
+
+/// Checks that every SGR attribute switched on in `render` is switched off again
+fn assert_balanced_sgr_codes(render: &str) -> Result<(), TestCaseError> {
+    sani::testing::check_balanced_sgr_codes(render).map_err(TestCaseError::fail)
+}
+
+/// Markdown-ish text, heavy on the characters that the parser treats specially
+fn markdown_text() -> impl Strategy<Value = String> {
+    prop::collection::vec(
+        prop_oneof![
+            Just("*".to_owned()),
+            Just("**".to_owned()),
+            Just("~".to_owned()),
+            Just("~~".to_owned()),
+            Just("\\".to_owned()),
+            Just("\n".to_owned()),
+            Just("\n\n".to_owned()),
+            Just("# ".to_owned()),
+            Just(" ".to_owned()),
+            "[a-zé日]{1,5}",
+            any::<char>().prop_map(String::from),
+        ],
+        0..40,
+    )
+    .prop_map(|pieces| pieces.concat())
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_text_renders_without_panicking(text in any::<String>()) {
+        let render = sani::render(sani::parse(&text), &RenderOptions::new());
+        assert_balanced_sgr_codes(&render)?;
+    }
+
+    #[test]
+    fn markdown_renders_with_balanced_formatting(text in markdown_text()) {
+        for element in sani::parse(&text) {
+            assert_balanced_sgr_codes(&element.render(&RenderOptions::new()))?;
+        }
+    }
+
+    #[test]
+    fn wrapped_markdown_renders_with_balanced_formatting(
+        text in markdown_text(),
+        width in 0_usize..30,
+    ) {
+        for element in sani::parse(&text) {
+            for line in element.render(&RenderOptions::new().width(width)).lines() {
+                assert_balanced_sgr_codes(line)?;
+            }
+        }
+    }
+
+    #[test]
+    fn spans_lie_on_char_boundaries(text in markdown_text()) {
+        for element in sani::parse(&text) {
+            let span = element.span();
+            prop_assert!(text.get(span.clone()).is_some(), "bad span {:?} in {:?}", span, text);
+        }
+    }
+}